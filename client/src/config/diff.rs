@@ -0,0 +1,170 @@
+//! Field-by-field comparison of a config file against the one a running daemon reports.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::ClientConfig;
+
+/// Keys `ReloadConfig` applies to a running daemon; keep in line with `apply_hot`.
+/// Everything else is read once at startup.
+const HOT_RELOAD_KEYS: &[&str] = &[
+    "public_hostname_ipv4",
+    "public_hostname_ipv6",
+    "port_range.min",
+    "port_range.max",
+    "reconcile_interval_secs",
+    "stun_servers",
+];
+
+/// What it takes for a changed key to reach the daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyMode {
+    Restart,
+    HotReload,
+}
+
+impl ApplyMode {
+    fn of(key: &str) -> Self {
+        if HOT_RELOAD_KEYS.contains(&key) {
+            ApplyMode::HotReload
+        } else {
+            ApplyMode::Restart
+        }
+    }
+}
+
+impl std::fmt::Display for ApplyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyMode::Restart => write!(f, "restart"),
+            ApplyMode::HotReload => write!(f, "hot reload"),
+        }
+    }
+}
+
+/// One key whose value differs; nested keys are dotted, e.g. `port_range.max`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    pub key: String,
+    pub running: Value,
+    pub file: Value,
+    pub apply: ApplyMode,
+}
+
+/// Compare `file` against the daemon's `GetConfig` answer. The answer goes through
+/// `ClientConfig` first, so fields an older daemon leaves out count as their defaults.
+pub fn diff(running: Value, file: &ClientConfig) -> Result<Vec<ConfigChange>, serde_json::Error> {
+    let running: ClientConfig = serde_json::from_value(running)?;
+    let mut running_keys = BTreeMap::new();
+    flatten("", serde_json::to_value(&running)?, &mut running_keys);
+    let mut file_keys = BTreeMap::new();
+    flatten("", serde_json::to_value(file)?, &mut file_keys);
+
+    let mut keys: Vec<&String> = running_keys.keys().chain(file_keys.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    Ok(keys
+        .into_iter()
+        .filter_map(|key| {
+            let running = running_keys.get(key).cloned().unwrap_or(Value::Null);
+            let file = file_keys.get(key).cloned().unwrap_or(Value::Null);
+            (running != file).then(|| ConfigChange { key: key.clone(), apply: ApplyMode::of(key), running, file })
+        })
+        .collect())
+}
+
+/// `running` with the hot-reloadable fields taken from `file`.
+pub fn apply_hot(running: &ClientConfig, file: &ClientConfig) -> ClientConfig {
+    ClientConfig {
+        public_hostname_ipv4: file.public_hostname_ipv4.clone(),
+        public_hostname_ipv6: file.public_hostname_ipv6.clone(),
+        port_range: file.port_range.clone(),
+        reconcile_interval_secs: file.reconcile_interval_secs,
+        stun_servers: file.stun_servers.clone(),
+        ..running.clone()
+    }
+}
+
+/// Objects become dotted keys; arrays and scalars are compared as whole values.
+fn flatten(prefix: &str, value: Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+                flatten(&key, value, out);
+            }
+        }
+        value => {
+            out.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_reports_changed_keys_only() {
+        let running = serde_json::to_value(ClientConfig::default()).unwrap();
+        assert!(diff(running.clone(), &ClientConfig::default()).unwrap().is_empty());
+
+        let file = ClientConfig {
+            data_dir: "/srv/cat4igp".into(),
+            port_range: crate::config::PortRange { min: 51820, max: 53000 },
+            public_hostname_ipv4: Some("node.example.com".to_string()),
+            ..Default::default()
+        };
+        let changes = diff(running, &file).unwrap();
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].key, "data_dir");
+        assert_eq!(changes[0].apply, ApplyMode::Restart);
+        assert_eq!(changes[1].key, "port_range.max");
+        assert_eq!(changes[1].running, serde_json::json!(52000));
+        assert_eq!(changes[1].file, serde_json::json!(53000));
+        assert_eq!(changes[1].apply, ApplyMode::HotReload);
+        assert_eq!(changes[2].key, "public_hostname_ipv4");
+        assert_eq!(changes[2].running, Value::Null);
+    }
+
+    #[test]
+    fn test_diff_treats_missing_fields_as_defaults() {
+        let mut running = serde_json::to_value(ClientConfig::default()).unwrap();
+        let fields = running.as_object_mut().unwrap();
+        fields.remove("fec");
+        fields.remove("reconcile_interval_secs");
+        fields.remove("cleanup_orphan_interfaces");
+        assert!(diff(running.clone(), &ClientConfig::default()).unwrap().is_empty());
+
+        let file = ClientConfig { reconcile_interval_secs: 60, ..Default::default() };
+        let changes = diff(running, &file).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, "reconcile_interval_secs");
+        assert_eq!(changes[0].running, serde_json::json!(30));
+    }
+
+    #[test]
+    fn test_apply_hot_matches_hot_reload_keys() {
+        let running = ClientConfig::default();
+        let file = ClientConfig {
+            daemon_socket: "/run/other.sock".into(),
+            public_hostname_ipv4: Some("node.example.com".to_string()),
+            port_range: crate::config::PortRange { min: 40000, max: 41000 },
+            reconcile_interval_secs: 5,
+            stun_servers: vec!["192.0.2.1:3478".parse().unwrap()],
+            cleanup_orphan_interfaces: false,
+            ..Default::default()
+        };
+        let applied = apply_hot(&running, &file);
+
+        let hot = diff(serde_json::to_value(&running).unwrap(), &applied).unwrap();
+        assert!(hot.iter().all(|c| c.apply == ApplyMode::HotReload), "{:?}", hot);
+        assert_eq!(hot.len(), 5);
+        let cold = diff(serde_json::to_value(&applied).unwrap(), &file).unwrap();
+        assert!(cold.iter().all(|c| c.apply == ApplyMode::Restart), "{:?}", cold);
+        assert_eq!(cold.len(), 2);
+    }
+}
@@ -0,0 +1,151 @@
+//! TOML output with a comment above every field, for `gen-config --documented`. `toml`
+//! drops comments when serializing, so this is a template filled in with the config's values.
+
+use std::fmt::Write;
+
+use super::{ClientConfig, IpStack};
+
+/// Quote a string as a TOML basic string.
+fn quoted(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+/// `key = "value"`, or the key commented out with `example` when unset.
+fn optional(key: &str, value: Option<&str>, example: &str) -> String {
+    match value {
+        Some(value) => format!("{} = {}", key, quoted(value)),
+        None => format!("# {} = {}", key, quoted(example)),
+    }
+}
+
+impl ClientConfig {
+    /// Serialize as TOML with explanatory comments. Parses back to the same config.
+    pub fn to_documented_toml(&self) -> String {
+        let ip_stack = match self.ip_stack {
+            IpStack::Dual => "dual",
+            IpStack::V4Only => "v4_only",
+            IpStack::V6Only => "v6_only",
+        };
+        let stun_servers: Vec<String> = self.stun_servers.iter().map(|s| quoted(&s.to_string())).collect();
+        let pid_file = self.pid_file.as_ref().map(|p| p.to_string_lossy().into_owned());
+
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            r#"# cat4igp client configuration
+
+# Unix socket the daemon listens on; the CLI talks to the daemon through it.
+daemon_socket = {daemon_socket}
+
+# Working directory. Holds server.json (server address, node key, WireGuard keys)
+# and the daemon secret, so keep it readable by the daemon user only.
+data_dir = {data_dir}
+
+# PID file written while the daemon runs, so `status` and `shutdown` can find it.
+{pid_file}
+
+# Hostnames peers should use to reach this node instead of the detected public
+# address, e.g. a dynamic DNS name. One per address family.
+{public_hostname_ipv4}
+{public_hostname_ipv6}
+
+# Address families this host has: "dual", "v4_only" or "v6_only". The other family
+# is never probed, and tunnels with an endpoint in it are declined.
+ip_stack = {ip_stack}
+
+# STUN servers ("ip:port", IPv6 as "[addr]:port") used for public address detection
+# instead of the public lists fetched from GitHub. Empty means use the public lists.
+stun_servers = [{stun_servers}]
+
+# Seconds between tunnel polls. Each wait is varied by ±20%, and doubles after each
+# failed poll up to 10 minutes. Values below 1 are treated as 1.
+reconcile_interval_secs = {reconcile_interval_secs}
+
+# Delete `cat…` WireGuard interfaces left behind by a previous run once the first
+# tunnel poll has brought up the current ones.
+cleanup_orphan_interfaces = {cleanup_orphan_interfaces}
+
+# Local UDP ports tunnels may listen on: `min` inclusive, `max` exclusive, min < max.
+# Open this range in the firewall.
+[port_range]
+min = {port_min}
+max = {port_max}
+
+# Tunnel protocols this node accepts.
+[tunnel_protocols]
+wireguard = {wireguard}
+
+# Forward error correction for tunnels the server flags with FEC. Each block of
+# `data_shards` packets gets `parity_shards` parity packets, recovering that many
+# losses at parity_shards / data_shards extra bandwidth. The two must add up to at
+# most 255 and data_shards must be at least 1.
+[fec]
+data_shards = {data_shards}
+parity_shards = {parity_shards}
+# Milliseconds a partially filled block waits before its parity is sent; at least 1.
+flush_timeout_ms = {flush_timeout_ms}
+"#,
+            daemon_socket = quoted(&self.daemon_socket.to_string_lossy()),
+            data_dir = quoted(&self.data_dir.to_string_lossy()),
+            pid_file = optional("pid_file", pid_file.as_deref(), "/run/cat4igp-client.pid"),
+            public_hostname_ipv4 = optional(
+                "public_hostname_ipv4",
+                self.public_hostname_ipv4.as_deref(),
+                "node.example.com"
+            ),
+            public_hostname_ipv6 = optional(
+                "public_hostname_ipv6",
+                self.public_hostname_ipv6.as_deref(),
+                "node6.example.com"
+            ),
+            ip_stack = quoted(ip_stack),
+            stun_servers = stun_servers.join(", "),
+            reconcile_interval_secs = self.reconcile_interval_secs,
+            cleanup_orphan_interfaces = self.cleanup_orphan_interfaces,
+            port_min = self.port_range.min,
+            port_max = self.port_range.max,
+            wireguard = self.tunnel_protocols.wireguard,
+            data_shards = self.fec.data_shards,
+            parity_shards = self.fec.parity_shards,
+            flush_timeout_ms = self.fec.flush_timeout_ms,
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_documented_toml_round_trips() {
+        let default = ClientConfig::default();
+        let parsed: ClientConfig = toml::from_str(&default.to_documented_toml()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(&default).unwrap());
+
+        let config = ClientConfig {
+            pid_file: Some("/run/cat \"4\" igp.pid".into()),
+            public_hostname_ipv6: Some("node6.example.net".to_string()),
+            ip_stack: IpStack::V6Only,
+            stun_servers: vec!["192.0.2.1:3478".parse().unwrap(), "[2001:db8::1]:19302".parse().unwrap()],
+            cleanup_orphan_interfaces: false,
+            ..Default::default()
+        };
+        let parsed: ClientConfig = toml::from_str(&config.to_documented_toml()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(&config).unwrap());
+    }
+
+    /// Fails when a field is added to `ClientConfig` but not to the template.
+    #[test]
+    fn test_documented_toml_covers_every_field() {
+        let documented = ClientConfig::default().to_documented_toml();
+        let value = serde_json::to_value(ClientConfig::default()).unwrap();
+        for key in value.as_object().unwrap().keys() {
+            assert!(
+                documented.contains(&format!("{} = ", key)) || documented.contains(&format!("[{}]", key)),
+                "{} is missing from the documented template",
+                key
+            );
+        }
+    }
+}
@@ -3,6 +3,8 @@ use std::path::{Path, PathBuf};
 use std::fs;
 use std::ops::Range;
 
+pub mod diff;
+mod documented;
 pub mod server;
 pub use server::ServerConfig;
 
@@ -14,6 +16,10 @@ pub struct ClientConfig {
     
     /// Directory for working data storage
     pub data_dir: PathBuf,
+
+    /// PID file written while the daemon runs; lets `status`/`shutdown` find the process
+    #[serde(default)]
+    pub pid_file: Option<PathBuf>,
     
     /// Usable port range for tunnels
     pub port_range: PortRange,
@@ -26,6 +32,36 @@ pub struct ClientConfig {
     
     /// Optional public IPv6 hostname for responding to connection requests
     pub public_hostname_ipv6: Option<String>,
+
+    /// Address families this host has; the other one is never probed or used
+    #[serde(default)]
+    pub ip_stack: IpStack,
+
+    /// Static STUN servers (`ip:port`) to use instead of fetching the public lists,
+    /// e.g. where HTTPS to GitHub is blocked but STUN over UDP is allowed
+    #[serde(default)]
+    pub stun_servers: Vec<std::net::SocketAddr>,
+
+    /// Forward error correction settings for tunnels flagged `fec`
+    #[serde(default)]
+    pub fec: FecConfig,
+
+    /// Seconds between tunnel polls/reconciles; each wait is jittered by ±20%
+    #[serde(default = "default_reconcile_interval_secs")]
+    pub reconcile_interval_secs: u64,
+
+    /// Remove `cat…` WireGuard interfaces left behind by a previous run once the first
+    /// tunnel poll has brought up the current ones
+    #[serde(default = "default_cleanup_orphan_interfaces")]
+    pub cleanup_orphan_interfaces: bool,
+}
+
+fn default_reconcile_interval_secs() -> u64 {
+    30
+}
+
+fn default_cleanup_orphan_interfaces() -> bool {
+    true
 }
 
 /// Port range configuration
@@ -52,6 +88,106 @@ impl PortRange {
     }
 }
 
+/// Forward error correction settings, applied to every tunnel that has FEC enabled.
+///
+/// Packets are grouped in blocks of `data_shards`, and `parity_shards` Reed-Solomon
+/// parity packets are sent with each block, so any `parity_shards` losses per block can
+/// be recovered. The price is bandwidth and latency: every block costs
+/// `parity_shards / data_shards` extra traffic (0.5 with the defaults, i.e. 50% more),
+/// and a partially filled block may wait up to `flush_timeout_ms` before its parity is sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FecConfig {
+    /// Number of data packets per FEC block
+    pub data_shards: u8,
+
+    /// Number of parity packets per FEC block
+    pub parity_shards: u8,
+
+    /// Maximum time to wait for a block to fill before flushing it
+    pub flush_timeout_ms: u64,
+}
+
+impl FecConfig {
+    /// Build a config from a redundancy ratio (parity packets per data packet).
+    pub fn from_redundancy(data_shards: u8, redundancy: f32) -> Result<Self, String> {
+        if !(0.0..=1.0).contains(&redundancy) {
+            return Err("redundancy must be between 0.0 and 1.0".to_string());
+        }
+        let config = FecConfig {
+            data_shards,
+            parity_shards: (data_shards as f32 * redundancy).round() as u8,
+            ..Default::default()
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.data_shards == 0 {
+            return Err("data_shards must be greater than 0".to_string());
+        }
+        if self.data_shards as u16 + self.parity_shards as u16 > 255 {
+            return Err("data_shards + parity_shards must not exceed 255".to_string());
+        }
+        if self.flush_timeout_ms == 0 {
+            return Err("flush_timeout_ms must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Extra bandwidth spent on parity, as a fraction of the payload.
+    pub fn redundancy(&self) -> f32 {
+        self.parity_shards as f32 / self.data_shards as f32
+    }
+}
+
+impl Default for FecConfig {
+    fn default() -> Self {
+        FecConfig {
+            data_shards: 4,
+            parity_shards: 2,
+            flush_timeout_ms: 50,
+        }
+    }
+}
+
+/// Which IP families the host can use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpStack {
+    #[default]
+    Dual,
+    V4Only,
+    V6Only,
+}
+
+impl IpStack {
+    pub fn has_ipv4(self) -> bool {
+        self != IpStack::V6Only
+    }
+
+    /// Also governs link-local address assignment on tunnel interfaces
+    pub fn has_ipv6(self) -> bool {
+        self != IpStack::V4Only
+    }
+
+    /// Whether a tunnel whose endpoint is IPv6 (or IPv4) can be used on this host
+    pub fn accepts_endpoint(self, endpoint_ipv6: bool) -> bool {
+        if endpoint_ipv6 { self.has_ipv6() } else { self.has_ipv4() }
+    }
+
+    /// Narrow a dual stack to the families actually found working. A single-stack setting
+    /// is kept as configured, as is everything when neither family looks usable (nothing to go on).
+    pub fn restrict_to(self, ipv4_usable: bool, ipv6_usable: bool) -> IpStack {
+        match (self, ipv4_usable, ipv6_usable) {
+            (IpStack::Dual, true, false) => IpStack::V4Only,
+            (IpStack::Dual, false, true) => IpStack::V6Only,
+            _ => self,
+        }
+    }
+}
+
 /// Tunnel protocols configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TunnelProtocols {
@@ -64,12 +200,18 @@ impl Default for ClientConfig {
         ClientConfig {
             daemon_socket: PathBuf::from("/tmp/cat4igp-client.sock"),
             data_dir: PathBuf::from("/var/lib/cat4igp-client"),
+            pid_file: None,
             port_range: PortRange { min: 51820, max: 52000 },
             tunnel_protocols: TunnelProtocols {
                 wireguard: true,
             },
             public_hostname_ipv4: None,
             public_hostname_ipv6: None,
+            ip_stack: IpStack::Dual,
+            stun_servers: Vec::new(),
+            fec: FecConfig::default(),
+            reconcile_interval_secs: default_reconcile_interval_secs(),
+            cleanup_orphan_interfaces: default_cleanup_orphan_interfaces(),
         }
     }
 }
@@ -82,6 +224,17 @@ impl ClientConfig {
         Ok(config)
     }
 
+    /// Base tunnel poll interval, at least one second.
+    pub fn reconcile_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.reconcile_interval_secs.max(1))
+    }
+
+    /// Public IP detector using `stun_servers` if set; otherwise the lists still need `init()`.
+    pub fn public_ip_detector(&self) -> crate::network::PublicIpDetector {
+        let (ipv4, ipv6) = self.stun_servers.iter().partition(|addr| addr.is_ipv4());
+        crate::network::PublicIpDetector::from_static_servers(ipv4, ipv6)
+    }
+
     /// Save configuration to a TOML file
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
         let content = toml::to_string_pretty(&self)?;
@@ -119,4 +272,72 @@ mod tests {
         assert_eq!(config.public_hostname_ipv4, None);
         assert_eq!(config.public_hostname_ipv6, None);
     }
+
+    #[test]
+    fn test_fec_config_redundancy() {
+        let config = FecConfig::from_redundancy(10, 0.3).unwrap();
+        assert_eq!(config.parity_shards, 3);
+        assert!((config.redundancy() - 0.3).abs() < f32::EPSILON);
+
+        assert!(FecConfig::from_redundancy(0, 0.5).is_err());
+        assert!(FecConfig::from_redundancy(4, 1.5).is_err());
+        assert!(FecConfig::from_redundancy(200, 0.5).is_err());
+    }
+
+    #[test]
+    fn test_fec_config_defaults_when_missing() {
+        let mut value = serde_json::to_value(ClientConfig::default()).unwrap();
+        value.as_object_mut().unwrap().remove("fec");
+        let config: ClientConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.fec.data_shards, 4);
+        assert_eq!(config.fec.parity_shards, 2);
+    }
+
+    #[test]
+    fn test_reconcile_interval() {
+        let mut value = serde_json::to_value(ClientConfig::default()).unwrap();
+        value.as_object_mut().unwrap().remove("reconcile_interval_secs");
+        let mut config: ClientConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.reconcile_interval_secs, 30);
+
+        config.reconcile_interval_secs = 0;
+        assert_eq!(config.reconcile_interval(), std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_ip_stack_modes() {
+        assert!(IpStack::Dual.accepts_endpoint(false) && IpStack::Dual.accepts_endpoint(true));
+        assert!(IpStack::V4Only.accepts_endpoint(false) && !IpStack::V4Only.accepts_endpoint(true));
+        assert!(!IpStack::V6Only.accepts_endpoint(false) && IpStack::V6Only.accepts_endpoint(true));
+        assert!(!IpStack::V4Only.has_ipv6());
+        assert!(!IpStack::V6Only.has_ipv4());
+
+        assert_eq!(IpStack::Dual.restrict_to(true, false), IpStack::V4Only);
+        assert_eq!(IpStack::Dual.restrict_to(false, true), IpStack::V6Only);
+        assert_eq!(IpStack::Dual.restrict_to(true, true), IpStack::Dual);
+        assert_eq!(IpStack::Dual.restrict_to(false, false), IpStack::Dual);
+        assert_eq!(IpStack::V6Only.restrict_to(true, false), IpStack::V6Only);
+
+        let mut value = serde_json::to_value(ClientConfig::default()).unwrap();
+        assert_eq!(value["ip_stack"], "dual");
+        value["ip_stack"] = serde_json::json!("v6_only");
+        let config: ClientConfig = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(config.ip_stack, IpStack::V6Only);
+
+        value.as_object_mut().unwrap().remove("ip_stack");
+        let config: ClientConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(config.ip_stack, IpStack::Dual);
+    }
+
+    #[test]
+    fn test_static_stun_servers() {
+        let mut value = serde_json::to_value(ClientConfig::default()).unwrap();
+        assert_eq!(value["stun_servers"], serde_json::json!([]));
+        value["stun_servers"] = serde_json::json!(["192.0.2.1:3478", "[2001:db8::1]:19302"]);
+        let config: ClientConfig = serde_json::from_value(value).unwrap();
+
+        let detector = config.public_ip_detector();
+        assert_eq!(detector.ipv4_servers().len(), 1);
+        assert_eq!(detector.ipv6_nat_servers().len(), 1);
+    }
 }
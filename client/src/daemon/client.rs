@@ -1,9 +1,9 @@
 use std::path::Path;
 use std::io;
+use std::time::Duration;
 use tokio::net::UnixStream;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-use super::protocol::{DaemonRequest, DaemonResponse, SharedSecret};
+use super::protocol::{read_frame, write_frame, DaemonEvent, DaemonRequest, DaemonResponse, SharedSecret};
 
 /// IPC message envelope
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -12,10 +12,24 @@ struct IpcMessage {
     request: DaemonRequest,
 }
 
+/// Connection attempts made by default, so a request sent while the daemon restarts still lands
+pub const DEFAULT_CONNECT_ATTEMPTS: u32 = 3;
+
+/// Wait before the first reconnect; doubled after each further failure
+const CONNECT_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Time the daemon gets to answer a request before the client gives up
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Lower bound for requests the daemon answers only after talking to the server
+const SERVER_REQUEST_TIMEOUT: Duration = Duration::from_secs(90);
+
 /// Client for communicating with the daemon via Unix socket
 pub struct DaemonClient {
     socket_path: std::path::PathBuf,
     secret: String,
+    connect_attempts: u32,
+    request_timeout: Duration,
 }
 
 impl DaemonClient {
@@ -25,56 +39,135 @@ impl DaemonClient {
         Ok(DaemonClient {
             socket_path: socket_path.to_path_buf(),
             secret: secret.value().to_string(),
+            connect_attempts: DEFAULT_CONNECT_ATTEMPTS,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
         })
     }
 
+    /// Make up to `attempts` connection attempts (at least one) while the socket is missing or
+    /// refusing connections. Errors after connecting, such as a rejected secret, are never retried.
+    pub fn with_retries(mut self, attempts: u32) -> Self {
+        self.connect_attempts = attempts.max(1);
+        self
+    }
+
+    /// Time allowed for each request, including connecting. Requests that wait on the
+    /// server still get at least 90 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
     /// Send a request to the daemon and wait for response
     pub async fn send_request(&self, request: DaemonRequest) -> io::Result<DaemonResponse> {
-        // Connect to the daemon socket
-        let mut stream = UnixStream::connect(&self.socket_path).await.map_err(|e| {
+        let timeout = self.timeout_for(&request);
+        self.send_request_with_timeout(request, timeout).await
+    }
+
+    /// Like `send_request`, failing with `TimedOut` if no response arrives within `timeout`
+    pub async fn send_request_with_timeout(
+        &self,
+        request: DaemonRequest,
+        timeout: Duration,
+    ) -> io::Result<DaemonResponse> {
+        let exchange = async {
+            let mut stream = self.connect_and_send(request).await?;
+            read_frame(&mut stream).await
+        };
+        tokio::time::timeout(timeout, exchange)
+            .await
+            .unwrap_or_else(|_| Err(Self::timed_out(timeout)))
+    }
+
+    fn timeout_for(&self, request: &DaemonRequest) -> Duration {
+        match request {
+            DaemonRequest::Register { .. }
+            | DaemonRequest::RotateKey
+            | DaemonRequest::RotateWireguardKey { .. }
+            | DaemonRequest::TestServer { .. } => {
+                self.request_timeout.max(SERVER_REQUEST_TIMEOUT)
+            }
+            // sub-requests run one after another
+            DaemonRequest::Batch { requests, .. } => requests
+                .iter()
+                .map(|r| self.timeout_for(r))
+                .sum::<Duration>()
+                .max(self.request_timeout),
+            _ => self.request_timeout,
+        }
+    }
+
+    fn timed_out(timeout: Duration) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("Daemon did not respond within {}s", timeout.as_secs_f32()),
+        )
+    }
+
+    /// Subscribe to daemon events. Drop the subscription to unsubscribe.
+    /// Only the initial acknowledgement is subject to the request timeout.
+    pub async fn subscribe(&self) -> io::Result<EventSubscription> {
+        let subscribe = async {
+            let mut stream = self.connect_and_send(DaemonRequest::Subscribe).await?;
+            let response: DaemonResponse = read_frame(&mut stream).await?;
+            Ok::<_, io::Error>((stream, response))
+        };
+        let (stream, response) = tokio::time::timeout(self.request_timeout, subscribe)
+            .await
+            .unwrap_or_else(|_| Err(Self::timed_out(self.request_timeout)))?;
+        match response {
+            DaemonResponse::Ok(_) => Ok(EventSubscription { stream }),
+            DaemonResponse::Error { message, .. } => Err(io::Error::new(io::ErrorKind::PermissionDenied, message)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Unexpected response to Subscribe")),
+        }
+    }
+
+    async fn connect_and_send(&self, request: DaemonRequest) -> io::Result<UnixStream> {
+        let mut stream = self.connect().await.map_err(|e| {
             io::Error::new(
                 io::ErrorKind::NotFound,
                 format!("Failed to connect to daemon at {:?}: {}", self.socket_path, e),
             )
         })?;
 
-        // Prepare the message
         let message = IpcMessage {
             secret: self.secret.clone(),
             request,
         };
+        write_frame(&mut stream, &message).await?;
+        Ok(stream)
+    }
 
-        let message_bytes = serde_json::to_vec(&message).map_err(|e| {
-            io::Error::new(io::ErrorKind::InvalidData, format!("Failed to serialize request: {}", e))
-        })?;
-
-        // Send length prefix
-        let len = (message_bytes.len() as u32).to_be_bytes();
-        stream.write_all(&len).await?;
-        stream.write_all(&message_bytes).await?;
-        stream.flush().await?;
-
-        // Read response length
-        let mut len_bytes = [0u8; 4];
-        stream.read_exact(&mut len_bytes).await?;
-        let response_len = u32::from_be_bytes(len_bytes) as usize;
-
-        if response_len > 1024 * 1024 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Response too large",
-            ));
+    /// Connect to the daemon socket, retrying while the daemon is (re)binding it
+    async fn connect(&self) -> io::Result<UnixStream> {
+        let mut backoff = CONNECT_RETRY_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match UnixStream::connect(&self.socket_path).await {
+                Ok(stream) => return Ok(stream),
+                Err(e)
+                    if attempt < self.connect_attempts
+                        && matches!(e.kind(), io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound) =>
+                {
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
         }
+    }
+}
 
-        // Read response
-        let mut response_buffer = vec![0u8; response_len];
-        stream.read_exact(&mut response_buffer).await?;
-
-        let response: DaemonResponse = serde_json::from_slice(&response_buffer).map_err(|e| {
-            io::Error::new(io::ErrorKind::InvalidData, format!("Invalid response JSON: {}", e))
-        })?;
+/// Open `Subscribe` connection yielding events as the daemon publishes them.
+pub struct EventSubscription {
+    stream: UnixStream,
+}
 
-        Ok(response)
+impl EventSubscription {
+    /// Wait for the next event; fails with `UnexpectedEof` once the daemon goes away.
+    pub async fn next(&mut self) -> io::Result<DaemonEvent> {
+        read_frame(&mut self.stream).await
     }
 }
 
@@ -94,4 +187,62 @@ mod tests {
 
         assert_eq!(deserialized.secret, "test-secret");
     }
+
+    fn test_client(dir: &Path) -> DaemonClient {
+        SharedSecret::generate().save(dir).unwrap();
+        DaemonClient::new(&dir.join("daemon.sock"), dir).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_socket_appears() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let client = test_client(dir.path()).with_retries(5);
+        let socket = dir.path().join("daemon.sock");
+
+        let daemon = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            let listener = tokio::net::UnixListener::bind(&socket).unwrap();
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let message: IpcMessage = read_frame(&mut stream).await.unwrap();
+            assert!(matches!(message.request, DaemonRequest::Status));
+            write_frame(&mut stream, &DaemonResponse::Ok(Some("up".to_string()))).await.unwrap();
+        });
+
+        match client.send_request(DaemonRequest::Status).await.unwrap() {
+            DaemonResponse::Ok(msg) => assert_eq!(msg.as_deref(), Some("up")),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        daemon.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_times_out_when_daemon_hangs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let client = test_client(dir.path()).with_timeout(Duration::from_millis(100));
+        let listener = tokio::net::UnixListener::bind(dir.path().join("daemon.sock")).unwrap();
+
+        // accepts and reads the request, then never answers
+        let daemon = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _: IpcMessage = read_frame(&mut stream).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let err = client.send_request(DaemonRequest::Status).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        daemon.abort();
+
+        // requests that wait on the server keep a longer floor
+        assert_eq!(client.timeout_for(&DaemonRequest::RotateKey), SERVER_REQUEST_TIMEOUT);
+        assert_eq!(client.timeout_for(&DaemonRequest::Status), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_attempts() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let client = test_client(dir.path()).with_retries(1);
+
+        let err = client.send_request(DaemonRequest::Status).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
 }
@@ -1,21 +1,197 @@
-use std::{collections::{HashMap, HashSet}, sync::Arc};
-use tokio::sync::{Mutex, RwLock};
+use std::{collections::{HashMap, HashSet}, error::Error, net::IpAddr, path::PathBuf, sync::Arc, time::Duration};
+use futures_util::future::BoxFuture;
+use tokio::sync::{broadcast, Mutex, RwLock};
 use cat4igp_shared::rest::client as REST;
 use cat4igp_shared::custom_type::WireguardAnswered;
 
-use crate::config::ClientConfig;
+use crate::config::{ClientConfig, FecConfig, IpStack};
+use crate::daemon::protocol::{DaemonEvent, TunnelConfig, TunnelStats};
 use crate::network::ports::PortRange;
+use crate::network::public_ip::NatType;
+use crate::tunnel::shared::Tunnel;
 
+pub mod snapshot;
 pub mod wireguard;
 
+/// A tunnel the daemon keeps in line with the server, independent of what carries it.
+pub trait ManagedTunnel: Send {
+    fn get_tunnel_id(&self) -> i32;
+    /// The OS interface underneath any FEC/FakeTCP layers.
+    fn os_tunnel(&self) -> &dyn Tunnel;
+    /// Port allocated from the port range for this tunnel, released when it is removed.
+    fn get_public_port(&self) -> u16;
+    /// Server description this tunnel was last built or updated from; `None` forces the next update.
+    fn get_applied_rest_info(&self) -> Option<&REST::WireguardTunnelInfo>;
+    /// Create and bring up the interface, returning its link-local address if one was assigned.
+    fn activate(&mut self) -> BoxFuture<'_, Result<Option<IpAddr>, Box<dyn Error>>>;
+    fn teardown(&mut self) -> BoxFuture<'_, Result<(), Box<dyn Error>>>;
+    fn update_from_rest(
+        &mut self,
+        rest_info: Arc<REST::WireguardTunnelInfo>,
+        daemon_memory: Arc<DaemonMemory>,
+    ) -> BoxFuture<'_, Result<(), Box<dyn Error>>>;
+    fn get_stats(&self) -> Result<TunnelStats, Box<dyn Error>>;
+    /// Switch to a new local private key, applying it to the interface if it exists.
+    fn set_local_private_key(&mut self, local_private_key: String) -> BoxFuture<'_, Result<(), Box<dyn Error>>>;
+    /// Configuration for export; `endpoint` is the published one since the OS tunnel may only see a local shim.
+    fn get_config(&self, show_private_key: bool, endpoint: Option<String>) -> TunnelConfig;
+}
+
 #[derive(Clone)]
 pub struct DaemonMemory {
-    wireguard: Arc<Mutex<HashMap<i32, wireguard::WireguardTunnelC>>>,
+    wireguard: Arc<Mutex<HashMap<i32, Box<dyn ManagedTunnel>>>>,
     pub(crate) port_mgmt: Arc<PortRange>,
+    pub(crate) fec_config: FecConfig,
+    pub(crate) ip_stack: IpStack,
     node_info: Arc<RwLock<Option<REST::NodeInfoResponse>>>,
     all_nodes: Arc<RwLock<Option<REST::AllNodesResponse>>>,
     wireguard_tunnels: Arc<RwLock<Option<REST::WireguardTunnelsResponse>>>,
+    /// ETag of `wireguard_tunnels`, sent back so the server can answer 304
+    wireguard_tunnels_etag: Arc<RwLock<Option<String>>>,
+    /// Current tunnel poll interval, including error backoff but not jitter
+    poll_interval: Arc<RwLock<Option<Duration>>>,
     last_poll_error: Arc<RwLock<Option<String>>>,
+    network_status: Arc<RwLock<NetworkStatus>>,
+    events: broadcast::Sender<DaemonEvent>,
+    /// Where the tunnel snapshot is kept
+    data_dir: PathBuf,
+    /// Tunnels from the snapshot a previous daemon left, until the first reconciliation
+    adoptable: Arc<Mutex<HashMap<i32, snapshot::TunnelSnapshot>>>,
+}
+
+/// Result of the daemon's last public address and NAT detection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkStatus {
+    pub public_ipv4: Option<IpAddr>,
+    pub public_ipv6: Option<IpAddr>,
+    pub nat_type: Option<NatType>,
+    /// `None` until the first detection
+    pub families: Option<UsableFamilies>,
+    /// Why the last round detected nothing, its results then being the previous round's;
+    /// `None` after a successful one
+    pub detection_error: Option<String>,
+}
+
+/// Detections in a row a family must be missing before tunnels already accepted on it are
+/// torn down, so a brief loss of the address doesn't drop them.
+pub const FAMILY_MISSING_DETECTIONS: u32 = 3;
+
+/// Which IP families had a usable address at the last detection, and for how many
+/// detections in a row each has been missing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsableFamilies {
+    pub ipv4: bool,
+    pub ipv6: bool,
+    ipv4_misses: u32,
+    ipv6_misses: u32,
+}
+
+impl UsableFamilies {
+    /// Fold a detection into the miss counts of the previous one.
+    pub fn record(previous: Option<Self>, ipv4: bool, ipv6: bool) -> Self {
+        let previous = previous.unwrap_or_default();
+        let misses = |usable: bool, before: u32| if usable { 0 } else { before.saturating_add(1) };
+        Self {
+            ipv4,
+            ipv6,
+            ipv4_misses: misses(ipv4, previous.ipv4_misses),
+            ipv6_misses: misses(ipv6, previous.ipv6_misses),
+        }
+    }
+
+    /// `configured` narrowed to the families usable at the last detection; new tunnels need one.
+    pub fn current(&self, configured: IpStack) -> IpStack {
+        configured.restrict_to(self.ipv4, self.ipv6)
+    }
+
+    /// `configured` narrowed to the families not yet missing for `FAMILY_MISSING_DETECTIONS`
+    /// detections in a row; accepted tunnels stay up on these.
+    pub fn kept(&self, configured: IpStack) -> IpStack {
+        configured.restrict_to(
+            self.ipv4_misses < FAMILY_MISSING_DETECTIONS,
+            self.ipv6_misses < FAMILY_MISSING_DETECTIONS,
+        )
+    }
+}
+
+/// Events buffered per subscriber before it is reported as lagging
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Tunnel IDs grouped by what a reconciliation does to them, each list sorted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReconcileOps {
+    pub create: Vec<i32>,
+    /// Taken over from the interfaces a previous daemon left up, without reconfiguring them
+    pub adopt: Vec<i32>,
+    pub update: Vec<i32>,
+    pub remove: Vec<i32>,
+    pub unchanged: Vec<i32>,
+    /// Unchanged tunnels whose endpoint is a hostname, resolved again in case its address moved
+    /// (e.g. dynamic DNS) or it failed to resolve before. Also listed in `unchanged`.
+    pub refresh: Vec<i32>,
+    /// Planned for creation but could not be set up; retried on the next reconciliation
+    pub failed: Vec<i32>,
+}
+
+/// Whether `desired` differs from `applied` in anything `update_from_rest` acts on.
+pub(crate) fn tunnel_info_changed(applied: &REST::WireguardTunnelInfo, desired: &REST::WireguardTunnelInfo) -> bool {
+    applied.peer_node_id != desired.peer_node_id
+        || applied.public_key != desired.public_key
+        || applied.remote_endpoint != desired.remote_endpoint
+        || applied.mtu != desired.mtu
+        || applied.endpoint_ipv6 != desired.endpoint_ipv6
+        || applied.fec != desired.fec
+        || applied.faketcp != desired.faketcp
+        || applied.keepalive != desired.keepalive
+        || applied.allowed_ips != desired.allowed_ips
+        || applied.local_overlay_addr != desired.local_overlay_addr
+        || applied.peer_overlay_addr != desired.peer_overlay_addr
+}
+
+/// Links named like a WireGuard tunnel of ours that no active tunnel owns, e.g. left behind
+/// by a crashed daemon, with what their names decode to.
+fn orphan_interfaces(
+    links: Vec<String>,
+    active: &HashSet<String>,
+) -> Vec<(String, wireguard::TunnelDescriptor)> {
+    links
+        .into_iter()
+        .filter(|name| !active.contains(name))
+        .filter_map(|name| wireguard::WireguardTunnelC::parse_interface_name(&name).map(|info| (name, info)))
+        .collect()
+}
+
+/// Diff the active tunnels (with the info they were last applied from) against the server's list.
+/// Tunnels not yet answered by both sides are never created, but existing ones are left alone.
+fn plan_reconcile(
+    active: &HashMap<i32, Option<&REST::WireguardTunnelInfo>>,
+    desired: &[REST::WireguardTunnelInfo],
+) -> ReconcileOps {
+    let mut ops = ReconcileOps::default();
+    for tunnel in desired {
+        let is_ready = matches!(tunnel.local_answered, WireguardAnswered::Answered)
+            && matches!(tunnel.remote_response, WireguardAnswered::Answered);
+        match active.get(&tunnel.tunnel_id) {
+            None if is_ready => ops.create.push(tunnel.tunnel_id),
+            None => {}
+            Some(_) if !is_ready => ops.unchanged.push(tunnel.tunnel_id),
+            Some(Some(applied)) if !tunnel_info_changed(applied, tunnel) => {
+                ops.unchanged.push(tunnel.tunnel_id);
+                if tunnel.remote_endpoint.as_deref().is_some_and(crate::network::resolve::is_hostname) {
+                    ops.refresh.push(tunnel.tunnel_id);
+                }
+            }
+            Some(_) => ops.update.push(tunnel.tunnel_id),
+        }
+    }
+
+    let desired_ids: HashSet<i32> = desired.iter().map(|t| t.tunnel_id).collect();
+    ops.remove = active.keys().copied().filter(|id| !desired_ids.contains(id)).collect();
+
+    for ids in [&mut ops.create, &mut ops.update, &mut ops.remove, &mut ops.unchanged, &mut ops.refresh] {
+        ids.sort_unstable();
+    }
+    ops
 }
 
 impl DaemonMemory {
@@ -26,13 +202,61 @@ impl DaemonMemory {
                 client_config.port_range.min,
                 client_config.port_range.max,
             )),
+            fec_config: client_config.fec,
+            ip_stack: client_config.ip_stack,
             node_info: Arc::new(RwLock::new(None)),
             all_nodes: Arc::new(RwLock::new(None)),
             wireguard_tunnels: Arc::new(RwLock::new(None)),
+            wireguard_tunnels_etag: Arc::new(RwLock::new(None)),
+            poll_interval: Arc::new(RwLock::new(None)),
             last_poll_error: Arc::new(RwLock::new(None)),
+            network_status: Arc::new(RwLock::new(NetworkStatus::default())),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            data_dir: client_config.data_dir,
+            adoptable: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Load the tunnels a previous daemon left up, for the first reconciliation to adopt.
+    /// An unreadable snapshot is logged and ignored, so every tunnel gets rebuilt.
+    pub async fn load_snapshot(&self) {
+        match snapshot::load(&self.data_dir) {
+            Ok(tunnels) => {
+                *self.adoptable.lock().await = tunnels.into_iter().map(|t| (t.info.tunnel_id, t)).collect();
+            }
+            Err(e) => eprintln!("[daemon] ignoring tunnel snapshot, rebuilding every tunnel: {}", e),
         }
     }
 
+    /// Record the active tunnels for the next daemon. Failures are logged; a missing
+    /// snapshot only costs the next start a full rebuild.
+    fn save_snapshot(&self, active: &HashMap<i32, Box<dyn ManagedTunnel>>, local_public_key: &str) {
+        let mut tunnels: Vec<snapshot::TunnelSnapshot> = active
+            .values()
+            .filter_map(|tunnel| {
+                Some(snapshot::TunnelSnapshot {
+                    interface: tunnel.os_tunnel().get_interface_name().to_string(),
+                    port: tunnel.get_public_port(),
+                    local_public_key: local_public_key.to_string(),
+                    info: tunnel.get_applied_rest_info()?.clone(),
+                })
+            })
+            .collect();
+        tunnels.sort_by_key(|t| t.info.tunnel_id);
+        if let Err(e) = snapshot::save(&self.data_dir, &tunnels) {
+            eprintln!("[daemon] failed to save tunnel snapshot: {}", e);
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DaemonEvent> {
+        self.events.subscribe()
+    }
+
+    /// Send an event to every subscriber; dropped silently when nobody listens.
+    pub fn publish(&self, event: DaemonEvent) {
+        let _ = self.events.send(event);
+    }
+
     pub async fn set_node_info(&self, node_info: REST::NodeInfoResponse) {
         *self.node_info.write().await = Some(node_info);
     }
@@ -41,8 +265,29 @@ impl DaemonMemory {
         *self.all_nodes.write().await = Some(all_nodes);
     }
 
-    pub async fn set_wireguard_tunnels(&self, wireguard_tunnels: REST::WireguardTunnelsResponse) {
+    pub async fn set_wireguard_tunnels(
+        &self,
+        wireguard_tunnels: REST::WireguardTunnelsResponse,
+        etag: Option<String>,
+    ) {
         *self.wireguard_tunnels.write().await = Some(wireguard_tunnels);
+        *self.wireguard_tunnels_etag.write().await = etag;
+    }
+
+    pub async fn get_wireguard_tunnels(&self) -> Option<REST::WireguardTunnelsResponse> {
+        self.wireguard_tunnels.read().await.clone()
+    }
+
+    pub async fn get_wireguard_tunnels_etag(&self) -> Option<String> {
+        self.wireguard_tunnels_etag.read().await.clone()
+    }
+
+    pub async fn set_poll_interval(&self, interval: Duration) {
+        *self.poll_interval.write().await = Some(interval);
+    }
+
+    pub async fn get_poll_interval(&self) -> Option<Duration> {
+        *self.poll_interval.read().await
     }
 
     pub async fn set_last_poll_error(&self, error: Option<String>) {
@@ -53,71 +298,416 @@ impl DaemonMemory {
         self.last_poll_error.read().await.clone()
     }
 
+    pub async fn set_network_status(&self, status: NetworkStatus) {
+        *self.network_status.write().await = status;
+    }
+
+    pub async fn get_network_status(&self) -> NetworkStatus {
+        self.network_status.read().await.clone()
+    }
+
     pub async fn wireguard_len(&self) -> usize {
         self.wireguard.lock().await.len()
     }
 
-    pub async fn reconcile_wireguard_tunnels(
+    /// IDs of the active tunnels, sorted.
+    pub async fn tunnel_ids(&self) -> Vec<i32> {
+        let mut ids: Vec<i32> = self.wireguard.lock().await.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Bring the active tunnels in line with the server's list, touching only tunnels that changed.
+    /// Unchanged tunnels are only re-activated if their interface disappeared; those count as updates.
+    pub async fn reconcile(
         &self,
-        snapshot: &REST::WireguardTunnelsResponse,
+        desired: Vec<REST::WireguardTunnelInfo>,
         local_private_key: &str,
-    ) -> Result<(), String> {
+    ) -> Result<ReconcileOps, String> {
         let mut active = self.wireguard.lock().await;
-        let desired_ids: HashSet<i32> = snapshot.tunnels.iter().map(|t| t.tunnel_id).collect();
         let memory_arc = Arc::new(self.clone());
+        let mut adoptable = std::mem::take(&mut *self.adoptable.lock().await);
+        let had_snapshot = !adoptable.is_empty();
+        let local_public_key = snapshot::public_key_of(local_private_key).unwrap_or_default();
 
-        for tunnel in &snapshot.tunnels {
-            let is_ready = matches!(tunnel.local_answered, WireguardAnswered::Answered)
-                && matches!(tunnel.remote_response, WireguardAnswered::Answered);
-            if !is_ready {
-                continue;
-            }
+        let mut ops = {
+            let applied: HashMap<i32, Option<&REST::WireguardTunnelInfo>> = active
+                .iter()
+                .map(|(id, tunnel)| (*id, tunnel.get_applied_rest_info()))
+                .collect();
+            plan_reconcile(&applied, &desired)
+        };
+        let mut desired: HashMap<i32, Arc<REST::WireguardTunnelInfo>> =
+            desired.into_iter().map(|t| (t.tunnel_id, Arc::new(t))).collect();
 
-            let tunnel_arc = Arc::new(tunnel.clone());
-            if let Some(existing) = active.get_mut(&tunnel.tunnel_id) {
-                existing
-                    .update_from_rest(tunnel_arc, memory_arc.clone())
+        let mut adopted = Vec::new();
+        let mut failed = Vec::new();
+        for tunnel_id in &ops.create {
+            let tunnel_arc = desired.remove(tunnel_id).expect("planned from desired");
+            let adoption = match adoptable.remove(tunnel_id) {
+                Some(saved) if saved.can_adopt(&tunnel_arc, &local_public_key) => {
+                    wireguard::WireguardTunnelC::adopt(
+                        tunnel_arc.clone(),
+                        local_private_key.to_string(),
+                        saved.port,
+                        memory_arc.clone(),
+                    )
                     .await
-                    .map_err(|e| format!("failed to update tunnel {}: {}", tunnel.tunnel_id, e))?;
-                existing
-                    .activate()
+                    .unwrap_or_else(|e| {
+                        eprintln!("[daemon] cannot adopt interface of tunnel {}, rebuilding it: {}", tunnel_id, e);
+                        None
+                    })
+                }
+                _ => None,
+            };
+
+            let new_tunnel: Box<dyn ManagedTunnel> = match adoption {
+                Some(tunnel) => {
+                    adopted.push(*tunnel_id);
+                    Box::new(tunnel)
+                }
+                None => {
+                    // one tunnel that can't be built must not hold up the rest of the plan
+                    let mut new_tunnel: Box<dyn ManagedTunnel> = match wireguard::WireguardTunnelC::new_from_rest(
+                        tunnel_arc,
+                        local_private_key.to_string(),
+                        memory_arc.clone(),
+                    )
                     .await
-                    .map_err(|e| format!("failed to activate tunnel {}: {}", tunnel.tunnel_id, e))?;
-                continue;
-            }
+                    {
+                        Ok((new_tunnel, _port)) => Box::new(new_tunnel),
+                        Err(e) => {
+                            eprintln!("[daemon] failed to create tunnel {}, skipping it: {}", tunnel_id, e);
+                            failed.push(*tunnel_id);
+                            continue;
+                        }
+                    };
 
-            let (mut new_tunnel, _port) = wireguard::WireguardTunnelC::new_from_rest(
-                tunnel_arc,
-                local_private_key.to_string(),
-                memory_arc.clone(),
-            )
-            .await
-            .map_err(|e| format!("failed to create tunnel {}: {}", tunnel.tunnel_id, e))?;
+                    if let Err(e) = new_tunnel.activate().await.map_err(|e| e.to_string()) {
+                        eprintln!("[daemon] failed to setup tunnel {}, skipping it: {}", tunnel_id, e);
+                        self.port_mgmt.release(new_tunnel.get_public_port());
+                        if let Err(e) = new_tunnel.teardown().await {
+                            eprintln!("[daemon] failed to teardown tunnel {}: {}", tunnel_id, e);
+                        }
+                        failed.push(*tunnel_id);
+                        continue;
+                    }
+                    new_tunnel
+                }
+            };
+            self.publish(DaemonEvent::TunnelCreated {
+                tunnel_id: *tunnel_id,
+                interface: new_tunnel.os_tunnel().get_interface_name().to_string(),
+            });
+            active.insert(*tunnel_id, new_tunnel);
+        }
 
-            new_tunnel
+        for tunnel_id in &ops.update {
+            let tunnel_arc = desired.remove(tunnel_id).expect("planned from desired");
+            let existing = active.get_mut(tunnel_id).expect("planned from active");
+            existing
+                .update_from_rest(tunnel_arc, memory_arc.clone())
+                .await
+                .map_err(|e| format!("failed to update tunnel {}: {}", tunnel_id, e))?;
+            existing
                 .activate()
                 .await
-                .map_err(|e| format!("failed to setup tunnel {}: {}", tunnel.tunnel_id, e))?;
-            active.insert(tunnel.tunnel_id, new_tunnel);
+                .map_err(|e| format!("failed to activate tunnel {}: {}", tunnel_id, e))?;
         }
 
-        let stale_ids: Vec<i32> = active
-            .keys()
-            .copied()
-            .filter(|id| !desired_ids.contains(id))
-            .collect();
+        // update_from_rest only reconfigures the peer when the resolved address differs
+        for tunnel_id in &ops.refresh {
+            let tunnel_arc = desired.remove(tunnel_id).expect("planned from desired");
+            let existing = active.get_mut(tunnel_id).expect("planned from active");
+            if let Err(e) = existing.update_from_rest(tunnel_arc, memory_arc.clone()).await.map_err(|e| e.to_string()) {
+                eprintln!("[daemon] failed to refresh endpoint of tunnel {}: {}", tunnel_id, e);
+            }
+        }
 
-        for stale_id in stale_ids {
-            if let Some(mut stale) = active.remove(&stale_id) {
-                if let Some(port) = stale.get_listen_port() {
-                    self.port_mgmt.release(port);
-                }
+        let mut restored = Vec::new();
+        for tunnel_id in &ops.unchanged {
+            let existing = active.get_mut(tunnel_id).expect("planned from active");
+            if existing.os_tunnel().is_ift_created() {
+                continue;
+            }
+            eprintln!("[daemon] interface of tunnel {} disappeared, recreating it", tunnel_id);
+            existing
+                .activate()
+                .await
+                .map_err(|e| format!("failed to activate tunnel {}: {}", tunnel_id, e))?;
+            restored.push(*tunnel_id);
+        }
+        ops.unchanged.retain(|id| !restored.contains(id));
+        ops.update.extend(restored);
+        ops.update.sort_unstable();
+
+        for stale_id in &ops.remove {
+            if let Some(mut stale) = active.remove(stale_id) {
+                self.port_mgmt.release(stale.get_public_port());
                 if let Err(e) = stale.teardown().await {
                     eprintln!("[daemon] failed to teardown stale tunnel {}: {}", stale_id, e);
                 }
+                self.publish(DaemonEvent::TunnelDestroyed { tunnel_id: *stale_id });
             }
         }
 
-        Ok(())
+        ops.create.retain(|id| !adopted.contains(id) && !failed.contains(id));
+        ops.adopt = adopted;
+        ops.failed = failed;
+        if had_snapshot || !(ops.create.is_empty() && ops.adopt.is_empty() && ops.update.is_empty() && ops.remove.is_empty()) {
+            self.save_snapshot(&active, &local_public_key);
+        }
+
+        Ok(ops)
+    }
+
+    /// Switch every active tunnel to `local_private_key`. Returns how many failed; failures
+    /// are logged and the rest still switch.
+    pub async fn switch_private_key(&self, local_private_key: &str) -> usize {
+        let mut active = self.wireguard.lock().await;
+        let mut failed = 0;
+        for (tunnel_id, tunnel) in active.iter_mut() {
+            if let Err(e) = tunnel.set_local_private_key(local_private_key.to_string()).await {
+                eprintln!("[daemon] failed to switch tunnel {} to the new WireGuard key: {}", tunnel_id, e);
+                failed += 1;
+            }
+        }
+        self.save_snapshot(&active, &snapshot::public_key_of(local_private_key).unwrap_or_default());
+        failed
+    }
+
+    /// Delete WireGuard interfaces that match our naming convention but belong to no active
+    /// tunnel. Returns how many were removed; failures are logged and skipped.
+    pub async fn cleanup_orphan_interfaces(&self) -> Result<usize, Box<dyn Error>> {
+        let active: HashSet<String> = self
+            .wireguard
+            .lock()
+            .await
+            .values()
+            .map(|tunnel| tunnel.os_tunnel().get_interface_name().to_string())
+            .collect();
+
+        let links = crate::interface::list_link_names().await?;
+        let mut removed = 0;
+        for (name, info) in orphan_interfaces(links, &active) {
+            match crate::interface::delete_link(name.clone()).await {
+                Ok(()) => {
+                    eprintln!("[daemon] removed orphan interface {} (tunnel {})", name, info.tunnel_id);
+                    removed += 1;
+                }
+                Err(e) => eprintln!("[daemon] failed to remove orphan interface {}: {}", name, e),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Health of every active tunnel, ordered by tunnel ID. Tunnels whose device can't be read are logged and skipped.
+    pub async fn get_tunnel_stats(&self) -> Vec<TunnelStats> {
+        let active = self.wireguard.lock().await;
+        let mut stats: Vec<_> = active
+            .values()
+            .filter_map(|tunnel| match tunnel.get_stats() {
+                Ok(stats) => Some(stats),
+                Err(e) => {
+                    eprintln!("[daemon] failed to read stats of tunnel {}: {}", tunnel.get_tunnel_id(), e);
+                    None
+                }
+            })
+            .collect();
+        stats.sort_by_key(|s| s.tunnel_id);
+        stats
+    }
+
+    /// Assemble the WireGuard configuration of an active tunnel for export.
+    pub async fn get_tunnel_config(
+        &self,
+        tunnel_id: i32,
+        show_private_key: bool,
+    ) -> Option<TunnelConfig> {
+        // the OS tunnel's peer is the local shim when FEC/FakeTCP are on, so take the
+        // published endpoint from the last server snapshot instead
+        let endpoint = self
+            .wireguard_tunnels
+            .read()
+            .await
+            .as_ref()
+            .and_then(|s| s.tunnels.iter().find(|t| t.tunnel_id == tunnel_id))
+            .and_then(|t| t.remote_endpoint.clone());
+
+        let active = self.wireguard.lock().await;
+        Some(active.get(&tunnel_id)?.get_config(show_private_key, endpoint))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shared with the submodules' tests.
+    pub(super) fn tunnel_info(tunnel_id: i32) -> REST::WireguardTunnelInfo {
+        REST::WireguardTunnelInfo {
+            tunnel_id,
+            peer_node_id: tunnel_id + 100,
+            public_key: format!("peer-key-{}", tunnel_id),
+            preferred_port: 51820,
+            remote_endpoint: Some("198.51.100.7:51820".to_string()),
+            local_answered: WireguardAnswered::Answered,
+            remote_response: WireguardAnswered::Answered,
+            mtu: 1420,
+            endpoint_ipv6: false,
+            fec: false,
+            faketcp: false,
+            keepalive: Some(25),
+            allowed_ips: Vec::new(),
+            local_overlay_addr: None,
+            peer_overlay_addr: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn applied(infos: &[REST::WireguardTunnelInfo]) -> HashMap<i32, Option<&REST::WireguardTunnelInfo>> {
+        infos.iter().map(|t| (t.tunnel_id, Some(t))).collect()
+    }
+
+    #[test]
+    fn test_plan_reconcile_add_only() {
+        let current = vec![tunnel_info(1)];
+        let mut pending = tunnel_info(3);
+        pending.remote_response = WireguardAnswered::Unanswered;
+        let desired = vec![tunnel_info(1), tunnel_info(2), pending];
+
+        let ops = plan_reconcile(&applied(&current), &desired);
+        assert_eq!(ops, ReconcileOps { create: vec![2], unchanged: vec![1], ..Default::default() });
+    }
+
+    #[test]
+    fn test_plan_reconcile_remove_only() {
+        let current = vec![tunnel_info(1), tunnel_info(2)];
+        let desired = vec![tunnel_info(2)];
+
+        let ops = plan_reconcile(&applied(&current), &desired);
+        assert_eq!(ops, ReconcileOps { remove: vec![1], unchanged: vec![2], ..Default::default() });
+    }
+
+    #[test]
+    fn test_plan_reconcile_no_op() {
+        let current = vec![tunnel_info(1), tunnel_info(2)];
+        let mut desired = current.clone();
+        // bookkeeping-only changes must not bounce the interface
+        desired[0].updated_at = 42;
+        desired[1].preferred_port = 51999;
+
+        let ops = plan_reconcile(&applied(&current), &desired);
+        assert_eq!(ops, ReconcileOps { unchanged: vec![1, 2], ..Default::default() });
+    }
+
+    #[test]
+    fn test_plan_reconcile_updates_changed_and_unknown() {
+        let current = vec![tunnel_info(1), tunnel_info(2)];
+        let mut desired = current.clone();
+        desired[0].mtu = 1380;
+        let mut active = applied(&current);
+        active.insert(3, None);
+        desired.push(tunnel_info(3));
+
+        let ops = plan_reconcile(&active, &desired);
+        assert_eq!(ops, ReconcileOps { update: vec![1, 3], unchanged: vec![2], ..Default::default() });
+    }
+
+    #[test]
+    fn test_plan_reconcile_refreshes_hostname_endpoints() {
+        let mut current = vec![tunnel_info(1), tunnel_info(2), tunnel_info(3)];
+        current[0].remote_endpoint = Some("peer.example.com:51820".to_string());
+        current[2].remote_endpoint = Some("[2001:db8::7]:51820".to_string());
+        let desired = current.clone();
+
+        let ops = plan_reconcile(&applied(&current), &desired);
+        assert_eq!(ops, ReconcileOps { unchanged: vec![1, 2, 3], refresh: vec![1], ..Default::default() });
+    }
+
+    #[test]
+    fn test_orphan_interfaces() {
+        let active_tunnel = wireguard::WireguardTunnelC::interface_name(&tunnel_info(1)).unwrap();
+        let stale_tunnel = wireguard::WireguardTunnelC::interface_name(&tunnel_info(2)).unwrap();
+        let links = vec![
+            "lo".to_string(),
+            "eth0".to_string(),
+            "catnotours".to_string(),
+            active_tunnel.clone(),
+            stale_tunnel.clone(),
+        ];
+
+        let orphans = orphan_interfaces(links, &HashSet::from([active_tunnel]));
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].0, stale_tunnel);
+        assert_eq!(orphans[0].1.tunnel_id, 2);
+        assert_eq!(orphans[0].1.peer_node_id, 102);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_skips_tunnel_it_cannot_name() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let memory = DaemonMemory::new(ClientConfig {
+            data_dir: dir.path().into(),
+            port_range: crate::config::PortRange { min: 40000, max: 40002 },
+            ..Default::default()
+        });
+        let key = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+
+        // tunnel 1 is set up where CAP_NET_ADMIN allows and fails harmlessly elsewhere; either
+        // way the out-of-range tunnel must not stop it from being tried
+        let ops = memory.reconcile(vec![tunnel_info(0x1_0000), tunnel_info(1)], key).await.unwrap();
+        assert!(ops.failed.contains(&0x1_0000));
+        assert!(!ops.create.contains(&0x1_0000));
+        assert_eq!(ops.create.len() + ops.failed.len(), 2, "{:?}", ops);
+
+        // tear down whatever was created; every port is free again afterwards
+        let ops = memory.reconcile(Vec::new(), key).await.unwrap();
+        assert!(ops.failed.is_empty());
+        assert_eq!(memory.wireguard_len().await, 0);
+        assert!(memory.port_mgmt.allocate(None).is_ok());
+        assert!(memory.port_mgmt.allocate(None).is_ok());
+    }
+
+    /// Deletes a test's dummy link however the test ends.
+    struct DummyLinkGuard(String);
+
+    impl Drop for DummyLinkGuard {
+        fn drop(&mut self) {
+            let name = self.0.clone();
+            // Drop can't await, and the test's own runtime can't be blocked on from inside it
+            let _ = std::thread::spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+                runtime.block_on(crate::interface::delete_link(name)).is_ok()
+            })
+            .join();
+        }
+    }
+
+    /// Needs CAP_NET_ADMIN and dummy link support; skipped where either is missing. Only the
+    /// test's own link is deleted, never other orphans on the host.
+    #[tokio::test]
+    async fn test_orphan_interfaces_finds_live_link() {
+        let name = wireguard::WireguardTunnelC::interface_name(&tunnel_info(4321)).unwrap();
+        let Ok((connection, handle, _)) = rtnetlink::new_connection() else {
+            return;
+        };
+        let conn_poll = tokio::spawn(connection);
+        let created = handle
+            .link()
+            .add(rtnetlink::LinkDummy::new(&name).build())
+            .execute()
+            .await;
+        conn_poll.abort();
+        if created.is_err() {
+            eprintln!("skipping: cannot create dummy link {}", name);
+            return;
+        }
+        let _guard = DummyLinkGuard(name.clone());
+
+        let links = crate::interface::list_link_names().await.unwrap();
+        let orphans = orphan_interfaces(links, &HashSet::new());
+        assert!(orphans.iter().any(|(orphan, info)| *orphan == name && info.tunnel_id == 4321));
     }
 }
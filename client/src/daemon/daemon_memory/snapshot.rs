@@ -0,0 +1,108 @@
+//! Active tunnels saved to `tunnels.json` in the data directory, so a restarted daemon can
+//! adopt the interfaces its predecessor left up instead of reconfiguring them.
+
+use std::{fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use cat4igp_shared::rest::client as REST;
+
+const SNAPSHOT_FILE: &str = "tunnels.json";
+
+/// One tunnel as it was applied to its interface.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TunnelSnapshot {
+    pub interface: String,
+    /// Public port allocated from the port range
+    pub port: u16,
+    /// Public key of the private key the interface was configured with
+    pub local_public_key: String,
+    pub info: REST::WireguardTunnelInfo,
+}
+
+impl TunnelSnapshot {
+    /// Whether the interface left behind already matches `desired`, so it can be taken over
+    /// as is. Tunnels with FEC or FakeTCP never qualify: those layers ran inside the old
+    /// daemon and died with it.
+    pub fn can_adopt(&self, desired: &REST::WireguardTunnelInfo, local_public_key: &str) -> bool {
+        !desired.fec
+            && !desired.faketcp
+            && self.local_public_key == local_public_key
+            && !super::tunnel_info_changed(&self.info, desired)
+    }
+}
+
+/// Public key of a base64 WireGuard private key; `None` if it doesn't parse.
+pub fn public_key_of(private_key: &str) -> Option<String> {
+    wireguard_control::Key::from_base64(private_key).ok().map(|key| key.get_public().to_base64())
+}
+
+/// The saved tunnels, or none if there is no snapshot. A file that doesn't parse is an
+/// `InvalidData` error.
+pub fn load(data_dir: &Path) -> io::Result<Vec<TunnelSnapshot>> {
+    let content = match fs::read_to_string(data_dir.join(SNAPSHOT_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Replace the snapshot through a temporary file, so a crash never leaves a truncated one.
+pub fn save(data_dir: &Path, tunnels: &[TunnelSnapshot]) -> io::Result<()> {
+    let content = serde_json::to_string_pretty(tunnels)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let tmp_path = data_dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, data_dir.join(SNAPSHOT_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::daemon_memory::tests::tunnel_info;
+
+    fn snapshot(tunnel_id: i32) -> TunnelSnapshot {
+        TunnelSnapshot {
+            interface: format!("cattest{}", tunnel_id),
+            port: 51820,
+            local_public_key: "local-pub".to_string(),
+            info: tunnel_info(tunnel_id),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(load(dir.path()).unwrap().is_empty());
+
+        save(dir.path(), &[snapshot(1), snapshot(2)]).unwrap();
+        let loaded = load(dir.path()).unwrap();
+        assert_eq!(loaded.iter().map(|s| s.info.tunnel_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(loaded[1].interface, "cattest2");
+        assert!(!dir.path().join("tunnels.json.tmp").exists());
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_is_invalid_data() {
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join(SNAPSHOT_FILE), "[{\"interface\":").unwrap();
+        assert_eq!(load(dir.path()).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_can_adopt() {
+        let saved = snapshot(1);
+        let mut desired = saved.info.clone();
+        // bookkeeping-only changes don't matter
+        desired.updated_at = 42;
+        assert!(saved.can_adopt(&desired, "local-pub"));
+        assert!(!saved.can_adopt(&desired, "rotated-pub"));
+
+        desired.mtu = 1380;
+        assert!(!saved.can_adopt(&desired, "local-pub"));
+
+        let mut fec = saved.clone();
+        fec.info.fec = true;
+        assert!(!fec.can_adopt(&fec.info, "local-pub"));
+    }
+}
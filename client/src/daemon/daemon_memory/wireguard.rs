@@ -1,11 +1,17 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 use std::{error::Error, sync::Arc};
 use base32::Alphabet::Crockford;
+use futures_util::future::BoxFuture;
 use cat4igp_shared::rest::client as REST;
 use cat4igp_libfec as FEC;
 
-use crate::tunnel::shared::Tunnel as _;
-use crate::daemon::daemon_memory::DaemonMemory;
+use crate::config::{FecConfig, IpStack};
+use crate::tunnel::faketcp::{self, FakeTcpBackend};
+use crate::tunnel::shared::Tunnel;
+use crate::tunnel::TunnelType;
+use crate::daemon::daemon_memory::{DaemonMemory, ManagedTunnel};
+use crate::daemon::protocol::{TunnelConfig, TunnelHealth, TunnelStats};
 
 pub struct WireguardTunnelC {
     tunnel_id: i32,
@@ -13,18 +19,37 @@ pub struct WireguardTunnelC {
     ipv6: bool,
     os_tun: crate::tunnel::wireguard::WireGuardTunnel,
     mtu: i32,
-    fec: Option<Arc<FEC::PeerEngine>>
+    fec: Option<Arc<FEC::PeerEngine>>,
+    faketcp: Option<Arc<dyn FakeTcpBackend>>,
+    /// Public port allocated from the port range (not necessarily WireGuard's listen port).
+    port: u16,
+    /// Link-local address assigned by the last successful `ensure_up`.
+    link_local: Option<IpAddr>,
+    /// Server description this tunnel was last built or updated from; `None` forces the next update.
+    applied: Option<Arc<REST::WireguardTunnelInfo>>,
+    /// No link-local address is assigned on IPv4-only hosts
+    ip_stack: IpStack,
+}
+
+/// Outer layers interposed between WireGuard and the network.
+struct Transport {
+    fec: Option<Arc<FEC::PeerEngine>>,
+    faketcp: Option<Arc<dyn FakeTcpBackend>>,
+    os_tun: crate::tunnel::wireguard::WireGuardTunnel,
 }
 
 impl WireguardTunnelC {
     /// DO NOT USE THIS DIRECTLY UNLESS YOU KNOW WHAT YOU ARE DOING. This is only for testing purposes. Use new_from_rest instead.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         tunnel_id: i32,
         peer_node_id: i32,
         ipv6: bool,
         mtu: i32,
         os_tun: crate::tunnel::wireguard::WireGuardTunnel,
-        fec: Option<Arc<FEC::PeerEngine>>
+        fec: Option<Arc<FEC::PeerEngine>>,
+        faketcp: Option<Arc<dyn FakeTcpBackend>>,
+        port: u16,
     ) -> Self {
         Self {
             tunnel_id,
@@ -32,7 +57,12 @@ impl WireguardTunnelC {
             ipv6,
             mtu,
             os_tun,
-            fec
+            fec,
+            faketcp,
+            port,
+            link_local: None,
+            applied: None,
+            ip_stack: IpStack::Dual,
         }
     }
 
@@ -41,14 +71,15 @@ impl WireguardTunnelC {
         local_private_key: String,
         daemon_memory: Arc<DaemonMemory>
     ) -> Result<(Self, u16), Box<dyn Error>> {
-        let port = daemon_memory.port_mgmt.allocate(Some(rest_info.preferred_port))?;
-        let fec_res = Self::gen_new_fec(rest_info.clone(), port).await?;
+        Self::validate_mtu(&rest_info)?;
 
-        let (fec, os_tun) = if let Some((fec, fec_listen_port, wg_port)) = fec_res {
-            let fec1 = fec.clone();
-            (Some(fec), Self::gen_new_wg_tunnel(rest_info.clone(), local_private_key, Some(fec1), wg_port, fec_listen_port))
-        } else {
-            (None, Self::gen_new_wg_tunnel(rest_info.clone(), local_private_key, None, port, 0))
+        let port = daemon_memory.port_mgmt.allocate(Some(rest_info.preferred_port))?;
+        let transport = match Self::gen_new_transport(rest_info.clone(), local_private_key, port, &daemon_memory.fec_config).await {
+            Ok(transport) => transport,
+            Err(e) => {
+                daemon_memory.port_mgmt.release(port);
+                return Err(e);
+            }
         };
 
         Ok((Self {
@@ -56,98 +87,241 @@ impl WireguardTunnelC {
             peer_node_id: rest_info.peer_node_id,
             ipv6: rest_info.endpoint_ipv6,
             mtu: rest_info.mtu,
-            fec,
-            os_tun,
+            fec: transport.fec,
+            faketcp: transport.faketcp,
+            os_tun: transport.os_tun,
+            port,
+            link_local: None,
+            applied: Some(rest_info),
+            ip_stack: daemon_memory.ip_stack,
         }, port))
     }
 
-    async fn gen_new_fec(rest_info: Arc<REST::WireguardTunnelInfo>, port: u16) -> Result<Option<(Arc<FEC::PeerEngine>, u16, u16)>, Box<dyn Error>> {
-        if rest_info.fec {
-            let local_bind_port = crate::network::ports::get_random_udp_port()?;
-            let local_app_port = crate::network::ports::get_random_udp_port()?;
+    /// Take over the interface a previous daemon left configured for `rest_info`, without
+    /// rebuilding it; `ensure_up` still checks its addresses, MTU and link state, which leaves
+    /// an interface that already matches untouched. `None` when that isn't possible (FEC or
+    /// FakeTCP, the port is taken or the interface is gone); the tunnel is then built normally.
+    pub async fn adopt(
+        rest_info: Arc<REST::WireguardTunnelInfo>,
+        local_private_key: String,
+        port: u16,
+        daemon_memory: Arc<DaemonMemory>
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        if rest_info.fec || rest_info.faketcp {
+            return Ok(None);
+        }
+        Self::validate_mtu(&rest_info)?;
 
-            let cfg = FEC::Config::new(
-                SocketAddr::new(if rest_info.endpoint_ipv6 {
-                    std::net::IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED)
-                } else {
-                    std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED)
-                }, port),
-                SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), local_bind_port),
-                SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), local_app_port),
-            );
-            Ok(Some((Arc::new(FEC::PeerEngine::start(cfg).await?), local_bind_port, local_app_port)))
-        } else {
-            Ok(None)
+        let allocated = daemon_memory.port_mgmt.allocate(Some(port))?;
+        if allocated != port {
+            daemon_memory.port_mgmt.release(allocated);
+            return Ok(None);
+        }
+        let remote_endpoint = Self::resolve_remote_endpoint(&rest_info).await;
+        let os_tun = match Self::gen_new_wg_tunnel(rest_info.clone(), local_private_key, remote_endpoint, port) {
+            Ok(os_tun) if os_tun.is_ift_created() => os_tun,
+            Ok(_) => {
+                daemon_memory.port_mgmt.release(port);
+                return Ok(None);
+            }
+            Err(e) => {
+                daemon_memory.port_mgmt.release(port);
+                return Err(e);
+            }
+        };
+
+        let mut tunnel = Self {
+            tunnel_id: rest_info.tunnel_id,
+            peer_node_id: rest_info.peer_node_id,
+            ipv6: rest_info.endpoint_ipv6,
+            mtu: rest_info.mtu,
+            fec: None,
+            faketcp: None,
+            os_tun,
+            port,
+            link_local: None,
+            applied: Some(rest_info),
+            ip_stack: daemon_memory.ip_stack,
+        };
+        if let Err(e) = tunnel.ensure_up().await {
+            daemon_memory.port_mgmt.release(port);
+            return Err(e);
         }
+        Ok(Some(tunnel))
     }
 
-    fn gen_new_wg_tunnel(
+    /// Build the layers between WireGuard and the network, outermost first:
+    /// FakeTCP (if enabled) owns the public port, then FEC (if enabled), then WireGuard.
+    /// Each inner layer talks to the next outer layer over loopback.
+    async fn gen_new_transport(
         rest_info: Arc<REST::WireguardTunnelInfo>,
         local_private_key: String,
-        fec: Option<Arc<FEC::PeerEngine>>,
         port: u16,
-        fec_peerport: u16
-    ) -> crate::tunnel::wireguard::WireGuardTunnel {
-        let mut bit_slice = [0u8; 8]; // 56 bits are required out of 64 bits.
-
-        // Protocol: 11100 (WireGuard)
-        bit_slice[0] |= 0b11100 << 3;
+        fec_config: &FecConfig,
+    ) -> Result<Transport, Box<dyn Error>> {
+        let unspecified = if rest_info.endpoint_ipv6 {
+            std::net::IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED)
+        } else {
+            std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED)
+        };
+        let remote_endpoint = Self::resolve_remote_endpoint(&rest_info).await;
 
-        // Peer node ID: 15 bits
-        let peer_node_id = rest_info.peer_node_id;
-        // take the 15 LSBs of the peer node ID
-        // byte 0 has 3 bits left, take the 3 MSBs of the peer node ID
-        bit_slice[0] |= ((peer_node_id >> 12) as u8) & 0b00000111;
-        // byte 1 takes the next 8 bits of the peer node ID
-        bit_slice[1] = (peer_node_id >> 4) as u8;
-        // byte 2 takes the last 4 bits of the peer node ID
-        bit_slice[2] = ((peer_node_id & 0b1111) as u8) << 4;
+        // Address the next inner layer binds to, and the peer it should send to.
+        let mut bind = SocketAddr::new(unspecified, port);
+        let mut peer = remote_endpoint;
 
-        // Start of protocol-specific data.
-        // First 3 bits indicates if tunnel uses IPv6, has FEC, and FakeTCP enabled.
-        if rest_info.endpoint_ipv6 {
-            bit_slice[2] |= 0b1000;
-        }
-        if rest_info.fec {
-            bit_slice[2] |= 0b0100;
-        }
-        if rest_info.faketcp {
-            bit_slice[2] |= 0b0010;
-        }
-        // Bit 4 is reserved for future use.
-
-        // Bit 5 to bit 20 are tunnel ID
-        let tunnel_id = rest_info.tunnel_id;
-        // take the 16 LSBs of the tunnel ID
-        // byte 3 takes the first 8 bits of the tunnel ID
-        bit_slice[3] = (tunnel_id >> 8) as u8;
-        // byte 4 takes the last 8 bits of the tunnel ID
-        bit_slice[4] = tunnel_id as u8;
+        let faketcp = if rest_info.faketcp {
+            let local_bind_port = crate::network::ports::get_random_udp_port()?;
+            let local_app_port = crate::network::ports::get_random_udp_port()?;
 
-        // Bit 21 to bit 36 are reserved for future use. Leave 0 for now.
+            let shim = faketcp::start(faketcp::FakeTcpConfig {
+                listen: bind,
+                local_bind: SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), local_bind_port),
+                local_app_endpoint: SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), local_app_port),
+                peer_addr: peer,
+            })
+            .await?;
+
+            bind = SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), local_app_port);
+            peer = Some(shim.local_bind_addr());
+            Some(shim)
+        } else {
+            None
+        };
 
-        let pend = if fec.is_some() {
-            Some(SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), fec_peerport))
-        } else if let Some(endpoint) = &rest_info.remote_endpoint {
-            endpoint.parse::<std::net::SocketAddr>().ok()
+        let fec = if rest_info.fec {
+            let (fec, fec_listen_port, wg_port) = Self::gen_new_fec(bind, peer, fec_config).await?;
+            bind = SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), wg_port);
+            peer = Some(SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), fec_listen_port));
+            Some(fec)
         } else {
             None
         };
 
-        crate::tunnel::wireguard::WireGuardTunnel::new(
-            format!(
-                "cat{}",
-                base32::encode(Crockford, bit_slice.as_slice())[..12].to_owned()
-            ),
+        let os_tun = Self::gen_new_wg_tunnel(rest_info.clone(), local_private_key, peer, bind.port())?;
+
+        Ok(Transport { fec, faketcp, os_tun })
+    }
+
+    async fn gen_new_fec(
+        bind: SocketAddr,
+        peer: Option<SocketAddr>,
+        fec_config: &FecConfig,
+    ) -> Result<(Arc<FEC::PeerEngine>, u16, u16), Box<dyn Error>> {
+        fec_config.validate()?;
+
+        let local_bind_port = crate::network::ports::get_random_udp_port()?;
+        let local_app_port = crate::network::ports::get_random_udp_port()?;
+
+        let mut cfg = FEC::Config::new(
+            bind,
+            SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), local_bind_port),
+            SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), local_app_port),
+        );
+        cfg.initial_peer_addr = peer;
+        cfg.fec_data_shards = fec_config.data_shards;
+        cfg.fec_parity_shards = fec_config.parity_shards;
+        cfg.fec_flush_timeout_ms = fec_config.flush_timeout_ms;
+        Ok((Arc::new(FEC::PeerEngine::start(cfg).await?), local_bind_port, local_app_port))
+    }
+
+    fn gen_new_wg_tunnel(
+        rest_info: Arc<REST::WireguardTunnelInfo>,
+        local_private_key: String,
+        endpoint: Option<SocketAddr>,
+        port: u16,
+    ) -> Result<crate::tunnel::wireguard::WireGuardTunnel, Box<dyn Error>> {
+        let mut tunnel = crate::tunnel::wireguard::WireGuardTunnel::new(
+            Self::interface_name(&rest_info)?,
             local_private_key,
             rest_info.public_key.clone(),
-            pend,
+            endpoint,
             if port == 0 {
                 None
             } else {
                 Some(port)
             },
-        )
+        );
+        tunnel.set_keepalive(rest_info.keepalive);
+        tunnel.set_allowed_ips(Self::parse_allowed_ips(&rest_info)?);
+        Ok(tunnel)
+    }
+
+    /// Reject MTUs the server should never have sent; casting them for netlink would wrap.
+    fn validate_mtu(rest_info: &REST::WireguardTunnelInfo) -> Result<(), Box<dyn Error>> {
+        if !cat4igp_shared::limits::is_valid_tunnel_mtu(rest_info.mtu) {
+            return Err(format!(
+                "MTU {} for tunnel {} is outside {}..={}",
+                rest_info.mtu,
+                rest_info.tunnel_id,
+                cat4igp_shared::limits::MIN_TUNNEL_MTU,
+                cat4igp_shared::limits::MAX_TUNNEL_MTU
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Resolve the peer endpoint, logging failures instead of silently dropping it.
+    async fn resolve_remote_endpoint(rest_info: &REST::WireguardTunnelInfo) -> Option<SocketAddr> {
+        let endpoint = rest_info.remote_endpoint.as_ref()?;
+        match crate::network::resolve::resolve_endpoint(endpoint, rest_info.endpoint_ipv6).await {
+            Ok(addr) => Some(addr),
+            Err(e) => {
+                eprintln!(
+                    "[daemon] failed to resolve endpoint {} for tunnel {}: {}",
+                    endpoint, rest_info.tunnel_id, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Invalid entries are logged and skipped rather than failing the whole tunnel, but a list
+    /// with no valid entry is an error: routing everything instead would hijack the host's traffic.
+    /// The peer's overlay address is added when the list doesn't already cover it; an empty list
+    /// routes everything anyway.
+    fn parse_allowed_ips(rest_info: &REST::WireguardTunnelInfo) -> Result<Vec<ipnet::IpNet>, Box<dyn Error>> {
+        let mut allowed_ips: Vec<ipnet::IpNet> = rest_info
+            .allowed_ips
+            .iter()
+            .filter_map(|ip| match ip.parse::<ipnet::IpNet>() {
+                Ok(net) => Some(net),
+                Err(_) => {
+                    eprintln!("[daemon] ignoring invalid allowed IP {} for tunnel {}", ip, rest_info.tunnel_id);
+                    None
+                }
+            })
+            .collect();
+        if allowed_ips.is_empty() && !rest_info.allowed_ips.is_empty() {
+            return Err(format!("tunnel {} has no valid allowed IP", rest_info.tunnel_id).into());
+        }
+
+        if !allowed_ips.is_empty()
+            && let Some(peer) = Self::parse_overlay_addr(rest_info.peer_overlay_addr.as_deref(), rest_info.tunnel_id)
+            && !allowed_ips.iter().any(|net| net.contains(&peer))
+        {
+            allowed_ips.push(peer.into());
+        }
+        Ok(allowed_ips)
+    }
+
+    /// An overlay address from the server; invalid ones are logged and ignored.
+    fn parse_overlay_addr(addr: Option<&str>, tunnel_id: i32) -> Option<IpAddr> {
+        let addr = addr?;
+        match addr.parse() {
+            Ok(addr) => Some(addr),
+            Err(_) => {
+                eprintln!("[daemon] ignoring invalid overlay address {} for tunnel {}", addr, tunnel_id);
+                None
+            }
+        }
+    }
+
+    /// This node's overlay address for the tunnel as last applied.
+    fn local_overlay_addr(&self) -> Option<IpAddr> {
+        let applied = self.applied.as_ref()?;
+        Self::parse_overlay_addr(applied.local_overlay_addr.as_deref(), applied.tunnel_id)
     }
 
     pub async fn update_from_rest(
@@ -160,67 +334,131 @@ impl WireguardTunnelC {
             return Err("Tunnel ID or peer node ID mismatch".into());
         }
 
+        Self::validate_mtu(&rest_info)?;
         self.mtu = rest_info.mtu;
-        if self.ipv6 != rest_info.endpoint_ipv6 {
-            // Completely destroy and recreate the tunnel because of name
-            let local_private_key = self.os_tun.get_local_private_key().to_string();
+        if self.ipv6 != rest_info.endpoint_ipv6
+            || self.fec.is_some() != rest_info.fec
+            || self.faketcp.is_some() != rest_info.faketcp
+            || self.os_tun.get_peer_public_key() != rest_info.public_key
+        {
+            // Completely destroy and recreate the tunnel because of name, transport layers or peer key
+            return self.rebuild_from_rest(rest_info, daemon_memory).await;
+        }
 
-            let ifcreated = self.os_tun.is_ift_created();
-            let _ = self.os_tun.destroy();
-            self.ipv6 = rest_info.endpoint_ipv6;
-            
+        let mut reconfigure = false;
+        let remote_endpoint = Self::resolve_remote_endpoint(&rest_info).await;
+        if let Some(endpoint) = remote_endpoint {
+            // The real endpoint lives in the outermost layer; WireGuard only talks to it over loopback.
+            if let Some(shim) = &self.faketcp {
+                if shim.get_peer_addr() != Some(endpoint) {
+                    shim.set_peer_addr(endpoint);
+                }
+            } else if let Some(fec) = &self.fec {
+                if fec.handle().get_peer_addr().await != Some(endpoint) {
+                    fec.handle().set_peer_addr(endpoint).await;
+                }
+            } else if self.os_tun.get_peer_endpoint() != Some(endpoint) {
+                self.os_tun.set_peer_endpoint(endpoint);
+                reconfigure = true;
+            }
+        }
 
-            let port = daemon_memory.port_mgmt.allocate(Some(rest_info.preferred_port))?;
-            let fec_res = Self::gen_new_fec(rest_info.clone(), port).await?;
+        if self.os_tun.get_keepalive() != rest_info.keepalive {
+            self.os_tun.set_keepalive(rest_info.keepalive);
+            reconfigure = true;
+        }
 
-            let (fec, os_tun) = if let Some((fec, fec_listen_port, wg_port)) = fec_res {
-                let fec1 = fec.clone();
-                (Some(fec), Self::gen_new_wg_tunnel(rest_info.clone(), local_private_key, Some(fec1), wg_port, fec_listen_port))
-            } else {
-                (None, Self::gen_new_wg_tunnel(rest_info.clone(), local_private_key, None, port, 0))
-            };
-
-            self.fec = fec;
-            self.os_tun = os_tun;
-        
-            if ifcreated {
-                self.os_tun.setup().await?;
-                self.ensure_up().await?;
-            }
+        let allowed_ips = Self::parse_allowed_ips(&rest_info)?;
+        let allowed_ips_changed = if allowed_ips.is_empty() {
+            self.os_tun.get_allowed_ips() != crate::tunnel::wireguard::default_allowed_ips()
+        } else {
+            self.os_tun.get_allowed_ips() != allowed_ips
+        };
+        if allowed_ips_changed {
+            self.os_tun.set_allowed_ips(allowed_ips);
+            reconfigure = true;
+        }
 
-            return Ok(());
+        if reconfigure && self.os_tun.is_ift_created() {
+            self.os_tun.reconfigure().await?;
         }
 
-        
-        // TODO: check for FEC, FakeTCP, and other WireGuard parameters.
+        let old_overlay = self.local_overlay_addr();
+        let new_overlay = Self::parse_overlay_addr(rest_info.local_overlay_addr.as_deref(), rest_info.tunnel_id);
+        if old_overlay != new_overlay && self.os_tun.is_ift_created() {
+            let ifname = self.os_tun.get_interface_name().to_string();
+            Self::ensure_overlay_addr(&ifname, old_overlay, new_overlay).await?;
+        }
+
+        // TODO: check for other WireGuard parameters.
+
+        self.applied = Some(rest_info);
+        Ok(())
+    }
+
+    async fn rebuild_from_rest(
+        &mut self,
+        rest_info: Arc<REST::WireguardTunnelInfo>,
+        daemon_memory: Arc<DaemonMemory>
+    ) -> Result<(), Box<dyn Error>> {
+        let local_private_key = self.os_tun.get_local_private_key().to_string();
+
+        let ifcreated = self.os_tun.is_ift_created();
+        if ifcreated {
+            let _ = self.os_tun.destroy().await;
+        }
+        // drop the old outer layers first so the public port can be reused
+        self.faketcp = None;
+        self.fec = None;
+        daemon_memory.port_mgmt.release(self.port);
+        self.ipv6 = rest_info.endpoint_ipv6;
+
+        let port = daemon_memory.port_mgmt.allocate(Some(rest_info.preferred_port))?;
+        let transport = match Self::gen_new_transport(rest_info.clone(), local_private_key, port, &daemon_memory.fec_config).await {
+            Ok(transport) => transport,
+            Err(e) => {
+                daemon_memory.port_mgmt.release(port);
+                return Err(e);
+            }
+        };
+
+        self.port = port;
+        self.fec = transport.fec;
+        self.faketcp = transport.faketcp;
+        self.os_tun = transport.os_tun;
+        self.link_local = None;
+        self.applied = Some(rest_info);
+
+        if ifcreated {
+            self.os_tun.setup().await?;
+            self.ensure_up().await?;
+        }
 
         Ok(())
     }
 
-    pub async fn activate(&mut self) -> Result<(), Box<dyn Error>> {
+    /// Create and bring up the interface, returning the link-local address assigned to it
+    /// (`None` on IPv4-only hosts).
+    pub async fn activate(&mut self) -> Result<Option<IpAddr>, Box<dyn Error>> {
         self.os_tun.setup().await?;
         self.ensure_up().await
     }
 
     pub async fn teardown(&mut self) -> Result<(), Box<dyn Error>> {
+        self.link_local = None;
         self.os_tun.destroy().await
     }
 
-    async fn ensure_up(&mut self) -> Result<(), Box<dyn Error>> {
+    async fn ensure_up(&mut self) -> Result<Option<IpAddr>, Box<dyn Error>> {
         let ifname = self.os_tun.get_interface_name().to_string();
-        
-        let llipv6 = crate::interface::generate_ipv6_lla_from_seed(ifname.as_bytes().to_vec());
-        let current_addrs = crate::interface::get_addr(ifname.clone()).await?;
-        let contain_current_addr = current_addrs.iter().find(|a| a.addr() == llipv6).is_some();
-        let filter_addrs: Vec<_> = current_addrs.into_iter().filter(|a| a.addr() != llipv6).collect();
 
-        if !contain_current_addr {
-            crate::interface::add_addr(ifname.clone(), llipv6.into()).await?;
-        }
-
-        for addr in filter_addrs {
-            crate::interface::del_addr(ifname.clone(), addr).await?;
-        }
+        let overlay = self.local_overlay_addr();
+        let llipv6 = if self.ip_stack.has_ipv6() {
+            Some(Self::ensure_link_local(&ifname, overlay).await?)
+        } else {
+            None
+        };
+        Self::ensure_overlay_addr(&ifname, None, overlay).await?;
 
         let current_mtu = self.os_tun.get_mtu().await.ok();
         if let Some(current_mtu) = current_mtu {
@@ -236,11 +474,87 @@ impl WireguardTunnelC {
             crate::interface::link_up_with_mtu(ifname.clone(), self.mtu as u32).await?;
         }
 
+        self.link_local = llipv6;
+        Ok(llipv6)
+    }
+
+    /// Assign the interface's IPv6 link-local address, removing any others except `keep`.
+    async fn ensure_link_local(ifname: &str, keep: Option<IpAddr>) -> Result<IpAddr, Box<dyn Error>> {
+        let ifname = ifname.to_string();
+
+        // Avoid addresses already used by other links, otherwise DAD fails and leaves us without one.
+        let taken: Vec<IpAddr> = crate::interface::get_addr_on_other_links(ifname.clone())
+            .await?
+            .into_iter()
+            .map(|a| a.addr())
+            .collect();
+        // Prefer the standard EUI-64 address when the link has a MAC; WireGuard links don't.
+        let mac_lla = crate::interface::get_mac(ifname.clone())
+            .await?
+            .map(crate::interface::generate_ipv6_lla_from_mac)
+            .filter(|a| !taken.contains(a));
+        let llipv6 = match mac_lla {
+            Some(addr) => addr,
+            None => {
+                let preferred = crate::interface::generate_ipv6_lla_from_seed(ifname.as_bytes().to_vec());
+                let addr = crate::interface::generate_ipv6_lla_avoiding(ifname.as_bytes(), &taken)
+                    .ok_or("no conflict-free link-local address available")?;
+                if addr != preferred {
+                    eprintln!(
+                        "[daemon] link-local address {} for {} is already in use, falling back to {}",
+                        preferred, ifname, addr
+                    );
+                }
+                addr
+            }
+        };
+
+        let current_addrs = crate::interface::get_addr(ifname.clone()).await?;
+        let contain_current_addr = current_addrs.iter().find(|a| a.addr() == llipv6).is_some();
+        let filter_addrs: Vec<_> = current_addrs
+            .into_iter()
+            .filter(|a| a.addr() != llipv6 && Some(a.addr()) != keep)
+            .collect();
+
+        if !contain_current_addr {
+            crate::interface::add_addr(ifname.clone(), llipv6.into()).await?;
+        }
+
+        for addr in filter_addrs {
+            crate::interface::del_addr(ifname.clone(), addr).await?;
+        }
+
+        Ok(llipv6)
+    }
+
+    /// Move the interface from overlay address `old` to `new`, each as a host address so
+    /// tunnels in the same mesh don't install competing subnet routes. The link-local
+    /// address is left alone.
+    async fn ensure_overlay_addr(
+        ifname: &str,
+        old: Option<IpAddr>,
+        new: Option<IpAddr>,
+    ) -> Result<(), Box<dyn Error>> {
+        let current = crate::interface::get_addr(ifname.to_string()).await?;
+        if let Some(old) = old.filter(|old| Some(*old) != new)
+            && let Some(assigned) = current.iter().find(|a| a.addr() == old)
+        {
+            crate::interface::del_addr(ifname.to_string(), *assigned).await?;
+        }
+        if let Some(new) = new
+            && !current.iter().any(|a| a.addr() == new)
+        {
+            crate::interface::add_addr(ifname.to_string(), new.into()).await?;
+        }
         Ok(())
     }
 
-    pub fn get_tunnel_id(&self) -> i32 {
-        self.tunnel_id
+    pub async fn set_local_private_key(&mut self, local_private_key: String) -> Result<(), Box<dyn Error>> {
+        self.os_tun.set_local_private_key(local_private_key);
+        if self.os_tun.is_ift_created() {
+            self.os_tun.reconfigure().await?;
+        }
+        Ok(())
     }
 
     pub fn get_peer_node_id(&self) -> i32 {
@@ -262,4 +576,433 @@ impl WireguardTunnelC {
     pub fn get_listen_port(&self) -> Option<u16> {
         self.os_tun.get_listen_port()
     }
-}
\ No newline at end of file
+
+    pub fn get_link_local_addr(&self) -> Option<IpAddr> {
+        self.link_local
+    }
+
+    pub fn get_mtu(&self) -> i32 {
+        self.mtu
+    }
+
+    pub fn has_fec(&self) -> bool {
+        self.fec.is_some()
+    }
+
+    pub fn has_faketcp(&self) -> bool {
+        self.faketcp.is_some()
+    }
+
+    fn classify_health(handshake_age: Option<Duration>) -> TunnelHealth {
+        match handshake_age {
+            None => TunnelHealth::NeverConnected,
+            Some(age) if age <= crate::tunnel::wireguard::HANDSHAKE_TIMEOUT => TunnelHealth::Healthy,
+            Some(_) => TunnelHealth::Stale,
+        }
+    }
+}
+
+impl ManagedTunnel for WireguardTunnelC {
+    fn get_tunnel_id(&self) -> i32 {
+        self.tunnel_id
+    }
+
+    fn os_tunnel(&self) -> &dyn Tunnel {
+        &self.os_tun
+    }
+
+    fn get_public_port(&self) -> u16 {
+        self.port
+    }
+
+    fn get_applied_rest_info(&self) -> Option<&REST::WireguardTunnelInfo> {
+        self.applied.as_deref()
+    }
+
+    fn activate(&mut self) -> BoxFuture<'_, Result<Option<IpAddr>, Box<dyn Error>>> {
+        Box::pin(WireguardTunnelC::activate(self))
+    }
+
+    fn teardown(&mut self) -> BoxFuture<'_, Result<(), Box<dyn Error>>> {
+        Box::pin(WireguardTunnelC::teardown(self))
+    }
+
+    fn update_from_rest(
+        &mut self,
+        rest_info: Arc<REST::WireguardTunnelInfo>,
+        daemon_memory: Arc<DaemonMemory>,
+    ) -> BoxFuture<'_, Result<(), Box<dyn Error>>> {
+        Box::pin(WireguardTunnelC::update_from_rest(self, rest_info, daemon_memory))
+    }
+
+    fn set_local_private_key(&mut self, local_private_key: String) -> BoxFuture<'_, Result<(), Box<dyn Error>>> {
+        Box::pin(WireguardTunnelC::set_local_private_key(self, local_private_key))
+    }
+
+    /// Handshake health and traffic totals, read from the WireGuard device.
+    fn get_stats(&self) -> Result<TunnelStats, Box<dyn Error>> {
+        let peer_stats = self.os_tun.peer_stats()?;
+        let handshake_age = peer_stats
+            .as_ref()
+            .and_then(|s| s.last_handshake_time)
+            .map(|t| t.elapsed().unwrap_or_default());
+
+        Ok(TunnelStats {
+            tunnel_id: self.tunnel_id,
+            peer_node_id: self.peer_node_id,
+            interface: self.os_tun.get_interface_name().to_string(),
+            health: Self::classify_health(handshake_age),
+            last_handshake_secs: handshake_age.map(|age| age.as_secs()),
+            rx_bytes: peer_stats.as_ref().map_or(0, |s| s.rx_bytes),
+            tx_bytes: peer_stats.as_ref().map_or(0, |s| s.tx_bytes),
+        })
+    }
+
+    fn get_config(&self, show_private_key: bool, endpoint: Option<String>) -> TunnelConfig {
+        TunnelConfig {
+            tunnel_id: self.tunnel_id,
+            interface: self.os_tun.get_interface_name().to_string(),
+            private_key: show_private_key.then(|| self.os_tun.get_local_private_key().to_string()),
+            listen_port: self.os_tun.get_listen_port(),
+            mtu: self.mtu,
+            peer_public_key: self.os_tun.get_peer_public_key().to_string(),
+            endpoint,
+            allowed_ips: self.os_tun.get_allowed_ips().iter().map(|ip| ip.to_string()).collect(),
+            persistent_keepalive: self.os_tun.get_keepalive(),
+            fec: self.fec.is_some(),
+            faketcp: self.faketcp.is_some(),
+        }
+    }
+}
+
+const INTERFACE_NAME_PREFIX: &str = "cat";
+/// Bytes packed into a name; 56 bits are used out of 64.
+const INTERFACE_NAME_BYTES: usize = 8;
+/// Base32 characters kept after the prefix (60 bits, covering the 56 used ones).
+const INTERFACE_NAME_ENCODED_LEN: usize = 12;
+
+/// Largest peer node ID a name can hold (15 bits)
+const MAX_NAMED_PEER_NODE_ID: i32 = 0x7fff;
+/// Largest tunnel ID a name can hold (16 bits)
+const MAX_NAMED_TUNNEL_ID: i32 = 0xffff;
+
+// unpadded base32 yields one character per started 5 bits
+const _: () = assert!((INTERFACE_NAME_BYTES * 8).div_ceil(5) >= INTERFACE_NAME_ENCODED_LEN);
+const _: () = assert!(
+    INTERFACE_NAME_PREFIX.len() + INTERFACE_NAME_ENCODED_LEN <= crate::interface::MAX_INTERFACE_NAME_LEN
+);
+
+/// What an interface name encodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TunnelDescriptor {
+    pub protocol: TunnelType,
+    /// Peer node ID, at most [`MAX_NAMED_PEER_NODE_ID`]
+    pub peer_node_id: i32,
+    /// Tunnel ID, at most [`MAX_NAMED_TUNNEL_ID`]
+    pub tunnel_id: i32,
+    pub endpoint_ipv6: bool,
+    pub fec: bool,
+    pub faketcp: bool,
+}
+
+impl WireguardTunnelC {
+    /// Interface name of a WireGuard tunnel, see docs/INTERFACE_NAMING_CONVENTION.md. IDs the
+    /// name has no room for are an error; truncating them would give two tunnels one name.
+    pub fn interface_name(rest_info: &REST::WireguardTunnelInfo) -> Result<String, Box<dyn Error>> {
+        if !(0..=MAX_NAMED_PEER_NODE_ID).contains(&rest_info.peer_node_id) {
+            return Err(format!(
+                "peer node ID {} does not fit an interface name (0 to {})",
+                rest_info.peer_node_id, MAX_NAMED_PEER_NODE_ID
+            )
+            .into());
+        }
+        if !(0..=MAX_NAMED_TUNNEL_ID).contains(&rest_info.tunnel_id) {
+            return Err(format!(
+                "tunnel ID {} does not fit an interface name (0 to {})",
+                rest_info.tunnel_id, MAX_NAMED_TUNNEL_ID
+            )
+            .into());
+        }
+
+        let mut bit_slice = [0u8; INTERFACE_NAME_BYTES];
+
+        // Protocol: 11100 (WireGuard)
+        bit_slice[0] |= 0b11100 << 3;
+
+        // Peer node ID: 15 bits
+        let peer_node_id = rest_info.peer_node_id;
+        // byte 0 has 3 bits left, take the 3 MSBs of the peer node ID
+        bit_slice[0] |= ((peer_node_id >> 12) as u8) & 0b00000111;
+        // byte 1 takes the next 8 bits of the peer node ID
+        bit_slice[1] = (peer_node_id >> 4) as u8;
+        // byte 2 takes the last 4 bits of the peer node ID
+        bit_slice[2] = ((peer_node_id & 0b1111) as u8) << 4;
+
+        // Start of protocol-specific data.
+        // First 3 bits indicates if tunnel uses IPv6, has FEC, and FakeTCP enabled.
+        if rest_info.endpoint_ipv6 {
+            bit_slice[2] |= 0b1000;
+        }
+        if rest_info.fec {
+            bit_slice[2] |= 0b0100;
+        }
+        if rest_info.faketcp {
+            bit_slice[2] |= 0b0010;
+        }
+        // Bit 4 is reserved for future use.
+
+        // Bit 5 to bit 20 are tunnel ID
+        let tunnel_id = rest_info.tunnel_id;
+        // byte 3 takes the first 8 bits of the tunnel ID
+        bit_slice[3] = (tunnel_id >> 8) as u8;
+        // byte 4 takes the last 8 bits of the tunnel ID
+        bit_slice[4] = tunnel_id as u8;
+
+        // Bit 21 to bit 36 are reserved for future use. Leave 0 for now.
+
+        let encoded = base32::encode(Crockford, bit_slice.as_slice());
+        let encoded = encoded.get(..INTERFACE_NAME_ENCODED_LEN).ok_or_else(|| {
+            format!("base32 encoding {:?} is shorter than {} characters", encoded, INTERFACE_NAME_ENCODED_LEN)
+        })?;
+        let name = format!("{}{}", INTERFACE_NAME_PREFIX, encoded);
+        crate::interface::validate_interface_name(&name)?;
+        Ok(name)
+    }
+
+    /// Decode a name produced by `interface_name`; `None` for anything else, including names of
+    /// other cat4igp protocol types.
+    pub fn parse_interface_name(name: &str) -> Option<TunnelDescriptor> {
+        let encoded = name.strip_prefix(INTERFACE_NAME_PREFIX)?;
+        if encoded.len() != INTERFACE_NAME_ENCODED_LEN {
+            return None;
+        }
+        let bits = base32::decode(Crockford, encoded)?;
+        if bits.len() < 5 || bits[0] >> 3 != 0b11100 {
+            return None;
+        }
+
+        Some(TunnelDescriptor {
+            protocol: TunnelType::WireGuard,
+            peer_node_id: (((bits[0] & 0b111) as i32) << 12) | ((bits[1] as i32) << 4) | ((bits[2] >> 4) as i32),
+            endpoint_ipv6: bits[2] & 0b1000 != 0,
+            fec: bits[2] & 0b0100 != 0,
+            faketcp: bits[2] & 0b0010 != 0,
+            tunnel_id: ((bits[3] as i32) << 8) | bits[4] as i32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_health() {
+        assert_eq!(WireguardTunnelC::classify_health(None), TunnelHealth::NeverConnected);
+        assert_eq!(WireguardTunnelC::classify_health(Some(Duration::from_secs(5))), TunnelHealth::Healthy);
+        assert_eq!(WireguardTunnelC::classify_health(Some(Duration::from_secs(180))), TunnelHealth::Healthy);
+        assert_eq!(WireguardTunnelC::classify_health(Some(Duration::from_secs(181))), TunnelHealth::Stale);
+    }
+
+    fn rest_info(tunnel_id: i32, peer_node_id: i32) -> REST::WireguardTunnelInfo {
+        REST::WireguardTunnelInfo {
+            tunnel_id,
+            peer_node_id,
+            public_key: String::new(),
+            preferred_port: 51820,
+            remote_endpoint: None,
+            local_answered: cat4igp_shared::custom_type::WireguardAnswered::Answered,
+            remote_response: cat4igp_shared::custom_type::WireguardAnswered::Answered,
+            mtu: 1420,
+            endpoint_ipv6: false,
+            fec: false,
+            faketcp: false,
+            keepalive: None,
+            allowed_ips: Vec::new(),
+            local_overlay_addr: None,
+            peer_overlay_addr: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_allowed_ips_adds_peer_overlay_addr() {
+        let mut info = rest_info(1, 2);
+        info.peer_overlay_addr = Some("10.20.30.2".to_string());
+        // empty already routes everything
+        assert!(WireguardTunnelC::parse_allowed_ips(&info).unwrap().is_empty());
+
+        info.allowed_ips = vec!["192.0.2.0/24".to_string()];
+        let nets: Vec<String> =
+            WireguardTunnelC::parse_allowed_ips(&info).unwrap().iter().map(|n| n.to_string()).collect();
+        assert_eq!(nets, ["192.0.2.0/24", "10.20.30.2/32"]);
+
+        info.allowed_ips = vec!["10.20.30.0/24".to_string()];
+        assert_eq!(WireguardTunnelC::parse_allowed_ips(&info).unwrap().len(), 1);
+
+        info.allowed_ips = vec!["192.0.2.0/24".to_string()];
+        info.peer_overlay_addr = Some("not-an-address".to_string());
+        assert_eq!(WireguardTunnelC::parse_allowed_ips(&info).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_allowed_ips_rejects_list_without_valid_entry() {
+        let mut info = rest_info(1, 2);
+        info.allowed_ips = vec!["bogus".to_string(), "192.0.2.0/24".to_string()];
+        assert_eq!(WireguardTunnelC::parse_allowed_ips(&info).unwrap().len(), 1);
+
+        // must not fall back to routing everything
+        info.allowed_ips = vec!["bogus".to_string()];
+        assert!(WireguardTunnelC::parse_allowed_ips(&info).is_err());
+    }
+
+    /// Needs CAP_NET_ADMIN and dummy link support; skipped where either is missing.
+    #[tokio::test]
+    async fn test_overlay_addr_on_dummy_link() {
+        let name = "catovltest0";
+        let Ok((connection, handle, _)) = rtnetlink::new_connection() else {
+            return;
+        };
+        let conn_poll = tokio::spawn(connection);
+        let created = handle.link().add(rtnetlink::LinkDummy::new(name).build()).execute().await;
+        conn_poll.abort();
+        if created.is_err() {
+            eprintln!("skipping: cannot create dummy link {}", name);
+            return;
+        }
+
+        let first: IpAddr = "10.20.30.1".parse().unwrap();
+        let second: IpAddr = "fd00:cafe::2".parse().unwrap();
+        let addrs = || async {
+            let mut addrs: Vec<String> =
+                crate::interface::get_addr(name.to_string()).await.unwrap().iter().map(|a| a.to_string()).collect();
+            addrs.sort();
+            addrs
+        };
+
+        let lla = WireguardTunnelC::ensure_link_local(name, None).await.unwrap();
+        WireguardTunnelC::ensure_overlay_addr(name, None, Some(first)).await.unwrap();
+        // re-running the link-local pass keeps the overlay address
+        WireguardTunnelC::ensure_link_local(name, Some(first)).await.unwrap();
+        let mut expected = vec![format!("{}/128", lla), "10.20.30.1/32".to_string()];
+        expected.sort();
+        assert_eq!(addrs().await, expected);
+
+        WireguardTunnelC::ensure_overlay_addr(name, Some(first), Some(second)).await.unwrap();
+        let mut expected = vec![format!("{}/128", lla), "fd00:cafe::2/128".to_string()];
+        expected.sort();
+        assert_eq!(addrs().await, expected);
+
+        WireguardTunnelC::ensure_overlay_addr(name, Some(second), None).await.unwrap();
+        assert_eq!(addrs().await, vec![format!("{}/128", lla)]);
+
+        crate::interface::delete_link(name.to_string()).await.unwrap();
+    }
+
+    #[test]
+    fn test_interface_name_round_trip() {
+        let mut info = rest_info(0x2345, 0x5abc);
+        info.endpoint_ipv6 = true;
+        info.fec = true;
+        let name = WireguardTunnelC::interface_name(&info).unwrap();
+        assert_eq!(name.len(), 15);
+        assert_eq!(
+            WireguardTunnelC::parse_interface_name(&name),
+            Some(TunnelDescriptor {
+                protocol: TunnelType::WireGuard,
+                peer_node_id: 0x5abc,
+                tunnel_id: 0x2345,
+                endpoint_ipv6: true,
+                fec: true,
+                faketcp: false,
+            })
+        );
+
+        info.fec = false;
+        assert_ne!(WireguardTunnelC::interface_name(&info).unwrap(), name);
+        assert_eq!(WireguardTunnelC::parse_interface_name(&WireguardTunnelC::interface_name(&info).unwrap()).unwrap().tunnel_id, 0x2345);
+
+        assert_eq!(WireguardTunnelC::parse_interface_name("eth0"), None);
+        assert_eq!(WireguardTunnelC::parse_interface_name("cat000000000000"), None);
+        assert_eq!(WireguardTunnelC::parse_interface_name("catUUUUUUUUUUUU"), None);
+    }
+
+    #[test]
+    fn test_interface_name_boundaries() {
+        for (tunnel_id, peer_node_id) in [(0, 0), (MAX_NAMED_TUNNEL_ID, MAX_NAMED_PEER_NODE_ID)] {
+            let mut info = rest_info(tunnel_id, peer_node_id);
+            info.endpoint_ipv6 = true;
+            info.fec = true;
+            info.faketcp = true;
+            let name = WireguardTunnelC::interface_name(&info).unwrap();
+            assert_eq!(name.len(), crate::interface::MAX_INTERFACE_NAME_LEN, "{}", name);
+            assert!(crate::interface::validate_interface_name(&name).is_ok());
+            let descriptor = WireguardTunnelC::parse_interface_name(&name).unwrap();
+            assert_eq!(descriptor.peer_node_id, peer_node_id);
+            assert_eq!(descriptor.tunnel_id, tunnel_id);
+            assert!(descriptor.endpoint_ipv6 && descriptor.fec && descriptor.faketcp);
+        }
+    }
+
+    #[test]
+    fn test_interface_name_rejects_ids_it_cannot_hold() {
+        // tunnel 0x1_0001 would otherwise alias tunnel 1 to the same peer
+        for (tunnel_id, peer_node_id) in [
+            (MAX_NAMED_TUNNEL_ID + 1, 1),
+            (0x1_0001, 1),
+            (1, MAX_NAMED_PEER_NODE_ID + 1),
+            (i32::MAX, i32::MAX),
+            (-1, 1),
+            (1, i32::MIN),
+        ] {
+            let err = WireguardTunnelC::interface_name(&rest_info(tunnel_id, peer_node_id)).unwrap_err();
+            assert!(err.to_string().contains("does not fit"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_interface_names_unique() {
+        let mut names = std::collections::HashSet::new();
+        let mut count = 0;
+        for tunnel_id in (0..=0xffff).step_by(0x111) {
+            for peer_node_id in (0..=0x7fff).step_by(0x123) {
+                for flags in 0..8 {
+                    let mut info = rest_info(tunnel_id, peer_node_id);
+                    info.endpoint_ipv6 = flags & 1 != 0;
+                    info.fec = flags & 2 != 0;
+                    info.faketcp = flags & 4 != 0;
+                    names.insert(WireguardTunnelC::interface_name(&info).unwrap());
+                    count += 1;
+                }
+            }
+        }
+        assert_eq!(names.len(), count);
+    }
+
+    #[test]
+    fn test_parse_gen_new_wg_tunnel_names() {
+        for (tunnel_id, peer_node_id) in [(1, 2), (300, 4000), (0xabcd, 0x7abc), (0xffff, 0x7fff)] {
+            for flags in 0..8 {
+                let mut info = rest_info(tunnel_id, peer_node_id);
+                info.endpoint_ipv6 = flags & 1 != 0;
+                info.fec = flags & 2 != 0;
+                info.faketcp = flags & 4 != 0;
+                let info = Arc::new(info);
+                let tunnel = WireguardTunnelC::gen_new_wg_tunnel(info.clone(), String::new(), None, 0).unwrap();
+
+                assert_eq!(
+                    WireguardTunnelC::parse_interface_name(tunnel.get_interface_name()),
+                    Some(TunnelDescriptor {
+                        protocol: TunnelType::WireGuard,
+                        peer_node_id,
+                        tunnel_id,
+                        endpoint_ipv6: info.endpoint_ipv6,
+                        fec: info.fec,
+                        faketcp: info.faketcp,
+                    })
+                );
+            }
+        }
+    }
+}
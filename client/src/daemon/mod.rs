@@ -1,28 +1,45 @@
-use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
+use std::collections::HashMap;
 use std::io;
 use std::future::Future;
 use std::time::Duration;
 use tokio::net::{UnixListener, UnixStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::AsyncReadExt;
 
 use crate::config::ClientConfig;
+use crate::config::IpStack;
 use crate::config::ServerConfig;
+use cat4igp_shared::custom_type::WireguardAnswered;
+use cat4igp_shared::rest::client as REST;
+use cat4igp_shared::rest::capability;
 use crate::server_rest::client::ServerRestClient;
 
 pub mod protocol;
 pub mod client;
+pub mod pid_file;
 mod daemon_memory;
+#[cfg(test)]
+mod test_harness;
 
-use protocol::{DaemonRequest, DaemonResponse, SharedSecret};
+use daemon_memory::{NetworkStatus, UsableFamilies};
+use protocol::{
+    read_frame, write_frame, DaemonEvent, DaemonRequest, DaemonResponse, ErrorCode, SharedSecret, TunnelHealth,
+};
 
 /// Daemon state and management
 pub struct Daemon {
-    config: Arc<ClientConfig>,
+    /// Replaced as a whole by `ReloadConfig`; read through `config()`
+    config: Arc<std::sync::RwLock<Arc<ClientConfig>>>,
+    /// File `ReloadConfig` re-reads; `None` when started without one
+    config_path: Option<PathBuf>,
     server_config: Arc<Mutex<Option<ServerConfig>>>,
     secret: SharedSecret,
     memory: Arc<daemon_memory::DaemonMemory>,
+    /// Whether this process may manage interfaces; without it tunnels are fetched but never set up
+    can_manage_interfaces: bool,
+    started_at: std::time::Instant,
 }
 
 /// IPC message envelope
@@ -38,11 +55,11 @@ impl Daemon {
         // Load or create shared secret
         let secret = match SharedSecret::load(&config.data_dir) {
             Ok(s) => s,
-            Err(_) => {
-                let new_secret = SharedSecret::generate();
-                let secret = SharedSecret {
-                    secret: new_secret,
-                };
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    eprintln!("[daemon] Replacing unusable daemon secret: {}", e);
+                }
+                let secret = SharedSecret::generate();
                 secret.save(&config.data_dir)?;
                 secret
             }
@@ -57,21 +74,55 @@ impl Daemon {
 
         let cfg_clone = config.clone();
 
+        let can_manage_interfaces = match crate::interface::privileges::has_net_admin(None) {
+            Ok(granted) => granted,
+            Err(e) => {
+                // let the first netlink call decide rather than refusing on a guess
+                eprintln!("[daemon] could not check privileges, assuming they are sufficient: {}", e);
+                true
+            }
+        };
+
+        let memory = daemon_memory::DaemonMemory::new(cfg_clone);
+        memory.load_snapshot().await;
+
         Ok(Daemon {
-            config: Arc::new(config),
+            config: Arc::new(std::sync::RwLock::new(Arc::new(config))),
+            config_path: None,
             server_config: Arc::new(Mutex::new(server_config)),
             secret,
-            memory: Arc::new(daemon_memory::DaemonMemory::new(cfg_clone)),
+            memory: Arc::new(memory),
+            can_manage_interfaces,
+            started_at: std::time::Instant::now(),
         })
     }
 
+    /// Remember the file the config was loaded from so `ReloadConfig` can re-read it.
+    pub fn with_config_path(mut self, path: PathBuf) -> Self {
+        self.config_path = Some(path);
+        self
+    }
+
+    /// Snapshot of the current configuration.
+    fn config(&self) -> Arc<ClientConfig> {
+        Arc::clone(&self.config.read().unwrap())
+    }
+
     /// Handle a request from the CLI
     pub async fn handle_request(&self, req: DaemonRequest, auth_secret: &str) -> DaemonResponse {
         // Verify authentication
         if !self.secret.verify(auth_secret) {
-            return DaemonResponse::Error("Authentication failed".to_string());
+            return DaemonResponse::error(ErrorCode::AuthFailed, "Authentication failed");
         }
 
+        match req {
+            DaemonRequest::Batch { requests, stop_on_error } => self.handle_batch(requests, stop_on_error).await,
+            req => self.dispatch(req).await,
+        }
+    }
+
+    /// Handle one authenticated request other than `Batch`.
+    async fn dispatch(&self, req: DaemonRequest) -> DaemonResponse {
         match req {
             DaemonRequest::Status => self.handle_status().await,
             DaemonRequest::SetServer {
@@ -79,6 +130,7 @@ impl Daemon {
                 invite_code,
                 verify_tls,
             } => self.handle_set_server(address, invite_code, verify_tls).await,
+            DaemonRequest::GetServer => self.handle_get_server().await,
             DaemonRequest::Register {
                 address,
                 invite_code,
@@ -87,6 +139,7 @@ impl Daemon {
             DaemonRequest::Restart => self.handle_restart().await,
             DaemonRequest::Shutdown => self.handle_shutdown().await,
             DaemonRequest::GetConfig => self.handle_get_config().await,
+            DaemonRequest::ReloadConfig => self.handle_reload_config().await,
             DaemonRequest::ModifyConfig {
                 public_hostname_ipv4,
                 public_hostname_ipv6,
@@ -94,6 +147,58 @@ impl Daemon {
                 self.handle_modify_config(public_hostname_ipv4, public_hostname_ipv6)
                     .await
             }
+            DaemonRequest::SetWireguardKey { private_key, force } => {
+                self.handle_set_wireguard_key(private_key, force).await
+            }
+            DaemonRequest::RotateKey => self.handle_rotate_key().await,
+            DaemonRequest::RotateWireguardKey { switch_after_secs } => {
+                self.handle_rotate_wireguard_key(switch_after_secs).await
+            }
+            DaemonRequest::TestServer { address, verify_tls } => {
+                self.handle_test_server(address, verify_tls).await
+            }
+            DaemonRequest::GetTunnelConfig {
+                tunnel_id,
+                show_private_key,
+            } => self.handle_get_tunnel_config(tunnel_id, show_private_key).await,
+            DaemonRequest::GetTunnelStats => DaemonResponse::TunnelStats(self.memory.get_tunnel_stats().await),
+            DaemonRequest::DumpState => self.handle_dump_state().await,
+            // handle_client streams these itself; getting here means a one-shot caller sent it
+            DaemonRequest::Subscribe => {
+                DaemonResponse::error(ErrorCode::Internal, "Subscribe requires a streaming connection")
+            }
+            DaemonRequest::Batch { .. } => DaemonResponse::error(ErrorCode::Internal, "Batch requests cannot be nested"),
+        }
+    }
+
+    /// Run `requests` in order. Nothing runs if any of them can't be batched.
+    async fn handle_batch(&self, requests: Vec<DaemonRequest>, stop_on_error: bool) -> DaemonResponse {
+        if let Some(invalid) = requests
+            .iter()
+            .find(|r| matches!(r, DaemonRequest::Batch { .. } | DaemonRequest::Subscribe))
+        {
+            let name = if matches!(invalid, DaemonRequest::Subscribe) { "Subscribe" } else { "Batch" };
+            return DaemonResponse::error(ErrorCode::Internal, format!("{} is not allowed inside a batch", name));
+        }
+
+        let mut responses = Vec::with_capacity(requests.len());
+        for request in requests {
+            let response = self.dispatch(request).await;
+            let failed = matches!(response, DaemonResponse::Error { .. });
+            responses.push(response);
+            if failed && stop_on_error {
+                break;
+            }
+        }
+
+        // the client refuses frames over the cap, so answer with something it can read
+        let response = DaemonResponse::Batch(responses);
+        match serde_json::to_vec(&response) {
+            Ok(bytes) if bytes.len() <= protocol::MAX_FRAME_LEN => response,
+            _ => DaemonResponse::error(ErrorCode::Internal, format!(
+                "Batch response exceeds {} bytes; split the batch",
+                protocol::MAX_FRAME_LEN
+            )),
         }
     }
 
@@ -108,12 +213,19 @@ impl Daemon {
             (server_configured, node_key_present)
         };
         let poll_error = self.memory.get_last_poll_error().await;
+        let poll_interval = self.memory.get_poll_interval().await;
+        // cached by the detection loop; Status never probes itself
+        let network = self.memory.get_network_status().await;
 
         DaemonResponse::Status {
             running: true,
             server_configured,
             node_key_present,
             message: poll_error,
+            reconcile_interval_secs: poll_interval.map(|d| d.as_secs()),
+            public_ipv4: network.public_ipv4,
+            public_ipv6: network.public_ipv6,
+            nat_type: network.nat_type,
         }
     }
 
@@ -123,51 +235,77 @@ impl Daemon {
         invite_code: String,
         verify_tls: bool,
     ) -> DaemonResponse {
+        let address = match ServerConfig::normalize_address(&address) {
+            Ok(address) => address,
+            Err(e) => return DaemonResponse::error(ErrorCode::Internal, e),
+        };
+        let invite_code = invite_code.trim().to_string();
+
         let mut server_config = self.server_config.lock().await;
         let mut config = ServerConfig {
             address,
             invite_code,
             verify_tls,
             node_key: None,
+            pending_node_key: None,
             wg_private_key: None,
             wg_public_key: None,
         };
 
         if let Err(e) = config.ensure_wireguard_keypair() {
-            return DaemonResponse::Error(format!("Failed to generate WireGuard keypair: {}", e));
+            return DaemonResponse::error(ErrorCode::Internal, format!("Failed to generate WireGuard keypair: {}", e));
         }
 
-        if let Err(e) = config.save(&self.config.data_dir) {
-            return DaemonResponse::Error(format!("Failed to save server config: {}", e));
+        if let Err(e) = config.save(&self.config().data_dir) {
+            return DaemonResponse::error(ErrorCode::Internal, format!("Failed to save server config: {}", e));
         }
 
         *server_config = Some(config);
         DaemonResponse::Ok(Some("Server configuration set".to_string()))
     }
 
+    async fn handle_get_server(&self) -> DaemonResponse {
+        match self.server_config.lock().await.as_ref() {
+            Some(config) => DaemonResponse::ServerConfig {
+                address: config.address.clone(),
+                invite_code: config.invite_code.clone(),
+                verify_tls: config.verify_tls,
+                registered: config.node_key.is_some(),
+            },
+            None => DaemonResponse::error(ErrorCode::ServerNotConfigured, "Server not configured"),
+        }
+    }
+
     async fn handle_register(
         &self,
         address: String,
         invite_code: String,
         verify_tls: bool,
     ) -> DaemonResponse {
+        let address = match ServerConfig::normalize_address(&address) {
+            Ok(address) => address,
+            Err(e) => return DaemonResponse::error(ErrorCode::Internal, e),
+        };
+        let invite_code = invite_code.trim().to_string();
+
         let mut config = ServerConfig {
             address,
             invite_code,
             verify_tls,
             node_key: None,
+            pending_node_key: None,
             wg_private_key: None,
             wg_public_key: None,
         };
 
         if let Err(e) = config.ensure_wireguard_keypair() {
-            return DaemonResponse::Error(format!("Failed to generate WireGuard keypair: {}", e));
+            return DaemonResponse::error(ErrorCode::Internal, format!("Failed to generate WireGuard keypair: {}", e));
         }
 
         let rest_client = match ServerRestClient::new(&config) {
             Ok(client) => client,
             Err(e) => {
-                return DaemonResponse::Error(format!("Failed to create server client: {}", e));
+                return DaemonResponse::error(ErrorCode::Internal, format!("Failed to create server client: {}", e));
             }
         };
 
@@ -175,33 +313,288 @@ impl Daemon {
         let registration = match rest_client.register(&node_name, &config.invite_code).await {
             Ok(response) => response,
             Err(e) => {
-                return DaemonResponse::Error(format!("Registration failed: {}", e));
+                return DaemonResponse::error(server_error_code(&*e), format!("Registration failed: {}", e));
             }
         };
 
         if !registration.success {
-            return DaemonResponse::Error("Registration failed: server returned unsuccessful response".to_string());
+            return DaemonResponse::error(ErrorCode::Internal, "Registration failed: server returned unsuccessful response");
         }
 
         config.node_key = Some(registration.auth_key);
+        let message = match registration.remaining_uses {
+            Some(0) => "Registration successful; the invite is now used up".to_string(),
+            Some(n) => format!("Registration successful; the invite has {} use(s) left", n),
+            None => "Registration successful".to_string(),
+        };
 
         if let Some(public_key) = config.wg_public_key.clone() {
             if let Err(e) = rest_client.update_wireguard_pubkey(&public_key).await {
-                return DaemonResponse::Error(format!(
+                return DaemonResponse::error(server_error_code(&*e), format!(
                     "Registration succeeded but failed to sync WireGuard public key: {}",
                     e
                 ));
             }
         }
 
-        if let Err(e) = config.save(&self.config.data_dir) {
-            return DaemonResponse::Error(format!("Failed to save server config: {}", e));
+        if let Err(e) = config.save(&self.config().data_dir) {
+            return DaemonResponse::error(ErrorCode::Internal, format!("Failed to save server config: {}", e));
         }
 
         let mut server_config = self.server_config.lock().await;
         *server_config = Some(config);
 
-        DaemonResponse::Ok(Some("Registration successful".to_string()))
+        DaemonResponse::Ok(Some(message))
+    }
+
+    async fn handle_test_server(&self, address: Option<String>, verify_tls: Option<bool>) -> DaemonResponse {
+        let configured = self.server_config.lock().await.clone();
+        let (address, default_verify_tls) = match (address, configured) {
+            (Some(address), _) => (address, true),
+            (None, Some(configured)) => (configured.address, configured.verify_tls),
+            (None, None) => {
+                return DaemonResponse::error(ErrorCode::ServerNotConfigured, "Server not configured; pass an address to test");
+            }
+        };
+
+        let probe = crate::server_rest::probe::probe_server(&address, verify_tls.unwrap_or(default_verify_tls)).await;
+        DaemonResponse::ServerProbe(probe)
+    }
+
+    /// Rotate the node's auth key. The new key is generated here and saved as
+    /// `pending_node_key` *before* the server is asked to switch to it, so whenever the
+    /// daemon stops, the server's key is on disk as either `node_key` or the pending one;
+    /// `resolve_pending_node_key` later works out which.
+    async fn handle_rotate_key(&self) -> DaemonResponse {
+        let mut server_config = self.server_config.lock().await;
+        let Some(mut config) = server_config.clone() else {
+            return DaemonResponse::error(ErrorCode::ServerNotConfigured, "Server not configured");
+        };
+        if config.node_key.as_deref().unwrap_or_default().is_empty() {
+            return DaemonResponse::error(ErrorCode::NotRegistered, "Not registered with the server");
+        }
+
+        if let Err(e) = self.resolve_pending_node_key(&mut config).await {
+            return DaemonResponse::error(ErrorCode::Internal, format!("Earlier key rotation is unresolved: {}", e));
+        }
+
+        // check before saving a pending key the server could never accept
+        let version = match ServerRestClient::new(&config) {
+            Ok(client) => client.get_version().await,
+            Err(e) => Err(e),
+        };
+        match version {
+            Ok(Some(version)) if version.supports(capability::ROTATE_KEY) => {}
+            Ok(_) => {
+                return DaemonResponse::error(ErrorCode::Internal, "Server does not support key rotation; upgrade it first");
+            }
+            Err(e) => return DaemonResponse::error(server_error_code(&*e), format!("Failed to query server version: {}", e)),
+        }
+
+        let new_key = uuid::Uuid::new_v4().to_string();
+        config.pending_node_key = Some(new_key.clone());
+        if let Err(e) = config.save(&self.config().data_dir) {
+            return DaemonResponse::error(ErrorCode::Internal, format!("Failed to save server config: {}", e));
+        }
+        *server_config = Some(config.clone());
+
+        let rotated = match ServerRestClient::new(&config) {
+            Ok(client) => client.rotate_key(&new_key).await,
+            Err(e) => Err(e),
+        };
+        let response = match rotated {
+            Ok(response) => response,
+            Err(e) => {
+                return DaemonResponse::error(server_error_code(&*e), format!(
+                    "Key rotation failed: {}; the new key stays pending and is checked on the next poll",
+                    e
+                ));
+            }
+        };
+
+        config.node_key = Some(response.auth_key);
+        config.pending_node_key = None;
+        if let Err(e) = config.save(&self.config().data_dir) {
+            *server_config = Some(config);
+            return DaemonResponse::error(ErrorCode::Internal, format!(
+                "Key rotated, but saving it failed ({}); it stays pending on disk",
+                e
+            ));
+        }
+        *server_config = Some(config);
+        DaemonResponse::Ok(Some("Node key rotated".to_string()))
+    }
+
+    /// Settle an interrupted key rotation: keep whichever of `pending_node_key` and
+    /// `node_key` the server accepts, then clear the pending key. Fails, leaving both in
+    /// place, when the server accepts neither (typically because it is unreachable).
+    async fn resolve_pending_node_key(&self, config: &mut ServerConfig) -> Result<(), String> {
+        let Some(pending) = config.pending_node_key.clone() else {
+            return Ok(());
+        };
+
+        let accepts = |node_key: Option<String>| {
+            let candidate = ServerConfig { node_key, ..config.clone() };
+            async move {
+                match ServerRestClient::new(&candidate) {
+                    Ok(client) => client.get_self_info().await.is_ok(),
+                    Err(_) => false,
+                }
+            }
+        };
+
+        if accepts(Some(pending.clone())).await {
+            eprintln!("[daemon] server switched to the pending node key; adopting it");
+            config.node_key = Some(pending);
+        } else if accepts(config.node_key.clone()).await {
+            eprintln!("[daemon] server still uses the previous node key; dropping the pending one");
+        } else {
+            return Err("server accepts neither the current nor the pending node key".to_string());
+        }
+
+        config.pending_node_key = None;
+        config.save(&self.config().data_dir).map_err(|e| format!("failed to save server config: {}", e))
+    }
+
+    /// `resolve_pending_node_key` on the live configuration, if a rotation was interrupted.
+    async fn settle_pending_node_key(&self) -> Result<(), String> {
+        let mut server_config = self.server_config.lock().await;
+        let Some(config) = server_config.as_mut().filter(|c| c.pending_node_key.is_some()) else {
+            return Ok(());
+        };
+
+        let mut updated = config.clone();
+        let result = self.resolve_pending_node_key(&mut updated).await;
+        *config = updated;
+        result
+    }
+
+    /// Replace the WireGuard keypair without a restart. The new public key is uploaded
+    /// first; the keypair is saved only once the server has it, so new tunnels use it right
+    /// away. Existing interfaces keep the old private key for `switch_after_secs` (by default
+    /// two poll intervals, so peers have fetched the new public key) and then switch. If the
+    /// upload or the save fails, the old public key is put back on the server.
+    async fn handle_rotate_wireguard_key(&self, switch_after_secs: Option<u64>) -> DaemonResponse {
+        let mut server_config = self.server_config.lock().await;
+        let Some(config) = server_config.clone() else {
+            return DaemonResponse::error(ErrorCode::ServerNotConfigured, "Server not configured");
+        };
+        if config.node_key.as_deref().unwrap_or_default().is_empty() {
+            return DaemonResponse::error(ErrorCode::NotRegistered, "Not registered with the server");
+        }
+        let rest_client = match ServerRestClient::new(&config) {
+            Ok(client) => client,
+            Err(e) => {
+                return DaemonResponse::error(ErrorCode::Internal, format!("Failed to create server client: {}", e));
+            }
+        };
+
+        let mut rotated = config.clone();
+        rotated.regenerate_wireguard_keypair();
+        let new_public_key = rotated.wg_public_key.clone().unwrap_or_default();
+        eprintln!("[daemon] rotating WireGuard key: uploading new public key {}", new_public_key);
+
+        if let Err(e) = rest_client.update_wireguard_pubkey(&new_public_key).await {
+            Self::restore_wireguard_pubkey(&rest_client, &config).await;
+            return DaemonResponse::error(
+                server_error_code(&*e),
+                format!("Failed to upload the new WireGuard public key: {}; keeping the old key", e),
+            );
+        }
+        if let Err(e) = rotated.save(&self.config().data_dir) {
+            Self::restore_wireguard_pubkey(&rest_client, &config).await;
+            return DaemonResponse::error(
+                ErrorCode::Internal,
+                format!("Failed to save the new WireGuard key: {}; keeping the old key", e),
+            );
+        }
+        let new_private_key = rotated.wg_private_key.clone().unwrap_or_default();
+        *server_config = Some(rotated);
+        drop(server_config);
+
+        let delay = switch_after_secs
+            .map(Duration::from_secs)
+            .unwrap_or_else(|| self.config().reconcile_interval() * 2);
+        eprintln!(
+            "[daemon] new WireGuard public key uploaded; switching interfaces in {}s",
+            delay.as_secs()
+        );
+        let server_config = Arc::clone(&self.server_config);
+        let memory = Arc::clone(&self.memory);
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            // a later rotation switches to its own key
+            let current = server_config.lock().await.as_ref().and_then(|c| c.wg_private_key.clone());
+            if current.as_deref() != Some(new_private_key.as_str()) {
+                eprintln!("[daemon] WireGuard key was replaced again before the switch; skipping it");
+                return;
+            }
+            let failed = memory.switch_private_key(&new_private_key).await;
+            if failed == 0 {
+                eprintln!("[daemon] switched all tunnels to the new WireGuard key");
+            } else {
+                eprintln!("[daemon] {} tunnel(s) kept the old WireGuard key; restart the daemon to rebuild them", failed);
+            }
+        });
+
+        DaemonResponse::Ok(Some(format!(
+            "New WireGuard public key uploaded; tunnels switch to it in {}s",
+            delay.as_secs()
+        )))
+    }
+
+    /// Put `config`'s public key back on the server after a rotation failed part-way.
+    async fn restore_wireguard_pubkey(rest_client: &ServerRestClient, config: &ServerConfig) {
+        let Some(public_key) = config.wg_public_key.as_deref() else {
+            return;
+        };
+        match rest_client.update_wireguard_pubkey(public_key).await {
+            Ok(_) => eprintln!("[daemon] WireGuard key rotation rolled back; the server has the old public key"),
+            Err(e) => eprintln!(
+                "[daemon] failed to restore the old WireGuard public key ({}); it is uploaded again at the next start",
+                e
+            ),
+        }
+    }
+
+    async fn handle_set_wireguard_key(&self, private_key: String, force: bool) -> DaemonResponse {
+        let mut server_config = self.server_config.lock().await;
+        let Some(mut config) = server_config.clone() else {
+            return DaemonResponse::error(ErrorCode::ServerNotConfigured, "Server not configured");
+        };
+
+        let replacing = config.wg_private_key.is_some();
+        if let Err(e) = config.import_wireguard_private_key(&private_key, force) {
+            return DaemonResponse::error(ErrorCode::Internal, e.to_string());
+        }
+
+        if let Err(e) = config.save(&self.config().data_dir) {
+            return DaemonResponse::error(ErrorCode::Internal, format!("Failed to save server config: {}", e));
+        }
+
+        *server_config = Some(config.clone());
+        drop(server_config);
+
+        if config.node_key.is_some() {
+            let public_key = config.wg_public_key.clone().unwrap_or_default();
+            let uploaded = match ServerRestClient::new(&config) {
+                Ok(client) => client.update_wireguard_pubkey(&public_key).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = uploaded {
+                return DaemonResponse::error(server_error_code(&*e), format!(
+                    "WireGuard key saved but failed to upload public key: {}",
+                    e
+                ));
+            }
+        }
+
+        let message = if replacing {
+            "WireGuard key replaced; restart the daemon to rebuild existing tunnels"
+        } else {
+            "WireGuard key imported"
+        };
+        DaemonResponse::Ok(Some(message.to_string()))
     }
 
     async fn handle_restart(&self) -> DaemonResponse {
@@ -214,10 +607,111 @@ impl Daemon {
         DaemonResponse::Ok(Some("Shutdown signal sent".to_string()))
     }
 
+    /// Config, server config, tunnels and detection results in one document. Secrets stay
+    /// in the structure as `REDACTED` so it is clear they exist.
+    async fn handle_dump_state(&self) -> DaemonResponse {
+        let redact = |value: &mut serde_json::Value, key: &str| {
+            if let Some(field) = value.get_mut(key).filter(|field| !field.is_null()) {
+                *field = serde_json::Value::from(protocol::REDACTED);
+            }
+        };
+
+        let mut server_config = match serde_json::to_value(&*self.server_config.lock().await) {
+            Ok(value) => value,
+            Err(e) => return DaemonResponse::error(ErrorCode::Internal, format!("Failed to serialize server config: {}", e)),
+        };
+        for key in ["invite_code", "node_key", "pending_node_key", "wg_private_key"] {
+            redact(&mut server_config, key);
+        }
+
+        let stats = self.memory.get_tunnel_stats().await;
+        let mut tunnels = Vec::new();
+        for tunnel_id in self.memory.tunnel_ids().await {
+            let mut config = serde_json::to_value(self.memory.get_tunnel_config(tunnel_id, false).await)
+                .unwrap_or_default();
+            if let Some(config) = config.as_object_mut() {
+                config.insert("private_key".to_string(), protocol::REDACTED.into());
+            }
+            let stats = stats.iter().find(|s| s.tunnel_id == tunnel_id);
+            tunnels.push(serde_json::json!({ "tunnel_id": tunnel_id, "config": config, "stats": stats }));
+        }
+
+        let network = self.memory.get_network_status().await;
+        DaemonResponse::State(serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "uptime_secs": self.started_at.elapsed().as_secs(),
+            "can_manage_interfaces": self.can_manage_interfaces,
+            "daemon_secret": protocol::REDACTED,
+            "config": &*self.config(),
+            "server_config": server_config,
+            "network": {
+                "public_ipv4": network.public_ipv4,
+                "public_ipv6": network.public_ipv6,
+                "nat_type": network.nat_type,
+                "detection_error": network.detection_error,
+            },
+            "poll": {
+                "interval_secs": self.memory.get_poll_interval().await.map(|d| d.as_secs()),
+                "last_error": self.memory.get_last_poll_error().await,
+            },
+            "tunnels": tunnels,
+        }))
+    }
+
     async fn handle_get_config(&self) -> DaemonResponse {
-        match serde_json::to_value(&*self.config) {
+        match serde_json::to_value(&*self.config()) {
             Ok(value) => DaemonResponse::Config(value),
-            Err(e) => DaemonResponse::Error(format!("Failed to serialize config: {}", e)),
+            Err(e) => DaemonResponse::error(ErrorCode::Internal, format!("Failed to serialize config: {}", e)),
+        }
+    }
+
+    /// Re-read the config file and swap in its hot-reloadable fields. Changes to the rest
+    /// are reported as deferred and wait for a restart.
+    async fn handle_reload_config(&self) -> DaemonResponse {
+        let Some(path) = &self.config_path else {
+            return DaemonResponse::error(ErrorCode::Internal, "Daemon was started without a config file");
+        };
+        let file = match ClientConfig::from_file(path) {
+            Ok(file) => file,
+            Err(e) => return DaemonResponse::error(ErrorCode::Internal, format!("Failed to load {:?}: {}", path, e)),
+        };
+        if let Err(e) = crate::config::PortRange::new(file.port_range.min, file.port_range.max) {
+            return DaemonResponse::error(ErrorCode::Internal, format!("Invalid port_range in {:?}: {}", path, e));
+        }
+
+        // held throughout so concurrent reloads apply one after the other
+        let mut config = self.config.write().unwrap();
+        let changes = match serde_json::to_value(&**config) {
+            Ok(running) => crate::config::diff::diff(running, &file),
+            Err(e) => Err(e),
+        };
+        let changes = match changes {
+            Ok(changes) => changes,
+            Err(e) => return DaemonResponse::error(ErrorCode::Internal, format!("Failed to compare configs: {}", e)),
+        };
+
+        let updated = crate::config::diff::apply_hot(&config, &file);
+        self.memory.port_mgmt.set_range(updated.port_range.min, updated.port_range.max);
+        *config = Arc::new(updated);
+
+        let (applied, deferred): (Vec<_>, Vec<_>) = changes
+            .into_iter()
+            .partition(|c| c.apply == crate::config::diff::ApplyMode::HotReload);
+        let applied: Vec<String> = applied.into_iter().map(|c| c.key).collect();
+        let deferred: Vec<String> = deferred.into_iter().map(|c| c.key).collect();
+        eprintln!(
+            "[daemon] reloaded {:?}: applied [{}], deferred until restart [{}]",
+            path,
+            applied.join(", "),
+            deferred.join(", ")
+        );
+        DaemonResponse::ConfigReloaded { applied, deferred }
+    }
+
+    async fn handle_get_tunnel_config(&self, tunnel_id: i32, show_private_key: bool) -> DaemonResponse {
+        match self.memory.get_tunnel_config(tunnel_id, show_private_key).await {
+            Some(config) => DaemonResponse::TunnelConfig(config),
+            None => DaemonResponse::error(ErrorCode::Internal, format!("Tunnel {} is not active", tunnel_id)),
         }
     }
 
@@ -231,7 +725,7 @@ impl Daemon {
         if public_hostname_ipv4.is_some() || public_hostname_ipv6.is_some() {
             DaemonResponse::Ok(Some("TODO: implement".to_string()))
         } else {
-            DaemonResponse::Error("No configuration parameters provided".to_string())
+            DaemonResponse::error(ErrorCode::Internal, "No configuration parameters provided")
         }
     }
 
@@ -241,8 +735,8 @@ impl Daemon {
     }
 
     /// Get the daemon socket path
-    pub fn get_socket_path(&self) -> &Path {
-        &self.config.daemon_socket
+    pub fn get_socket_path(&self) -> PathBuf {
+        self.config().daemon_socket.clone()
     }
 
     /// Check if server is configured
@@ -252,19 +746,30 @@ impl Daemon {
 
     /// Start the daemon's Unix socket server
     pub async fn run(&self) -> io::Result<()> {
+        // the socket is bound once; ReloadConfig leaves daemon_socket for the next start
+        let socket = self.get_socket_path();
+
         // Remove existing socket file if it exists
-        if self.config.daemon_socket.exists() {
-            std::fs::remove_file(&self.config.daemon_socket)?;
+        if socket.exists() {
+            std::fs::remove_file(&socket)?;
         }
 
         // Create parent directory if it doesn't exist
-        if let Some(parent) = self.config.daemon_socket.parent() {
+        if let Some(parent) = socket.parent() {
             std::fs::create_dir_all(parent)?;
         }
 
-        let listener = UnixListener::bind(&self.config.daemon_socket)?;
-        println!("✓ Listening on socket: {:?}", self.config.daemon_socket);
+        let listener = UnixListener::bind(&socket)?;
+        println!("✓ Listening on socket: {:?}", socket);
+
+        if !self.can_manage_interfaces {
+            eprintln!("⚠ The daemon cannot manage network interfaces; {}.", crate::interface::privileges::MISSING_NET_ADMIN_HINT);
+            eprintln!("⚠ Running read-only: tunnels are fetched from the server but not set up.");
+        }
 
+        if let Err(e) = self.settle_pending_node_key().await {
+            eprintln!("[daemon] pending node key check failed: {}", e);
+        }
         if let Err(e) = self.sync_public_key_on_startup().await {
             eprintln!("[daemon] startup WireGuard public key sync failed: {}", e);
         }
@@ -273,6 +778,10 @@ impl Daemon {
         tokio::spawn(async move {
             daemon_for_updates.run_update_loop().await;
         });
+        let daemon_for_network = self.clone_for_handler();
+        tokio::spawn(async move {
+            daemon_for_network.run_network_detection_loop().await;
+        });
 
         loop {
             match listener.accept().await {
@@ -296,20 +805,26 @@ impl Daemon {
         // We need to restructure to use Arc<Daemon> instead
         // For now, create a simplified approach
         Arc::new(Daemon {
-            config: self.config.clone(),
+            config: Arc::clone(&self.config),
+            config_path: self.config_path.clone(),
             server_config: Arc::clone(&self.server_config),
-            secret: SharedSecret {
-                secret: self.secret.secret.clone(),
-            },
+            secret: self.secret.clone(),
             // do not clone memory! clone the Arc instead
             memory: Arc::clone(&self.memory),
+            can_manage_interfaces: self.can_manage_interfaces,
+            started_at: self.started_at,
         })
     }
 
     async fn run_update_loop(self: Arc<Self>) {
         let mut self_info_interval = tokio::time::interval(Duration::from_secs(300));
         let mut all_nodes_interval = tokio::time::interval(Duration::from_secs(300));
-        let mut wg_tunnel_interval = tokio::time::interval(Duration::from_secs(30));
+        let mut wg_poll_failures = 0u32;
+        let wg_poll = tokio::time::sleep(Duration::ZERO);
+        tokio::pin!(wg_poll);
+        let mut health_interval = tokio::time::interval(Duration::from_secs(10));
+        let mut last_health: HashMap<i32, TunnelHealth> = HashMap::new();
+        let mut orphans_cleaned = !self.config().cleanup_orphan_interfaces;
 
         loop {
             tokio::select! {
@@ -329,18 +844,138 @@ impl Daemon {
                         self.memory.set_last_poll_error(None).await;
                     }
                 }
-                _ = wg_tunnel_interval.tick() => {
-                    if let Err(e) = self.poll_wireguard_tunnels().await {
-                        eprintln!("[daemon] wireguard poll failed: {}", e);
-                        self.memory.set_last_poll_error(Some(format!("wireguard poll failed: {}", e))).await;
-                    } else {
-                        self.memory.set_last_poll_error(None).await;
+                () = &mut wg_poll => {
+                    match self.poll_wireguard_tunnels().await {
+                        Err(e) => {
+                            eprintln!("[daemon] wireguard poll failed: {}", e);
+                            self.memory.set_last_poll_error(Some(format!("wireguard poll failed: {}", e))).await;
+                            wg_poll_failures = wg_poll_failures.saturating_add(1);
+                        }
+                        // the server answered, so this must not back off polling
+                        Ok(Some(skipped)) => {
+                            eprintln!("[daemon] wireguard poll: {}", skipped);
+                            self.memory.set_last_poll_error(Some(format!("wireguard poll: {}", skipped))).await;
+                            wg_poll_failures = 0;
+                        }
+                        Ok(None) => {
+                            self.memory.set_last_poll_error(None).await;
+                            wg_poll_failures = 0;
+                        }
                     }
+                    // only once the desired tunnels are up, so theirs are never mistaken for orphans
+                    if !orphans_cleaned && wg_poll_failures == 0 && self.can_manage_interfaces {
+                        orphans_cleaned = true;
+                        if let Err(e) = self.memory.cleanup_orphan_interfaces().await {
+                            eprintln!("[daemon] orphan interface cleanup failed: {}", e);
+                        }
+                    }
+
+                    // read each time so ReloadConfig takes effect from the next poll
+                    let delay = reconcile_delay(self.config().reconcile_interval(), wg_poll_failures);
+                    self.memory.set_poll_interval(delay).await;
+                    wg_poll.as_mut().reset(tokio::time::Instant::now() + with_jitter(delay));
+                }
+                _ = health_interval.tick() => {
+                    self.publish_health_changes(&mut last_health).await;
                 }
             }
         }
     }
 
+    /// Detect the public addresses and NAT type every `NETWORK_DETECTION_INTERVAL` so `Status`
+    /// can report them without probing. Runs apart from the update loop since STUN can be slow.
+    async fn run_network_detection_loop(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(NETWORK_DETECTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            let previous = self.memory.get_network_status().await;
+            let status = self.detect_network_status(previous).await;
+            self.memory.set_network_status(status).await;
+        }
+    }
+
+    /// One detection round on top of `previous`. Usable families are always refreshed, but
+    /// when no public address can be detected at all the previous addresses and NAT type are
+    /// kept and only the error is recorded: a failed round says nothing about the network.
+    async fn detect_network_status(&self, previous: NetworkStatus) -> NetworkStatus {
+        let families = self.usable_families(previous.families).await;
+        let ip_stack = families.current(self.config().ip_stack);
+        match self.detect_public_addresses(ip_stack).await {
+            Ok(status) => NetworkStatus { families: Some(families), ..status },
+            Err(e) => {
+                eprintln!("[daemon] public address detection failed: {}", e);
+                NetworkStatus { families: Some(families), detection_error: Some(e), ..previous }
+            }
+        }
+    }
+
+    /// Public address of each family in `ip_stack`, and the NAT type (IPv4's when there is one).
+    /// Families that fail are left `None`; it is an error only when no address was detected.
+    async fn detect_public_addresses(&self, ip_stack: IpStack) -> Result<NetworkStatus, String> {
+        let config = self.config();
+        let mut detector = config.public_ip_detector();
+        if config.stun_servers.is_empty() {
+            detector.init().await?;
+        }
+
+        let mut status = NetworkStatus::default();
+        if ip_stack.has_ipv4() {
+            status.public_ipv4 = detector.detect_public_ipv4().await.ok();
+            status.nat_type = detector.detect_nat_type_ipv4().await.ok();
+        }
+        if ip_stack.has_ipv6() {
+            status.public_ipv6 = detector.detect_public_ipv6().await.ok();
+            if status.nat_type.is_none() {
+                status.nat_type = detector.detect_nat_type_ipv6().await.ok();
+            }
+        }
+        if status.public_ipv4.is_none() && status.public_ipv6.is_none() {
+            return Err("no STUN server reported a public address".to_string());
+        }
+        Ok(status)
+    }
+
+    /// Compare tunnel health with the previous check and publish the differences.
+    async fn publish_health_changes(&self, last_health: &mut HashMap<i32, TunnelHealth>) {
+        let current: HashMap<i32, TunnelHealth> = self
+            .memory
+            .get_tunnel_stats()
+            .await
+            .into_iter()
+            .map(|stats| (stats.tunnel_id, stats.health))
+            .collect();
+
+        for (&tunnel_id, &health) in &current {
+            if last_health.get(&tunnel_id) != Some(&health) {
+                self.memory.publish(DaemonEvent::HealthChanged { tunnel_id, health });
+            }
+        }
+        *last_health = current;
+    }
+
+    /// Which families this host can use right now, counted onto `previous`: a family needs a
+    /// usable interface address outside our own tunnels, and IPv6 must also pass a STUN check
+    /// when static IPv6 STUN servers are configured. When the addresses can't be listed,
+    /// `previous` is kept, or every family assumed before the first detection.
+    async fn usable_families(&self, previous: Option<UsableFamilies>) -> UsableFamilies {
+        let config = self.config();
+        let is_tunnel = |name: &str| daemon_memory::wireguard::WireguardTunnelC::parse_interface_name(name).is_some();
+        let (has_ipv4, mut has_ipv6) = match crate::interface::get_usable_families(is_tunnel).await {
+            Ok(families) => families,
+            Err(e) => {
+                eprintln!("[daemon] failed to list interface addresses: {}", e);
+                return previous.unwrap_or_else(|| UsableFamilies::record(None, true, true));
+            }
+        };
+
+        if has_ipv6 && config.ip_stack == IpStack::Dual && config.stun_servers.iter().any(|s| s.is_ipv6()) {
+            let detector = config.public_ip_detector().with_timeout(Duration::from_secs(2));
+            has_ipv6 = detector.detect_public_ipv6().await.is_ok();
+        }
+
+        UsableFamilies::record(previous, has_ipv4, has_ipv6)
+    }
+
     async fn registered_server_config(&self) -> Result<ServerConfig, String> {
         let cfg = self.server_config.lock().await.clone();
         let cfg = cfg.ok_or_else(|| "server not configured".to_string())?;
@@ -373,6 +1008,7 @@ impl Daemon {
     }
 
     async fn poll_self_info(&self) -> Result<(), String> {
+        self.settle_pending_node_key().await?;
         let cfg = self.registered_server_config().await?;
         let client = ServerRestClient::new(&cfg).map_err(|e| e.to_string())?;
         let response = self
@@ -398,17 +1034,33 @@ impl Daemon {
         Ok(())
     }
 
-    async fn poll_wireguard_tunnels(&self) -> Result<(), String> {
+    /// Fetch the tunnel list, decline what this host can't serve and reconcile the rest.
+    /// `Ok(Some(..))` says why tunnels were left alone: read-only mode, or tunnels that could
+    /// not be set up. `Err` is a failed poll.
+    async fn poll_wireguard_tunnels(&self) -> Result<Option<String>, String> {
         let cfg = self.registered_server_config().await?;
         let client = ServerRestClient::new(&cfg).map_err(|e| e.to_string())?;
-        let response = self
+        let etag = self.memory.get_wireguard_tunnels_etag().await;
+        let fetched = self
             .retry_with_backoff("/client/wg_tun", || {
                 let client = client.clone();
-                async move { client.get_wireguard_tunnels().await }
+                let etag = etag.clone();
+                async move { client.get_wireguard_tunnels(etag.as_deref()).await }
             })
             .await?;
 
-        self.memory.set_wireguard_tunnels(response.clone()).await;
+        // on 304 still reconcile against the cached list: local state may have drifted
+        let response = match fetched {
+            Some((response, etag)) => {
+                self.memory.set_wireguard_tunnels(response.clone(), etag).await;
+                response
+            }
+            None => self
+                .memory
+                .get_wireguard_tunnels()
+                .await
+                .ok_or_else(|| "server answered 304 but no tunnel list is cached".to_string())?,
+        };
 
         let local_private_key = cfg
             .wg_private_key
@@ -416,11 +1068,58 @@ impl Daemon {
             .filter(|v| !v.is_empty())
             .ok_or_else(|| "wireguard private key missing from server configuration".to_string())?;
 
-        self.memory
-            .reconcile_wireguard_tunnels(&response, &local_private_key)
+        // detected by the network loop; only probed here until its first detection finishes
+        let families = match self.memory.get_network_status().await.families {
+            Some(families) => families,
+            None => self.usable_families(None).await,
+        };
+        let configured = self.config().ip_stack;
+        let ip_stack = families.current(configured);
+        let (usable, to_decline) = filter_by_ip_stack(response.tunnels, ip_stack, families.kept(configured));
+        for tunnel_id in to_decline {
+            let payload = REST::WireguardTunnelAnswerPayload {
+                tunnel_id,
+                decline_type: Some(WireguardAnswered::RejectedNoIpStack as i16),
+                endpoint: None,
+            };
+            match client.answer_wireguard_tunnel(&payload).await {
+                Ok(_) => eprintln!(
+                    "[daemon] declined tunnel {}: endpoint family not available ({:?})",
+                    tunnel_id, ip_stack
+                ),
+                Err(e) => eprintln!("[daemon] failed to decline tunnel {}: {}", tunnel_id, e),
+            }
+        }
+
+        if !self.can_manage_interfaces {
+            return Ok(Some(format!(
+                "read-only: not setting up {} tunnel(s); {}",
+                usable.len(),
+                crate::interface::privileges::MISSING_NET_ADMIN_HINT
+            )));
+        }
+
+        let ops = self
+            .memory
+            .reconcile(usable, &local_private_key)
             .await?;
+        if !(ops.create.is_empty()
+            && ops.adopt.is_empty()
+            && ops.update.is_empty()
+            && ops.remove.is_empty()
+            && ops.failed.is_empty())
+        {
+            eprintln!(
+                "[daemon] tunnels reconciled: created {:?}, adopted {:?}, updated {:?}, removed {:?}, failed {:?}",
+                ops.create, ops.adopt, ops.update, ops.remove, ops.failed
+            );
+        }
 
-        Ok(())
+        if ops.failed.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(format!("could not set up tunnel(s) {:?}", ops.failed)))
+        }
     }
 
     async fn sync_public_key_on_startup(&self) -> Result<(), String> {
@@ -451,42 +1150,114 @@ impl Daemon {
     }
 }
 
-/// Handle a client connection
-async fn handle_client(mut stream: UnixStream, daemon: Arc<Daemon>) -> io::Result<()> {
-    // Read the request
-    let mut len_bytes = [0u8; 4];
-    stream.read_exact(&mut len_bytes).await?;
-    let len = u32::from_be_bytes(len_bytes) as usize;
+/// How often the daemon re-detects its public addresses and NAT type
+const NETWORK_DETECTION_INTERVAL: Duration = Duration::from_secs(900);
+
+/// Fraction by which each tunnel poll wait is randomly stretched or shortened, so nodes
+/// started together do not hit the server in lockstep.
+const RECONCILE_JITTER: f64 = 0.2;
+/// Longest wait between tunnel polls while the server keeps failing.
+const MAX_RECONCILE_BACKOFF: Duration = Duration::from_secs(600);
 
-    if len > 1024 * 1024 {
-        // Max 1MB message
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Message too large",
-        ));
+/// Poll interval after `consecutive_failures` failed polls: doubled per failure, capped at
+/// `MAX_RECONCILE_BACKOFF` (or `base`, if that is longer).
+fn reconcile_delay(base: Duration, consecutive_failures: u32) -> Duration {
+    let factor = 1u32 << consecutive_failures.min(16);
+    base.saturating_mul(factor).min(MAX_RECONCILE_BACKOFF.max(base))
+}
+
+fn with_jitter(delay: Duration) -> Duration {
+    delay.mul_f64(rand::random_range(1.0 - RECONCILE_JITTER..=1.0 + RECONCILE_JITTER))
+}
+
+/// `ErrorCode` for a failed server request: transport failures are `NetworkError`, and a
+/// 401 or 403 answer `AuthFailed`. Other client errors, such as an undecodable body, are
+/// `Internal`.
+pub(crate) fn server_error_code(e: &(dyn std::error::Error + Send + Sync + 'static)) -> ErrorCode {
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return if e.is_connect() || e.is_timeout() || e.is_request() {
+            ErrorCode::NetworkError
+        } else {
+            ErrorCode::Internal
+        };
+    }
+    match e.downcast_ref::<crate::server_rest::client::StatusError>() {
+        Some(e) if matches!(e.status, reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN) => {
+            ErrorCode::AuthFailed
+        }
+        _ => ErrorCode::Internal,
     }
+}
 
-    let mut buffer = vec![0u8; len];
-    stream.read_exact(&mut buffer).await?;
+/// Split the server's tunnels into those whose endpoint family `ip_stack` allows, and the IDs
+/// of conflicting tunnels we have not answered yet, which should be declined. Conflicting
+/// tunnels we already answered stay while `kept_stack` allows them, and are dropped after,
+/// so reconcile tears them down.
+fn filter_by_ip_stack(
+    tunnels: Vec<REST::WireguardTunnelInfo>,
+    ip_stack: IpStack,
+    kept_stack: IpStack,
+) -> (Vec<REST::WireguardTunnelInfo>, Vec<i32>) {
+    let mut to_decline = Vec::new();
+    let usable = tunnels
+        .into_iter()
+        .filter(|tunnel| {
+            if ip_stack.accepts_endpoint(tunnel.endpoint_ipv6) {
+                return true;
+            }
+            if matches!(tunnel.local_answered, WireguardAnswered::Unanswered) {
+                to_decline.push(tunnel.tunnel_id);
+                return false;
+            }
+            kept_stack.accepts_endpoint(tunnel.endpoint_ipv6)
+        })
+        .collect();
+    (usable, to_decline)
+}
 
-    let message: IpcMessage = serde_json::from_slice(&buffer).map_err(|e| {
-        io::Error::new(io::ErrorKind::InvalidData, format!("Invalid JSON: {}", e))
-    })?;
+/// Handle a client connection: one request and one response, except for `Subscribe`,
+/// which keeps the connection open and streams events.
+async fn handle_client(mut stream: UnixStream, daemon: Arc<Daemon>) -> io::Result<()> {
+    let message: IpcMessage = read_frame(&mut stream).await?;
+
+    if matches!(message.request, DaemonRequest::Subscribe) {
+        return stream_events(stream, daemon, &message.secret).await;
+    }
 
-    // Handle the request
     let response = daemon.handle_request(message.request, &message.secret).await;
+    write_frame(&mut stream, &response).await
+}
 
-    // Send the response
-    let response_bytes = serde_json::to_vec(&response).map_err(|e| {
-        io::Error::new(io::ErrorKind::InvalidData, format!("Failed to serialize response: {}", e))
-    })?;
+/// Acknowledge the subscription, then forward events until the client disconnects.
+async fn stream_events(mut stream: UnixStream, daemon: Arc<Daemon>, auth_secret: &str) -> io::Result<()> {
+    if !daemon.secret.verify(auth_secret) {
+        let response = DaemonResponse::error(ErrorCode::AuthFailed, "Authentication failed");
+        return write_frame(&mut stream, &response).await;
+    }
 
-    let response_len = (response_bytes.len() as u32).to_be_bytes();
-    stream.write_all(&response_len).await?;
-    stream.write_all(&response_bytes).await?;
-    stream.flush().await?;
+    // subscribe before acknowledging so nothing published after the ack is missed
+    let mut events = daemon.memory.subscribe();
+    write_frame(&mut stream, &DaemonResponse::Ok(Some("Subscribed".to_string()))).await?;
+
+    let mut probe = [0u8; 1];
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(missed)) => DaemonEvent::Lagged { missed },
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            // clients never send after subscribing, so any read result means they are gone
+            _ = stream.read(&mut probe) => return Ok(()),
+        };
 
-    Ok(())
+        if let Err(e) = write_frame(&mut stream, &event).await {
+            return match e.kind() {
+                io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset => Ok(()),
+                _ => Err(e),
+            };
+        }
+    }
 }
 
 #[cfg(test)]
@@ -546,10 +1317,231 @@ mod tests {
         let response = daemon.handle_request(req, "wrong-secret").await;
 
         match response {
-            DaemonResponse::Error(msg) => {
-                assert!(msg.contains("Authentication"));
+            DaemonResponse::Error { code, message } => {
+                assert_eq!(code, ErrorCode::AuthFailed);
+                assert!(message.contains("Authentication"));
             }
             _ => panic!("Expected error response"),
         }
     }
+
+    #[tokio::test]
+    async fn test_rotate_wireguard_key_keeps_old_key_when_upload_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = ClientConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let daemon = Daemon::new(config.clone()).await.unwrap();
+        let secret = daemon.get_secret().to_string();
+        let rotate = DaemonRequest::RotateWireguardKey { switch_after_secs: Some(0) };
+
+        match daemon.handle_request(rotate.clone(), &secret).await {
+            DaemonResponse::Error { code, .. } => assert_eq!(code, ErrorCode::ServerNotConfigured),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // nothing listens on port 1, so the upload is refused
+        let mut server = ServerConfig::new("http://127.0.0.1:1".to_string(), "invite".to_string());
+        server.node_key = Some("node-key".to_string());
+        server.ensure_wireguard_keypair().unwrap();
+        server.save(temp_dir.path()).unwrap();
+        let daemon = Daemon::new(config).await.unwrap();
+        let secret = daemon.get_secret().to_string();
+
+        match daemon.handle_request(rotate, &secret).await {
+            DaemonResponse::Error { code, message } => {
+                assert_eq!(code, ErrorCode::NetworkError);
+                assert!(message.contains("keeping the old key"), "{}", message);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        let saved = ServerConfig::load(temp_dir.path()).unwrap();
+        assert_eq!(saved.wg_private_key, server.wg_private_key);
+        let live = daemon.server_config.lock().await.clone().unwrap();
+        assert_eq!(live.wg_public_key, server.wg_public_key);
+    }
+
+    #[tokio::test]
+    async fn test_dump_state_redacts_secrets() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = ClientConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let mut server = ServerConfig::new("https://controller.example.com".to_string(), "invite-secret".to_string());
+        server.node_key = Some("node-key-secret".to_string());
+        server.ensure_wireguard_keypair().unwrap();
+        server.save(temp_dir.path()).unwrap();
+        let daemon = Daemon::new(config).await.unwrap();
+        let secret = daemon.get_secret().to_string();
+
+        let state = match daemon.handle_request(DaemonRequest::DumpState, &secret).await {
+            DaemonResponse::State(state) => state,
+            other => panic!("Unexpected response: {:?}", other),
+        };
+        let dumped = state.to_string();
+        for leaked in [&secret, "invite-secret", "node-key-secret", server.wg_private_key.as_deref().unwrap()] {
+            assert!(!dumped.contains(leaked), "{} leaked into {}", leaked, dumped);
+        }
+
+        assert_eq!(state["daemon_secret"], protocol::REDACTED);
+        assert_eq!(state["server_config"]["node_key"], protocol::REDACTED);
+        assert_eq!(state["server_config"]["wg_private_key"], protocol::REDACTED);
+        // unset secrets stay null rather than suggesting a value exists
+        assert!(state["server_config"]["pending_node_key"].is_null());
+        assert_eq!(state["server_config"]["address"], "https://controller.example.com");
+        assert_eq!(state["server_config"]["wg_public_key"].as_str(), server.wg_public_key.as_deref());
+        assert_eq!(state["config"]["data_dir"], temp_dir.path().to_str().unwrap());
+        assert_eq!(state["tunnels"], serde_json::json!([]));
+        assert!(state["uptime_secs"].is_u64());
+    }
+
+    fn tunnel(tunnel_id: i32, endpoint_ipv6: bool, local_answered: WireguardAnswered) -> REST::WireguardTunnelInfo {
+        REST::WireguardTunnelInfo {
+            tunnel_id,
+            peer_node_id: 2,
+            public_key: "peer-key".to_string(),
+            preferred_port: 51820,
+            remote_endpoint: None,
+            local_answered,
+            remote_response: WireguardAnswered::Answered,
+            mtu: 1420,
+            endpoint_ipv6,
+            fec: false,
+            faketcp: false,
+            keepalive: Some(25),
+            allowed_ips: Vec::new(),
+            local_overlay_addr: None,
+            peer_overlay_addr: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_reconcile_delay_backoff() {
+        let base = Duration::from_secs(30);
+        assert_eq!(reconcile_delay(base, 0), base);
+        assert_eq!(reconcile_delay(base, 1), Duration::from_secs(60));
+        assert_eq!(reconcile_delay(base, 3), Duration::from_secs(240));
+        assert_eq!(reconcile_delay(base, 5), MAX_RECONCILE_BACKOFF);
+        assert_eq!(reconcile_delay(base, u32::MAX), MAX_RECONCILE_BACKOFF);
+        assert_eq!(reconcile_delay(Duration::from_secs(900), 2), Duration::from_secs(900));
+
+        for _ in 0..100 {
+            let jittered = with_jitter(base);
+            assert!(jittered >= Duration::from_secs(24) && jittered <= Duration::from_secs(36));
+        }
+    }
+
+    #[test]
+    fn test_filter_by_ip_stack() {
+        let tunnels = || {
+            vec![
+                tunnel(1, false, WireguardAnswered::Answered),
+                tunnel(2, true, WireguardAnswered::Answered),
+                tunnel(3, false, WireguardAnswered::Unanswered),
+                tunnel(4, true, WireguardAnswered::Unanswered),
+            ]
+        };
+        let ids = |tunnels: Vec<REST::WireguardTunnelInfo>| tunnels.iter().map(|t| t.tunnel_id).collect::<Vec<_>>();
+
+        let (usable, declined) = filter_by_ip_stack(tunnels(), IpStack::Dual, IpStack::Dual);
+        assert_eq!(ids(usable), vec![1, 2, 3, 4]);
+        assert!(declined.is_empty());
+
+        let (usable, declined) = filter_by_ip_stack(tunnels(), IpStack::V4Only, IpStack::V4Only);
+        assert_eq!(ids(usable), vec![1, 3]);
+        assert_eq!(declined, vec![4]);
+
+        let (usable, declined) = filter_by_ip_stack(tunnels(), IpStack::V6Only, IpStack::V6Only);
+        assert_eq!(ids(usable), vec![2, 4]);
+        assert_eq!(declined, vec![3]);
+
+        // IPv6 went missing only recently: the accepted tunnel stays, the new one is declined
+        let (usable, declined) = filter_by_ip_stack(tunnels(), IpStack::V4Only, IpStack::Dual);
+        assert_eq!(ids(usable), vec![1, 2, 3]);
+        assert_eq!(declined, vec![4]);
+    }
+
+    #[test]
+    fn test_v6_tunnel_declined_on_v4_only_node() {
+        // dual stack configured, but the host only has IPv4 addresses
+        let ip_stack = IpStack::Dual.restrict_to(true, false);
+        let (usable, declined) =
+            filter_by_ip_stack(vec![tunnel(9, true, WireguardAnswered::Unanswered)], ip_stack, ip_stack);
+        assert!(usable.is_empty());
+        assert_eq!(declined, vec![9]);
+    }
+
+    #[test]
+    fn test_usable_families_keep_a_family_until_missing_repeatedly() {
+        let mut families = UsableFamilies::record(None, true, true);
+        assert_eq!(families.kept(IpStack::Dual), IpStack::Dual);
+
+        for _ in 1..daemon_memory::FAMILY_MISSING_DETECTIONS {
+            families = UsableFamilies::record(Some(families), true, false);
+            assert_eq!(families.current(IpStack::Dual), IpStack::V4Only);
+            assert_eq!(families.kept(IpStack::Dual), IpStack::Dual);
+        }
+        families = UsableFamilies::record(Some(families), true, false);
+        assert_eq!(families.kept(IpStack::Dual), IpStack::V4Only);
+
+        // one detection with IPv6 back resets the count
+        families = UsableFamilies::record(Some(families), true, true);
+        families = UsableFamilies::record(Some(families), true, false);
+        assert_eq!(families.kept(IpStack::Dual), IpStack::Dual);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_streams_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = ClientConfig {
+            data_dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let daemon = Arc::new(Daemon::new(config).await.unwrap());
+        let (mut cli, server) = UnixStream::pair().unwrap();
+        tokio::spawn(handle_client(server, daemon.clone()));
+
+        let message = IpcMessage {
+            secret: daemon.get_secret().to_string(),
+            request: DaemonRequest::Subscribe,
+        };
+        write_frame(&mut cli, &message).await.unwrap();
+        let ack: DaemonResponse = read_frame(&mut cli).await.unwrap();
+        assert!(matches!(ack, DaemonResponse::Ok(_)));
+
+        let event = DaemonEvent::TunnelDestroyed { tunnel_id: 7 };
+        daemon.memory.publish(event.clone());
+        let received: DaemonEvent = read_frame(&mut cli).await.unwrap();
+        assert_eq!(received, event);
+    }
+
+    #[tokio::test]
+    async fn test_server_error_code_separates_transport_from_decode_errors() {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 8\r\nconnection: close\r\n\r\nnot json")
+                .await;
+        });
+
+        let response = reqwest::get(format!("http://{}", addr)).await.unwrap();
+        let e: Box<dyn std::error::Error + Send + Sync> =
+            response.json::<serde_json::Value>().await.unwrap_err().into();
+        assert_eq!(server_error_code(&*e), ErrorCode::Internal);
+
+        // nothing listens on a port whose listener was dropped, so the connection is refused
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let e: Box<dyn std::error::Error + Send + Sync> =
+            reqwest::get(format!("http://{}", closed)).await.unwrap_err().into();
+        assert_eq!(server_error_code(&*e), ErrorCode::NetworkError);
+    }
 }
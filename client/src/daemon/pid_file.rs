@@ -0,0 +1,170 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// PID file owned by the running daemon; removed on drop if it still names this process.
+pub struct PidFile {
+    path: PathBuf,
+    pid: i32,
+}
+
+impl PidFile {
+    /// Write the current PID to `path`, refusing if a live process already owns it.
+    /// The file is created exclusively, so two daemons starting together cannot both win.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = match create_exclusive(path) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                // only a stale file is removed; a second collision means another daemon won
+                check_not_running(path)?;
+                create_exclusive(path)?
+            }
+            result => result?,
+        };
+
+        let pid = std::process::id() as i32;
+        if let Err(e) = writeln!(file, "{}", pid) {
+            let _ = fs::remove_file(path);
+            return Err(e);
+        }
+        Ok(PidFile {
+            path: path.to_path_buf(),
+            pid,
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if matches!(read_pid(&self.path), Ok(Some(pid)) if pid == self.pid) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Fail if `path` names a running process. A stale or unreadable PID file is removed.
+pub fn check_not_running(path: &Path) -> io::Result<()> {
+    let stale = match read_pid(path) {
+        Ok(None) => return Ok(()),
+        Ok(Some(pid)) if is_alive(pid) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Daemon already running with PID {} (see {:?})", pid, path),
+            ));
+        }
+        Ok(Some(pid)) => format!("process {} is gone", pid),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => e.to_string(),
+        Err(e) => return Err(e),
+    };
+
+    eprintln!("Removing stale PID file {:?}: {}", path, stale);
+    fs::remove_file(path)
+}
+
+/// PID recorded in `path` if that process is still running.
+pub fn live_pid(path: &Path) -> io::Result<Option<i32>> {
+    Ok(read_pid(path)?.filter(|pid| is_alive(*pid)))
+}
+
+/// Ask the process to shut down with SIGTERM.
+pub fn terminate(pid: i32) -> io::Result<()> {
+    if unsafe { libc::kill(pid, libc::SIGTERM) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn create_exclusive(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+fn read_pid(path: &Path) -> io::Result<Option<i32>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    match content.trim().parse::<i32>() {
+        Ok(pid) if pid > 0 => Ok(Some(pid)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid PID file content {:?}", content.trim()),
+        )),
+    }
+}
+
+/// Signal 0 only checks for existence; EPERM still means the process exists.
+fn is_alive(pid: i32) -> bool {
+    let exists = unsafe { libc::kill(pid, 0) } == 0;
+    exists || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Fork into the background: the parent exits, the child starts a new session and points
+/// stdio at /dev/null. Must be called before the tokio runtime starts any threads.
+/// The working directory is kept so relative paths in the configuration still resolve.
+pub fn detach() -> io::Result<()> {
+    fork_and_exit_parent()?;
+    if unsafe { libc::setsid() } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // fork again so the daemon, no longer a session leader, can never reacquire a terminal
+    fork_and_exit_parent()?;
+
+    let devnull = fs::OpenOptions::new().read(true).write(true).open("/dev/null")?;
+    for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        if unsafe { libc::dup2(devnull.as_raw_fd(), fd) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn fork_and_exit_parent() -> io::Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(()),
+        _ => unsafe { libc::_exit(0) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_pid_file_lifecycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("run").join("client.pid");
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(live_pid(&path).unwrap(), Some(std::process::id() as i32));
+
+        // this process is alive, so a second daemon must refuse to start
+        let err = PidFile::create(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_pid_file_is_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("client.pid");
+
+        // PIDs are capped far below i32::MAX, so no process has this one
+        fs::write(&path, format!("{}\n", i32::MAX)).unwrap();
+        assert_eq!(live_pid(&path).unwrap(), None);
+        check_not_running(&path).unwrap();
+        assert!(!path.exists());
+
+        fs::write(&path, "garbage").unwrap();
+        let _pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(live_pid(&path).unwrap(), Some(std::process::id() as i32));
+    }
+}
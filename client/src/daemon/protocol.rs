@@ -1,8 +1,19 @@
 use rand::RngExt;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::path::Path;
 use std::fs;
 use std::io;
+use std::net::IpAddr;
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::network::public_ip::NatType;
+
+/// Largest IPC frame accepted in either direction
+pub const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+/// Stands in for secrets in `DumpState` output, keeping the field visible
+pub const REDACTED: &str = "<redacted>";
 
 /// Request sent from CLI to daemon
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,6 +26,8 @@ pub enum DaemonRequest {
         invite_code: String,
         verify_tls: bool,
     },
+    /// Get the stored server configuration
+    GetServer,
     /// Register with server and store node key
     Register {
         address: String,
@@ -27,26 +40,82 @@ pub enum DaemonRequest {
     Shutdown,
     /// Get daemon configuration
     GetConfig,
+    /// Re-read the config file the daemon was started with and apply the fields that can
+    /// change at runtime, answered with `DaemonResponse::ConfigReloaded`
+    ReloadConfig,
     /// Modify daemon configuration
     ModifyConfig {
         public_hostname_ipv4: Option<String>,
         public_hostname_ipv6: Option<String>,
     },
+    /// Replace the WireGuard private key with an externally provisioned one
+    SetWireguardKey {
+        /// Base64-encoded 32-byte private key
+        private_key: String,
+        /// Replace an existing key (breaks tunnels until peers learn the new public key)
+        force: bool,
+    },
+    /// Replace the node's server auth key with a freshly generated one
+    RotateKey,
+    /// Generate a new WireGuard keypair, upload its public key, and switch the interfaces
+    /// to it once peers have had time to pick it up
+    RotateWireguardKey {
+        /// Seconds between the upload and the switch; defaults to two poll intervals
+        switch_after_secs: Option<u64>,
+    },
+    /// Check that a server answers `GET /`. `address` defaults to the configured server;
+    /// `verify_tls` defaults to that server's setting, or to true for another address
+    TestServer {
+        address: Option<String>,
+        verify_tls: Option<bool>,
+    },
+    /// Get the WireGuard configuration of an active tunnel
+    GetTunnelConfig {
+        tunnel_id: i32,
+        /// Include the interface private key instead of redacting it
+        show_private_key: bool,
+    },
+    /// Get handshake health and traffic totals of every active tunnel
+    GetTunnelStats,
+    /// Everything the daemon knows, for bug reports, with secrets redacted; answered with
+    /// `DaemonResponse::State`
+    DumpState,
+    /// Keep the connection open and stream `DaemonEvent` frames until the client disconnects
+    Subscribe,
+    /// Run several requests in order over one connection, answered with `DaemonResponse::Batch`
+    Batch {
+        requests: Vec<DaemonRequest>,
+        /// Skip the remaining requests after the first `Error` response
+        #[serde(default)]
+        stop_on_error: bool,
+    },
 }
 
-/// Response sent from daemon to CLI
+/// Response sent from daemon to CLI. Deserializing also accepts the `{"Error": "message"}`
+/// shape of older daemons, as `ErrorCode::Internal`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub enum DaemonResponse {
     /// Success with optional message
     Ok(Option<String>),
-    /// Error with message
-    Error(String),
+    /// Request failed; `code` is for programs, `message` for people
+    Error { code: ErrorCode, message: String },
     /// Status information
     Status {
         running: bool,
         server_configured: bool,
         node_key_present: bool,
         message: Option<String>,
+        /// Current tunnel poll interval in seconds (base interval times error backoff)
+        #[serde(default)]
+        reconcile_interval_secs: Option<u64>,
+        /// From the daemon's last public address detection; `None` until it succeeds
+        #[serde(default)]
+        public_ipv4: Option<IpAddr>,
+        #[serde(default)]
+        public_ipv6: Option<IpAddr>,
+        #[serde(default)]
+        nat_type: Option<NatType>,
     },
     /// Server configuration details
     ServerConfig {
@@ -57,26 +126,262 @@ pub enum DaemonResponse {
     },
     /// Daemon configuration details
     Config(serde_json::Value),
+    /// Outcome of `DumpState`; secrets are replaced with `REDACTED`
+    State(serde_json::Value),
+    /// Outcome of `ReloadConfig`: changed keys (dotted when nested, e.g. `port_range.max`)
+    /// now in effect, and those that only take effect after a restart
+    ConfigReloaded {
+        applied: Vec<String>,
+        deferred: Vec<String>,
+    },
+    /// WireGuard configuration of one tunnel
+    TunnelConfig(TunnelConfig),
+    /// Health of every active tunnel, ordered by tunnel ID
+    TunnelStats(Vec<TunnelStats>),
+    /// Outcome of `TestServer`
+    ServerProbe(ServerProbe),
+    /// One response per request of a `Batch` that ran, in request order
+    Batch(Vec<DaemonResponse>),
+}
+
+impl DaemonResponse {
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        DaemonResponse::Error { code, message: message.into() }
+    }
+}
+
+impl Serialize for DaemonResponse {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DaemonResponse::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DaemonResponse {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        if let Some(message) = value.get("Error").and_then(serde_json::Value::as_str) {
+            return Ok(DaemonResponse::error(ErrorCode::Internal, message));
+        }
+        DaemonResponse::deserialize(value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Why a request failed. The names are stable; the CLI exits with `exit_code`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// Wrong daemon secret, or the server rejected the node or invite key
+    AuthFailed,
+    /// No server has been set or registered with
+    ServerNotConfigured,
+    /// A server is set but the node has no node key yet
+    NotRegistered,
+    /// The server could not be reached
+    NetworkError,
+    /// Anything else, including invalid requests
+    Internal,
+}
+
+impl ErrorCode {
+    /// Process exit code the CLI uses for this error. 1 stays the catch-all.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCode::Internal => 1,
+            ErrorCode::AuthFailed => 3,
+            ErrorCode::ServerNotConfigured => 4,
+            ErrorCode::NotRegistered => 5,
+            ErrorCode::NetworkError => 6,
+        }
+    }
 }
 
+/// Pushed to `Subscribe` connections as things change in the daemon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DaemonEvent {
+    /// A tunnel interface was created and brought up
+    TunnelCreated { tunnel_id: i32, interface: String },
+    /// A tunnel was torn down because the server no longer lists it
+    TunnelDestroyed { tunnel_id: i32 },
+    /// A tunnel's handshake health changed since the last check
+    HealthChanged { tunnel_id: i32, health: TunnelHealth },
+    /// The subscriber fell behind and this many events were dropped
+    Lagged { missed: u64 },
+}
+
+/// How recently a tunnel's peer completed a WireGuard handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TunnelHealth {
+    /// Handshake within the last 3 minutes
+    Healthy,
+    /// Handshaked before, but not recently
+    Stale,
+    /// No handshake since the interface came up
+    NeverConnected,
+}
+
+/// Per-tunnel health as reported by `GetTunnelStats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelStats {
+    pub tunnel_id: i32,
+    pub peer_node_id: i32,
+    pub interface: String,
+    pub health: TunnelHealth,
+    /// Seconds since the last handshake, if there was one
+    pub last_handshake_secs: Option<u64>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// How far a `TestServer` request to the server root got.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerProbe {
+    /// Address as tested, after normalization
+    pub address: String,
+    /// Addresses the server's host resolved to
+    pub resolved: Vec<std::net::SocketAddr>,
+    /// HTTP status of `GET /`, if the server answered
+    pub status: Option<u16>,
+    /// First line of the server's root page, e.g. its name
+    pub banner: Option<String>,
+    /// Answer of `GET /version`; `None` for servers that predate it
+    #[serde(default)]
+    pub version: Option<cat4igp_shared::rest::VersionResponse>,
+    /// Stage that failed; `None` when the server answered with a 2xx status
+    pub failure: Option<ProbeFailure>,
+    /// Error message behind `failure`
+    pub detail: Option<String>,
+}
+
+/// Stage at which a server check failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProbeFailure {
+    /// Not an http(s) URL with a host
+    InvalidAddress,
+    /// The host name did not resolve
+    Dns,
+    /// Nothing listens on the port, or a firewall rejects the connection
+    ConnectionRefused,
+    /// No answer in time, often a firewall dropping packets
+    Timeout,
+    /// TLS handshake failed: untrusted certificate, or https against a plain-http port
+    Tls,
+    /// The server answered with a non-2xx status
+    Http,
+    Other,
+}
+
+/// The pieces of a tunnel's WireGuard configuration, as assembled by the daemon.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelConfig {
+    pub tunnel_id: i32,
+    pub interface: String,
+    /// `None` when redacted
+    pub private_key: Option<String>,
+    pub listen_port: Option<u16>,
+    pub mtu: i32,
+    pub peer_public_key: String,
+    /// Peer's endpoint as published by the server, not the local FEC/FakeTCP shim
+    pub endpoint: Option<String>,
+    pub allowed_ips: Vec<String>,
+    pub persistent_keepalive: Option<u16>,
+    pub fec: bool,
+    pub faketcp: bool,
+}
+
+impl TunnelConfig {
+    /// Render as a `wg-quick` style config file.
+    pub fn to_wg_quick(&self) -> String {
+        let mut out = format!("# cat4igp tunnel {} ({})\n", self.tunnel_id, self.interface);
+        if self.fec || self.faketcp {
+            out.push_str("# NOTE: this tunnel is wrapped in FEC/FakeTCP; plain WireGuard cannot reach the endpoint\n");
+        }
+
+        out.push_str("[Interface]\n");
+        out.push_str(&format!(
+            "PrivateKey = {}\n",
+            self.private_key.as_deref().unwrap_or("<redacted>")
+        ));
+        if let Some(port) = self.listen_port {
+            out.push_str(&format!("ListenPort = {}\n", port));
+        }
+        out.push_str(&format!("MTU = {}\n", self.mtu));
+
+        out.push_str("\n[Peer]\n");
+        out.push_str(&format!("PublicKey = {}\n", self.peer_public_key));
+        if let Some(endpoint) = &self.endpoint {
+            out.push_str(&format!("Endpoint = {}\n", endpoint));
+        }
+        if !self.allowed_ips.is_empty() {
+            out.push_str(&format!("AllowedIPs = {}\n", self.allowed_ips.join(", ")));
+        }
+        if let Some(keepalive) = self.persistent_keepalive.filter(|k| *k > 0) {
+            out.push_str(&format!("PersistentKeepalive = {}\n", keepalive));
+        }
+
+        out
+    }
+}
+
+/// Write `value` as one JSON frame with a big-endian u32 length prefix.
+pub async fn write_frame<W, T>(writer: &mut W, value: &T) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let bytes = serde_json::to_vec(value).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("Failed to serialize frame: {}", e))
+    })?;
+    writer.write_all(&(bytes.len() as u32).to_be_bytes()).await?;
+    writer.write_all(&bytes).await?;
+    writer.flush().await
+}
+
+/// Read one length-prefixed JSON frame, rejecting frames over `MAX_FRAME_LEN`.
+pub async fn read_frame<R, T>(reader: &mut R) -> io::Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes).await?;
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Message too large"));
+    }
+
+    let mut buffer = vec![0u8; len];
+    reader.read_exact(&mut buffer).await?;
+    serde_json::from_slice(&buffer)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid JSON: {}", e)))
+}
+
+/// Length of a daemon secret; every character is ASCII alphanumeric
+const SECRET_LEN: usize = 32;
+
 /// Shared secret for CLI-daemon authentication
+#[derive(Clone)]
 pub struct SharedSecret {
-    pub secret: String,
+    secret: String,
 }
 
 impl SharedSecret {
-    /// Create a new shared secret
-    pub fn generate() -> String {
+    /// Create a new random shared secret
+    pub fn generate() -> Self {
         const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
                                   abcdefghijklmnopqrstuvwxyz\
                                   0123456789";
         let mut rng = rand::rng();
-        (0..32)
+        let secret = (0..SECRET_LEN)
             .map(|_| {
                 let idx = rng.random_range(0..CHARSET.len());
                 CHARSET[idx] as char
             })
-            .collect()
+            .collect();
+        Self::from_value(secret)
+    }
+
+    /// Wrap an existing secret value, e.g. one read from disk
+    pub fn from_value(secret: String) -> Self {
+        SharedSecret { secret }
     }
 
     /// Load shared secret from file
@@ -89,9 +394,19 @@ impl SharedSecret {
             ));
         }
         let secret = fs::read_to_string(secret_path)?;
-        Ok(SharedSecret {
-            secret: secret.trim().to_string(),
-        })
+        let secret = secret.trim();
+        if !Self::is_well_formed(secret) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Daemon secret must be {} alphanumeric characters", SECRET_LEN),
+            ));
+        }
+        Ok(SharedSecret::from_value(secret.to_string()))
+    }
+
+    /// Whether `secret` looks like one produced by [`SharedSecret::generate`]
+    fn is_well_formed(secret: &str) -> bool {
+        secret.len() == SECRET_LEN && secret.bytes().all(|b| b.is_ascii_alphanumeric())
     }
 
     /// Save shared secret to file
@@ -109,17 +424,13 @@ impl SharedSecret {
         Ok(())
     }
 
-    /// Verify a secret matches
+    /// Verify a secret matches.
+    ///
+    /// The bytes are compared in constant time over the whole secret; a length mismatch fails
+    /// the same comparison. The secret never leaves the local machine, so this is defense in
+    /// depth rather than a hard requirement.
     pub fn verify(&self, secret: &str) -> bool {
-        // Constant-time comparison
-        self.secret.as_bytes().len() == secret.as_bytes().len()
-            && self
-                .secret
-                .as_bytes()
-                .iter()
-                .zip(secret.as_bytes().iter())
-                .fold(0, |acc, (a, b)| acc | (a ^ b))
-                == 0
+        self.secret.as_bytes().ct_eq(secret.as_bytes()).into()
     }
 
     /// Get the secret value
@@ -135,17 +446,41 @@ mod tests {
     #[test]
     fn test_generate_secret() {
         let secret = SharedSecret::generate();
-        assert_eq!(secret.len(), 32);
+        assert_eq!(secret.value().len(), 32);
+        assert!(secret.value().chars().all(|c| c.is_ascii_alphanumeric()));
     }
 
     #[test]
     fn test_verify_secret() {
-        let secret = SharedSecret::generate();
-        let shared = SharedSecret {
-            secret: secret.clone(),
-        };
-        assert!(shared.verify(&secret));
+        let shared = SharedSecret::generate();
+        assert!(shared.verify(shared.value()));
         assert!(!shared.verify("wrong"));
+        assert!(!shared.verify(""));
+        // a correct prefix with extra bytes is still a mismatch
+        assert!(!shared.verify(&format!("{}x", shared.value())));
+        let mut flipped = shared.value().to_string().into_bytes();
+        flipped[SECRET_LEN - 1] ^= 1;
+        assert!(!shared.verify(std::str::from_utf8(&flipped).unwrap()));
+
+        let loaded = SharedSecret::from_value(shared.value().to_string());
+        assert!(loaded.verify(shared.value()));
+    }
+
+    #[test]
+    fn test_load_rejects_malformed_secret() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let secret_path = temp_dir.path().join(".daemon_secret");
+
+        for bad in ["", "\n", "short", &"a".repeat(SECRET_LEN - 1), &format!("{}!", "a".repeat(SECRET_LEN - 1))] {
+            fs::write(&secret_path, bad).unwrap();
+            let err = SharedSecret::load(temp_dir.path()).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", bad);
+        }
+
+        let secret = SharedSecret::generate();
+        secret.save(temp_dir.path()).unwrap();
+        let loaded = SharedSecret::load(temp_dir.path()).unwrap();
+        assert_eq!(loaded.value(), secret.value());
     }
 
     #[test]
@@ -169,4 +504,100 @@ mod tests {
             _ => panic!("Wrong request type"),
         }
     }
+
+    #[test]
+    fn test_status_network_fields() {
+        // a Status from an older daemon has no network fields
+        let json = r#"{"Status":{"running":true,"server_configured":false,"node_key_present":false,"message":null}}"#;
+        match serde_json::from_str(json).unwrap() {
+            DaemonResponse::Status { public_ipv4, public_ipv6, nat_type, .. } => {
+                assert_eq!((public_ipv4, public_ipv6, nat_type), (None, None, None));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let status = DaemonResponse::Status {
+            running: true,
+            server_configured: true,
+            node_key_present: true,
+            message: None,
+            reconcile_interval_secs: Some(30),
+            public_ipv4: Some("198.51.100.7".parse().unwrap()),
+            public_ipv6: None,
+            nat_type: Some(NatType::AddressPortDependentMapping),
+        };
+        match serde_json::from_str(&serde_json::to_string(&status).unwrap()).unwrap() {
+            DaemonResponse::Status { public_ipv4, nat_type, .. } => {
+                assert_eq!(public_ipv4, Some("198.51.100.7".parse().unwrap()));
+                assert_eq!(nat_type, Some(NatType::AddressPortDependentMapping));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_response_codes() {
+        let json = serde_json::to_string(&DaemonResponse::error(ErrorCode::NotRegistered, "no key")).unwrap();
+        assert_eq!(json, r#"{"Error":{"code":"NotRegistered","message":"no key"}}"#);
+        match serde_json::from_str(&json).unwrap() {
+            DaemonResponse::Error { code, message } => {
+                assert_eq!(code, ErrorCode::NotRegistered);
+                assert_eq!(message, "no key");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // older daemons send a bare message, also inside batches
+        let json = r#"{"Batch":[{"Ok":null},{"Error":"Server not configured"}]}"#;
+        match serde_json::from_str(json).unwrap() {
+            DaemonResponse::Batch(responses) => match &responses[1] {
+                DaemonResponse::Error { code, message } => {
+                    assert_eq!(*code, ErrorCode::Internal);
+                    assert_eq!(message, "Server not configured");
+                }
+                other => panic!("Unexpected response: {:?}", other),
+            },
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tunnel_config_to_wg_quick() {
+        let mut config = TunnelConfig {
+            tunnel_id: 7,
+            interface: "cat0000000000A7".to_string(),
+            private_key: None,
+            listen_port: Some(51820),
+            mtu: 1420,
+            peer_public_key: "peerkey=".to_string(),
+            endpoint: Some("peer.example.com:51820".to_string()),
+            allowed_ips: vec!["0.0.0.0/0".to_string(), "::/0".to_string()],
+            persistent_keepalive: Some(25),
+            fec: false,
+            faketcp: false,
+        };
+
+        assert_eq!(
+            config.to_wg_quick(),
+            "# cat4igp tunnel 7 (cat0000000000A7)\n\
+             [Interface]\n\
+             PrivateKey = <redacted>\n\
+             ListenPort = 51820\n\
+             MTU = 1420\n\
+             \n\
+             [Peer]\n\
+             PublicKey = peerkey=\n\
+             Endpoint = peer.example.com:51820\n\
+             AllowedIPs = 0.0.0.0/0, ::/0\n\
+             PersistentKeepalive = 25\n"
+        );
+
+        config.private_key = Some("privkey=".to_string());
+        config.persistent_keepalive = Some(0);
+        config.fec = true;
+        let rendered = config.to_wg_quick();
+        assert!(rendered.contains("PrivateKey = privkey=\n"));
+        assert!(!rendered.contains("PersistentKeepalive"));
+        assert!(rendered.contains("# NOTE:"));
+    }
 }
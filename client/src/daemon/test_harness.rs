@@ -0,0 +1,238 @@
+//! Runs a real daemon on a temporary socket so tests can go through the IPC path the CLI uses.
+
+use std::path::PathBuf;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::net::UnixStream;
+use tokio::task::JoinHandle;
+
+use super::client::DaemonClient;
+use super::protocol::{read_frame, write_frame, DaemonRequest, DaemonResponse, ErrorCode};
+use super::{Daemon, IpcMessage};
+use crate::config::ClientConfig;
+
+/// Daemon listening in its own temp directory; stopped and cleaned up on drop.
+pub struct TestDaemon {
+    pub client: DaemonClient,
+    /// Config file the daemon was started from, for `ReloadConfig`
+    pub config_file: PathBuf,
+    socket: PathBuf,
+    task: JoinHandle<std::io::Result<()>>,
+    _dir: TempDir,
+}
+
+impl TestDaemon {
+    pub async fn start() -> Self {
+        let dir = TempDir::new().unwrap();
+        let config = ClientConfig {
+            daemon_socket: dir.path().join("daemon.sock"),
+            data_dir: dir.path().join("data"),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&config.data_dir).unwrap();
+        let config_file = dir.path().join("client.toml");
+        config.save_to_file(&config_file).unwrap();
+
+        let socket = config.daemon_socket.clone();
+        let data_dir = config.data_dir.clone();
+        let daemon = Daemon::new(config).await.unwrap().with_config_path(config_file.clone());
+        let task = tokio::spawn(async move { daemon.run().await });
+
+        for _ in 0..100 {
+            if socket.exists() {
+                break;
+            }
+            assert!(!task.is_finished(), "daemon exited before listening");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(socket.exists(), "daemon did not create {:?}", socket);
+
+        TestDaemon {
+            client: DaemonClient::new(&socket, &data_dir).unwrap(),
+            config_file,
+            socket,
+            task,
+            _dir: dir,
+        }
+    }
+
+    /// Send `request` over the socket authenticated with `secret` instead of the real one.
+    pub async fn send_with_secret(&self, secret: &str, request: DaemonRequest) -> DaemonResponse {
+        let mut stream = UnixStream::connect(&self.socket).await.unwrap();
+        let message = IpcMessage {
+            secret: secret.to_string(),
+            request,
+        };
+        write_frame(&mut stream, &message).await.unwrap();
+        read_frame(&mut stream).await.unwrap()
+    }
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_set_and_get_server() {
+        let daemon = TestDaemon::start().await;
+
+        match daemon.client.send_request(DaemonRequest::GetServer).await.unwrap() {
+            DaemonResponse::Error { code, message } => {
+                assert_eq!(code, ErrorCode::ServerNotConfigured);
+                assert!(message.contains("not configured"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let response = daemon
+            .client
+            .send_request(DaemonRequest::SetServer {
+                address: "https://example.com/ ".to_string(),
+                invite_code: " test-invite ".to_string(),
+                verify_tls: false,
+            })
+            .await
+            .unwrap();
+        assert!(matches!(response, DaemonResponse::Ok(_)), "{:?}", response);
+
+        match daemon.client.send_request(DaemonRequest::GetServer).await.unwrap() {
+            DaemonResponse::ServerConfig { address, invite_code, verify_tls, registered } => {
+                assert_eq!(address, "https://example.com");
+                assert_eq!(invite_code, "test-invite");
+                assert!(!verify_tls);
+                assert!(!registered);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_set_then_get_server() {
+        let daemon = TestDaemon::start().await;
+
+        let request = DaemonRequest::Batch {
+            requests: vec![
+                DaemonRequest::SetServer {
+                    address: "https://example.com".to_string(),
+                    invite_code: "test-invite".to_string(),
+                    verify_tls: true,
+                },
+                DaemonRequest::GetServer,
+            ],
+            stop_on_error: true,
+        };
+        let responses = match daemon.client.send_request(request).await.unwrap() {
+            DaemonResponse::Batch(responses) => responses,
+            other => panic!("Unexpected response: {:?}", other),
+        };
+        assert_eq!(responses.len(), 2);
+        assert!(matches!(responses[0], DaemonResponse::Ok(_)), "{:?}", responses[0]);
+        match &responses[1] {
+            DaemonResponse::ServerConfig { address, invite_code, .. } => {
+                assert_eq!(address, "https://example.com");
+                assert_eq!(invite_code, "test-invite");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_stop_on_error_and_nesting() {
+        let daemon = TestDaemon::start().await;
+
+        // GetServer fails while no server is configured
+        let batch = |stop_on_error| DaemonRequest::Batch {
+            requests: vec![DaemonRequest::GetServer, DaemonRequest::Status],
+            stop_on_error,
+        };
+        match daemon.client.send_request(batch(true)).await.unwrap() {
+            DaemonResponse::Batch(responses) => {
+                assert_eq!(responses.len(), 1);
+                assert!(matches!(responses[0], DaemonResponse::Error { .. }));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        match daemon.client.send_request(batch(false)).await.unwrap() {
+            DaemonResponse::Batch(responses) => {
+                assert_eq!(responses.len(), 2);
+                assert!(matches!(responses[1], DaemonResponse::Status { .. }));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let nested = DaemonRequest::Batch {
+            requests: vec![DaemonRequest::Status, batch(false)],
+            stop_on_error: false,
+        };
+        match daemon.client.send_request(nested).await.unwrap() {
+            DaemonResponse::Error { message, .. } => assert!(message.contains("not allowed inside a batch"), "{}", message),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status() {
+        let daemon = TestDaemon::start().await;
+
+        match daemon.client.send_request(DaemonRequest::Status).await.unwrap() {
+            DaemonResponse::Status { running, server_configured, node_key_present, .. } => {
+                assert!(running);
+                assert!(!server_configured);
+                assert!(!node_key_present);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_secret() {
+        let daemon = TestDaemon::start().await;
+
+        match daemon.send_with_secret("wrong-secret", DaemonRequest::Status).await {
+            DaemonResponse::Error { code, message } => {
+                assert_eq!(code, ErrorCode::AuthFailed);
+                assert_eq!(message, "Authentication failed");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // a bad request leaves the daemon serving
+        let response = daemon.client.send_request(DaemonRequest::Status).await.unwrap();
+        assert!(matches!(response, DaemonResponse::Status { .. }), "{:?}", response);
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_applies_hot_fields() {
+        let daemon = TestDaemon::start().await;
+        let mut config = ClientConfig::from_file(&daemon.config_file).unwrap();
+        config.reconcile_interval_secs = 7;
+        config.public_hostname_ipv4 = Some("node.example.com".to_string());
+        config.daemon_socket = daemon.config_file.with_file_name("moved.sock");
+        config.save_to_file(&daemon.config_file).unwrap();
+
+        match daemon.client.send_request(DaemonRequest::ReloadConfig).await.unwrap() {
+            DaemonResponse::ConfigReloaded { applied, deferred } => {
+                assert_eq!(applied, vec!["public_hostname_ipv4", "reconcile_interval_secs"]);
+                assert_eq!(deferred, vec!["daemon_socket"]);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // served on the original socket, now with the new values
+        match daemon.client.send_request(DaemonRequest::GetConfig).await.unwrap() {
+            DaemonResponse::Config(value) => {
+                assert_eq!(value["reconcile_interval_secs"], 7);
+                assert_eq!(value["public_hostname_ipv4"], "node.example.com");
+                assert_eq!(value["daemon_socket"], daemon.socket.to_str().unwrap());
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(!daemon.config_file.with_file_name("moved.sock").exists());
+    }
+}
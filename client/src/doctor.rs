@@ -0,0 +1,336 @@
+//! `cat4igp-client doctor`: run every diagnostic and print a checklist with hints.
+
+use std::path::Path;
+
+use crate::config::{ClientConfig, PortRange};
+use crate::daemon::client::DaemonClient;
+use crate::daemon::protocol::{DaemonRequest, DaemonResponse, ProbeFailure, ServerProbe};
+use crate::interface::privileges;
+use crate::network::public_ip::{NatReport, NatType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but something may limit connectivity
+    Warn,
+    /// The node can't work until this is fixed
+    Fail,
+    /// Not run because an earlier check failed
+    Skip,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Check { name, status, detail: detail.into(), hint: None }
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Run every check in order. Checks that need the daemon are skipped when it can't be reached.
+pub async fn run(config_path: &Path) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let (config, check) = check_config(config_path);
+    checks.push(check);
+
+    let status = match query_daemon(&config, DaemonRequest::Status).await {
+        Ok(status @ DaemonResponse::Status { .. }) => {
+            checks.push(Check::new("Daemon", CheckStatus::Pass, format!("reachable at {:?}", config.daemon_socket)));
+            Some(status)
+        }
+        Ok(other) => {
+            checks.push(Check::new("Daemon", CheckStatus::Fail, format!("unexpected answer: {:?}", other)));
+            None
+        }
+        Err(e) => {
+            checks.push(
+                Check::new("Daemon", CheckStatus::Fail, e)
+                    .with_hint("start it with `cat4igp-client daemon`, and run the doctor as the same user"),
+            );
+            None
+        }
+    };
+
+    match &status {
+        Some(DaemonResponse::Status { server_configured: true, node_key_present, .. }) => {
+            checks.push(check_server(&config).await);
+            checks.push(if *node_key_present {
+                Check::new("Registration", CheckStatus::Pass, "node key present")
+            } else {
+                Check::new("Registration", CheckStatus::Fail, "server configured but not registered")
+                    .with_hint("run `cat4igp-client register --server <URL> --invite <CODE>`")
+            });
+        }
+        Some(_) => {
+            let hint = "run `cat4igp-client register --server <URL> --invite <CODE>`";
+            checks.push(Check::new("Server", CheckStatus::Fail, "no server configured").with_hint(hint));
+            checks.push(Check::new("Registration", CheckStatus::Skip, "no server configured"));
+        }
+        None => {
+            checks.push(Check::new("Server", CheckStatus::Skip, "daemon not reachable"));
+            checks.push(Check::new("Registration", CheckStatus::Skip, "daemon not reachable"));
+        }
+    }
+
+    checks.extend(check_network(&config).await);
+    checks.push(check_wireguard_module(Path::new("/sys/module")));
+    checks.push(check_net_admin(&config));
+    checks
+}
+
+/// Print the checklist; returns whether any check failed.
+pub fn print(checks: &[Check]) -> bool {
+    for check in checks {
+        let mark = match check.status {
+            CheckStatus::Pass => "✓",
+            CheckStatus::Warn => "⚠",
+            CheckStatus::Fail => "✗",
+            CheckStatus::Skip => "-",
+        };
+        println!("{} {}: {}", mark, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("    → {}", hint);
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.status == CheckStatus::Fail).count();
+    if failed > 0 {
+        eprintln!("✗ {} check(s) failed", failed);
+    }
+    failed > 0
+}
+
+/// Load and validate the configuration; defaults are used for the remaining checks if it is bad.
+fn check_config(path: &Path) -> (ClientConfig, Check) {
+    if !path.exists() {
+        let check = Check::new("Configuration", CheckStatus::Warn, format!("{:?} not found, using defaults", path))
+            .with_hint("write one with `cat4igp-client gen-config --output <FILE>`");
+        return (ClientConfig::default(), check);
+    }
+
+    let config = match ClientConfig::from_file(path) {
+        Ok(config) => config,
+        Err(e) => {
+            let check = Check::new("Configuration", CheckStatus::Fail, format!("{:?}: {}", path, e))
+                .with_hint("fix the file, or compare it with `cat4igp-client gen-config`");
+            return (ClientConfig::default(), check);
+        }
+    };
+
+    let problem = PortRange::new(config.port_range.min, config.port_range.max)
+        .err()
+        .map(|e| format!("port_range: {}", e))
+        .or_else(|| config.fec.validate().err().map(|e| format!("fec: {}", e)));
+    let check = match problem {
+        Some(problem) => Check::new("Configuration", CheckStatus::Fail, problem)
+            .with_hint(format!("fix the setting in {:?}", path)),
+        None => Check::new("Configuration", CheckStatus::Pass, format!("{:?}", path)),
+    };
+    (config, check)
+}
+
+async fn query_daemon(config: &ClientConfig, request: DaemonRequest) -> Result<DaemonResponse, String> {
+    let client = DaemonClient::new(&config.daemon_socket, &config.data_dir)
+        .map_err(|e| format!("cannot read the daemon secret in {:?}: {}", config.data_dir, e))?;
+    // a missing daemon should be reported, not waited for
+    client.with_retries(1).send_request(request).await.map_err(|e| e.to_string())
+}
+
+async fn check_server(config: &ClientConfig) -> Check {
+    let request = DaemonRequest::TestServer { address: None, verify_tls: None };
+    match query_daemon(config, request).await {
+        Ok(DaemonResponse::ServerProbe(probe)) => server_check(&probe),
+        Ok(DaemonResponse::Error { message, .. }) => Check::new("Server", CheckStatus::Fail, message),
+        Ok(other) => Check::new("Server", CheckStatus::Fail, format!("unexpected answer: {:?}", other)),
+        Err(e) => Check::new("Server", CheckStatus::Fail, e),
+    }
+}
+
+fn server_check(probe: &ServerProbe) -> Check {
+    let Some(failure) = probe.failure else {
+        let detail = match &probe.version {
+            Some(version) => format!("{} answers (version {})", probe.address, version.version),
+            None => format!("{} answers", probe.address),
+        };
+        return Check::new("Server", CheckStatus::Pass, detail);
+    };
+
+    let hint = match failure {
+        ProbeFailure::InvalidAddress => "the address must be an http(s) URL",
+        ProbeFailure::Dns => "check the host name and this machine's DNS resolver",
+        ProbeFailure::ConnectionRefused => "check the port, that the server is running, and firewalls",
+        ProbeFailure::Timeout => "check routing and firewalls between this node and the server",
+        ProbeFailure::Tls => "install a trusted certificate on the server, or try http:// if it has no TLS",
+        ProbeFailure::Http => "check the server logs, or a reverse proxy in front of it",
+        ProbeFailure::Other => "run `cat4igp-client server --test` for details",
+    };
+    let detail = match &probe.detail {
+        Some(detail) => format!("{} failed ({:?}): {}", probe.address, failure, detail),
+        None => format!("{} failed ({:?})", probe.address, failure),
+    };
+    Check::new("Server", CheckStatus::Fail, detail).with_hint(hint)
+}
+
+/// Public address of each configured family, then the NAT type of the first one that has an address.
+async fn check_network(config: &ClientConfig) -> Vec<Check> {
+    let mut detector = config.public_ip_detector();
+    if config.stun_servers.is_empty() && let Err(e) = detector.init().await {
+        return vec![
+            Check::new("Public IP", CheckStatus::Fail, format!("could not load STUN server lists: {}", e))
+                .with_hint("set `stun_servers` in the configuration if HTTPS to the lists is blocked"),
+            Check::new("NAT type", CheckStatus::Skip, "no STUN servers"),
+        ];
+    }
+
+    let mut detected = Vec::new();
+    let mut missing = Vec::new();
+    if config.ip_stack.has_ipv4() {
+        match detector.detect_public_ipv4().await {
+            Ok(ip) => detected.push(format!("IPv4 {}", ip)),
+            Err(e) => missing.push(format!("IPv4: {}", e)),
+        }
+    }
+    if config.ip_stack.has_ipv6() {
+        match detector.detect_public_ipv6().await {
+            Ok(ip) => detected.push(format!("IPv6 {}", ip)),
+            Err(e) => missing.push(format!("IPv6: {}", e)),
+        }
+    }
+
+    let public_ip = match (detected.is_empty(), missing.is_empty()) {
+        (false, true) => Check::new("Public IP", CheckStatus::Pass, detected.join(", ")),
+        (false, false) => Check::new(
+            "Public IP",
+            CheckStatus::Warn,
+            format!("{}; not detected: {}", detected.join(", "), missing.join("; ")),
+        )
+        .with_hint("set `ip_stack` to the families this host actually has"),
+        (true, _) => Check::new("Public IP", CheckStatus::Fail, missing.join("; "))
+            .with_hint("check that outbound UDP is allowed; STUN uses it"),
+    };
+    if public_ip.status == CheckStatus::Fail {
+        return vec![public_ip, Check::new("NAT type", CheckStatus::Skip, "no public address detected")];
+    }
+
+    let report = if detected.iter().any(|d| d.starts_with("IPv4")) {
+        detector.detect_nat_report_ipv4().await
+    } else {
+        detector.detect_nat_report_ipv6().await
+    };
+    vec![public_ip, nat_check(report)]
+}
+
+fn nat_check(report: Result<NatReport, String>) -> Check {
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => return Check::new("NAT type", CheckStatus::Warn, e),
+    };
+
+    if report.is_cgnat {
+        return Check::new("NAT type", CheckStatus::Warn, format!("{:?}, behind carrier-grade NAT", report.nat_type))
+            .with_hint("direct tunnels will likely fail; peers will need a relay");
+    }
+    match report.nat_type {
+        NatType::NoUdpConnectivity => Check::new("NAT type", CheckStatus::Fail, "no UDP connectivity")
+            .with_hint("WireGuard needs UDP; allow it outbound and inbound on `port_range`"),
+        NatType::AddressDependentMapping | NatType::AddressPortDependentMapping | NatType::Unknown => {
+            Check::new("NAT type", CheckStatus::Warn, format!("{:?}", report.nat_type))
+                .with_hint("peers behind NAT may not reach this node; forward `port_range` or set a public hostname")
+        }
+        nat_type => Check::new("NAT type", CheckStatus::Pass, format!("{:?}", nat_type)),
+    }
+}
+
+/// The kernel WireGuard module, as listed under `modules_dir` (normally /sys/module).
+fn check_wireguard_module(modules_dir: &Path) -> Check {
+    if !cfg!(target_os = "linux") {
+        return Check::new("WireGuard module", CheckStatus::Skip, "only checked on Linux");
+    }
+    if modules_dir.join("wireguard").exists() {
+        Check::new("WireGuard module", CheckStatus::Pass, "loaded")
+    } else {
+        Check::new("WireGuard module", CheckStatus::Fail, "kernel module `wireguard` is not loaded")
+            .with_hint("run `modprobe wireguard`, or install it for this kernel")
+    }
+}
+
+/// CAP_NET_ADMIN of the running daemon if its PID is known, else of this process.
+fn check_net_admin(config: &ClientConfig) -> Check {
+    let daemon_pid = config
+        .pid_file
+        .as_deref()
+        .and_then(|path| crate::daemon::pid_file::live_pid(path).ok().flatten());
+    let subject = match daemon_pid {
+        Some(pid) => format!("daemon (PID {})", pid),
+        None => "this process".to_string(),
+    };
+
+    match privileges::has_net_admin(daemon_pid) {
+        Ok(true) => Check::new("Permissions", CheckStatus::Pass, format!("{} can manage interfaces", subject)),
+        Ok(false) => Check::new("Permissions", CheckStatus::Fail, format!("{} cannot manage interfaces", subject))
+            .with_hint(privileges::MISSING_NET_ADMIN_HINT),
+        Err(e) => Check::new("Permissions", CheckStatus::Warn, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let (_, check) = check_config(&dir.path().join("missing.toml"));
+        assert_eq!(check.status, CheckStatus::Warn);
+
+        let path = dir.path().join("client.toml");
+        std::fs::write(&path, "not = [toml").unwrap();
+        assert_eq!(check_config(&path).1.status, CheckStatus::Fail);
+
+        let mut config = ClientConfig::default();
+        config.save_to_file(&path).unwrap();
+        assert_eq!(check_config(&path).1.status, CheckStatus::Pass);
+
+        config.port_range.max = config.port_range.min;
+        config.save_to_file(&path).unwrap();
+        let (_, check) = check_config(&path);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.starts_with("port_range"), "{}", check.detail);
+    }
+
+    #[test]
+    fn test_nat_check() {
+        let report = |nat_type, is_cgnat| Ok(NatReport { nat_type, mapped_addr: None, local_ip: None, is_cgnat });
+
+        assert_eq!(nat_check(report(NatType::OpenInternet, false)).status, CheckStatus::Pass);
+        assert_eq!(nat_check(report(NatType::AddressPortDependentMapping, false)).status, CheckStatus::Warn);
+        assert_eq!(nat_check(report(NatType::EndpointIndependentNoFiltering, true)).status, CheckStatus::Warn);
+        assert_eq!(nat_check(report(NatType::NoUdpConnectivity, false)).status, CheckStatus::Fail);
+        assert_eq!(nat_check(Err("timeout".to_string())).status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn test_check_wireguard_module() {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(check_wireguard_module(dir.path()).status, CheckStatus::Fail);
+        std::fs::create_dir(dir.path().join("wireguard")).unwrap();
+        assert_eq!(check_wireguard_module(dir.path()).status, CheckStatus::Pass);
+    }
+}
@@ -6,9 +6,41 @@ use blake2::{Blake2s256, Digest};
 use std::net::IpAddr;
 use std::net::Ipv6Addr;
 
+pub mod privileges;
+
 pub const IPV4_DEFAULT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0));
 pub const IPV6_DEFAULT: IpAddr = IpAddr::V6(std::net::Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0));
 
+/// Longest interface name the kernel accepts: IFNAMSIZ (16) minus the terminating NUL.
+pub const MAX_INTERFACE_NAME_LEN: usize = 15;
+
+/// Check a name before handing it to netlink, which would otherwise reject or truncate it.
+pub fn validate_interface_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_INTERFACE_NAME_LEN {
+        return Err(format!(
+            "interface name {:?} must be 1..={} bytes, got {}",
+            name,
+            MAX_INTERFACE_NAME_LEN,
+            name.len()
+        ));
+    }
+    if !name.bytes().all(|b| b.is_ascii_graphic() && b != b'/' && b != b':') {
+        return Err(format!("interface name {:?} contains characters Linux does not allow", name));
+    }
+    Ok(())
+}
+
+/// Interface-level traffic counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+}
+
 pub fn generate_ipv6_lla_from_mac(mac: [u8; 6]) -> IpAddr {
     let mut addr = [0u8; 16];
     addr[0] = 0xfe;
@@ -24,11 +56,20 @@ pub fn generate_ipv6_lla_from_mac(mac: [u8; 6]) -> IpAddr {
     IpAddr::V6(std::net::Ipv6Addr::from(addr))
 }
 
+/// Derive a stable `fe80::/64` address from `seed` (usually the interface name).
+///
+/// The interface identifier is always marked locally administered (U/L bit cleared),
+/// and is never all zero: a seed hashing to that is rehashed with a counter appended.
 pub fn generate_ipv6_lla_from_seed(seed: Vec<u8>) -> IpAddr {
-    let mut hasher = Blake2s256::new();
-
-    hasher.update(seed);
-    let hash = hasher.finalize();
+    let mut hash = Blake2s256::digest(&seed);
+    let mut counter: u8 = 0;
+    while !has_nonzero_iid(&hash) {
+        counter = counter.wrapping_add(1);
+        let mut hasher = Blake2s256::new();
+        hasher.update(&seed);
+        hasher.update([counter]);
+        hash = hasher.finalize();
+    }
 
     let prefix: [u8; 16] = [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
     let mask: [u8; 16] = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0];
@@ -38,16 +79,36 @@ pub fn generate_ipv6_lla_from_seed(seed: Vec<u8>) -> IpAddr {
     for i in 0..16 {
         result[i] = (prefix[i] & mask[i]) | (hash[i] & not_mask[i]);
     }
+    result[8] &= !0x02;
 
     IpAddr::V6(Ipv6Addr::from(result))
 }
 
+/// Pick the first seeded link-local address for `seed` that is not in `taken`.
+///
+/// The first candidate is `generate_ipv6_lla_from_seed(seed)`; fallbacks salt the seed
+/// with `#1`, `#2`, ... so the choice stays deterministic for the same set of conflicts.
+pub fn generate_ipv6_lla_avoiding(seed: &[u8], taken: &[IpAddr]) -> Option<IpAddr> {
+    (0..=u8::MAX).map(|salt| {
+        let mut salted = seed.to_vec();
+        if salt > 0 {
+            salted.extend_from_slice(format!("#{}", salt).as_bytes());
+        }
+        generate_ipv6_lla_from_seed(salted)
+    }).find(|addr| !taken.contains(addr))
+}
 
-pub async fn add_addr(interface: String, addr: IpNet) -> Result<(), Box<dyn std::error::Error>> {
-    let (connection, handle, _) = new_connection()?;
+/// Whether the interface identifier left after clearing the U/L bit would be nonzero.
+fn has_nonzero_iid(hash: &[u8]) -> bool {
+    (hash[8] & !0x02) != 0 || hash[9..16].iter().any(|b| *b != 0)
+}
 
-    let conn_poll = tokio::spawn(connection);
+/// Whether `err` is a netlink NACK carrying `errno`.
+fn is_netlink_errno(err: &rtnetlink::Error, errno: i32) -> bool {
+    matches!(err, rtnetlink::Error::NetlinkError(msg) if msg.raw_code() == -errno)
+}
 
+async fn get_link_index(handle: &rtnetlink::Handle, interface: String) -> Result<u32, Box<dyn std::error::Error>> {
     let mut link_list_stream = handle.link().get().match_name(interface).execute();
 
     let mut id = None;
@@ -55,13 +116,30 @@ pub async fn add_addr(interface: String, addr: IpNet) -> Result<(), Box<dyn std:
         id = Some(link_msg.header.index);
     }
 
-    let link_index = id.ok_or("failed to find interface")?;
+    Ok(id.ok_or("failed to find interface")?)
+}
+
+pub async fn add_addr(interface: String, addr: IpNet) -> Result<(), Box<dyn std::error::Error>> {
+    let (connection, handle, _) = new_connection()?;
+
+    let conn_poll = tokio::spawn(connection);
+
+    let link_index = get_link_index(&handle, interface).await?;
 
-    handle
+    // NLM_F_REPLACE updates an existing address (e.g. a different prefix length) instead of failing.
+    let result = handle
         .address()
         .add(link_index, addr.addr(), addr.prefix_len())
+        .replace()
         .execute()
-        .await?;
+        .await;
+    match result {
+        Err(e) if !is_netlink_errno(&e, libc::EEXIST) => {
+            conn_poll.abort();
+            return Err(e.into());
+        }
+        _ => {}
+    }
 
     conn_poll.abort();
     Ok(())
@@ -72,19 +150,42 @@ pub async fn get_addr(interface: String) -> Result<Vec<IpNet>, Box<dyn std::erro
 
     let conn_poll = tokio::spawn(connection);
 
-    let mut link_list_stream = handle.link().get().match_name(interface).execute();
+    let link_index = get_link_index(&handle, interface).await?;
 
-    let mut id = None;
-    if let Some(Ok(link_msg)) = link_list_stream.next().await {
-        id = Some(link_msg.header.index);
+    let mut addr_list_stream = handle.address().get().set_link_index_filter(link_index).execute();
+
+    let mut addrs = Vec::new();
+    while let Some(Ok(addr_msg)) = addr_list_stream.next().await {
+        let ip = addr_msg.attributes.iter().find_map(|attr| {
+            match attr {
+                AddressAttribute::Address(a) => Some(a),
+                _ => None,
+            }
+        }).ok_or("failed to find address attribute")?.to_owned();
+
+        addrs.push(IpNet::new(ip, addr_msg.header.prefix_len).map_err(|_| "failed to parse IP network")?);
     }
 
-    let link_index = id.ok_or("failed to find interface")?;
+    conn_poll.abort();
+    Ok(addrs)
+}
 
-    let mut addr_list_stream = handle.address().get().set_link_index_filter(link_index).execute();
+/// Get addresses assigned to every link except `interface`.
+pub async fn get_addr_on_other_links(interface: String) -> Result<Vec<IpNet>, Box<dyn std::error::Error>> {
+    let (connection, handle, _) = new_connection()?;
+
+    let conn_poll = tokio::spawn(connection);
+
+    let link_index = get_link_index(&handle, interface).await?;
+
+    let mut addr_list_stream = handle.address().get().execute();
 
     let mut addrs = Vec::new();
     while let Some(Ok(addr_msg)) = addr_list_stream.next().await {
+        if addr_msg.header.index == link_index {
+            continue;
+        }
+
         let ip = addr_msg.attributes.iter().find_map(|attr| {
             match attr {
                 AddressAttribute::Address(a) => Some(a),
@@ -99,19 +200,68 @@ pub async fn get_addr(interface: String) -> Result<Vec<IpNet>, Box<dyn std::erro
     Ok(addrs)
 }
 
-pub async fn del_addr(interface: String, addr: IpNet) -> Result<(), Box<dyn std::error::Error>> {
+/// Which families have at least one address that can reach beyond this host, across all links
+/// except those `skip_link` matches by name, e.g. our own tunnels carrying overlay addresses.
+pub async fn get_usable_families(
+    skip_link: impl Fn(&str) -> bool,
+) -> Result<(bool, bool), Box<dyn std::error::Error>> {
     let (connection, handle, _) = new_connection()?;
 
     let conn_poll = tokio::spawn(connection);
 
-    let mut link_list_stream = handle.link().get().match_name(interface).execute();
+    let mut skipped = Vec::new();
+    let mut link_list_stream = handle.link().get().execute();
+    while let Some(Ok(link_msg)) = link_list_stream.next().await {
+        let name = link_msg.attributes.iter().find_map(|attr| match attr {
+            LinkAttribute::IfName(name) => Some(name),
+            _ => None,
+        });
+        if name.is_some_and(|name| skip_link(name)) {
+            skipped.push(link_msg.header.index);
+        }
+    }
 
-    let mut id = None;
-    if let Some(Ok(link_msg)) = link_list_stream.next().await {
-        id = Some(link_msg.header.index);
+    let mut addr_list_stream = handle.address().get().execute();
+
+    let (mut has_ipv4, mut has_ipv6) = (false, false);
+    while let Some(Ok(addr_msg)) = addr_list_stream.next().await {
+        if skipped.contains(&addr_msg.header.index) {
+            continue;
+        }
+
+        let ip = addr_msg.attributes.iter().find_map(|attr| match attr {
+            AddressAttribute::Address(a) => Some(*a),
+            _ => None,
+        });
+        match ip {
+            Some(ip @ IpAddr::V4(_)) if is_usable_address(ip) => has_ipv4 = true,
+            Some(ip @ IpAddr::V6(_)) if is_usable_address(ip) => has_ipv6 = true,
+            _ => {}
+        }
     }
 
-    let link_index = id.ok_or("failed to find interface")?;
+    conn_poll.abort();
+    Ok((has_ipv4, has_ipv6))
+}
+
+/// IPv4 behind NAT is still usable, so only loopback and link-local are excluded there.
+/// IPv6 needs a global address: loopback, link-local (fe80::/10) and ULA (fc00::/7) don't count.
+pub fn is_usable_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => !(v4.is_loopback() || v4.is_link_local() || v4.is_unspecified()),
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            !(v6.is_loopback() || v6.is_unspecified() || (first & 0xffc0) == 0xfe80 || (first & 0xfe00) == 0xfc00)
+        }
+    }
+}
+
+pub async fn del_addr(interface: String, addr: IpNet) -> Result<(), Box<dyn std::error::Error>> {
+    let (connection, handle, _) = new_connection()?;
+
+    let conn_poll = tokio::spawn(connection);
+
+    let link_index = get_link_index(&handle, interface).await?;
 
     let header = AddressHeader {
         index: link_index,
@@ -122,29 +272,62 @@ pub async fn del_addr(interface: String, addr: IpNet) -> Result<(), Box<dyn std:
     message.header = header;
     message.attributes = vec![AddressAttribute::Address(addr.addr().to_owned())];
 
-    handle
+    let result = handle
         .address()
         .del(message)
         .execute()
-        .await?;
+        .await;
+    match result {
+        Err(e) if !is_netlink_errno(&e, libc::EADDRNOTAVAIL) => {
+            conn_poll.abort();
+            return Err(e.into());
+        }
+        _ => {}
+    }
 
     conn_poll.abort();
     Ok(())
 }
 
-pub async fn link_up_with_mtu(interface: String, mtu: u32) -> Result<(), Box<dyn std::error::Error>> {
+/// Names of every link on the host.
+pub async fn list_link_names() -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let (connection, handle, _) = new_connection()?;
 
     let conn_poll = tokio::spawn(connection);
 
-    let mut link_list_stream = handle.link().get().match_name(interface).execute();
+    let mut link_list_stream = handle.link().get().execute();
 
-    let mut id = None;
-    if let Some(Ok(link_msg)) = link_list_stream.next().await {
-        id = Some(link_msg.header.index);
+    let mut names = Vec::new();
+    while let Some(Ok(link_msg)) = link_list_stream.next().await {
+        names.extend(link_msg.attributes.iter().find_map(|attr| match attr {
+            LinkAttribute::IfName(name) => Some(name.clone()),
+            _ => None,
+        }));
     }
 
-    let link_index = id.ok_or("failed to find interface")?;
+    conn_poll.abort();
+    Ok(names)
+}
+
+/// Delete a link, e.g. a WireGuard interface, by name.
+pub async fn delete_link(interface: String) -> Result<(), Box<dyn std::error::Error>> {
+    let (connection, handle, _) = new_connection()?;
+
+    let conn_poll = tokio::spawn(connection);
+
+    let link_index = get_link_index(&handle, interface).await?;
+    handle.link().del(link_index).execute().await?;
+
+    conn_poll.abort();
+    Ok(())
+}
+
+pub async fn link_up_with_mtu(interface: String, mtu: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let (connection, handle, _) = new_connection()?;
+
+    let conn_poll = tokio::spawn(connection);
+
+    let link_index = get_link_index(&handle, interface).await?;
 
     let header = LinkHeader {
         index: link_index,
@@ -166,14 +349,7 @@ pub async fn link_down(interface: String) -> Result<(), Box<dyn std::error::Erro
 
     let conn_poll = tokio::spawn(connection);
 
-    let mut link_list_stream = handle.link().get().match_name(interface).execute();
-
-    let mut id = None;
-    if let Some(Ok(link_msg)) = link_list_stream.next().await {
-        id = Some(link_msg.header.index);
-    }
-
-    let link_index = id.ok_or("failed to find interface")?;
+    let link_index = get_link_index(&handle, interface).await?;
 
     let header = LinkHeader {
         index: link_index,
@@ -209,3 +385,225 @@ pub async fn get_mtu(interface: String) -> Result<u32, Box<dyn std::error::Error
     conn_poll.abort();
     mtu.ok_or_else(|| "failed to find MTU".into())
 }
+
+/// Get the hardware address of `interface`, if it has an Ethernet-style one.
+pub async fn get_mac(interface: String) -> Result<Option<[u8; 6]>, Box<dyn std::error::Error>> {
+    let (connection, handle, _) = new_connection()?;
+
+    let conn_poll = tokio::spawn(connection);
+
+    let mut link_list_stream = handle.link().get().match_name(interface).execute();
+
+    let mut mac = None;
+    if let Some(Ok(link_msg)) = link_list_stream.next().await {
+        mac = mac_from_attributes(&link_msg.attributes);
+    }
+
+    conn_poll.abort();
+    Ok(mac)
+}
+
+/// Layer 3 links (WireGuard, tun) report no address; an all-zero one is treated the same.
+fn mac_from_attributes(attributes: &[LinkAttribute]) -> Option<[u8; 6]> {
+    attributes.iter().find_map(|attr| match attr {
+        LinkAttribute::Address(bytes) => <[u8; 6]>::try_from(bytes.as_slice()).ok(),
+        _ => None,
+    }).filter(|mac| mac.iter().any(|b| *b != 0))
+}
+
+pub async fn get_link_stats(interface: &str) -> Result<LinkStats, Box<dyn std::error::Error>> {
+    let (connection, handle, _) = new_connection()?;
+
+    let conn_poll = tokio::spawn(connection);
+
+    let mut link_list_stream = handle.link().get().match_name(interface.to_string()).execute();
+
+    let mut stats = None;
+    if let Some(Ok(link_msg)) = link_list_stream.next().await {
+        stats = link_stats_from_attributes(&link_msg.attributes);
+    }
+
+    conn_poll.abort();
+    stats.ok_or_else(|| "failed to find link stats".into())
+}
+
+/// Prefer the 64-bit counters, falling back to the 32-bit ones on kernels that only report those.
+fn link_stats_from_attributes(attributes: &[LinkAttribute]) -> Option<LinkStats> {
+    let stats64 = attributes.iter().find_map(|attr| match attr {
+        LinkAttribute::Stats64(s) => Some(LinkStats {
+            rx_bytes: s.rx_bytes,
+            tx_bytes: s.tx_bytes,
+            rx_packets: s.rx_packets,
+            tx_packets: s.tx_packets,
+            rx_errors: s.rx_errors,
+            tx_errors: s.tx_errors,
+        }),
+        _ => None,
+    });
+
+    stats64.or_else(|| attributes.iter().find_map(|attr| match attr {
+        LinkAttribute::Stats(s) => Some(LinkStats {
+            rx_bytes: s.rx_bytes as u64,
+            tx_bytes: s.tx_bytes as u64,
+            rx_packets: s.rx_packets as u64,
+            tx_packets: s.tx_packets as u64,
+            rx_errors: s.rx_errors as u64,
+            tx_errors: s.tx_errors as u64,
+        }),
+        _ => None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_usable_address() {
+        assert!(is_usable_address("192.168.1.10".parse().unwrap()));
+        assert!(is_usable_address("2001:db8::1".parse().unwrap()));
+        assert!(!is_usable_address("127.0.0.1".parse().unwrap()));
+        assert!(!is_usable_address("169.254.3.4".parse().unwrap()));
+        assert!(!is_usable_address("::1".parse().unwrap()));
+        assert!(!is_usable_address("fe80::1".parse().unwrap()));
+        assert!(!is_usable_address("fd12:3456::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_generate_ipv6_lla_from_seed_is_deterministic_link_local() {
+        let a = generate_ipv6_lla_from_seed(b"cat0123456789ab".to_vec());
+        let b = generate_ipv6_lla_from_seed(b"cat0123456789ab".to_vec());
+        assert_eq!(a, b);
+
+        let IpAddr::V6(v6) = a else { panic!("expected an IPv6 address") };
+        assert_eq!(&v6.octets()[..8], &[0xfe, 0x80, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_generate_ipv6_lla_from_seed_valid_iid() {
+        for i in 0..1000u32 {
+            let IpAddr::V6(v6) = generate_ipv6_lla_from_seed(i.to_be_bytes().to_vec()) else {
+                panic!("expected an IPv6 address")
+            };
+            let octets = v6.octets();
+            assert!(octets[8..].iter().any(|b| *b != 0));
+            assert_eq!(octets[8] & 0x02, 0);
+        }
+    }
+
+    #[test]
+    fn test_generate_ipv6_lla_avoiding_conflicts() {
+        let seed = b"catW000000000AB";
+        let primary = generate_ipv6_lla_from_seed(seed.to_vec());
+        assert_eq!(generate_ipv6_lla_avoiding(seed, &[]), Some(primary));
+
+        let fallback = generate_ipv6_lla_avoiding(seed, &[primary]).unwrap();
+        assert_ne!(fallback, primary);
+        assert_eq!(generate_ipv6_lla_avoiding(seed, &[primary]), Some(fallback));
+    }
+
+    #[test]
+    fn test_link_stats_prefers_stats64() {
+        use rtnetlink::packet_route::link::{Stats, Stats64};
+
+        let mut stats32 = Stats::default();
+        stats32.rx_bytes = 1;
+        stats32.tx_bytes = 2;
+        let mut stats64 = Stats64::default();
+        stats64.rx_bytes = 1 << 40;
+        stats64.rx_packets = 4;
+
+        let only32 = link_stats_from_attributes(&[LinkAttribute::Stats(stats32)]).unwrap();
+        assert_eq!(only32.rx_bytes, 1);
+        assert_eq!(only32.tx_bytes, 2);
+
+        let both = link_stats_from_attributes(&[LinkAttribute::Stats(stats32), LinkAttribute::Stats64(stats64)]).unwrap();
+        assert_eq!(both.rx_bytes, 1 << 40);
+        assert_eq!(both.rx_packets, 4);
+
+        assert!(link_stats_from_attributes(&[LinkAttribute::Mtu(1420)]).is_none());
+    }
+
+    #[test]
+    fn test_mac_from_attributes() {
+        let mac = [0x02, 0x42, 0xac, 0x11, 0x00, 0x02];
+        assert_eq!(mac_from_attributes(&[LinkAttribute::Address(mac.to_vec())]), Some(mac));
+        assert_eq!(mac_from_attributes(&[LinkAttribute::Address(vec![0; 6])]), None);
+        assert_eq!(mac_from_attributes(&[LinkAttribute::Address(vec![10, 0, 0, 1])]), None);
+        assert_eq!(mac_from_attributes(&[LinkAttribute::Mtu(1420)]), None);
+    }
+
+    #[test]
+    fn test_is_netlink_errno() {
+        let mut msg = rtnetlink::packet_core::ErrorMessage::default();
+        msg.code = std::num::NonZeroI32::new(-libc::EEXIST);
+        let err = rtnetlink::Error::NetlinkError(msg);
+
+        assert!(is_netlink_errno(&err, libc::EEXIST));
+        assert!(!is_netlink_errno(&err, libc::EADDRNOTAVAIL));
+        assert!(!is_netlink_errno(&rtnetlink::Error::RequestFailed, libc::EEXIST));
+    }
+
+    /// Needs CAP_NET_ADMIN: `sudo -E cargo test -p cat4igp-client -- --ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn test_addr_ops_are_idempotent_on_dummy_interface() {
+        let ifname = "cat4igptest0".to_string();
+        let (connection, handle, _) = new_connection().unwrap();
+        let conn_poll = tokio::spawn(connection);
+        handle
+            .link()
+            .add(rtnetlink::LinkDummy::new(&ifname).build())
+            .execute()
+            .await
+            .unwrap();
+
+        let addr: IpNet = "fe80::1234/64".parse().unwrap();
+        add_addr(ifname.clone(), addr).await.unwrap();
+        add_addr(ifname.clone(), addr).await.unwrap();
+        assert_eq!(get_addr(ifname.clone()).await.unwrap().iter().filter(|a| a.addr() == addr.addr()).count(), 1);
+
+        let wider: IpNet = "fe80::1234/10".parse().unwrap();
+        add_addr(ifname.clone(), wider).await.unwrap();
+        assert!(get_addr(ifname.clone()).await.unwrap().contains(&wider));
+
+        del_addr(ifname.clone(), wider).await.unwrap();
+        del_addr(ifname.clone(), wider).await.unwrap();
+
+        let index = get_link_index(&handle, ifname).await.unwrap();
+        handle.link().del(index).execute().await.unwrap();
+        conn_poll.abort();
+    }
+
+    #[test]
+    fn test_has_nonzero_iid() {
+        let mut hash = [0xffu8; 32];
+        hash[8..16].fill(0);
+        assert!(!has_nonzero_iid(&hash));
+        hash[8] = 0x02;
+        assert!(!has_nonzero_iid(&hash));
+        hash[15] = 1;
+        assert!(has_nonzero_iid(&hash));
+    }
+
+    #[test]
+    fn test_generate_ipv6_lla_from_seed_similar_names_do_not_collide() {
+        let alphabet = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+        let mut seen = std::collections::HashSet::new();
+        for a in alphabet {
+            for b in alphabet {
+                let ifname = format!("catW000000000{}{}", *a as char, *b as char);
+                assert!(seen.insert(generate_ipv6_lla_from_seed(ifname.into_bytes())));
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_interface_name() {
+        assert!(validate_interface_name("cat0123456789AB").is_ok());
+        assert!(validate_interface_name("cat0123456789ABC").is_err());
+        assert!(validate_interface_name("").is_err());
+        assert!(validate_interface_name("wg 0").is_err());
+        assert!(validate_interface_name("wg/0").is_err());
+    }
+}
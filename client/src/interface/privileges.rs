@@ -0,0 +1,45 @@
+//! Whether a process may create links, assign addresses and configure WireGuard.
+
+/// CAP_NET_ADMIN bit in the capability sets of /proc/<pid>/status
+const CAP_NET_ADMIN: u32 = 12;
+
+/// Shown wherever missing privileges are reported
+pub const MISSING_NET_ADMIN_HINT: &str =
+    "run as root or grant CAP_NET_ADMIN (e.g. `setcap cap_net_admin+ep <binary>`)";
+
+/// Whether `pid` (this process if `None`) can manage network interfaces. On Linux that is
+/// CAP_NET_ADMIN in its effective set; elsewhere only this process can be checked, by being root.
+pub fn has_net_admin(pid: Option<i32>) -> Result<bool, String> {
+    if cfg!(target_os = "linux") {
+        let path = match pid {
+            Some(pid) => format!("/proc/{}/status", pid),
+            None => "/proc/self/status".to_string(),
+        };
+        let status = std::fs::read_to_string(&path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+        parse_net_admin(&status).ok_or_else(|| format!("no CapEff line in {}", path))
+    } else if pid.is_none() {
+        Ok(unsafe { libc::geteuid() } == 0)
+    } else {
+        Err("privileges of another process can only be checked on Linux".to_string())
+    }
+}
+
+/// Whether the effective capability set in a /proc/<pid>/status text includes CAP_NET_ADMIN.
+fn parse_net_admin(proc_status: &str) -> Option<bool> {
+    let hex = proc_status.lines().find_map(|line| line.strip_prefix("CapEff:"))?;
+    let caps = u64::from_str_radix(hex.trim(), 16).ok()?;
+    Some(caps & (1 << CAP_NET_ADMIN) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_net_admin() {
+        assert_eq!(parse_net_admin("Name:\tcat\nCapEff:\t000001ffffffffff\n"), Some(true));
+        assert_eq!(parse_net_admin("CapEff:\t0000000000001000\n"), Some(true));
+        assert_eq!(parse_net_admin("CapEff:\t0000000000000000\n"), Some(false));
+        assert_eq!(parse_net_admin("Name:\tcat\n"), None);
+    }
+}
@@ -1,7 +1,9 @@
 mod config;
 mod daemon;
+mod doctor;
 mod interface;
 mod network;
+mod operator;
 mod tunnel;
 mod server_rest;
 
@@ -29,6 +31,10 @@ enum Commands {
         /// Configuration file path
         #[arg(short, long, value_name = "FILE")]
         config: Option<PathBuf>,
+
+        /// Fork into the background instead of staying in the foreground
+        #[arg(long)]
+        detach: bool,
     },
 
     /// Register with server
@@ -49,6 +55,9 @@ enum Commands {
     /// Daemon control commands
     Status,
 
+    /// Stop the daemon recorded in the configured PID file
+    Shutdown,
+
     /// Generate a default configuration file
     GenConfig {
         /// Output file path
@@ -58,6 +67,10 @@ enum Commands {
         /// Generate as JSON instead of TOML
         #[arg(long)]
         json: bool,
+
+        /// Generate TOML with a comment explaining each field
+        #[arg(long, conflicts_with = "json")]
+        documented: bool,
     },
 
     /// Show configuration
@@ -71,6 +84,105 @@ enum Commands {
         json: bool,
     },
 
+    /// Show how a config file differs from the configuration the running daemon uses
+    ConfigDiff {
+        /// Configuration file to compare; the daemon is reached through the global --config
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+    },
+
+    /// Make the daemon re-read its config file and apply what can change without a restart
+    ReloadConfig,
+
+    /// Server settings
+    Server {
+        /// Import a base64 WireGuard private key ("-" reads it from stdin)
+        #[arg(long, value_name = "BASE64")]
+        set_wg_key: Option<String>,
+
+        /// Replace an existing WireGuard key (breaks current tunnels until peers update)
+        #[arg(long)]
+        force: bool,
+
+        /// Replace the node's server auth key, e.g. after it may have leaked
+        #[arg(long, conflicts_with = "set_wg_key")]
+        rotate_key: bool,
+
+        /// Replace the WireGuard keypair; tunnels switch once peers have the new public key
+        #[arg(long, conflicts_with_all = ["set_wg_key", "rotate_key"])]
+        rotate_wg_key: bool,
+
+        /// With --rotate-wg-key, seconds to wait before switching (default: two poll intervals)
+        #[arg(long, value_name = "SECS", requires = "rotate_wg_key")]
+        switch_after: Option<u64>,
+
+        /// Check that the configured server, or the given URL, is reachable
+        #[arg(
+            long,
+            value_name = "URL",
+            num_args = 0..=1,
+            default_missing_value = "",
+            conflicts_with_all = ["set_wg_key", "rotate_key", "rotate_wg_key"]
+        )]
+        test: Option<String>,
+
+        /// With --test, skip TLS certificate verification
+        #[arg(long, requires = "test")]
+        insecure: bool,
+    },
+
+    /// Manage the server through its operator API, e.g. invites
+    Operator {
+        /// Server address; defaults to the server this node is configured for
+        #[arg(long, value_name = "URL")]
+        server: Option<String>,
+
+        /// Operator key ("-" reads it from stdin); defaults to $OPERATOR_AUTH_KEY
+        #[arg(long, value_name = "KEY")]
+        token: Option<String>,
+
+        /// Disable TLS certificate verification
+        #[arg(long)]
+        insecure: bool,
+
+        /// Print the server's answer as JSON
+        #[arg(long)]
+        json: bool,
+
+        #[command(subcommand)]
+        command: operator::OperatorCommand,
+    },
+
+    /// List active tunnels and whether their peers are handshaking
+    Tunnels {
+        /// Also show last handshake age and rx/tx totals
+        #[arg(long)]
+        stats: bool,
+    },
+
+    /// Follow tunnel events from the daemon until interrupted
+    Events,
+
+    /// Print an active tunnel's WireGuard config in wg-quick format
+    TunnelConfig {
+        /// Tunnel ID as assigned by the server
+        tunnel_id: i32,
+
+        /// Show the interface private key instead of redacting it
+        #[arg(long)]
+        show_private_key: bool,
+    },
+
+    /// Check configuration, daemon, server, network and permissions, with hints for failures
+    Doctor,
+
+    /// Show everything the running daemon knows, secrets redacted, e.g. for a bug report
+    DumpState {
+        /// Print the full state as JSON instead of a summary
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Detect public IP
     PublicIp {
         /// IP family (ipv4, ipv6, or both)
@@ -79,35 +191,69 @@ enum Commands {
         /// Detect NAT type
         #[arg(long)]
         nat: bool,
+
+        /// Use only this STUN server (ip:port), skipping the fetched lists
+        #[arg(long, value_name = "IP:PORT")]
+        server: Option<std::net::SocketAddr>,
+
+        /// Ask N servers at once and report the address a majority agrees on
+        #[arg(long, value_name = "N")]
+        consensus: Option<usize>,
     },
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
     let config_path = cli.config.clone().unwrap_or_else(|| {
         PathBuf::from("/etc/cat4igp/client.toml")
     });
 
+    // The daemon is started outside the runtime: detaching forks, which must happen
+    // before tokio spawns its worker threads.
     match cli.command {
-        Some(Commands::Daemon { config: cmd_config }) => {
-            let config_path = cmd_config.unwrap_or(config_path);
-            let client_config = if config_path.exists() {
-                config::ClientConfig::from_file(&config_path)?
-            } else {
-                println!("Configuration file not found: {:?}", config_path);
-                config::ClientConfig::default()
-            };
-
-            start_daemon(client_config).await?;
+        Some(Commands::Daemon { config: cmd_config, detach }) => {
+            run_daemon(cmd_config.unwrap_or(config_path), detach)
         }
+        // Default to daemon mode
+        None => run_daemon(config_path, false),
+        Some(command) => tokio::runtime::Runtime::new()?.block_on(run_command(command, config_path)),
+    }
+}
+
+fn run_daemon(config_path: PathBuf, detach: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let (client_config, config_path) = if config_path.exists() {
+        (config::ClientConfig::from_file(&config_path)?, Some(config_path))
+    } else {
+        println!("Configuration file not found: {:?}", config_path);
+        (config::ClientConfig::default(), None)
+    };
 
-        Some(Commands::Register {
+    // check before detaching so a refusal is still visible on the terminal
+    if let Some(path) = &client_config.pid_file {
+        daemon::pid_file::check_not_running(path)?;
+    }
+    if detach {
+        daemon::pid_file::detach()?;
+    }
+    let pid_file = client_config
+        .pid_file
+        .as_deref()
+        .map(daemon::pid_file::PidFile::create)
+        .transpose()?;
+
+    let result = tokio::runtime::Runtime::new()?.block_on(start_daemon(client_config, config_path));
+    drop(pid_file);
+    result
+}
+
+async fn run_command(command: Commands, config_path: PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Commands::Register {
             server,
             invite,
             insecure,
-        }) => {
+        } => {
             let client_config = if config_path.exists() {
                 config::ClientConfig::from_file(&config_path)?
             } else {
@@ -120,8 +266,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             )?;
 
             let request = DaemonRequest::Register {
-                address: server,
-                invite_code: invite,
+                address: server.trim().to_string(),
+                invite_code: invite.trim().to_string(),
                 verify_tls: !insecure,
             };
 
@@ -129,9 +275,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 daemon::protocol::DaemonResponse::Ok(msg) => {
                     println!("✓ {}", msg.unwrap_or("Registered successfully".to_string()));
                 }
-                daemon::protocol::DaemonResponse::Error(e) => {
-                    eprintln!("✗ Error: {}", e);
-                    std::process::exit(1);
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
                 }
                 _ => {
                     eprintln!("✗ Unexpected response");
@@ -140,13 +286,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Some(Commands::Status) => {
+        Commands::Status => {
             let client_config = if config_path.exists() {
                 config::ClientConfig::from_file(&config_path)?
             } else {
                 config::ClientConfig::default()
             };
 
+            if let Some(path) = &client_config.pid_file {
+                match daemon::pid_file::live_pid(path) {
+                    Ok(Some(pid)) => println!("Daemon process: PID {}", pid),
+                    Ok(None) => println!("Daemon process: not running (no live PID in {:?})", path),
+                    Err(e) => eprintln!("⚠ Could not read PID file {:?}: {}", path, e),
+                }
+            }
+
             let client = DaemonClient::new(
                 &client_config.daemon_socket,
                 &client_config.data_dir,
@@ -159,18 +313,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     server_configured,
                     node_key_present,
                     message,
+                    reconcile_interval_secs,
+                    public_ipv4,
+                    public_ipv6,
+                    nat_type,
                 } => {
                     println!("Daemon Status:");
                     println!("  Running: {}", if running { "Yes" } else { "No" });
                     println!("  Server Configured: {}", if server_configured { "Yes" } else { "No" });
                     println!("  Node Key Present: {}", if node_key_present { "Yes" } else { "No" });
+                    if let Some(secs) = reconcile_interval_secs {
+                        println!("  Poll Interval: {}s", secs);
+                    }
+                    if let Some(ip) = public_ipv4 {
+                        println!("  Public IPv4: {}", ip);
+                    }
+                    if let Some(ip) = public_ipv6 {
+                        println!("  Public IPv6: {}", ip);
+                    }
+                    if let Some(nat_type) = nat_type {
+                        println!("  NAT Type: {:?}", nat_type);
+                    }
                     if let Some(msg) = message {
                         println!("  Message: {}", msg);
                     }
                 }
-                daemon::protocol::DaemonResponse::Error(e) => {
-                    eprintln!("✗ Error: {}", e);
-                    std::process::exit(1);
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
                 }
                 _ => {
                     eprintln!("✗ Unexpected response");
@@ -179,9 +349,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Some(Commands::GenConfig { output, json }) => {
+        Commands::Shutdown => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let Some(path) = &client_config.pid_file else {
+                eprintln!("✗ No pid_file configured; stop the daemon through your service manager");
+                std::process::exit(1);
+            };
+            match daemon::pid_file::live_pid(path)? {
+                Some(pid) => {
+                    daemon::pid_file::terminate(pid)?;
+                    println!("✓ Sent SIGTERM to daemon (PID {})", pid);
+                }
+                None => {
+                    eprintln!("✗ Daemon is not running (no live PID in {:?})", path);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::GenConfig { output, json, documented } => {
             let default_config = config::ClientConfig::default();
-            if json {
+            if documented {
+                std::fs::write(&output, default_config.to_documented_toml())?;
+                println!("Generated documented TOML configuration to {:?}", output);
+            } else if json {
                 let content = default_config.to_json()?;
                 std::fs::write(&output, content)?;
                 println!("Generated JSON configuration to {:?}", output);
@@ -191,7 +387,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Some(Commands::ShowConfig { config: cmd_config, json }) => {
+        Commands::ShowConfig { config: cmd_config, json } => {
             let config_path = cmd_config.unwrap_or(config_path);
             let client_config = if config_path.exists() {
                 config::ClientConfig::from_file(&config_path)?
@@ -206,11 +402,327 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Some(Commands::PublicIp { family, nat }) => {
-            let mut detector = network::public_ip::PublicIpDetector::new();
+        Commands::ConfigDiff { config: new_config_path } => {
+            let new_config = config::ClientConfig::from_file(&new_config_path)?;
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let client = DaemonClient::new(
+                &client_config.daemon_socket,
+                &client_config.data_dir,
+            )?;
+            let running = match client.send_request(DaemonRequest::GetConfig).await? {
+                daemon::protocol::DaemonResponse::Config(value) => value,
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
+                }
+                _ => {
+                    eprintln!("✗ Unexpected response");
+                    std::process::exit(1);
+                }
+            };
+
+            let changes = config::diff::diff(running, &new_config)?;
+            if changes.is_empty() {
+                println!("✓ {:?} matches the running daemon", new_config_path);
+            } else {
+                for change in &changes {
+                    println!("  {}: {} → {} ({})", change.key, change.running, change.file, change.apply);
+                }
+                if changes.iter().any(|c| c.apply == config::diff::ApplyMode::HotReload) {
+                    println!("Run `reload-config` to apply the hot reload changes");
+                }
+                if changes.iter().any(|c| c.apply == config::diff::ApplyMode::Restart) {
+                    println!("⚠ Restart the daemon to apply the other changes");
+                }
+            }
+        }
+
+        Commands::ReloadConfig => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let client = DaemonClient::new(
+                &client_config.daemon_socket,
+                &client_config.data_dir,
+            )?;
+            match client.send_request(DaemonRequest::ReloadConfig).await? {
+                daemon::protocol::DaemonResponse::ConfigReloaded { applied, deferred } => {
+                    if applied.is_empty() && deferred.is_empty() {
+                        println!("✓ Configuration reloaded, nothing changed");
+                    }
+                    if !applied.is_empty() {
+                        println!("✓ Applied: {}", applied.join(", "));
+                    }
+                    if !deferred.is_empty() {
+                        println!("⚠ Needs a restart: {}", deferred.join(", "));
+                    }
+                }
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
+                }
+                _ => {
+                    eprintln!("✗ Unexpected response");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Server { set_wg_key, force, rotate_key, rotate_wg_key, switch_after, test, insecure } => {
+            let request = if let Some(url) = test {
+                DaemonRequest::TestServer {
+                    address: Some(url).filter(|url| !url.is_empty()),
+                    verify_tls: insecure.then_some(false),
+                }
+            } else if rotate_key {
+                DaemonRequest::RotateKey
+            } else if rotate_wg_key {
+                DaemonRequest::RotateWireguardKey { switch_after_secs: switch_after }
+            } else if let Some(mut private_key) = set_wg_key {
+                // keeps the key out of shell history and the process list
+                if private_key == "-" {
+                    private_key = String::new();
+                    std::io::stdin().read_line(&mut private_key)?;
+                }
+                DaemonRequest::SetWireguardKey {
+                    private_key: private_key.trim().to_string(),
+                    force,
+                }
+            } else {
+                eprintln!("✗ Nothing to do; see `cat4igp-client server --help`");
+                std::process::exit(1);
+            };
+
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let client = DaemonClient::new(
+                &client_config.daemon_socket,
+                &client_config.data_dir,
+            )?;
+
+            match client.send_request(request).await? {
+                daemon::protocol::DaemonResponse::Ok(msg) => {
+                    println!("✓ {}", msg.unwrap_or("Done".to_string()));
+                }
+                daemon::protocol::DaemonResponse::ServerProbe(probe) => {
+                    print_server_probe(&probe);
+                    if probe.failure.is_some() {
+                        std::process::exit(1);
+                    }
+                }
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
+                }
+                _ => {
+                    eprintln!("✗ Unexpected response");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Tunnels { stats } => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let client = DaemonClient::new(
+                &client_config.daemon_socket,
+                &client_config.data_dir,
+            )?;
+
+            match client.send_request(DaemonRequest::GetTunnelStats).await? {
+                daemon::protocol::DaemonResponse::TunnelStats(tunnels) => {
+                    if tunnels.is_empty() {
+                        println!("No active tunnels");
+                    }
+                    for tunnel in &tunnels {
+                        print_tunnel_stats(tunnel, stats);
+                    }
+                }
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
+                }
+                _ => {
+                    eprintln!("✗ Unexpected response");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Events => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let client = DaemonClient::new(
+                &client_config.daemon_socket,
+                &client_config.data_dir,
+            )?;
+
+            let mut subscription = match client.subscribe().await {
+                Ok(subscription) => subscription,
+                Err(e) => {
+                    eprintln!("✗ Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            loop {
+                match subscription.next().await {
+                    Ok(event) => println!("{:?}", event),
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                        eprintln!("✗ Daemon closed the connection");
+                        std::process::exit(1);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        Commands::TunnelConfig { tunnel_id, show_private_key } => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let client = DaemonClient::new(
+                &client_config.daemon_socket,
+                &client_config.data_dir,
+            )?;
+
+            let request = DaemonRequest::GetTunnelConfig { tunnel_id, show_private_key };
+            match client.send_request(request).await? {
+                daemon::protocol::DaemonResponse::TunnelConfig(tunnel_config) => {
+                    print!("{}", tunnel_config.to_wg_quick());
+                }
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
+                }
+                _ => {
+                    eprintln!("✗ Unexpected response");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::Operator { server, token, insecure, json, command } => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let (address, verify_tls) = match (server, config::ServerConfig::load(&client_config.data_dir).ok()) {
+                (Some(address), _) => (address, !insecure),
+                (None, Some(configured)) => (configured.address, configured.verify_tls && !insecure),
+                (None, None) => {
+                    eprintln!("✗ No server configured; pass --server");
+                    std::process::exit(1);
+                }
+            };
+            let address = match config::ServerConfig::normalize_address(&address) {
+                Ok(address) => address,
+                Err(e) => {
+                    eprintln!("✗ {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            // "-" keeps the key out of shell history and the process list
+            let token = match token {
+                Some(token) if token == "-" => {
+                    let mut token = String::new();
+                    std::io::stdin().read_line(&mut token)?;
+                    token
+                }
+                Some(token) => token,
+                None => std::env::var("OPERATOR_AUTH_KEY").unwrap_or_default(),
+            };
+            let token = token.trim();
+            if token.is_empty() {
+                eprintln!("✗ No operator key; pass --token or set OPERATOR_AUTH_KEY");
+                std::process::exit(1);
+            }
+
+            let result = match server_rest::operator::OperatorRestClient::new(&address, token, verify_tls) {
+                Ok(client) => operator::run(&client, command, json).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                eprintln!("✗ Error: {}", e);
+                std::process::exit(daemon::server_error_code(&*e).exit_code());
+            }
+        }
+
+        Commands::Doctor => {
+            let checks = doctor::run(&config_path).await;
+            if doctor::print(&checks) {
+                std::process::exit(1);
+            }
+        }
+
+        Commands::DumpState { json } => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+
+            let client = DaemonClient::new(
+                &client_config.daemon_socket,
+                &client_config.data_dir,
+            )?;
+
+            match client.send_request(DaemonRequest::DumpState).await? {
+                daemon::protocol::DaemonResponse::State(state) if json => {
+                    println!("{}", serde_json::to_string_pretty(&state)?);
+                }
+                daemon::protocol::DaemonResponse::State(state) => print_state_summary(&state),
+                daemon::protocol::DaemonResponse::Error { code, message } => {
+                    eprintln!("✗ Error: {}", message);
+                    std::process::exit(code.exit_code());
+                }
+                _ => {
+                    eprintln!("✗ Unexpected response");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::PublicIp { family, nat, server, consensus } => {
+            let client_config = if config_path.exists() {
+                config::ClientConfig::from_file(&config_path)?
+            } else {
+                config::ClientConfig::default()
+            };
+            let mut detector = client_config.public_ip_detector();
+            let ip_stack = client_config.ip_stack;
             
-            // Initialize detector by fetching STUN server lists
-            if let Err(e) = detector.init().await {
+            if let Some(server) = server {
+                detector.use_only_server(server);
+            } else if client_config.stun_servers.is_empty()
+                && let Err(e) = detector.init().await
+            {
+                // Fetch STUN server lists unless the config names static servers
                 eprintln!("Failed to initialize STUN detector: {}", e);
                 std::process::exit(1);
             }
@@ -219,54 +731,79 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 // Detect NAT type
                 match family.as_deref() {
                     Some("ipv4") | Some("IPv4") | Some("4") => {
-                        match detector.detect_nat_type_ipv4().await {
-                            Ok(nat_type) => println!("NAT Type (IPv4): {:?}", nat_type),
+                        match detector.detect_nat_report_ipv4().await {
+                            Ok(report) => print_nat_report("IPv4", &report),
                             Err(e) => eprintln!("Error: {}", e),
                         }
                     }
                     Some("ipv6") | Some("IPv6") | Some("6") => {
-                        match detector.detect_nat_type_ipv6().await {
-                            Ok(nat_type) => println!("NAT Type (IPv6): {:?}", nat_type),
+                        match detector.detect_nat_report_ipv6().await {
+                            Ok(report) => print_nat_report("IPv6", &report),
                             Err(e) => eprintln!("Error: {}", e),
                         }
                     }
                     None | Some("both") | Some("all") => {
-                        match detector.detect_nat_type_ipv4().await {
-                            Ok(nat_type) => println!("NAT Type (IPv4): {:?}", nat_type),
-                            Err(e) => eprintln!("IPv4 NAT Error: {}", e),
+                        // only the families this host has, per `ip_stack`
+                        if ip_stack.has_ipv4() {
+                            match detector.detect_nat_report_ipv4().await {
+                                Ok(report) => print_nat_report("IPv4", &report),
+                                Err(e) => eprintln!("IPv4 NAT Error: {}", e),
+                            }
                         }
-                        match detector.detect_nat_type_ipv6().await {
-                            Ok(nat_type) => println!("NAT Type (IPv6): {:?}", nat_type),
-                            Err(e) => eprintln!("IPv6 NAT Error: {}", e),
+                        if ip_stack.has_ipv6() {
+                            match detector.detect_nat_report_ipv6().await {
+                                Ok(report) => print_nat_report("IPv6", &report),
+                                Err(e) => eprintln!("IPv6 NAT Error: {}", e),
+                            }
                         }
                     }
                     Some(family) => {
                         eprintln!("Unknown family: {}. Use 'ipv4', 'ipv6', or 'both'", family);
                     }
                 }
+            } else if let Some(n) = consensus {
+                let (ipv4, ipv6) = match family.as_deref() {
+                    Some("ipv4") | Some("IPv4") | Some("4") => (true, false),
+                    Some("ipv6") | Some("IPv6") | Some("6") => (false, true),
+                    None | Some("both") | Some("all") => (ip_stack.has_ipv4(), ip_stack.has_ipv6()),
+                    Some(family) => {
+                        eprintln!("Unknown family: {}. Use 'ipv4', 'ipv6', or 'both'", family);
+                        (false, false)
+                    }
+                };
+                if ipv4 {
+                    print_consensus("IPv4", detector.detect_public_ipv4_consensus(n).await);
+                }
+                if ipv6 {
+                    print_consensus("IPv6", detector.detect_public_ipv6_consensus(n).await);
+                }
             } else {
                 // Detect public IP
                 match family.as_deref() {
                     Some("ipv4") | Some("IPv4") | Some("4") => {
-                        match detector.detect_public_ipv4().await {
-                            Ok(ip) => println!("Public IPv4: {}", ip),
+                        match detector.detect_public_ipv4_with_source().await {
+                            Ok((ip, source)) => println!("Public IPv4: {} (via {})", ip, source),
                             Err(e) => eprintln!("Error: {}", e),
                         }
                     }
                     Some("ipv6") | Some("IPv6") | Some("6") => {
-                        match detector.detect_public_ipv6().await {
-                            Ok(ip) => println!("Public IPv6: {}", ip),
+                        match detector.detect_public_ipv6_with_source().await {
+                            Ok((ip, source)) => println!("Public IPv6: {} (via {})", ip, source),
                             Err(e) => eprintln!("Error: {}", e),
                         }
                     }
                     None | Some("both") | Some("all") => {
-                        match detector.detect_public_ipv4().await {
-                            Ok(ip) => println!("Public IPv4: {}", ip),
-                            Err(e) => eprintln!("IPv4 Error: {}", e),
+                        if ip_stack.has_ipv4() {
+                            match detector.detect_public_ipv4_with_source().await {
+                                Ok((ip, source)) => println!("Public IPv4: {} (via {})", ip, source),
+                                Err(e) => eprintln!("IPv4 Error: {}", e),
+                            }
                         }
-                        match detector.detect_public_ipv6().await {
-                            Ok(ip) => println!("Public IPv6: {}", ip),
-                            Err(e) => eprintln!("IPv6 Error: {}", e),
+                        if ip_stack.has_ipv6() {
+                            match detector.detect_public_ipv6_with_source().await {
+                                Ok((ip, source)) => println!("Public IPv6: {} (via {})", ip, source),
+                                Err(e) => eprintln!("IPv6 Error: {}", e),
+                            }
                         }
                     }
                     Some(family) => {
@@ -276,22 +813,160 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        None => {
-            // Default to daemon mode
-            let client_config = if config_path.exists() {
-                config::ClientConfig::from_file(&config_path)?
-            } else {
-                config::ClientConfig::default()
+        Commands::Daemon { .. } => unreachable!("the daemon is started before the runtime"),
+    }
+
+    Ok(())
+}
+
+/// Headline facts of a `DumpState` answer; the full document is behind `--json`.
+fn print_state_summary(state: &serde_json::Value) {
+    let text = |value: &serde_json::Value| match value {
+        serde_json::Value::Null => "-".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+
+    println!("Daemon {} up {}s", text(&state["version"]), text(&state["uptime_secs"]));
+    match state["server_config"].as_object() {
+        Some(server) => println!(
+            "Server: {} ({})",
+            text(&server["address"]),
+            if server["node_key"].is_null() { "not registered" } else { "registered" }
+        ),
+        None => println!("Server: not configured"),
+    }
+    println!(
+        "Public IPv4: {}, IPv6: {}, NAT: {}",
+        text(&state["network"]["public_ipv4"]),
+        text(&state["network"]["public_ipv6"]),
+        text(&state["network"]["nat_type"])
+    );
+    if let Some(error) = state["poll"]["last_error"].as_str() {
+        println!("⚠ Last poll failed: {}", error);
+    }
+
+    let tunnels = state["tunnels"].as_array().cloned().unwrap_or_default();
+    println!("Tunnels: {}", tunnels.len());
+    for tunnel in &tunnels {
+        match serde_json::from_value::<daemon::protocol::TunnelStats>(tunnel["stats"].clone()) {
+            Ok(stats) => print_tunnel_stats(&stats, true),
+            Err(_) => println!("Tunnel {}: stats unavailable", text(&tunnel["tunnel_id"])),
+        }
+    }
+}
+
+/// One line per tunnel; health is colored green/yellow/red when stdout is a terminal.
+fn print_tunnel_stats(tunnel: &daemon::protocol::TunnelStats, with_counters: bool) {
+    use daemon::protocol::TunnelHealth;
+    use std::io::IsTerminal;
+
+    let (label, color) = match tunnel.health {
+        TunnelHealth::Healthy => ("healthy", "\x1b[32m"),
+        TunnelHealth::Stale => ("stale", "\x1b[33m"),
+        TunnelHealth::NeverConnected => ("never connected", "\x1b[31m"),
+    };
+    let health = if std::io::stdout().is_terminal() {
+        format!("{}{}\x1b[0m", color, label)
+    } else {
+        label.to_string()
+    };
+
+    let mut line = format!(
+        "Tunnel {} ({}, peer node {}): {}",
+        tunnel.tunnel_id, tunnel.interface, tunnel.peer_node_id, health
+    );
+    if with_counters {
+        let handshake = tunnel
+            .last_handshake_secs
+            .map(|secs| format!("{}s ago", secs))
+            .unwrap_or_else(|| "never".to_string());
+        line.push_str(&format!(
+            ", last handshake {}, rx {} B, tx {} B",
+            handshake, tunnel.rx_bytes, tunnel.tx_bytes
+        ));
+    }
+    println!("{}", line);
+}
+
+fn print_server_probe(probe: &daemon::protocol::ServerProbe) {
+    use daemon::protocol::ProbeFailure;
+
+    match probe.failure {
+        None => println!("✓ Server reachable: {}", probe.address),
+        Some(failure) => {
+            let stage = match failure {
+                ProbeFailure::InvalidAddress => "Invalid server address",
+                ProbeFailure::Dns => "DNS lookup failed",
+                ProbeFailure::ConnectionRefused => "Connection refused (server down, wrong port, or firewall)",
+                ProbeFailure::Timeout => "Timed out (host unreachable or firewall dropping traffic)",
+                ProbeFailure::Tls => "TLS handshake failed (bad certificate, or wrong scheme: try http://)",
+                ProbeFailure::Http => "Server returned an error",
+                ProbeFailure::Other => "Request failed",
             };
+            eprintln!("✗ {}: {}", stage, probe.address);
+        }
+    }
 
-            start_daemon(client_config).await?;
+    if !probe.resolved.is_empty() {
+        let addrs: Vec<String> = probe.resolved.iter().map(|a| a.to_string()).collect();
+        println!("  Resolved: {}", addrs.join(", "));
+    }
+    if let Some(status) = probe.status {
+        println!("  HTTP status: {}", status);
+    }
+    if let Some(banner) = &probe.banner {
+        println!("  Banner: {}", banner);
+    }
+    if let Some(version) = &probe.version {
+        println!("  Server version: {} (API v{})", version.version, version.api_version);
+        if version.api_version != cat4igp_shared::rest::API_VERSION {
+            println!(
+                "  ⚠ This client speaks API v{}; some calls may fail",
+                cat4igp_shared::rest::API_VERSION
+            );
         }
     }
+    if let Some(detail) = &probe.detail {
+        eprintln!("  Detail: {}", detail);
+    }
+}
 
-    Ok(())
+fn print_nat_report(label: &str, report: &network::public_ip::NatReport) {
+    println!("NAT Type ({}): {:?}", label, report.nat_type);
+    if report.is_cgnat {
+        println!("Behind carrier-grade NAT (likely): direct connections will need a relay");
+    }
 }
 
-async fn start_daemon(config: config::ClientConfig) -> Result<(), Box<dyn std::error::Error>> {
+fn print_consensus(label: &str, result: Result<network::public_ip::PublicIpConsensus, String>) {
+    match result {
+        Ok(consensus) => {
+            let agreeing = consensus.votes.len() - consensus.dissenters().len();
+            println!(
+                "Public {}: {} ({}/{} servers agree)",
+                label,
+                consensus.ip,
+                agreeing,
+                consensus.votes.len()
+            );
+            if !consensus.is_confirmed() {
+                eprintln!("  Warning: unconfirmed, no second server reported the same address");
+            }
+            if !consensus.is_unanimous() {
+                for (ip, server) in consensus.dissenters() {
+                    eprintln!("  Warning: {} reported {}", server, ip);
+                }
+            }
+        }
+        Err(e) => eprintln!("{} Error: {}", label, e),
+    }
+}
+
+async fn start_daemon(
+    config: config::ClientConfig,
+    config_path: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting cat4igp client daemon...");
     println!("Configuration:");
     println!("  Daemon socket: {:?}", config.daemon_socket);
@@ -305,7 +980,10 @@ async fn start_daemon(config: config::ClientConfig) -> Result<(), Box<dyn std::e
         println!("  Public IPv6 hostname: {}", hostname);
     }
 
-    let daemon = daemon::Daemon::new(config).await?;
+    let mut daemon = daemon::Daemon::new(config).await?;
+    if let Some(path) = config_path {
+        daemon = daemon.with_config_path(path);
+    }
     println!("✓ Daemon initialized");
     println!("  Daemon secret: {}", daemon.get_secret());
 
@@ -317,9 +995,28 @@ async fn start_daemon(config: config::ClientConfig) -> Result<(), Box<dyn std::e
 
     println!("Daemon is running...");
 
-    // Run the daemon's Unix socket server
-    daemon.run().await?;
+    // Run the daemon's Unix socket server until asked to stop
+    tokio::select! {
+        result = daemon.run() => result?,
+        _ = shutdown_signal() => println!("Shutting down"),
+    }
 
     Ok(())
 }
 
+/// Resolves on SIGINT or SIGTERM, so the PID file is cleaned up on the way out.
+async fn shutdown_signal() {
+    let mut sigterm = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            eprintln!("⚠ Failed to install SIGTERM handler: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = sigterm.recv() => {}
+    }
+}
+
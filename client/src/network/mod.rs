@@ -1,6 +1,8 @@
 pub mod public_ip;
 pub mod tls;
 pub mod ports;
+pub mod resolve;
+pub mod retry;
 
 pub use public_ip::PublicIpDetector;
 pub use tls::TlsVerifier;
@@ -17,33 +17,39 @@ pub fn get_random_udp_port() -> std::io::Result<u16> {
 /// Manages port allocation within a specified range
 #[derive(Clone)]
 pub struct PortRange {
-    start: u16,
-    end: u16,
+    /// `(start, end)`, both inclusive; replaced by `set_range` on config reload
+    bounds: Arc<Mutex<(u16, u16)>>,
     allocated: Arc<Mutex<HashSet<u16>>>,
 }
 
 impl PortRange {
     pub fn new(start: u16, end: u16) -> Self {
         Self {
-            start,
-            end,
+            bounds: Arc::new(Mutex::new((start, end))),
             allocated: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 
+    /// Allocate from a new range from now on. Ports already handed out stay allocated
+    /// until released, even if they fall outside it.
+    pub fn set_range(&self, start: u16, end: u16) {
+        *self.bounds.lock().unwrap() = (start, end);
+    }
+
     /// Request a specific port, or allocate an unused port in range if unavailable
     pub fn allocate(&self, requested: Option<u16>) -> std::io::Result<u16> {
+        let (start, end) = *self.bounds.lock().unwrap();
         let mut allocated = self.allocated.lock().unwrap();
 
         if let Some(port) = requested {
-            if port >= self.start && port <= self.end && !allocated.contains(&port) {
+            if port >= start && port <= end && !allocated.contains(&port) {
                 allocated.insert(port);
                 return Ok(port);
             }
         }
 
         // Find an unused port in range
-        for port in self.start..=self.end {
+        for port in start..=end {
             if !allocated.contains(&port) {
                 allocated.insert(port);
                 return Ok(port);
@@ -60,4 +66,24 @@ impl PortRange {
     pub fn release(&self, port: u16) {
         self.allocated.lock().unwrap().remove(&port);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_range_keeps_allocations() {
+        let ports = PortRange::new(51820, 51821);
+        assert_eq!(ports.allocate(Some(51821)).unwrap(), 51821);
+
+        ports.set_range(52000, 52000);
+        assert_eq!(ports.allocate(Some(51820)).unwrap(), 52000);
+        assert!(ports.allocate(None).is_err());
+
+        // a port from the old range is still tracked and can be released
+        ports.release(51821);
+        ports.set_range(51821, 51821);
+        assert_eq!(ports.allocate(None).unwrap(), 51821);
+    }
+}
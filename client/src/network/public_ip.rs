@@ -1,17 +1,29 @@
 use rand::prelude::*;
-use std::net::{IpAddr, ToSocketAddrs, Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
 use std::os::unix::io::AsRawFd;
 use tokio::net::UdpSocket;
 use rand::seq::SliceRandom;
 
+use super::retry::{RetryError, RetryPolicy};
+
 const IPV4_STUN_LIST_URL: &str = "https://raw.githubusercontent.com/pradt2/always-online-stun/master/valid_ipv4s.txt";
 const IPV6_STUN_LIST_URL: &str = "https://raw.githubusercontent.com/pradt2/always-online-stun/master/valid_ipv6s.txt";
 const IPV4_NAT_TESTING_LIST_URL: &str = "https://raw.githubusercontent.com/pradt2/always-online-stun/master/valid_nat_testing_ipv4s.txt";
 const IPV6_NAT_TESTING_LIST_URL: &str = "https://raw.githubusercontent.com/pradt2/always-online-stun/master/valid_nat_testing_ipv6s.txt";
 
+/// Port assumed for list entries that don't name one
+const DEFAULT_STUN_PORT: u16 = 3478;
+
+/// Concurrent DNS lookups while resolving a STUN list
+const RESOLVE_CONCURRENCY: usize = 32;
+/// Give up on a single STUN hostname after this long
+const RESOLVE_HOST_TIMEOUT: Duration = Duration::from_secs(3);
+/// Bound on resolving a whole list, so slow DNS can't stall init
+const RESOLVE_TOTAL_TIMEOUT: Duration = Duration::from_secs(20);
+
 /// NAT type as determined by RFC 5780 STUN NAT Behavior Discovery
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum NatType {
     /// Open Internet - no NAT detected
     OpenInternet,
@@ -38,12 +50,123 @@ pub enum NatType {
     Unknown,
 }
 
+/// Result of NAT behaviour discovery against one STUN server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatReport {
+    pub nat_type: NatType,
+    /// Address the STUN server saw us as (Test I), if it answered
+    pub mapped_addr: Option<SocketAddr>,
+    /// Local interface address the answer arrived on
+    pub local_ip: Option<IpAddr>,
+    /// Whether we are probably behind carrier-grade NAT, see [`is_cgnat_likely`]
+    pub is_cgnat: bool,
+}
+
+/// RFC 6598 shared address space (100.64.0.0/10), handed out only by carriers
+fn is_shared_address_space(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    a == 100 && (b & 0xc0) == 64
+}
+
+/// Heuristic for carrier-grade NAT, where direct p2p is close to impossible and a relay is needed.
+///
+/// CGNAT is assumed when the interface address is not the mapped one and either the interface
+/// address is in 100.64.0.0/10, or it is private (RFC 1918) and the mapping is address- or
+/// port-dependent. This is a guess: a home router with symmetric NAT looks exactly like CGNAT,
+/// and CGNAT with endpoint-independent mapping behind a home router is missed. IPv6 is never CGNAT.
+pub fn is_cgnat_likely(nat_type: &NatType, local_ip: IpAddr, mapped_ip: IpAddr) -> bool {
+    let IpAddr::V4(local) = local_ip else {
+        return false;
+    };
+    if local_ip == mapped_ip {
+        return false;
+    }
+    if is_shared_address_space(local) {
+        return true;
+    }
+    local.is_private()
+        && matches!(
+            nat_type,
+            NatType::AddressDependentMapping | NatType::AddressPortDependentMapping
+        )
+}
+
+/// Public address agreed on by several STUN servers
+#[derive(Debug, Clone)]
+pub struct PublicIpConsensus {
+    /// Address reported by a strict majority of the servers that answered. When only one
+    /// answered, this is its answer, unconfirmed; see [`PublicIpConsensus::is_confirmed`]
+    pub ip: IpAddr,
+    /// Every answer received: the reported address and the server that sent it
+    pub votes: Vec<(IpAddr, SocketAddr)>,
+}
+
+impl PublicIpConsensus {
+    /// Whether every server that answered reported the same address
+    pub fn is_unanimous(&self) -> bool {
+        self.votes.iter().all(|(ip, _)| *ip == self.ip)
+    }
+
+    /// Servers that disagreed with the majority, with what they reported
+    pub fn dissenters(&self) -> Vec<(IpAddr, SocketAddr)> {
+        self.votes.iter().filter(|(ip, _)| *ip != self.ip).copied().collect()
+    }
+
+    /// Whether at least [`MIN_CONFIRMING_VOTES`] servers reported the address; a single
+    /// answer could come from one lying or misconfigured server.
+    pub fn is_confirmed(&self) -> bool {
+        self.votes.iter().filter(|(ip, _)| *ip == self.ip).count() >= MIN_CONFIRMING_VOTES
+    }
+}
+
+/// Agreeing answers needed before a consensus address counts as confirmed
+pub const MIN_CONFIRMING_VOTES: usize = 2;
+
 /// A STUN server with separate IPv4 and IPv6 addresses
 #[derive(Debug, Clone)]
-struct StunServer {
-    port: u16,
-    ipv4_addrs: Vec<Ipv4Addr>,
-    ipv6_addrs: Vec<Ipv6Addr>,
+pub struct StunServer {
+    pub port: u16,
+    pub ipv4_addrs: Vec<Ipv4Addr>,
+    pub ipv6_addrs: Vec<Ipv6Addr>,
+}
+
+impl StunServer {
+    fn from_addr(addr: SocketAddr) -> Self {
+        let mut server = StunServer {
+            port: addr.port(),
+            ipv4_addrs: Vec::new(),
+            ipv6_addrs: Vec::new(),
+        };
+        match addr.ip() {
+            IpAddr::V4(ip) => server.ipv4_addrs.push(ip),
+            IpAddr::V6(ip) => server.ipv6_addrs.push(ip),
+        }
+        server
+    }
+
+    /// Every socket address this server can be reached at
+    pub fn addrs(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.ipv4_addrs
+            .iter()
+            .map(|ip| IpAddr::V4(*ip))
+            .chain(self.ipv6_addrs.iter().map(|ip| IpAddr::V6(*ip)))
+            .map(|ip| SocketAddr::new(ip, self.port))
+    }
+
+    /// Drop `addr` from this server; returns whether it was present
+    fn remove_addr(&mut self, addr: SocketAddr) -> bool {
+        if addr.port() != self.port {
+            return false;
+        }
+        let before = self.ipv4_addrs.len() + self.ipv6_addrs.len();
+        self.ipv4_addrs.retain(|ip| IpAddr::V4(*ip) != addr.ip());
+        self.ipv6_addrs.retain(|ip| IpAddr::V6(*ip) != addr.ip());
+        before != self.ipv4_addrs.len() + self.ipv6_addrs.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ipv4_addrs.is_empty() && self.ipv6_addrs.is_empty()
+    }
 }
 
 /// Public IP detection
@@ -58,6 +181,8 @@ pub struct PublicIpDetector {
     ipv6_nat_servers: Vec<StunServer>,
     /// Timeout for STUN queries
     timeout: Duration,
+    /// Retries for fetching the STUN server lists
+    retry: RetryPolicy,
 }
 
 impl Default for PublicIpDetector {
@@ -75,15 +200,27 @@ impl PublicIpDetector {
             ipv4_nat_servers: Vec::new(),
             ipv6_nat_servers: Vec::new(),
             timeout: Duration::from_secs(5),
+            retry: RetryPolicy::default(),
         }
     }
 
+    /// Create a detector that uses only the given servers, without fetching any lists.
+    /// The servers are used both for address detection and as NAT testing servers.
+    pub fn from_static_servers(ipv4: Vec<SocketAddr>, ipv6: Vec<SocketAddr>) -> Self {
+        let mut detector = Self::new();
+        for addr in ipv4.into_iter().chain(ipv6) {
+            detector.add_server(addr);
+            detector.add_nat_server(addr);
+        }
+        detector
+    }
+
     /// Initialize the detector by fetching STUN server lists (should be called before use)
     pub async fn init(&mut self) -> Result<(), String> {
-        self.ipv4_servers = Self::fetch_ipv4_servers().await?;
-        self.ipv6_servers = Self::fetch_ipv6_servers().await?;
-        self.ipv4_nat_servers = Self::fetch_ipv4_nat_servers().await?;
-        self.ipv6_nat_servers = Self::fetch_ipv6_nat_servers().await?;
+        self.ipv4_servers = self.fetch_ipv4_servers().await?;
+        self.ipv6_servers = self.fetch_ipv6_servers().await?;
+        self.ipv4_nat_servers = self.fetch_ipv4_nat_servers().await?;
+        self.ipv6_nat_servers = self.fetch_ipv6_nat_servers().await?;
         Ok(())
     }
 
@@ -93,43 +230,126 @@ impl PublicIpDetector {
         self
     }
 
+    /// Set how STUN list downloads are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Loaded IPv4 STUN servers
+    pub fn ipv4_servers(&self) -> &[StunServer] {
+        &self.ipv4_servers
+    }
+
+    /// Loaded IPv6 STUN servers
+    pub fn ipv6_servers(&self) -> &[StunServer] {
+        &self.ipv6_servers
+    }
+
+    /// Loaded IPv4 NAT testing servers
+    pub fn ipv4_nat_servers(&self) -> &[StunServer] {
+        &self.ipv4_nat_servers
+    }
+
+    /// Loaded IPv6 NAT testing servers
+    pub fn ipv6_nat_servers(&self) -> &[StunServer] {
+        &self.ipv6_nat_servers
+    }
+
+    /// Add a STUN server for public IP detection, to the list matching its family
+    pub fn add_server(&mut self, addr: SocketAddr) {
+        let servers = if addr.is_ipv4() { &mut self.ipv4_servers } else { &mut self.ipv6_servers };
+        if !servers.iter().any(|s| s.addrs().any(|a| a == addr)) {
+            servers.push(StunServer::from_addr(addr));
+        }
+    }
+
+    /// Add an RFC 5780 capable server for NAT type detection
+    pub fn add_nat_server(&mut self, addr: SocketAddr) {
+        let servers = if addr.is_ipv4() { &mut self.ipv4_nat_servers } else { &mut self.ipv6_nat_servers };
+        if !servers.iter().any(|s| s.addrs().any(|a| a == addr)) {
+            servers.push(StunServer::from_addr(addr));
+        }
+    }
+
+    /// Exclude `addr` from every list; returns whether it was loaded
+    pub fn remove_server(&mut self, addr: SocketAddr) -> bool {
+        let mut removed = false;
+        for servers in [
+            &mut self.ipv4_servers,
+            &mut self.ipv6_servers,
+            &mut self.ipv4_nat_servers,
+            &mut self.ipv6_nat_servers,
+        ] {
+            for server in servers.iter_mut() {
+                removed |= server.remove_addr(addr);
+            }
+            servers.retain(|s| !s.is_empty());
+        }
+        removed
+    }
+
+    /// Forget all loaded servers and use only `addr`, for both detection and NAT testing
+    pub fn use_only_server(&mut self, addr: SocketAddr) {
+        self.ipv4_servers.clear();
+        self.ipv6_servers.clear();
+        self.ipv4_nat_servers.clear();
+        self.ipv6_nat_servers.clear();
+        self.add_server(addr);
+        self.add_nat_server(addr);
+    }
+
     /// Fetch IPv4 STUN servers from the remote list
-    async fn fetch_ipv4_servers() -> Result<Vec<StunServer>, String> {
-        Self::fetch_servers_from_list(IPV4_STUN_LIST_URL, true).await
+    async fn fetch_ipv4_servers(&self) -> Result<Vec<StunServer>, String> {
+        Self::fetch_servers_from_list(IPV4_STUN_LIST_URL, true, &self.retry).await
     }
 
     /// Fetch IPv6 STUN servers from the remote list
-    async fn fetch_ipv6_servers() -> Result<Vec<StunServer>, String> {
-        Self::fetch_servers_from_list(IPV6_STUN_LIST_URL, false).await
+    async fn fetch_ipv6_servers(&self) -> Result<Vec<StunServer>, String> {
+        Self::fetch_servers_from_list(IPV6_STUN_LIST_URL, false, &self.retry).await
     }
 
     /// Fetch IPv4 NAT testing STUN servers from the remote list
-    async fn fetch_ipv4_nat_servers() -> Result<Vec<StunServer>, String> {
-        Self::fetch_servers_from_list(IPV4_NAT_TESTING_LIST_URL, true).await
+    async fn fetch_ipv4_nat_servers(&self) -> Result<Vec<StunServer>, String> {
+        Self::fetch_servers_from_list(IPV4_NAT_TESTING_LIST_URL, true, &self.retry).await
     }
 
     /// Fetch IPv6 NAT testing STUN servers from the remote list
-    async fn fetch_ipv6_nat_servers() -> Result<Vec<StunServer>, String> {
-        Self::fetch_servers_from_list(IPV6_NAT_TESTING_LIST_URL, false).await
+    async fn fetch_ipv6_nat_servers(&self) -> Result<Vec<StunServer>, String> {
+        Self::fetch_servers_from_list(IPV6_NAT_TESTING_LIST_URL, false, &self.retry).await
     }
 
-    /// Fetch STUN servers from a list URL
-    async fn fetch_servers_from_list(url: &str, is_ipv4: bool) -> Result<Vec<StunServer>, String> {
+    /// Download a list, retrying transient failures (timeouts, 5xx) but not 4xx
+    async fn fetch_list_text(url: &str, retry: &RetryPolicy) -> Result<String, String> {
         let client = reqwest::Client::new();
-        let response = client
-            .get(url)
-            .timeout(Duration::from_secs(10))
-            .send()
-            .await
-            .map_err(|e| format!("Failed to fetch STUN list: {}", e))?;
+        super::retry::retry(retry, || async {
+            let response = client
+                .get(url)
+                .timeout(Duration::from_secs(10))
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| RetryError::from_reqwest("Failed to fetch STUN list", e))?;
+
+            response
+                .text()
+                .await
+                .map_err(|e| RetryError::from_reqwest("Failed to read STUN list", e))
+        })
+        .await
+    }
 
-        let text = response
-            .text()
-            .await
-            .map_err(|e| format!("Failed to read STUN list: {}", e))?;
+    /// Fetch STUN servers from a list URL
+    async fn fetch_servers_from_list(url: &str, is_ipv4: bool, retry: &RetryPolicy) -> Result<Vec<StunServer>, String> {
+        let text = Self::fetch_list_text(url, retry).await?;
+        Self::servers_from_list_text(&text, is_ipv4).await
+    }
 
-        let mut servers = Vec::new();
+    /// Parse and resolve the body of a STUN list, one `host:port` per line
+    async fn servers_from_list_text(text: &str, is_ipv4: bool) -> Result<Vec<StunServer>, String> {
+        use futures_util::StreamExt;
 
+        let mut entries = Vec::new();
         for line in text.lines() {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
@@ -137,25 +357,41 @@ impl PublicIpDetector {
             }
 
             // Parse "hostname:port" or "[ipv6]:port" format
-            let (hostname, port) = Self::parse_stun_server_line(line)?;
-
-            // Resolve hostname to addresses
-            let (ipv4_addrs, ipv6_addrs) = Self::resolve_hostname(&hostname).await;
+            entries.push(Self::parse_stun_server_line(line)?);
+        }
 
-            if is_ipv4 && !ipv4_addrs.is_empty() {
-                servers.push(StunServer {
-                    port,
-                    ipv4_addrs,
-                    ipv6_addrs: Vec::new(),
-                });
-            } else if !is_ipv4 && !ipv6_addrs.is_empty() {
-                servers.push(StunServer {
+        // Resolve concurrently, keeping list order; every lookup ends by the shared deadline
+        let deadline = tokio::time::Instant::now() + RESOLVE_TOTAL_TIMEOUT;
+        let resolved: Vec<(u16, Vec<SocketAddr>)> = futures_util::stream::iter(entries)
+            .map(|(hostname, port)| async move {
+                let host_deadline = deadline.min(tokio::time::Instant::now() + RESOLVE_HOST_TIMEOUT);
+                let addrs = tokio::time::timeout_at(host_deadline, Self::resolve_hostname(&hostname, port, is_ipv4))
+                    .await
+                    .unwrap_or_default();
+                (port, addrs)
+            })
+            .buffered(RESOLVE_CONCURRENCY)
+            .collect()
+            .await;
+
+        let servers = resolved
+            .into_iter()
+            .filter(|(_, addrs)| !addrs.is_empty())
+            .map(|(port, addrs)| {
+                let mut server = StunServer {
                     port,
                     ipv4_addrs: Vec::new(),
-                    ipv6_addrs,
-                });
-            }
-        }
+                    ipv6_addrs: Vec::new(),
+                };
+                for addr in addrs {
+                    match addr.ip() {
+                        IpAddr::V4(ip) => server.ipv4_addrs.push(ip),
+                        IpAddr::V6(ip) => server.ipv6_addrs.push(ip),
+                    }
+                }
+                server
+            })
+            .collect();
 
         Ok(servers)
     }
@@ -173,6 +409,9 @@ impl PublicIpDetector {
                 }
             }
             Err("Invalid IPv6 format".to_string())
+        } else if line.parse::<Ipv6Addr>().is_ok() {
+            // Bare IPv6 literal: a trailing ":3478" would be part of the address, not a port
+            Ok((line.to_string(), DEFAULT_STUN_PORT))
         } else {
             // IPv4 format: hostname:port
             if let Some(colon_pos) = line.rfind(':') {
@@ -185,53 +424,46 @@ impl PublicIpDetector {
         }
     }
 
-    /// Resolve a hostname to IPv4 and IPv6 addresses
-    async fn resolve_hostname(hostname: &str) -> (Vec<Ipv4Addr>, Vec<Ipv6Addr>) {
-        let mut ipv4_addrs = Vec::new();
-        let mut ipv6_addrs = Vec::new();
+    /// Resolve a STUN server to socket addresses of one family, without blocking the runtime.
+    /// Literal addresses (most list entries) skip DNS entirely.
+    async fn resolve_hostname(hostname: &str, port: u16, is_ipv4: bool) -> Vec<SocketAddr> {
+        if let Ok(ip) = hostname.parse::<IpAddr>() {
+            return if ip.is_ipv4() == is_ipv4 { vec![SocketAddr::new(ip, port)] } else { Vec::new() };
+        }
 
-        let addr_str = format!("{}:3478", hostname);
-        match addr_str.to_socket_addrs() {
-            Ok(addrs) => {
-                for addr in addrs {
-                    match addr.ip() {
-                        IpAddr::V4(ip) => {
-                            if !ipv4_addrs.contains(&ip) {
-                                ipv4_addrs.push(ip);
-                            }
-                        }
-                        IpAddr::V6(ip) => {
-                            if !ipv6_addrs.contains(&ip) {
-                                ipv6_addrs.push(ip);
-                            }
-                        }
-                    }
+        let mut addrs = Vec::new();
+        // Hostname resolution failures just skip this server
+        if let Ok(resolved) = tokio::net::lookup_host((hostname, port)).await {
+            for addr in resolved {
+                if addr.is_ipv4() == is_ipv4 && !addrs.contains(&addr) {
+                    addrs.push(addr);
                 }
             }
-            Err(_) => {
-                // Hostname resolution failed, skip this server
-            }
         }
 
-        (ipv4_addrs, ipv6_addrs)
+        addrs
     }
 
     /// Detect public IPv4 address using STUN
     pub async fn detect_public_ipv4(&self) -> Result<IpAddr, String> {
+        self.detect_public_ipv4_with_source().await.map(|(ip, _)| ip)
+    }
+
+    /// Detect public IPv4 address using STUN, along with the server that reported it
+    pub async fn detect_public_ipv4_with_source(&self) -> Result<(IpAddr, SocketAddr), String> {
         if self.ipv4_servers.is_empty() {
             return Err("No IPv4 STUN servers available - call init() first".to_string());
         }
 
-        // Randomize server order
-        let mut rng = rand::rng();
+        // Randomize server order; the thread-local RNG must not live across the awaits below
         let mut servers = self.ipv4_servers.clone();
-        servers.shuffle(&mut rng);
+        servers.shuffle(&mut rand::rng());
 
         for server in &servers {
             for ip in &server.ipv4_addrs {
-                let addr = format!("{}:{}", ip, server.port);
-                match self.query_stun_ipv4(&addr).await {
-                    Ok(public_ip) => return Ok(public_ip),
+                let addr = SocketAddr::new(IpAddr::V4(*ip), server.port);
+                match self.query_stun_ipv4(&addr.to_string()).await {
+                    Ok(public_ip) => return Ok((public_ip, addr)),
                     Err(_) => continue,
                 }
             }
@@ -242,20 +474,24 @@ impl PublicIpDetector {
 
     /// Detect public IPv6 address using STUN
     pub async fn detect_public_ipv6(&self) -> Result<IpAddr, String> {
+        self.detect_public_ipv6_with_source().await.map(|(ip, _)| ip)
+    }
+
+    /// Detect public IPv6 address using STUN, along with the server that reported it
+    pub async fn detect_public_ipv6_with_source(&self) -> Result<(IpAddr, SocketAddr), String> {
         if self.ipv6_servers.is_empty() {
             return Err("No IPv6 STUN servers available - call init() first".to_string());
         }
 
-        // Randomize server order
-        let mut rng = rand::rng();
+        // Randomize server order; the thread-local RNG must not live across the awaits below
         let mut servers = self.ipv6_servers.clone();
-        servers.shuffle(&mut rng);
+        servers.shuffle(&mut rand::rng());
 
         for server in &servers {
             for ip in &server.ipv6_addrs {
-                let addr = format!("[{}]:{}", ip, server.port);
-                match self.query_stun_ipv6(&addr).await {
-                    Ok(public_ip) => return Ok(public_ip),
+                let addr = SocketAddr::new(IpAddr::V6(*ip), server.port);
+                match self.query_stun_ipv6(&addr.to_string()).await {
+                    Ok(public_ip) => return Ok((public_ip, addr)),
                     Err(_) => continue,
                 }
             }
@@ -264,16 +500,97 @@ impl PublicIpDetector {
         Err("Failed to detect public IPv6 address from any STUN server".to_string())
     }
 
-    /// Detect NAT type for IPv4 using 2 STUN servers
+    /// Ask up to `n` distinct IPv4 STUN servers concurrently and return the address a majority agrees on
+    pub async fn detect_public_ipv4_consensus(&self, n: usize) -> Result<PublicIpConsensus, String> {
+        let mut servers = self.ipv4_servers.clone();
+        servers.shuffle(&mut rand::rng());
+        let candidates = servers
+            .iter()
+            .filter_map(|server| server.ipv4_addrs.first().map(|ip| SocketAddr::new(IpAddr::V4(*ip), server.port)))
+            .take(n)
+            .collect();
+        self.consensus(candidates, true).await
+    }
+
+    /// Ask up to `n` distinct IPv6 STUN servers concurrently and return the address a majority agrees on
+    pub async fn detect_public_ipv6_consensus(&self, n: usize) -> Result<PublicIpConsensus, String> {
+        let mut servers = self.ipv6_servers.clone();
+        servers.shuffle(&mut rand::rng());
+        let candidates = servers
+            .iter()
+            .filter_map(|server| server.ipv6_addrs.first().map(|ip| SocketAddr::new(IpAddr::V6(*ip), server.port)))
+            .take(n)
+            .collect();
+        self.consensus(candidates, false).await
+    }
+
+    /// Query all `candidates` at once and tally their answers. A lone answer is returned
+    /// unconfirmed rather than as an error, so callers can still show it.
+    async fn consensus(&self, candidates: Vec<SocketAddr>, is_ipv4: bool) -> Result<PublicIpConsensus, String> {
+        use futures_util::StreamExt;
+
+        if candidates.is_empty() {
+            return Err("No STUN servers available - call init() first".to_string());
+        }
+
+        let concurrency = candidates.len();
+        let votes: Vec<(IpAddr, SocketAddr)> = futures_util::stream::iter(candidates)
+            .map(|server| async move {
+                let result = if is_ipv4 {
+                    self.query_stun_ipv4(&server.to_string()).await
+                } else {
+                    self.query_stun_ipv6(&server.to_string()).await
+                };
+                result.ok().map(|ip| (ip, server))
+            })
+            .buffer_unordered(concurrency)
+            .filter_map(|vote| async move { vote })
+            .collect()
+            .await;
+
+        if votes.is_empty() {
+            return Err("No STUN server answered".to_string());
+        }
+
+        let mut tally: Vec<(IpAddr, usize)> = Vec::new();
+        for (ip, _) in &votes {
+            match tally.iter_mut().find(|(seen, _)| seen == ip) {
+                Some((_, count)) => *count += 1,
+                None => tally.push((*ip, 1)),
+            }
+        }
+        tally.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+        let (ip, count) = tally[0];
+        if count * 2 <= votes.len() {
+            let breakdown: Vec<String> = tally.iter().map(|(ip, count)| format!("{} x{}", ip, count)).collect();
+            return Err(format!(
+                "STUN servers disagree on the public address: {}",
+                breakdown.join(", ")
+            ));
+        }
+
+        Ok(PublicIpConsensus { ip, votes })
+    }
+
     /// Detect NAT type for IPv4 using RFC 5780
     pub async fn detect_nat_type_ipv4(&self) -> Result<NatType, String> {
+        self.detect_nat_report_ipv4().await.map(|report| report.nat_type)
+    }
+
+    /// Detect NAT type for IPv6 using RFC 5780
+    pub async fn detect_nat_type_ipv6(&self) -> Result<NatType, String> {
+        self.detect_nat_report_ipv6().await.map(|report| report.nat_type)
+    }
+
+    /// Full IPv4 NAT report, including the CGNAT determination
+    pub async fn detect_nat_report_ipv4(&self) -> Result<NatReport, String> {
         if self.ipv4_nat_servers.is_empty() {
             return Err("No IPv4 NAT testing servers available - call init() first".to_string());
         }
 
-        // Pick a random NAT testing server
-        let mut rng = rand::rng();
-        let server = self.ipv4_nat_servers.choose(&mut rng)
+        // Pick a random NAT testing server; the RNG is not Send, so don't hold it across the probe
+        let server = self.ipv4_nat_servers.choose(&mut rand::rng())
             .ok_or("No NAT testing servers available")?;
 
         let server_ip = server.ipv4_addrs.first()
@@ -283,15 +600,14 @@ impl PublicIpDetector {
         self.detect_nat_type_rfc5780(&server_addr, true).await
     }
 
-    /// Detect NAT type for IPv6 using RFC 5780
-    pub async fn detect_nat_type_ipv6(&self) -> Result<NatType, String> {
+    /// Full IPv6 NAT report
+    pub async fn detect_nat_report_ipv6(&self) -> Result<NatReport, String> {
         if self.ipv6_nat_servers.is_empty() {
             return Err("No IPv6 NAT testing servers available - call init() first".to_string());
         }
 
-        // Pick a random NAT testing server
-        let mut rng = rand::rng();
-        let server = self.ipv6_nat_servers.choose(&mut rng)
+        // Pick a random NAT testing server; the RNG is not Send, so don't hold it across the probe
+        let server = self.ipv6_nat_servers.choose(&mut rand::rng())
             .ok_or("No NAT testing servers available")?;
 
         let server_ip = server.ipv6_addrs.first()
@@ -306,7 +622,7 @@ impl PublicIpDetector {
         &self,
         server_addr: &str,
         is_ipv4: bool,
-    ) -> Result<NatType, String> {
+    ) -> Result<NatReport, String> {
         // RFC 5780 Section 4: NAT Behavior Discovery
         // IMPORTANT: All tests must share the same socket to preserve source port
         
@@ -329,14 +645,24 @@ impl PublicIpDetector {
             Err(e) => {
                 // No UDP connectivity or recv_sas failed
                 eprintln!("Test I failed: {}", e);
-                return Ok(NatType::NoUdpConnectivity);
+                return Ok(NatReport {
+                    nat_type: NatType::NoUdpConnectivity,
+                    mapped_addr: None,
+                    local_ip: None,
+                    is_cgnat: false,
+                });
             }
         };
         
         // Check if we're behind NAT by comparing with actual interface IP
         if test1_mapped_addr.ip() == local_interface_ip {
             // No NAT - Open Internet
-            return Ok(NatType::OpenInternet);
+            return Ok(NatReport {
+                nat_type: NatType::OpenInternet,
+                mapped_addr: Some(test1_mapped_addr),
+                local_ip: Some(local_interface_ip),
+                is_cgnat: false,
+            });
         }
 
         // Test II: Request with CHANGE-REQUEST to test filtering
@@ -353,8 +679,7 @@ impl PublicIpDetector {
         // Test IV: Binding request to alternate server to check mapping behavior
         // We need another server for this - use regular STUN servers as fallback
         let mapping_behavior = if !self.ipv4_servers.is_empty() && is_ipv4 {
-            let mut rng = rand::rng();
-            let alt_server = self.ipv4_servers.choose(&mut rng).unwrap();
+            let alt_server = self.ipv4_servers.choose(&mut rand::rng()).unwrap();
             let alt_ip = alt_server.ipv4_addrs.first().unwrap();
             let alt_addr = format!("{}:{}", alt_ip, alt_server.port);
             
@@ -375,8 +700,7 @@ impl PublicIpDetector {
                 }
             }
         } else if !self.ipv6_servers.is_empty() && !is_ipv4 {
-            let mut rng = rand::rng();
-            let alt_server = self.ipv6_servers.choose(&mut rng).unwrap();
+            let alt_server = self.ipv6_servers.choose(&mut rand::rng()).unwrap();
             let alt_ip = alt_server.ipv6_addrs.first().unwrap();
             let alt_addr = format!("[{}]:{}", alt_ip, alt_server.port);
             
@@ -398,28 +722,36 @@ impl PublicIpDetector {
         };
 
         // Determine NAT type based on test results
-        match mapping_behavior {
+        let nat_type = match mapping_behavior {
             "endpoint-independent" => {
                 // Endpoint-Independent Mapping
                 if test2_response.is_ok() {
                     // No filtering
-                    Ok(NatType::EndpointIndependentNoFiltering)
+                    NatType::EndpointIndependentNoFiltering
                 } else if test3_response.is_ok() {
                     // Address-dependent filtering
-                    Ok(NatType::EndpointIndependentAddressFiltering)
+                    NatType::EndpointIndependentAddressFiltering
                 } else {
                     // Address and port-dependent filtering
-                    Ok(NatType::EndpointIndependentAddressPortFiltering)
+                    NatType::EndpointIndependentAddressPortFiltering
                 }
             }
             "address-dependent" => {
-                Ok(NatType::AddressDependentMapping)
+                NatType::AddressDependentMapping
             }
             "address-port-dependent" => {
-                Ok(NatType::AddressPortDependentMapping)
+                NatType::AddressPortDependentMapping
             }
-            _ => Ok(NatType::Unknown)
-        }
+            _ => NatType::Unknown
+        };
+
+        let is_cgnat = is_cgnat_likely(&nat_type, local_interface_ip, test1_mapped_addr.ip());
+        Ok(NatReport {
+            nat_type,
+            mapped_addr: Some(test1_mapped_addr),
+            local_ip: Some(local_interface_ip),
+            is_cgnat,
+        })
     }
 
     /// Test I: Basic STUN binding request (using shared socket)
@@ -770,6 +1102,156 @@ impl PublicIpDetector {
 mod tests {
     use super::*;
 
+    /// Minimal STUN server on localhost that answers every request with a MAPPED-ADDRESS of `mapped`
+    async fn spawn_fake_stun(mapped: Ipv4Addr) -> SocketAddr {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            while let Ok((n, peer)) = server.recv_from(&mut buf).await {
+                let mut response = vec![0x01, 0x01, 0x00, 0x0c];
+                response.extend_from_slice(&buf[4..20.min(n)]);
+                response.extend_from_slice(&[0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x0d, 0x96]);
+                response.extend_from_slice(&mapped.octets());
+                let _ = server.send_to(&response, peer).await;
+            }
+        });
+        server_addr
+    }
+
+    #[tokio::test]
+    async fn test_static_servers_detect_without_fetch() {
+        let server_addr = spawn_fake_stun(Ipv4Addr::new(198, 51, 100, 7)).await;
+
+        let detector = PublicIpDetector::from_static_servers(vec![server_addr], Vec::new())
+            .with_timeout(Duration::from_secs(2));
+        assert_eq!(detector.ipv4_nat_servers().len(), 1);
+        assert!(detector.ipv6_servers().is_empty());
+
+        let (ip, source) = detector.detect_public_ipv4_with_source().await.unwrap();
+        assert_eq!(ip, "198.51.100.7".parse::<IpAddr>().unwrap());
+        assert_eq!(source, server_addr);
+    }
+
+    #[tokio::test]
+    async fn test_public_ipv4_consensus() {
+        let honest = Ipv4Addr::new(198, 51, 100, 7);
+        let rogue = Ipv4Addr::new(203, 0, 113, 66);
+        let servers = vec![
+            spawn_fake_stun(honest).await,
+            spawn_fake_stun(honest).await,
+            spawn_fake_stun(rogue).await,
+        ];
+
+        let detector = PublicIpDetector::from_static_servers(servers.clone(), Vec::new())
+            .with_timeout(Duration::from_secs(2));
+        let consensus = detector.detect_public_ipv4_consensus(3).await.unwrap();
+        assert_eq!(consensus.ip, IpAddr::V4(honest));
+        assert_eq!(consensus.votes.len(), 3);
+        assert!(!consensus.is_unanimous());
+        assert!(consensus.is_confirmed());
+        assert_eq!(consensus.dissenters(), vec![(IpAddr::V4(rogue), servers[2])]);
+
+        // a single answer is not confirmed
+        let detector = PublicIpDetector::from_static_servers(servers[..1].to_vec(), Vec::new())
+            .with_timeout(Duration::from_secs(2));
+        let consensus = detector.detect_public_ipv4_consensus(3).await.unwrap();
+        assert_eq!(consensus.ip, IpAddr::V4(honest));
+        assert!(!consensus.is_confirmed());
+
+        // a tie is not a majority
+        let detector = PublicIpDetector::from_static_servers(servers[1..].to_vec(), Vec::new())
+            .with_timeout(Duration::from_secs(2));
+        let err = detector.detect_public_ipv4_consensus(3).await.unwrap_err();
+        assert!(err.contains("disagree"), "{}", err);
+    }
+
+    #[test]
+    fn test_is_cgnat_likely() {
+        let public: IpAddr = "198.51.100.7".parse().unwrap();
+        let shared: IpAddr = "100.72.3.4".parse().unwrap();
+        let private: IpAddr = "192.168.1.10".parse().unwrap();
+
+        assert!(is_cgnat_likely(&NatType::EndpointIndependentNoFiltering, shared, public));
+        assert!(is_cgnat_likely(&NatType::AddressPortDependentMapping, private, public));
+        assert!(!is_cgnat_likely(&NatType::EndpointIndependentAddressFiltering, private, public));
+        assert!(!is_cgnat_likely(&NatType::OpenInternet, public, public));
+        // 100.128.0.0 is just past the shared address space
+        assert!(!is_cgnat_likely(&NatType::AddressPortDependentMapping, "100.128.0.1".parse().unwrap(), public));
+        assert!(!is_cgnat_likely(
+            &NatType::AddressPortDependentMapping,
+            "fd00::1".parse().unwrap(),
+            "2001:db8::1".parse().unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_add_and_remove_servers() {
+        let mut detector = PublicIpDetector::new();
+        let v4: SocketAddr = "192.0.2.1:3478".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:5349".parse().unwrap();
+
+        detector.add_server(v4);
+        detector.add_server(v4);
+        detector.add_server(v6);
+        detector.add_nat_server(v4);
+        assert_eq!(detector.ipv4_servers().len(), 1);
+        assert_eq!(detector.ipv6_servers()[0].addrs().collect::<Vec<_>>(), vec![v6]);
+        assert_eq!(detector.ipv4_nat_servers().len(), 1);
+
+        assert!(!detector.remove_server("192.0.2.1:9999".parse().unwrap()));
+        assert!(detector.remove_server(v4));
+        assert!(detector.ipv4_servers().is_empty());
+        assert!(detector.ipv4_nat_servers().is_empty());
+
+        detector.use_only_server(v4);
+        assert!(detector.ipv6_servers().is_empty());
+        assert_eq!(detector.ipv4_servers()[0].addrs().collect::<Vec<_>>(), vec![v4]);
+        assert_eq!(detector.ipv4_nat_servers()[0].addrs().collect::<Vec<_>>(), vec![v4]);
+    }
+
+    #[test]
+    fn test_parse_stun_server_line() {
+        let parse = PublicIpDetector::parse_stun_server_line;
+
+        assert_eq!(parse("[2001:db8::1]:5349"), Ok(("2001:db8::1".to_string(), 5349)));
+        assert_eq!(parse("stun.example.com:3478"), Ok(("stun.example.com".to_string(), 3478)));
+        assert_eq!(parse("192.0.2.1:19302"), Ok(("192.0.2.1".to_string(), 19302)));
+        assert_eq!(parse("2001:db8::1:3478"), Ok(("2001:db8::1:3478".to_string(), DEFAULT_STUN_PORT)));
+        assert_eq!(parse("2001:db8::1"), Ok(("2001:db8::1".to_string(), DEFAULT_STUN_PORT)));
+        // nine groups cannot be an address, so the last one is the port
+        assert_eq!(parse("2001:db8:1:2:3:4:5:6:5349"), Ok(("2001:db8:1:2:3:4:5:6".to_string(), 5349)));
+        assert!(parse("[2001:db8::1]").is_err());
+        assert!(parse("stun.example.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_hostname_filters_family() {
+        let v4: SocketAddr = "192.0.2.1:3478".parse().unwrap();
+        assert_eq!(PublicIpDetector::resolve_hostname("192.0.2.1", 3478, true).await, vec![v4]);
+        assert!(PublicIpDetector::resolve_hostname("192.0.2.1", 3478, false).await.is_empty());
+        assert!(PublicIpDetector::resolve_hostname("2001:db8::1", 3478, true).await.is_empty());
+
+        let localhost = PublicIpDetector::resolve_hostname("localhost", 5349, true).await;
+        assert!(localhost.iter().all(|addr| addr.is_ipv4() && addr.port() == 5349));
+    }
+
+    #[tokio::test]
+    async fn test_servers_from_list_keeps_port() {
+        let servers = PublicIpDetector::servers_from_list_text("# comment\n192.0.2.1:5349\nlocalhost:3478\n", true)
+            .await
+            .unwrap();
+
+        assert_eq!(servers[0].port, 5349);
+        assert_eq!(servers[0].ipv4_addrs, vec!["192.0.2.1".parse::<Ipv4Addr>().unwrap()]);
+        assert!(servers.iter().skip(1).all(|s| s.port == 3478));
+
+        let servers = PublicIpDetector::servers_from_list_text("[2001:db8::1]:5349\n", false)
+            .await
+            .unwrap();
+        assert_eq!(servers[0].port, 5349);
+    }
+
     #[test]
     fn test_detector_creation() {
         let detector = PublicIpDetector::new();
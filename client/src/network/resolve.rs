@@ -0,0 +1,68 @@
+use std::io;
+use std::net::SocketAddr;
+
+/// Resolve a `host:port` (or literal socket address) endpoint for a tunnel of the given family.
+///
+/// Both A and AAAA records are looked up. Literal addresses are returned as-is; for hostnames
+/// only addresses of the tunnel's family are considered, since the family is part of the
+/// interface name. Within that family the resolver's order is kept, which already follows the
+/// RFC 6724 destination preference that happy eyeballs builds on. WireGuard is connectionless,
+/// so there is no connection to race; the first candidate is used and re-resolved later.
+pub async fn resolve_endpoint(endpoint: &str, ipv6: bool) -> io::Result<SocketAddr> {
+    if let Ok(addr) = endpoint.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host(endpoint).await?.collect();
+    pick_endpoint(&addrs, ipv6).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} has no {} record",
+                endpoint,
+                if ipv6 { "AAAA" } else { "A" }
+            ),
+        )
+    })
+}
+
+/// Whether `endpoint` names a host rather than a literal address, so its address can change.
+pub fn is_hostname(endpoint: &str) -> bool {
+    endpoint.parse::<SocketAddr>().is_err()
+}
+
+fn pick_endpoint(addrs: &[SocketAddr], ipv6: bool) -> Option<SocketAddr> {
+    addrs.iter().copied().find(|addr| addr.is_ipv6() == ipv6)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_endpoint_matches_family() {
+        let addrs: Vec<SocketAddr> = vec![
+            "[2001:db8::1]:51820".parse().unwrap(),
+            "192.0.2.1:51820".parse().unwrap(),
+            "[2001:db8::2]:51820".parse().unwrap(),
+        ];
+
+        assert_eq!(pick_endpoint(&addrs, true), Some(addrs[0]));
+        assert_eq!(pick_endpoint(&addrs, false), Some(addrs[1]));
+        assert_eq!(pick_endpoint(&addrs[..1], false), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_endpoint() {
+        let literal = resolve_endpoint("192.0.2.1:51820", true).await.unwrap();
+        assert_eq!(literal, "192.0.2.1:51820".parse::<SocketAddr>().unwrap());
+
+        let localhost = resolve_endpoint("localhost:51820", false).await.unwrap();
+        assert!(localhost.is_ipv4());
+        assert_eq!(localhost.port(), 51820);
+
+        assert!(resolve_endpoint("localhost", false).await.is_err());
+        assert!(is_hostname("localhost:51820"));
+        assert!(!is_hostname("[2001:db8::1]:51820"));
+    }
+}
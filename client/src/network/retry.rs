@@ -0,0 +1,154 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rand::RngExt;
+
+/// How often and how patiently to retry a fallible network operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one
+    pub attempts: u32,
+    /// Delay before the second attempt; doubled for each further attempt
+    pub base_delay: Duration,
+    /// Upper bound for a single delay (before jitter)
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after failed attempt `attempt` (1-based). `jitter` in `[0, 1)` adds up to 50%.
+    fn delay_for(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let delay = self.base_delay.saturating_mul(1 << exponent).min(self.max_delay);
+        delay.mul_f64(1.0 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// Error from one attempt, tagged with whether trying again could help.
+#[derive(Debug, Clone)]
+pub struct RetryError {
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl RetryError {
+    /// Timeouts, connection failures, 5xx and the like
+    pub fn transient(message: impl Into<String>) -> Self {
+        Self { message: message.into(), retryable: true }
+    }
+
+    /// 4xx, malformed responses: the same request will fail the same way
+    pub fn permanent(message: impl Into<String>) -> Self {
+        Self { message: message.into(), retryable: false }
+    }
+
+    /// Classify a `reqwest` error; status errors are retryable only for 5xx and 429.
+    pub fn from_reqwest(context: &str, e: reqwest::Error) -> Self {
+        let retryable = match e.status() {
+            Some(status) => status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+            None => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+        };
+        Self { message: format!("{}: {}", context, e), retryable }
+    }
+}
+
+/// Run `op` until it succeeds, fails permanently, or `policy.attempts` are used up,
+/// sleeping with exponential backoff and jitter in between.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, mut op: F) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RetryError>>,
+{
+    let attempts = policy.attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if !e.retryable => return Err(e.message),
+            Err(e) if attempt >= attempts => {
+                return Err(format!("{} (after {} attempts)", e.message, attempt));
+            }
+            Err(_) => {
+                let jitter = rand::rng().random::<f64>();
+                tokio::time::sleep(policy.delay_for(attempt, jitter)).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const FAST: RetryPolicy = RetryPolicy {
+        attempts: 3,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(2),
+    };
+
+    #[test]
+    fn test_delay_for() {
+        let policy = RetryPolicy {
+            attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+        };
+        assert_eq!(policy.delay_for(1, 0.0), Duration::from_millis(100));
+        assert_eq!(policy.delay_for(2, 0.0), Duration::from_millis(200));
+        assert_eq!(policy.delay_for(3, 0.0), Duration::from_millis(300));
+        assert_eq!(policy.delay_for(2, 0.999), Duration::from_millis(200).mul_f64(1.4995));
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_until_success() {
+        let calls = AtomicU32::new(0);
+        let result = retry(&FAST, || async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(RetryError::transient("timeout"))
+            } else {
+                Ok(42)
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok(42));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), String> = retry(&FAST, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(RetryError::transient("timeout"))
+        })
+        .await;
+
+        assert_eq!(result, Err("timeout (after 3 attempts)".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_on_permanent_error() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), String> = retry(&FAST, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(RetryError::permanent("404"))
+        })
+        .await;
+
+        assert_eq!(result, Err("404".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
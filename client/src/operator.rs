@@ -0,0 +1,178 @@
+//! `cat4igp-client operator`: manage invites through the server's operator API.
+
+use std::error::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cat4igp_shared::rest::operator as rest;
+use clap::Subcommand;
+
+use crate::server_rest::operator::OperatorRestClient;
+
+#[derive(Subcommand)]
+pub enum OperatorCommand {
+    /// Create, list and revoke invite codes
+    Invite {
+        #[command(subcommand)]
+        command: InviteCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum InviteCommand {
+    /// Create an invite and print its code
+    Create {
+        /// Seconds until the invite expires; never by default
+        #[arg(long, value_name = "SECS")]
+        expires_in: Option<u64>,
+
+        /// How many nodes may register with it; unlimited by default
+        #[arg(long, value_name = "N")]
+        max_uses: Option<i32>,
+
+        /// Mesh that nodes registering with it join
+        #[arg(long, value_name = "MESH_ID")]
+        join_mesh: Option<i32>,
+
+        /// Note to tell invites apart, at most 128 characters
+        #[arg(long)]
+        label: Option<String>,
+    },
+
+    /// List every invite with its uses, expiry and state
+    List,
+
+    /// Revoke an invite so no more nodes can register with it
+    Revoke {
+        /// Invite ID as shown by `list`
+        invite_id: i32,
+    },
+}
+
+/// Run `command` against the server, printing its answer as JSON if `json` is set.
+pub async fn run(
+    client: &OperatorRestClient,
+    command: OperatorCommand,
+    json: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let OperatorCommand::Invite { command } = command;
+    match command {
+        InviteCommand::Create { expires_in, max_uses, join_mesh, label } => {
+            let payload = rest::CreateInvitePayload {
+                expires_at: expires_in.map(|secs| unix_millis(SystemTime::now() + Duration::from_secs(secs))),
+                max_uses,
+                join_mesh,
+                label,
+            };
+            let response = client.create_invite(&payload).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&response)?);
+            } else {
+                println!("✓ Invite code: {}", response.invite_code);
+            }
+        }
+        InviteCommand::List => {
+            let response = client.get_invites().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&response.invites)?);
+            } else if response.invites.is_empty() {
+                println!("No invites");
+            } else {
+                print!("{}", format_invites(&response.invites, unix_millis(SystemTime::now()) / 1000));
+            }
+        }
+        InviteCommand::Revoke { invite_id } => {
+            let response = client.revoke_invite(invite_id).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&response)?);
+            } else {
+                println!("✓ Invite {} revoked", invite_id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Milliseconds since the Unix epoch, the unit `CreateInvitePayload::expires_at` takes.
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+/// Whether an invite still registers nodes at `now` (Unix seconds), and why not otherwise.
+fn invite_state(invite: &rest::Invite, now: i64) -> &'static str {
+    if invite.revoked {
+        "revoked"
+    } else if invite.expires_at.is_some_and(|at| at.and_utc().timestamp() <= now) {
+        "expired"
+    } else if invite.max_uses.is_some_and(|max| invite.used_count >= max) {
+        "used up"
+    } else {
+        "active"
+    }
+}
+
+/// One row per invite, under a header line.
+fn format_invites(invites: &[rest::Invite], now: i64) -> String {
+    let mut out = format!("{:<6} {:<38} {:<9} {:<20} {:<8} {}\n", "ID", "CODE", "USES", "EXPIRES (UTC)", "STATE", "LABEL");
+    for invite in invites {
+        let uses = match invite.max_uses {
+            Some(max) => format!("{}/{}", invite.used_count, max),
+            None => invite.used_count.to_string(),
+        };
+        let expires = invite
+            .expires_at
+            .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "never".to_string());
+        out.push_str(&format!(
+            "{:<6} {:<38} {:<9} {:<20} {:<8} {}\n",
+            invite.id,
+            invite.code,
+            uses,
+            expires,
+            invite_state(invite, now),
+            invite.label.as_deref().unwrap_or("")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite(id: i32, json: &str) -> rest::Invite {
+        let mut value: serde_json::Value = serde_json::from_str(json).unwrap();
+        let fields = value.as_object_mut().unwrap();
+        fields.insert("id".to_string(), id.into());
+        fields.entry("code").or_insert("code".into());
+        fields.entry("created_at").or_insert("2026-10-01T00:00:00".into());
+        fields.entry("used_count").or_insert(0.into());
+        fields.entry("revoked").or_insert(false.into());
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_invite_state() {
+        // 2026-10-16T00:00:00Z
+        let now = 1_792_108_800;
+        assert_eq!(invite_state(&invite(1, "{}"), now), "active");
+        assert_eq!(invite_state(&invite(2, r#"{"revoked":true}"#), now), "revoked");
+        assert_eq!(invite_state(&invite(3, r#"{"expires_at":"2026-10-15T23:59:59"}"#), now), "expired");
+        assert_eq!(invite_state(&invite(4, r#"{"expires_at":"2026-10-16T00:00:01"}"#), now), "active");
+        assert_eq!(invite_state(&invite(5, r#"{"max_uses":2,"used_count":2}"#), now), "used up");
+    }
+
+    #[test]
+    fn test_format_invites() {
+        let invites = [
+            invite(1, r#"{"code":"first","max_uses":5,"used_count":1,"label":"lab"}"#),
+            invite(2, r#"{"code":"second","expires_at":"2026-10-20T08:30:00"}"#),
+        ];
+        let out = format_invites(&invites, 0);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("ID "));
+        assert!(lines[1].contains("first") && lines[1].contains("1/5") && lines[1].contains("never"));
+        assert!(lines[1].ends_with("lab"));
+        assert!(lines[2].contains("2026-10-20 08:30:00") && lines[2].contains("active"));
+    }
+}
@@ -1,4 +1,5 @@
 use std::error::Error;
+use std::time::Duration;
 
 use cat4igp_shared::rest::client as rest;
 use cat4igp_shared::rest::StandardResponse;
@@ -8,6 +9,9 @@ use serde::Serialize;
 
 use crate::config::ServerConfig;
 
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub struct ServerRestClient {
     base_url: String,
@@ -19,6 +23,8 @@ impl ServerRestClient {
     pub fn new(config: &ServerConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
         let client = reqwest::Client::builder()
             .danger_accept_invalid_certs(!config.verify_tls)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
             .build()?;
 
         Ok(Self {
@@ -35,7 +41,7 @@ impl ServerRestClient {
 
         if let Some(auth_key) = &self.auth_key {
             if !auth_key.is_empty() {
-                request = request.header("Authorization", auth_key);
+                request = request.header("Authorization", format!("Bearer {}", auth_key));
             }
         }
 
@@ -63,12 +69,32 @@ impl ServerRestClient {
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(format!("request failed with {}: {}", status, body).into());
+            return Err(StatusError { status, message: error_message(status, &body) }.into());
         }
 
         Ok(response.json::<T>().await?)
     }
 
+    /// Server version and capabilities, or `None` for servers that predate `GET /version`.
+    pub async fn get_version(
+        &self,
+    ) -> Result<Option<cat4igp_shared::rest::VersionResponse>, Box<dyn Error + Send + Sync>> {
+        let response = self
+            .client
+            .get(format!("{}/version", self.base_url))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(StatusError { status, message: error_message(status, &body) }.into());
+        }
+        Ok(Some(response.json().await?))
+    }
+
     pub async fn register(
         &self,
         node_name: &str,
@@ -103,15 +129,45 @@ impl ServerRestClient {
         .await
     }
 
+    pub async fn set_metadata(
+        &self,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<StandardResponse, Box<dyn Error + Send + Sync>> {
+        let payload = rest::SetNodeMetadataPayload {
+            key: key.to_string(),
+            value: value.map(str::to_string),
+        };
+        self.send_json(Method::POST, "self/metadata", Some(&payload)).await
+    }
+
+    /// Fetch the tunnel list, sending `etag` as `If-None-Match`. Returns `None` when the
+    /// server answers 304 (nothing changed), otherwise the list and its new ETag.
     pub async fn get_wireguard_tunnels(
         &self,
-    ) -> Result<rest::WireguardTunnelsResponse, Box<dyn Error + Send + Sync>> {
-        self.send_json::<rest::WireguardTunnelsResponse, serde_json::Value>(
-            Method::GET,
-            "wg_tun",
-            None,
-        )
-        .await
+        etag: Option<&str>,
+    ) -> Result<Option<(rest::WireguardTunnelsResponse, Option<String>)>, Box<dyn Error + Send + Sync>> {
+        let mut request = self.request(Method::GET, "wg_tun");
+        if let Some(etag) = etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(StatusError { status, message: error_message(status, &body) }.into());
+        }
+
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        Ok(Some((response.json().await?, etag)))
     }
 
     pub async fn answer_wireguard_tunnel(
@@ -138,4 +194,64 @@ impl ServerRestClient {
         };
         self.send_json(Method::POST, "wg_pubkey", Some(&payload)).await
     }
+
+    /// Ask the server to replace this client's auth key with `new_key`.
+    pub async fn rotate_key(
+        &self,
+        new_key: &str,
+    ) -> Result<rest::RotateKeyResponse, Box<dyn Error + Send + Sync>> {
+        let payload = rest::RotateKeyPayload {
+            auth_key: Some(new_key.to_string()),
+        };
+        self.send_json(Method::POST, "rotate_key", Some(&payload)).await
+    }
+}
+
+/// Prefer the server's `StandardResponse` message over the raw body when there is one.
+/// The server answered with a non-2xx status.
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: reqwest::StatusCode,
+    message: String,
+}
+
+impl StatusError {
+    pub(super) fn new(status: reqwest::StatusCode, message: String) -> Self {
+        StatusError { status, message }
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for StatusError {}
+
+pub(super) fn error_message(status: reqwest::StatusCode, body: &str) -> String {
+    match serde_json::from_str::<StandardResponse>(body) {
+        Ok(StandardResponse { message: Some(message), .. }) => {
+            format!("request failed with {}: {}", status, message)
+        }
+        _ => format!("request failed with {}: {}", status, body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_message_uses_standard_response() {
+        let body = r#"{"success":false,"message":"Invite code revoked"}"#;
+        assert_eq!(
+            error_message(reqwest::StatusCode::BAD_REQUEST, body),
+            "request failed with 400 Bad Request: Invite code revoked"
+        );
+        assert_eq!(
+            error_message(reqwest::StatusCode::BAD_GATEWAY, "upstream down"),
+            "request failed with 502 Bad Gateway: upstream down"
+        );
+    }
 }
@@ -1 +1,3 @@
 pub mod client;
+pub mod operator;
+pub mod probe;
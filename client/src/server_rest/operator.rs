@@ -0,0 +1,141 @@
+//! Client for the server's `/operator` API, authenticated with the operator key.
+
+use std::error::Error;
+use std::time::Duration;
+
+use cat4igp_shared::rest::operator as rest;
+use cat4igp_shared::rest::StandardResponse;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::client::{error_message, StatusError};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
+pub struct OperatorRestClient {
+    base_url: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl OperatorRestClient {
+    pub fn new(address: &str, token: &str, verify_tls: bool) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let client = reqwest::Client::builder()
+            .danger_accept_invalid_certs(!verify_tls)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            base_url: address.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            client,
+        })
+    }
+
+    async fn send_json<T, P>(
+        &self,
+        method: Method,
+        path: &str,
+        payload: Option<&P>,
+    ) -> Result<T, Box<dyn Error + Send + Sync>>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        let mut request = self
+            .client
+            .request(method, format!("{}/operator/{}", self.base_url, path))
+            .header("Authorization", format!("Bearer {}", self.token));
+        if let Some(payload) = payload {
+            request = request.json(payload);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        // the operator middleware answers 401 in plain text, without saying which side is wrong
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(StatusError::new(
+                status,
+                "operator token rejected (401): it must match the server's OPERATOR_AUTH_KEY, which must be set"
+                    .to_string(),
+            )
+            .into());
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(StatusError::new(status, error_message(status, &body)).into());
+        }
+
+        Ok(response.json::<T>().await?)
+    }
+
+    pub async fn create_invite(
+        &self,
+        payload: &rest::CreateInvitePayload,
+    ) -> Result<rest::CreateInviteResponse, Box<dyn Error + Send + Sync>> {
+        self.send_json(Method::POST, "create_invite", Some(payload)).await
+    }
+
+    pub async fn get_invites(&self) -> Result<rest::GetInvitesResponse, Box<dyn Error + Send + Sync>> {
+        self.send_json::<_, ()>(Method::GET, "invites", None).await
+    }
+
+    pub async fn revoke_invite(&self, invite_id: i32) -> Result<StandardResponse, Box<dyn Error + Send + Sync>> {
+        let payload = rest::RevokeInvitePayload { invite_id };
+        self.send_json(Method::POST, "revoke_invite", Some(&payload)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Plain-HTTP server answering every connection with `status` and `body`.
+    async fn spawn_http_server(status: &'static str, body: &'static str) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_get_invites() {
+        let body = r#"{"success":true,"invites":[{"id":3,"code":"abc","created_at":"2026-10-16T12:00:00","expires_at":null,"used_count":1,"override_join_mesh":null,"max_uses":5,"revoked":false,"label":"lab"}]}"#;
+        let addr = spawn_http_server("200 OK", body).await;
+        let client = OperatorRestClient::new(&format!("http://{}/", addr), "token", true).unwrap();
+
+        let invites = client.get_invites().await.unwrap().invites;
+        assert_eq!(invites.len(), 1);
+        assert_eq!(invites[0].code, "abc");
+        assert_eq!(invites[0].label.as_deref(), Some("lab"));
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_is_explained() {
+        let addr = spawn_http_server("401 Unauthorized", "Unauthorized").await;
+        let client = OperatorRestClient::new(&format!("http://{}", addr), "wrong", true).unwrap();
+
+        let err = client.revoke_invite(1).await.err().unwrap();
+        let status = err.downcast_ref::<StatusError>().unwrap().status;
+        assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+        assert!(err.to_string().contains("OPERATOR_AUTH_KEY"), "{}", err);
+    }
+}
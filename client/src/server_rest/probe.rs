@@ -0,0 +1,189 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::config::ServerConfig;
+use crate::server_rest::client::ServerRestClient;
+use crate::daemon::protocol::{ProbeFailure, ServerProbe};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest banner kept from the server's root page
+const MAX_BANNER_LEN: usize = 200;
+
+/// Check that `GET /` on the server succeeds with the given TLS settings, one stage at
+/// a time (address, DNS, connection/TLS, HTTP), so a failure says which stage broke.
+pub async fn probe_server(address: &str, verify_tls: bool) -> ServerProbe {
+    let mut probe = ServerProbe {
+        address: address.to_string(),
+        resolved: Vec::new(),
+        status: None,
+        banner: None,
+        version: None,
+        failure: None,
+        detail: None,
+    };
+
+    let config = match ServerConfig::normalize_address(address) {
+        Ok(address) => ServerConfig {
+            verify_tls,
+            ..ServerConfig::new(address, String::new())
+        },
+        Err(e) => return probe.failed(ProbeFailure::InvalidAddress, e),
+    };
+    probe.address = config.address.clone();
+    let (host, port) = match (config.get_host(), config.get_port()) {
+        (Ok(host), Ok(port)) => (host, port),
+        (Err(e), _) | (_, Err(e)) => return probe.failed(ProbeFailure::InvalidAddress, e.to_string()),
+    };
+
+    match tokio::time::timeout(PROBE_TIMEOUT, tokio::net::lookup_host((host.as_str(), port))).await {
+        Ok(Ok(addrs)) => probe.resolved = addrs.collect(),
+        Ok(Err(e)) => return probe.failed(ProbeFailure::Dns, e.to_string()),
+        Err(_) => return probe.failed(ProbeFailure::Dns, format!("lookup of {} timed out", host)),
+    }
+    if probe.resolved.is_empty() {
+        return probe.failed(ProbeFailure::Dns, format!("{} has no addresses", host));
+    }
+
+    let client = match reqwest::Client::builder()
+        .danger_accept_invalid_certs(!verify_tls)
+        .connect_timeout(PROBE_TIMEOUT)
+        .timeout(PROBE_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return probe.failed(ProbeFailure::Other, e.to_string()),
+    };
+
+    let response = match client.get(format!("{}/", config.address)).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            let failure = if e.is_timeout() {
+                ProbeFailure::Timeout
+            } else {
+                classify_error(&e)
+            };
+            return probe.failed(failure, error_chain(&e));
+        }
+    };
+
+    let status = response.status();
+    probe.status = Some(status.as_u16());
+    let body = response.text().await.unwrap_or_default();
+    probe.banner = body
+        .lines()
+        .next()
+        .map(|line| line.trim().chars().take(MAX_BANNER_LEN).collect::<String>())
+        .filter(|line| !line.is_empty());
+
+    if !status.is_success() {
+        return probe.failed(ProbeFailure::Http, format!("server answered {}", status));
+    }
+
+    // informational: an old server without the endpoint is still reachable
+    if let Ok(client) = ServerRestClient::new(&config) {
+        probe.version = client.get_version().await.ok().flatten();
+    }
+    probe
+}
+
+impl ServerProbe {
+    fn failed(mut self, failure: ProbeFailure, detail: String) -> Self {
+        self.failure = Some(failure);
+        self.detail = Some(detail);
+        self
+    }
+}
+
+/// Find the underlying cause of a failed request: a refused connection, a timeout, or a
+/// TLS problem (bad certificate, or https spoken to a plain-http port).
+fn classify_error(err: &(dyn Error + 'static)) -> ProbeFailure {
+    let mut cause = Some(err);
+    while let Some(e) = cause {
+        if e.is::<rustls::Error>() {
+            return ProbeFailure::Tls;
+        }
+        cause = e.source();
+
+        if let Some(io_err) = e.downcast_ref::<std::io::Error>() {
+            match io_err.kind() {
+                std::io::ErrorKind::ConnectionRefused => return ProbeFailure::ConnectionRefused,
+                std::io::ErrorKind::TimedOut => return ProbeFailure::Timeout,
+                _ => {}
+            }
+            // io::Error::source() skips the error it wraps, and the TLS stack nests
+            // the rustls error a couple of io::Errors deep
+            if let Some(inner) = io_err.get_ref() {
+                cause = Some(inner);
+            }
+        }
+    }
+    ProbeFailure::Other
+}
+
+/// reqwest's own message is generic ("error sending request"); the causes say what happened.
+fn error_chain(err: &(dyn Error + 'static)) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Plain-HTTP server answering every connection with `body` and status 200.
+    async fn spawn_http_server(body: &'static str) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_probe_reachable_server() {
+        let addr = spawn_http_server("CAT4IGP Controller Server\n").await;
+
+        let probe = probe_server(&format!("http://{}/", addr), true).await;
+        assert_eq!(probe.failure, None, "{:?}", probe.detail);
+        assert_eq!(probe.address, format!("http://{}", addr));
+        assert_eq!(probe.resolved, vec![addr]);
+        assert_eq!(probe.status, Some(200));
+        assert_eq!(probe.banner.as_deref(), Some("CAT4IGP Controller Server"));
+        assert!(probe.version.is_none());
+
+        // https spoken to a plain-http port fails the TLS handshake
+        let probe = probe_server(&format!("https://{}", addr), false).await;
+        assert_eq!(probe.failure, Some(ProbeFailure::Tls), "{:?}", probe.detail);
+    }
+
+    #[tokio::test]
+    async fn test_probe_failures() {
+        let probe = probe_server("example.com", true).await;
+        assert_eq!(probe.failure, Some(ProbeFailure::InvalidAddress));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let probe = probe_server(&format!("http://{}", addr), true).await;
+        assert_eq!(probe.failure, Some(ProbeFailure::ConnectionRefused), "{:?}", probe.detail);
+        assert!(probe.detail.unwrap().contains("refused"));
+    }
+}
@@ -1,6 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+pub mod faketcp;
+pub mod relay;
 pub mod shared;
 pub mod wireguard;
 
+/// Tunnel protocol backing an interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TunnelType {
     WireGuard,
-}
\ No newline at end of file
+    /// Traffic forwarded through a relay node or the controller's relay
+    Relay,
+}
+
+impl std::fmt::Display for TunnelType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TunnelType::WireGuard => write!(f, "wireguard"),
+            TunnelType::Relay => write!(f, "relay"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tunnel_type_display_and_serde() {
+        assert_eq!(TunnelType::WireGuard.to_string(), "wireguard");
+
+        // serde uses the same name as Display, as the tunnel_protocols config keys do
+        for tunnel_type in [TunnelType::WireGuard, TunnelType::Relay] {
+            let json = serde_json::to_string(&tunnel_type).unwrap();
+            assert_eq!(json, format!("\"{}\"", tunnel_type));
+            assert_eq!(serde_json::from_str::<TunnelType>(&json).unwrap(), tunnel_type);
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Addresses used to interpose a FakeTCP shim between a UDP protocol and the network.
+///
+/// The shim receives plain datagrams from the inner layer on `local_bind` (only from
+/// `local_app_endpoint`), carries them to the peer over TCP-looking traffic on `listen`,
+/// and delivers datagrams received from the peer back to `local_app_endpoint`.
+#[derive(Debug, Clone)]
+pub struct FakeTcpConfig {
+    /// Public address the shim listens on and connects from.
+    pub listen: SocketAddr,
+    /// Local UDP address the inner layer sends its datagrams to.
+    pub local_bind: SocketAddr,
+    /// Local UDP address of the inner layer.
+    pub local_app_endpoint: SocketAddr,
+    /// Remote shim address, if already known.
+    pub peer_addr: Option<SocketAddr>,
+}
+
+/// A running FakeTCP shim. Dropping the last reference stops it.
+pub trait FakeTcpBackend: Send + Sync {
+    /// Public address the shim listens on.
+    fn listen_addr(&self) -> SocketAddr;
+    /// Local UDP address the inner layer should use as its peer endpoint.
+    fn local_bind_addr(&self) -> SocketAddr;
+    /// Update the remote shim address without restarting.
+    fn set_peer_addr(&self, addr: SocketAddr);
+    /// Get the remote shim address currently in use.
+    fn get_peer_addr(&self) -> Option<SocketAddr>;
+}
+
+/// Start the FakeTCP backend compiled into this build.
+#[cfg(feature = "faketcp")]
+pub async fn start(config: FakeTcpConfig) -> io::Result<Arc<dyn FakeTcpBackend>> {
+    Ok(Arc::new(stream::StreamBackend::start(config).await?))
+}
+
+/// Start the FakeTCP backend compiled into this build.
+#[cfg(not(feature = "faketcp"))]
+pub async fn start(_config: FakeTcpConfig) -> io::Result<Arc<dyn FakeTcpBackend>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "FakeTCP support is not compiled in; rebuild cat4igp-client with `--features faketcp`",
+    ))
+}
+
+/// Stream backend: frames each datagram with a 2-byte length prefix over a TCP connection.
+///
+/// Both peers listen and, once the remote address is known, dial out as well; whichever
+/// connection was established last is used for sending, while every open connection keeps
+/// delivering what it receives.
+#[cfg(feature = "faketcp")]
+mod stream {
+    use std::io;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+    use tokio::net::{TcpListener, TcpSocket, TcpStream, UdpSocket};
+    use tokio::task::JoinHandle;
+
+    use super::{FakeTcpBackend, FakeTcpConfig};
+
+    const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+    pub struct StreamBackend {
+        listen: SocketAddr,
+        local_bind: SocketAddr,
+        peer_addr: Arc<Mutex<Option<SocketAddr>>>,
+        tasks: Vec<JoinHandle<()>>,
+        pump: SharedPump,
+    }
+
+    type SharedWriter = Arc<tokio::sync::Mutex<Option<OwnedWriteHalf>>>;
+    /// Task reading the current stream into the app; replaced along with the stream
+    type SharedPump = Arc<Mutex<Option<JoinHandle<()>>>>;
+
+    impl StreamBackend {
+        pub async fn start(config: FakeTcpConfig) -> io::Result<Self> {
+            let udp = Arc::new(UdpSocket::bind(config.local_bind).await?);
+            let local_bind = udp.local_addr()?;
+            let listener = listen_socket(config.listen)?;
+            let listen = listener.local_addr()?;
+
+            let peer_addr = Arc::new(Mutex::new(config.peer_addr));
+            let writer: SharedWriter = Arc::new(tokio::sync::Mutex::new(None));
+            let pump: SharedPump = Arc::new(Mutex::new(None));
+            let app = config.local_app_endpoint;
+
+            let accept_task = {
+                let udp = Arc::clone(&udp);
+                let writer = Arc::clone(&writer);
+                let pump = Arc::clone(&pump);
+                tokio::spawn(async move {
+                    loop {
+                        match listener.accept().await {
+                            Ok((stream, _)) => install_stream(stream, &writer, &pump, &udp, app).await,
+                            Err(e) => {
+                                eprintln!("[faketcp] accept failed on {}: {}", listen, e);
+                                tokio::time::sleep(RECONNECT_INTERVAL).await;
+                            }
+                        }
+                    }
+                })
+            };
+
+            let dial_task = {
+                let udp = Arc::clone(&udp);
+                let writer = Arc::clone(&writer);
+                let pump = Arc::clone(&pump);
+                let peer_addr = Arc::clone(&peer_addr);
+                tokio::spawn(async move {
+                    let mut attempted: Option<SocketAddr> = None;
+                    loop {
+                        let target = *peer_addr.lock().unwrap();
+                        let connected = writer.lock().await.is_some();
+                        if let Some(target) = target.filter(|t| !connected || attempted != Some(*t)) {
+                            attempted = Some(target);
+                            if let Ok(stream) = dial(listen, target).await {
+                                install_stream(stream, &writer, &pump, &udp, app).await;
+                            }
+                        }
+                        tokio::time::sleep(RECONNECT_INTERVAL).await;
+                    }
+                })
+            };
+
+            let forward_task = {
+                let udp = Arc::clone(&udp);
+                let writer = Arc::clone(&writer);
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; u16::MAX as usize];
+                    loop {
+                        let (n, src) = match udp.recv_from(&mut buf).await {
+                            Ok(v) => v,
+                            Err(_) => continue,
+                        };
+                        if src != app {
+                            continue;
+                        }
+
+                        let mut guard = writer.lock().await;
+                        if let Some(w) = guard.as_mut() {
+                            let mut frame = Vec::with_capacity(n + 2);
+                            frame.extend_from_slice(&(n as u16).to_be_bytes());
+                            frame.extend_from_slice(&buf[..n]);
+                            if w.write_all(&frame).await.is_err() {
+                                *guard = None;
+                            }
+                        }
+                    }
+                })
+            };
+
+            Ok(Self {
+                listen,
+                local_bind,
+                peer_addr,
+                tasks: vec![accept_task, dial_task, forward_task],
+                pump,
+            })
+        }
+    }
+
+    impl Drop for StreamBackend {
+        fn drop(&mut self) {
+            for task in &self.tasks {
+                task.abort();
+            }
+            if let Some(pump) = self.pump.lock().unwrap().take() {
+                pump.abort();
+            }
+        }
+    }
+
+    impl FakeTcpBackend for StreamBackend {
+        fn listen_addr(&self) -> SocketAddr {
+            self.listen
+        }
+
+        fn local_bind_addr(&self) -> SocketAddr {
+            self.local_bind
+        }
+
+        fn set_peer_addr(&self, addr: SocketAddr) {
+            *self.peer_addr.lock().unwrap() = Some(addr);
+        }
+
+        fn get_peer_addr(&self) -> Option<SocketAddr> {
+            *self.peer_addr.lock().unwrap()
+        }
+    }
+
+    /// Listen with SO_REUSEPORT so the dialer can share the port.
+    fn listen_socket(addr: SocketAddr) -> io::Result<TcpListener> {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(true)?;
+        socket.bind(addr)?;
+        socket.listen(64)
+    }
+
+    /// Dial the peer from our listen port so middleboxes see a single flow per direction.
+    async fn dial(listen: SocketAddr, target: SocketAddr) -> io::Result<TcpStream> {
+        let socket = if target.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(true)?;
+        if listen.is_ipv4() == target.is_ipv4() {
+            socket.bind(listen)?;
+        }
+        socket.connect(target).await
+    }
+
+    /// Make `stream` the current connection, stopping the previous one's pump so a replaced
+    /// stream doesn't keep feeding the app.
+    async fn install_stream(
+        stream: TcpStream,
+        writer: &SharedWriter,
+        pump: &SharedPump,
+        udp: &Arc<UdpSocket>,
+        app: SocketAddr,
+    ) {
+        let _ = stream.set_nodelay(true);
+        let (reader, write_half) = stream.into_split();
+        *writer.lock().await = Some(write_half);
+
+        let udp = Arc::clone(udp);
+        let task = tokio::spawn(async move {
+            let _ = pump_to_app(reader, udp, app).await;
+        });
+        if let Some(previous) = pump.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+    }
+
+    async fn pump_to_app(mut reader: OwnedReadHalf, udp: Arc<UdpSocket>, app: SocketAddr) -> io::Result<()> {
+        let mut buf = vec![0u8; u16::MAX as usize];
+        loop {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len).await?;
+            let len = u16::from_be_bytes(len) as usize;
+            reader.read_exact(&mut buf[..len]).await?;
+            udp.send_to(&buf[..len], app).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "faketcp"))]
+    #[tokio::test]
+    async fn test_start_without_feature_is_unsupported() {
+        let config = FakeTcpConfig {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            local_bind: "127.0.0.1:0".parse().unwrap(),
+            local_app_endpoint: "127.0.0.1:0".parse().unwrap(),
+            peer_addr: None,
+        };
+
+        let err = start(config).await.err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[cfg(feature = "faketcp")]
+    #[tokio::test]
+    async fn test_stream_backend_round_trip() {
+        use tokio::net::UdpSocket;
+
+        let app_a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let app_b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let shim_b = start(FakeTcpConfig {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            local_bind: "127.0.0.1:0".parse().unwrap(),
+            local_app_endpoint: app_b.local_addr().unwrap(),
+            peer_addr: None,
+        })
+        .await
+        .unwrap();
+        let shim_a = start(FakeTcpConfig {
+            listen: "127.0.0.1:0".parse().unwrap(),
+            local_bind: "127.0.0.1:0".parse().unwrap(),
+            local_app_endpoint: app_a.local_addr().unwrap(),
+            peer_addr: Some(shim_b.listen_addr()),
+        })
+        .await
+        .unwrap();
+
+        // datagrams sent before the shims connect are dropped, so resend until one arrives
+        let mut buf = [0u8; 64];
+        let mut received = None;
+        for _ in 0..50 {
+            app_a.send_to(b"hello", shim_a.local_bind_addr()).await.unwrap();
+            if let Ok(Ok((n, _))) =
+                tokio::time::timeout(std::time::Duration::from_millis(100), app_b.recv_from(&mut buf)).await
+            {
+                received = Some(buf[..n].to_vec());
+                break;
+            }
+        }
+        assert_eq!(received.as_deref(), Some(&b"hello"[..]));
+
+        app_b.send_to(b"world", shim_b.local_bind_addr()).await.unwrap();
+        let (n, _) = tokio::time::timeout(std::time::Duration::from_secs(2), app_a.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..n], b"world");
+    }
+}
@@ -1,13 +1,17 @@
+use futures_util::future::BoxFuture;
+
 use crate::tunnel::TunnelType;
 
-pub trait Tunnel {
-    async fn setup(&mut self) -> Result<(), Box<dyn std::error::Error>>;
-    async fn destroy(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+/// An OS-level tunnel interface. Async methods return boxed futures so the trait can be
+/// used as `dyn Tunnel`.
+pub trait Tunnel: Send + Sync {
+    fn setup(&mut self) -> BoxFuture<'_, Result<(), Box<dyn std::error::Error>>>;
+    fn destroy(&mut self) -> BoxFuture<'_, Result<(), Box<dyn std::error::Error>>>;
+    /// Re-apply the current configuration to an existing interface without tearing it down.
+    fn reconfigure(&mut self) -> BoxFuture<'_, Result<(), Box<dyn std::error::Error>>>;
     fn get_interface_name(&self) -> &str;
     fn get_type(&self) -> TunnelType;
-    async fn get_mtu(&self) -> Result<u32, Box<dyn std::error::Error>>;
+    fn get_mtu(&self) -> BoxFuture<'_, Result<u32, Box<dyn std::error::Error>>>;
     fn is_ift_created(&self) -> bool;
     fn is_connected(&self) -> Result<bool, Box<dyn std::error::Error>>;
 }
-
-
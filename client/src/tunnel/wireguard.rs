@@ -1,3 +1,5 @@
+use futures_util::future::BoxFuture;
+use ipnet::IpNet;
 use std::io;
 use std::{net::SocketAddr, str::FromStr};
 use wireguard_control::{Backend, Device, DeviceUpdate, InterfaceName, PeerConfigBuilder};
@@ -20,9 +22,25 @@ pub struct WireGuardTunnel {
     peer_public_key: String,
     peer_endpoint: Option<SocketAddr>,
     listen_port: Option<u16>,
+    keepalive: Option<u16>,
+    allowed_ips: Vec<IpNet>,
     force_userspace: bool
 }
 
+/// Persistent keepalive used unless the controller says otherwise.
+pub const DEFAULT_KEEPALIVE: u16 = 25;
+
+/// A peer whose last handshake is older than this is no longer considered connected.
+pub const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(180);
+
+/// Route everything into the tunnel (`0.0.0.0/0` and `::/0`).
+pub fn default_allowed_ips() -> Vec<IpNet> {
+    vec![
+        IpNet::new(IPV4_DEFAULT, 0).unwrap(),
+        IpNet::new(IPV6_DEFAULT, 0).unwrap(),
+    ]
+}
+
 impl WireGuardTunnel {
     pub fn new(
         interface: String,
@@ -37,6 +55,8 @@ impl WireGuardTunnel {
             peer_public_key,
             peer_endpoint,
             listen_port,
+            keepalive: Some(DEFAULT_KEEPALIVE),
+            allowed_ips: default_allowed_ips(),
             force_userspace: false
         }
     }
@@ -54,6 +74,8 @@ impl WireGuardTunnel {
             peer_public_key,
             peer_endpoint,
             listen_port,
+            keepalive: Some(DEFAULT_KEEPALIVE),
+            allowed_ips: default_allowed_ips(),
             force_userspace: true
         }
     }
@@ -66,6 +88,28 @@ impl WireGuardTunnel {
         self.listen_port = Some(port);
     }
 
+    /// `None` or `Some(0)` disables persistent keepalive.
+    pub fn set_keepalive(&mut self, keepalive: Option<u16>) {
+        self.keepalive = keepalive;
+    }
+
+    pub fn get_keepalive(&self) -> Option<u16> {
+        self.keepalive
+    }
+
+    /// An empty list falls back to `default_allowed_ips()`.
+    pub fn set_allowed_ips(&mut self, allowed_ips: Vec<IpNet>) {
+        self.allowed_ips = if allowed_ips.is_empty() {
+            default_allowed_ips()
+        } else {
+            allowed_ips
+        };
+    }
+
+    pub fn get_allowed_ips(&self) -> &[IpNet] {
+        &self.allowed_ips
+    }
+
     pub fn get_peer_endpoint(&self) -> Option<SocketAddr> {
         self.peer_endpoint
     }
@@ -74,54 +118,21 @@ impl WireGuardTunnel {
         self.listen_port
     }
 
-    pub fn get_local_private_key(&self) -> &str {
-        &self.local_private_key
+    /// Takes effect on the interface at the next `setup` or `reconfigure`.
+    pub fn set_local_private_key(&mut self, local_private_key: String) {
+        self.local_private_key = local_private_key;
     }
-}
 
-impl Tunnel for WireGuardTunnel {
-    fn is_connected(&self) -> Result<bool, Box<dyn std::error::Error>> {
-        if let Ok(device) = Device::get(
-            &InterfaceName::from_str(self.interface.as_str()).map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "failed to parse interface name",
-                )
-            })?,
-            if self.force_userspace {
-                Backend::Userspace
-            } else {
-                BACKEND
-            },
-        ) {
-            // check connected status by comparing last handshake time of the peer with current time. it should be 3 minutes (180 seconds) or less if the tunnel is active
-            if let Some(peer) = device
-                .peers
-                .iter()
-                .find(|p| p.config.public_key.to_base64() == self.peer_public_key)
-            {
-                if let Some(last_handshake) = peer.stats.last_handshake_time {
-                    return Ok(last_handshake.elapsed().unwrap_or_default().as_secs() <= 180);
-                }
-            }
-        }
-        Ok(false)
+    pub fn get_local_private_key(&self) -> &str {
+        &self.local_private_key
     }
 
-    async fn get_mtu(&self) -> Result<u32, Box<dyn std::error::Error>> {
-        crate::interface::get_mtu(self.interface.clone()).await
+    pub fn get_peer_public_key(&self) -> &str {
+        &self.peer_public_key
     }
 
-    async fn setup(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let ifname = InterfaceName::from_str(self.interface.as_str()).map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "failed to parse interface name",
-            )
-        })?;
-
-        self.interface = ifname.as_str_lossy().to_string();
-
+    /// Push keys, peer, endpoint and listen port to the interface, creating it if needed.
+    fn apply_config(&self, ifname: &InterfaceName) -> Result<(), Box<dyn std::error::Error>> {
         let mut device = DeviceUpdate::new();
 
         let mut peer_config = PeerConfigBuilder::new(
@@ -132,9 +143,14 @@ impl Tunnel for WireGuardTunnel {
                 )
             })?,
         )
-        .add_allowed_ip(IPV4_DEFAULT, 0)
-        .add_allowed_ip(IPV6_DEFAULT, 0)
-        .set_persistent_keepalive_interval(25);
+        // replace rather than add, so reconfigure can narrow the list
+        .replace_allowed_ips()
+        // always set it, so reconfigure can turn a previously enabled keepalive off
+        .set_persistent_keepalive_interval(self.keepalive.unwrap_or(0));
+
+        for allowed_ip in &self.allowed_ips {
+            peer_config = peer_config.add_allowed_ip(allowed_ip.addr(), allowed_ip.prefix_len());
+        }
 
         if let Some(endpoint) = &self.peer_endpoint {
             peer_config = peer_config.set_endpoint(endpoint.clone());
@@ -158,7 +174,7 @@ impl Tunnel for WireGuardTunnel {
                 )?,
             )
             .apply(
-                &ifname,
+                ifname,
                 if self.force_userspace {
                     Backend::Userspace
                 } else {
@@ -168,9 +184,12 @@ impl Tunnel for WireGuardTunnel {
 
         Ok(())
     }
+}
 
-    async fn destroy(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        Device::get(
+impl WireGuardTunnel {
+    /// Handshake time and byte counters of our peer, or `None` if the device or peer is missing.
+    pub fn peer_stats(&self) -> Result<Option<wireguard_control::PeerStats>, Box<dyn std::error::Error>> {
+        if let Ok(device) = Device::get(
             &InterfaceName::from_str(self.interface.as_str()).map_err(|_| {
                 io::Error::new(
                     io::ErrorKind::InvalidInput,
@@ -182,10 +201,83 @@ impl Tunnel for WireGuardTunnel {
             } else {
                 BACKEND
             },
-        )?
-        .delete()?;
+        ) {
+            return Ok(device
+                .peers
+                .into_iter()
+                .find(|p| p.config.public_key.to_base64() == self.peer_public_key)
+                .map(|p| p.stats));
+        }
+        Ok(None)
+    }
+}
 
-        Ok(())
+impl Tunnel for WireGuardTunnel {
+    fn is_connected(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        // the tunnel is active if the peer's last handshake is within HANDSHAKE_TIMEOUT
+        let last_handshake = self.peer_stats()?.and_then(|stats| stats.last_handshake_time);
+        Ok(last_handshake.is_some_and(|t| t.elapsed().unwrap_or_default() <= HANDSHAKE_TIMEOUT))
+    }
+
+    fn get_mtu(&self) -> BoxFuture<'_, Result<u32, Box<dyn std::error::Error>>> {
+        Box::pin(crate::interface::get_mtu(self.interface.clone()))
+    }
+
+    fn setup(&mut self) -> BoxFuture<'_, Result<(), Box<dyn std::error::Error>>> {
+        Box::pin(async move {
+            let ifname = InterfaceName::from_str(self.interface.as_str()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "failed to parse interface name",
+                )
+            })?;
+
+            self.interface = ifname.as_str_lossy().to_string();
+
+            self.apply_config(&ifname)
+        })
+    }
+
+    fn reconfigure(&mut self) -> BoxFuture<'_, Result<(), Box<dyn std::error::Error>>> {
+        Box::pin(async move {
+            if !self.is_ift_created() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "interface is not created",
+                )
+                .into());
+            }
+
+            let ifname = InterfaceName::from_str(self.interface.as_str()).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "failed to parse interface name",
+                )
+            })?;
+
+            self.apply_config(&ifname)
+        })
+    }
+
+    fn destroy(&mut self) -> BoxFuture<'_, Result<(), Box<dyn std::error::Error>>> {
+        Box::pin(async move {
+            Device::get(
+                &InterfaceName::from_str(self.interface.as_str()).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "failed to parse interface name",
+                    )
+                })?,
+                if self.force_userspace {
+                    Backend::Userspace
+                } else {
+                    BACKEND
+                },
+            )?
+            .delete()?;
+
+            Ok(())
+        })
     }
 
     fn is_ift_created(&self) -> bool {
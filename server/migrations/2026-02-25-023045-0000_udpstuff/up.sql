@@ -1,11 +1,3 @@
 -- Your SQL goes here
-ALTER TABLE `invites` ADD COLUMN `override_join_mesh` INTEGER;
-
-
-
-
-
-
-ALTER TABLE `wireguard_tunnels` ADD COLUMN `fec` BOOL NOT NULL;
-ALTER TABLE `wireguard_tunnels` ADD COLUMN `faketcp` BOOL NOT NULL;
-
+ALTER TABLE `wireguard_tunnels` ADD COLUMN `fec` BOOL NOT NULL DEFAULT 0;
+ALTER TABLE `wireguard_tunnels` ADD COLUMN `faketcp` BOOL NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE `invites` ADD COLUMN `revoked` BOOL NOT NULL DEFAULT 0;
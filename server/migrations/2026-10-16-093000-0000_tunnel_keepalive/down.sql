@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `wireguard_tunnels` DROP COLUMN `persistent_keepalive`;
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE `wireguard_tunnels` ADD COLUMN `persistent_keepalive` INTEGER DEFAULT 25;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `wireguard_tunnels` DROP COLUMN `allowed_ips_peer2`;
+ALTER TABLE `wireguard_tunnels` DROP COLUMN `allowed_ips_peer1`;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Comma-separated CIDRs each peer routes into the tunnel; NULL means 0.0.0.0/0 and ::/0.
+ALTER TABLE `wireguard_tunnels` ADD COLUMN `allowed_ips_peer1` TEXT;
+ALTER TABLE `wireguard_tunnels` ADD COLUMN `allowed_ips_peer2` TEXT;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- keep only the most recently updated row of each key, so the index can be created
+DELETE FROM `settings` WHERE EXISTS (
+	SELECT 1 FROM `settings` AS `newer`
+	WHERE `newer`.`key` = `settings`.`key`
+		AND (`newer`.`updated_at` > `settings`.`updated_at`
+			OR (`newer`.`updated_at` = `settings`.`updated_at` AND `newer`.`id` > `settings`.`id`))
+);
+CREATE UNIQUE INDEX `settings_key_unique` ON `settings`(`key`);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+CREATE UNIQUE INDEX `invites_code_unique` ON `invites`(`code`);
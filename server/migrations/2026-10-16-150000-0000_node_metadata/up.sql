@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE `node_metadata`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`node_id` INTEGER NOT NULL REFERENCES `nodes`(`id`),
+	`key` TEXT NOT NULL,
+	`value` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(`node_id`, `key`)
+);
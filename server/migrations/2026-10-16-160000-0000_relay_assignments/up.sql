@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE `relay_assignments`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`tunnel_id` INTEGER NOT NULL REFERENCES `wireguard_tunnels`(`id`),
+	`relay_node_id` INTEGER REFERENCES `nodes`(`id`),
+	`relay_endpoint` TEXT NOT NULL,
+	`token` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(`tunnel_id`)
+);
@@ -0,0 +1,78 @@
+-- This file should undo anything in `up.sql`
+-- Rebuild the tables with their previous definitions; rows are kept as they are.
+
+CREATE TABLE `wireguard_static_key_old`(
+	`node_id` INTEGER NOT NULL PRIMARY KEY,
+	`public_key` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO `wireguard_static_key_old` SELECT `node_id`, `public_key`, `created_at` FROM `wireguard_static_key`;
+
+CREATE TABLE `wireguard_tunnels_old`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`node_id_peer1` INTEGER NOT NULL,
+	`node_id_peer2` INTEGER NOT NULL,
+	`endpoint_peer1` TEXT,
+	`endpoint_peer2` TEXT,
+	`peer1_answered` SMALLINT NOT NULL DEFAULT 0,
+	`peer2_answered` SMALLINT NOT NULL DEFAULT 0,
+	`mtu` INTEGER NOT NULL DEFAULT 1280,
+	`endpoint_ipv6` BOOL NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`fec` BOOL NOT NULL DEFAULT 0,
+	`faketcp` BOOL NOT NULL DEFAULT 0,
+	`persistent_keepalive` INTEGER DEFAULT 25,
+	`allowed_ips_peer1` TEXT,
+	`allowed_ips_peer2` TEXT
+);
+INSERT INTO `wireguard_tunnels_old`
+	SELECT `id`, `node_id_peer1`, `node_id_peer2`, `endpoint_peer1`, `endpoint_peer2`,
+		`peer1_answered`, `peer2_answered`, `mtu`, `endpoint_ipv6`, `created_at`, `updated_at`,
+		`fec`, `faketcp`, `persistent_keepalive`, `allowed_ips_peer1`, `allowed_ips_peer2`
+	FROM `wireguard_tunnels`;
+
+CREATE TABLE `relay_assignments_old`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`tunnel_id` INTEGER NOT NULL REFERENCES `wireguard_tunnels_old`(`id`),
+	`relay_node_id` INTEGER REFERENCES `nodes`(`id`),
+	`relay_endpoint` TEXT NOT NULL,
+	`token` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(`tunnel_id`)
+);
+INSERT INTO `relay_assignments_old`
+	SELECT `id`, `tunnel_id`, `relay_node_id`, `relay_endpoint`, `token`, `created_at` FROM `relay_assignments`;
+
+CREATE TABLE `mesh_group_memberships_old`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`mesh_group_id` INTEGER NOT NULL,
+	`node_id` INTEGER NOT NULL,
+	`created_at` TIMESTAMP NOT NULL
+);
+INSERT INTO `mesh_group_memberships_old`
+	SELECT `id`, `mesh_group_id`, `node_id`, `created_at` FROM `mesh_group_memberships`;
+
+CREATE TABLE `node_metadata_old`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`node_id` INTEGER NOT NULL REFERENCES `nodes`(`id`),
+	`key` TEXT NOT NULL,
+	`value` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(`node_id`, `key`)
+);
+INSERT INTO `node_metadata_old`
+	SELECT `id`, `node_id`, `key`, `value`, `created_at`, `updated_at` FROM `node_metadata`;
+
+DROP TABLE `relay_assignments`;
+DROP TABLE `wireguard_tunnels`;
+DROP TABLE `wireguard_static_key`;
+DROP TABLE `mesh_group_memberships`;
+DROP TABLE `node_metadata`;
+
+ALTER TABLE `wireguard_tunnels_old` RENAME TO `wireguard_tunnels`;
+ALTER TABLE `relay_assignments_old` RENAME TO `relay_assignments`;
+ALTER TABLE `wireguard_static_key_old` RENAME TO `wireguard_static_key`;
+ALTER TABLE `mesh_group_memberships_old` RENAME TO `mesh_group_memberships`;
+ALTER TABLE `node_metadata_old` RENAME TO `node_metadata`;
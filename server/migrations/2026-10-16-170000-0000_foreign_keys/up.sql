@@ -0,0 +1,91 @@
+-- Your SQL goes here
+-- SQLite can't add constraints to existing tables, so rebuild every table that points at
+-- a node. Rows already pointing at deleted nodes are dropped on the way.
+-- New tables reference the `_new` tables they depend on; renaming those updates the references.
+
+CREATE TABLE `wireguard_static_key_new`(
+	`node_id` INTEGER NOT NULL PRIMARY KEY REFERENCES `nodes`(`id`) ON DELETE CASCADE,
+	`public_key` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO `wireguard_static_key_new`
+	SELECT `node_id`, `public_key`, `created_at` FROM `wireguard_static_key`
+	WHERE `node_id` IN (SELECT `id` FROM `nodes`);
+
+CREATE TABLE `wireguard_tunnels_new`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`node_id_peer1` INTEGER NOT NULL REFERENCES `nodes`(`id`) ON DELETE CASCADE,
+	`node_id_peer2` INTEGER NOT NULL REFERENCES `nodes`(`id`) ON DELETE CASCADE,
+	`endpoint_peer1` TEXT,
+	`endpoint_peer2` TEXT,
+	`peer1_answered` SMALLINT NOT NULL DEFAULT 0,
+	`peer2_answered` SMALLINT NOT NULL DEFAULT 0,
+	`mtu` INTEGER NOT NULL DEFAULT 1280,
+	`endpoint_ipv6` BOOL NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`fec` BOOL NOT NULL DEFAULT 0,
+	`faketcp` BOOL NOT NULL DEFAULT 0,
+	`persistent_keepalive` INTEGER DEFAULT 25,
+	`allowed_ips_peer1` TEXT,
+	`allowed_ips_peer2` TEXT
+);
+INSERT INTO `wireguard_tunnels_new`
+	SELECT `id`, `node_id_peer1`, `node_id_peer2`, `endpoint_peer1`, `endpoint_peer2`,
+		`peer1_answered`, `peer2_answered`, `mtu`, `endpoint_ipv6`, `created_at`, `updated_at`,
+		`fec`, `faketcp`, `persistent_keepalive`, `allowed_ips_peer1`, `allowed_ips_peer2`
+	FROM `wireguard_tunnels`
+	WHERE `node_id_peer1` IN (SELECT `id` FROM `nodes`)
+		AND `node_id_peer2` IN (SELECT `id` FROM `nodes`);
+
+CREATE TABLE `relay_assignments_new`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`tunnel_id` INTEGER NOT NULL REFERENCES `wireguard_tunnels_new`(`id`) ON DELETE CASCADE,
+	`relay_node_id` INTEGER REFERENCES `nodes`(`id`) ON DELETE CASCADE,
+	`relay_endpoint` TEXT NOT NULL,
+	`token` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(`tunnel_id`)
+);
+INSERT INTO `relay_assignments_new`
+	SELECT `id`, `tunnel_id`, `relay_node_id`, `relay_endpoint`, `token`, `created_at`
+	FROM `relay_assignments`
+	WHERE `tunnel_id` IN (SELECT `id` FROM `wireguard_tunnels_new`)
+		AND (`relay_node_id` IS NULL OR `relay_node_id` IN (SELECT `id` FROM `nodes`));
+
+CREATE TABLE `mesh_group_memberships_new`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`mesh_group_id` INTEGER NOT NULL REFERENCES `mesh_groups`(`id`) ON DELETE CASCADE,
+	`node_id` INTEGER NOT NULL REFERENCES `nodes`(`id`) ON DELETE CASCADE,
+	`created_at` TIMESTAMP NOT NULL
+);
+INSERT INTO `mesh_group_memberships_new`
+	SELECT `id`, `mesh_group_id`, `node_id`, `created_at` FROM `mesh_group_memberships`
+	WHERE `node_id` IN (SELECT `id` FROM `nodes`)
+		AND `mesh_group_id` IN (SELECT `id` FROM `mesh_groups`);
+
+CREATE TABLE `node_metadata_new`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`node_id` INTEGER NOT NULL REFERENCES `nodes`(`id`) ON DELETE CASCADE,
+	`key` TEXT NOT NULL,
+	`value` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(`node_id`, `key`)
+);
+INSERT INTO `node_metadata_new`
+	SELECT `id`, `node_id`, `key`, `value`, `created_at`, `updated_at` FROM `node_metadata`
+	WHERE `node_id` IN (SELECT `id` FROM `nodes`);
+
+-- children before parents, so no drop trips a constraint
+DROP TABLE `relay_assignments`;
+DROP TABLE `wireguard_tunnels`;
+DROP TABLE `wireguard_static_key`;
+DROP TABLE `mesh_group_memberships`;
+DROP TABLE `node_metadata`;
+
+ALTER TABLE `wireguard_tunnels_new` RENAME TO `wireguard_tunnels`;
+ALTER TABLE `relay_assignments_new` RENAME TO `relay_assignments`;
+ALTER TABLE `wireguard_static_key_new` RENAME TO `wireguard_static_key`;
+ALTER TABLE `mesh_group_memberships_new` RENAME TO `mesh_group_memberships`;
+ALTER TABLE `node_metadata_new` RENAME TO `node_metadata`;
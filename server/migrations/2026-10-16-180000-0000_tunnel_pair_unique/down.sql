@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX `wireguard_tunnels_pair_unique`;
@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- Drop self-tunnels and every tunnel but the oldest for a node pair and address family.
+DELETE FROM `relay_assignments` WHERE `tunnel_id` IN (
+	SELECT `id` FROM `wireguard_tunnels`
+	WHERE `node_id_peer1` = `node_id_peer2`
+		OR `id` NOT IN (
+			SELECT MIN(`id`) FROM `wireguard_tunnels`
+			GROUP BY min(`node_id_peer1`, `node_id_peer2`), max(`node_id_peer1`, `node_id_peer2`), `endpoint_ipv6`
+		)
+);
+DELETE FROM `wireguard_tunnels`
+WHERE `node_id_peer1` = `node_id_peer2`
+	OR `id` NOT IN (
+		SELECT MIN(`id`) FROM `wireguard_tunnels`
+		GROUP BY min(`node_id_peer1`, `node_id_peer2`), max(`node_id_peer1`, `node_id_peer2`), `endpoint_ipv6`
+	);
+
+-- Store the lower node ID as peer 1, swapping each peer's columns along with it.
+UPDATE `wireguard_tunnels` SET
+	`node_id_peer1` = `node_id_peer2`, `node_id_peer2` = `node_id_peer1`,
+	`endpoint_peer1` = `endpoint_peer2`, `endpoint_peer2` = `endpoint_peer1`,
+	`peer1_answered` = `peer2_answered`, `peer2_answered` = `peer1_answered`,
+	`allowed_ips_peer1` = `allowed_ips_peer2`, `allowed_ips_peer2` = `allowed_ips_peer1`
+WHERE `node_id_peer1` > `node_id_peer2`;
+
+-- Indexed on the unordered pair, so a row written in either order is still caught.
+CREATE UNIQUE INDEX `wireguard_tunnels_pair_unique` ON `wireguard_tunnels`(
+	min(`node_id_peer1`, `node_id_peer2`), max(`node_id_peer1`, `node_id_peer2`), `endpoint_ipv6`
+);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX `mesh_group_memberships_pair_unique`;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- join_mesh upserts on the pair, which needs a unique index to conflict on.
+DELETE FROM `mesh_group_memberships` WHERE `id` NOT IN (
+	SELECT MIN(`id`) FROM `mesh_group_memberships` GROUP BY `mesh_group_id`, `node_id`
+);
+CREATE UNIQUE INDEX `mesh_group_memberships_pair_unique` ON `mesh_group_memberships`(`mesh_group_id`, `node_id`);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE `node_overlay_addrs`;
+ALTER TABLE `mesh_groups` DROP COLUMN `mesh_subnet`;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE `mesh_groups` ADD COLUMN `mesh_subnet` TEXT;
+
+CREATE TABLE `node_overlay_addrs`(
+	`id` INTEGER NOT NULL PRIMARY KEY,
+	`mesh_group_id` INTEGER NOT NULL REFERENCES `mesh_groups`(`id`) ON DELETE CASCADE,
+	`node_id` INTEGER NOT NULL REFERENCES `nodes`(`id`) ON DELETE CASCADE,
+	`address` TEXT NOT NULL,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	UNIQUE(`mesh_group_id`, `node_id`),
+	UNIQUE(`mesh_group_id`, `address`)
+);
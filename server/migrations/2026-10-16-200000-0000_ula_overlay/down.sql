@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `mesh_groups` DROP COLUMN `ula_overlay`;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Members get `{mesh_subnet}::{node_id}`, with mesh_subnet a ULA /64 derived from the mesh.
+ALTER TABLE `mesh_groups` ADD COLUMN `ula_overlay` BOOL NOT NULL DEFAULT 0;
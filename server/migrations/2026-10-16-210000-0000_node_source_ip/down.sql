@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `nodes` DROP COLUMN `last_source_ip`;
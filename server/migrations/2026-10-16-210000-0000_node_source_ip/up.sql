@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- Address the node's latest authenticated request came from, for checking reported endpoints.
+ALTER TABLE `nodes` ADD COLUMN `last_source_ip` TEXT;
@@ -3,13 +3,104 @@ use crate::{
     models::{Invite, Node},
 };
 use diesel::prelude::*;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use uuid::Uuid;
 
-pub fn establish_connection() -> SqliteConnection {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    SqliteConnection::establish(&database_url)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
+/// Open a connection to `DATABASE_URL` with [`SqliteOptions::from_env`] applied; fails if the
+/// variable is unset, an option is invalid, or the database can't be opened.
+pub fn establish_connection() -> Result<SqliteConnection, Box<dyn std::error::Error + Send + Sync>> {
+    let database_url = env::var("DATABASE_URL").map_err(|_| "DATABASE_URL is not set")?;
+    let options = SqliteOptions::from_env()?;
+    let mut conn = SqliteConnection::establish(&database_url)
+        .map_err(|e| format!("cannot open database {}: {}", database_url, e))?;
+    options
+        .apply(&mut conn)
+        .map_err(|e| format!("cannot configure database {}: {}", database_url, e))?;
+    Ok(conn)
+}
+
+const JOURNAL_MODES: [&str; 6] = ["DELETE", "TRUNCATE", "PERSIST", "MEMORY", "WAL", "OFF"];
+
+/// Pragmas run on every new connection. WAL lets readers work alongside the single writer,
+/// and the busy timeout makes a second writer wait instead of failing with `SQLITE_BUSY`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqliteOptions {
+    pub journal_mode: String,
+    pub busy_timeout_ms: u32,
+    pub foreign_keys: bool,
+}
+
+impl Default for SqliteOptions {
+    fn default() -> Self {
+        SqliteOptions {
+            journal_mode: "WAL".to_string(),
+            busy_timeout_ms: 5000,
+            foreign_keys: true,
+        }
+    }
+}
+
+impl SqliteOptions {
+    /// Reads `SQLITE_JOURNAL_MODE`, `SQLITE_BUSY_TIMEOUT_MS` and `SQLITE_FOREIGN_KEYS`,
+    /// keeping the default for each one that is unset.
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let mut options = SqliteOptions::default();
+
+        if let Some(mode) = lookup("SQLITE_JOURNAL_MODE") {
+            let mode = mode.trim().to_ascii_uppercase();
+            if !JOURNAL_MODES.contains(&mode.as_str()) {
+                return Err(format!(
+                    "SQLITE_JOURNAL_MODE must be one of {}",
+                    JOURNAL_MODES.join(", ")
+                ));
+            }
+            options.journal_mode = mode;
+        }
+        if let Some(timeout) = lookup("SQLITE_BUSY_TIMEOUT_MS") {
+            options.busy_timeout_ms = timeout
+                .trim()
+                .parse()
+                .map_err(|_| "SQLITE_BUSY_TIMEOUT_MS must be a number of milliseconds".to_string())?;
+        }
+        if let Some(foreign_keys) = lookup("SQLITE_FOREIGN_KEYS") {
+            options.foreign_keys = match foreign_keys.trim().to_ascii_lowercase().as_str() {
+                "1" | "true" | "on" => true,
+                "0" | "false" | "off" => false,
+                _ => return Err("SQLITE_FOREIGN_KEYS must be on or off".to_string()),
+            };
+        }
+
+        Ok(options)
+    }
+
+    pub fn apply(&self, conn: &mut SqliteConnection) -> Result<(), diesel::result::Error> {
+        // busy_timeout first, so switching to WAL also waits out other connections
+        diesel::sql_query(format!("PRAGMA busy_timeout = {}", self.busy_timeout_ms)).execute(conn)?;
+        // journal_mode is checked against JOURNAL_MODES, so it is safe to splice in
+        diesel::sql_query(format!("PRAGMA journal_mode = {}", self.journal_mode)).execute(conn)?;
+        diesel::sql_query(format!(
+            "PRAGMA foreign_keys = {}",
+            if self.foreign_keys { "ON" } else { "OFF" }
+        ))
+        .execute(conn)?;
+        Ok(())
+    }
+}
+
+/// Cheapest query that proves the database is reachable.
+pub fn ping(conn: &mut SqliteConnection) -> Result<(), diesel::result::Error> {
+    diesel::sql_query("SELECT 1").execute(conn)?;
+    Ok(())
+}
+
+/// Node auth keys are canonical lowercase hyphenated UUIDs (see [`register_node`]).
+pub fn is_well_formed_auth_key(key: &str) -> bool {
+    Uuid::try_parse(key).is_ok_and(|parsed| parsed.hyphenated().to_string() == key)
 }
 
 pub fn authenticate(conn: &mut SqliteConnection, key: &str) -> Result<Node, diesel::result::Error> {
@@ -21,41 +112,66 @@ pub fn authenticate(conn: &mut SqliteConnection, key: &str) -> Result<Node, dies
         .first(conn)
 }
 
+/// Invites are the only registration gate, so codes carry 160 bits from the OS-seeded CSPRNG.
+fn generate_invite_code() -> String {
+    let bytes: [u8; 20] = rand::random();
+    base32::encode(base32::Alphabet::Crockford, &bytes)
+}
+
 pub fn create_invite_key(
     conn: &mut SqliteConnection,
     expires_at: Option<chrono::NaiveDateTime>,
     max_uses: Option<i32>,
     override_join_mesh: Option<i32>,
+    label: Option<&str>,
 ) -> Result<String, diesel::result::Error> {
     use crate::schema::invites;
 
-    let invite_code = Uuid::new_v4().to_string();
-
-    let new_invite = crate::models::NewInvite {
-        code: &invite_code,
-        expires_at,
-        max_uses,
-        override_join_mesh,
-    };
-
-    diesel::insert_into(invites::table)
-        .values(&new_invite)
-        .execute(conn)?;
+    const ATTEMPTS: usize = 3;
+
+    for attempt in 1..=ATTEMPTS {
+        let invite_code = generate_invite_code();
+
+        let new_invite = crate::models::NewInvite {
+            code: &invite_code,
+            expires_at,
+            max_uses,
+            override_join_mesh,
+            label,
+        };
+
+        // codes are unique in the database; a collision just means drawing again
+        match diesel::insert_into(invites::table)
+            .values(&new_invite)
+            .execute(conn)
+        {
+            Ok(_) => return Ok(invite_code),
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            )) if attempt < ATTEMPTS => continue,
+            Err(e) => return Err(e),
+        }
+    }
 
-    Ok(invite_code)
+    unreachable!("the last attempt always returns")
 }
 
+/// Create a node using an invite. Returns the node ID, its auth key, the invite's mesh
+/// override and the invite's remaining uses after this registration.
 pub fn register_node(
     conn: &mut SqliteConnection,
     node_name: &str,
     invitation_key: &str,
-) -> Result<(i32, String, Option<i32>), diesel::result::Error> {
+) -> Result<(i32, String, Option<i32>, Option<i32>), diesel::result::Error> {
     use crate::schema::invites::dsl::*;
     use crate::schema::nodes;
 
-    let inv = invites
+    let mut inv = invites
         .filter(code.eq(invitation_key))
-        .first::<Invite>(conn)?;
+        .filter(revoked.eq(false))
+        .select(Invite::as_select())
+        .first(conn)?;
 
     if let Some(max) = inv.max_uses {
         if inv.used_count >= max {
@@ -66,6 +182,7 @@ pub fn register_node(
     diesel::update(invites.filter(id.eq(inv.id)))
         .set(used_count.eq(used_count + 1))
         .execute(conn)?;
+    inv.used_count += 1;
 
     let nauthk = Uuid::new_v4().to_string();
 
@@ -78,7 +195,66 @@ pub fn register_node(
         .values(&new_node)
         .get_result::<crate::models::Node>(conn)?;
 
-    Ok((node.id, nauthk, inv.override_join_mesh))
+    Ok((node.id, nauthk, inv.override_join_mesh, inv.remaining_uses()))
+}
+
+pub fn revoke_invite(
+    conn: &mut SqliteConnection,
+    invite_id_val: i32,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::invites::dsl::*;
+
+    let updated = diesel::update(invites.filter(id.eq(invite_id_val)))
+        .set(revoked.eq(true))
+        .execute(conn)?;
+
+    if updated == 0 {
+        return Err(diesel::result::Error::NotFound);
+    }
+
+    Ok(())
+}
+
+/// Replace a node's auth key with a fresh one and return it; the old key stops
+/// authenticating at once. `NotFound` for an unknown node.
+pub fn rotate_node_auth_key(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+) -> Result<String, diesel::result::Error> {
+    let new_key = Uuid::new_v4().to_string();
+    set_node_auth_key(conn, node_id_val, &new_key)?;
+    Ok(new_key)
+}
+
+/// Replace a node's auth key with `new_key`, which the caller has checked with
+/// [`is_well_formed_auth_key`]. Refuses, with `RollbackTransaction`, a key any node
+/// already holds, including the node's current one.
+pub fn set_node_auth_key(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+    new_key: &str,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::nodes::dsl::*;
+
+    conn.transaction(|conn| {
+        let taken = nodes
+            .filter(auth_key.eq(new_key))
+            .count()
+            .get_result::<i64>(conn)?;
+        if taken > 0 {
+            return Err(diesel::result::Error::RollbackTransaction);
+        }
+
+        let updated = diesel::update(nodes.filter(id.eq(node_id_val)))
+            .set(auth_key.eq(new_key))
+            .execute(conn)?;
+
+        if updated == 0 {
+            return Err(diesel::result::Error::NotFound);
+        }
+
+        Ok(())
+    })
 }
 
 pub fn get_invites(
@@ -107,6 +283,55 @@ pub fn update_node_name(
     Ok(())
 }
 
+/// Delete a node. Its key, tunnels (and their relays), mesh memberships and metadata go
+/// with it through `ON DELETE CASCADE`, which needs `foreign_keys` on (see [`SqliteOptions`]).
+/// `NotFound` for an unknown node.
+pub fn delete_node(conn: &mut SqliteConnection, node_id_val: i32) -> Result<(), diesel::result::Error> {
+    use crate::schema::nodes::dsl::*;
+
+    let deleted = diesel::delete(nodes.filter(id.eq(node_id_val))).execute(conn)?;
+    if deleted == 0 {
+        return Err(diesel::result::Error::NotFound);
+    }
+
+    Ok(())
+}
+
+/// `last_seen` is only rewritten once it is this old, so polling doesn't cost a write per request.
+const LAST_SEEN_RESOLUTION: chrono::TimeDelta = chrono::TimeDelta::seconds(15);
+
+/// Record that a node just made an authenticated request, from `source_ip` if known. A new
+/// source address is stored right away, regardless of `LAST_SEEN_RESOLUTION`.
+pub fn touch_last_seen(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+    source_ip: Option<std::net::IpAddr>,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::nodes::dsl::*;
+
+    let now = chrono::Utc::now().naive_utc();
+    let stale = last_seen.is_null().or(last_seen.lt(now - LAST_SEEN_RESOLUTION));
+    match source_ip {
+        Some(ip) => {
+            let ip = ip.to_canonical().to_string();
+            diesel::update(
+                nodes
+                    .filter(id.eq(node_id_val))
+                    .filter(stale.or(last_source_ip.is_null()).or(last_source_ip.ne(&ip))),
+            )
+            .set((last_seen.eq(now), last_source_ip.eq(&ip)))
+            .execute(conn)?;
+        }
+        None => {
+            diesel::update(nodes.filter(id.eq(node_id_val)).filter(stale))
+                .set(last_seen.eq(now))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
 pub fn get_server_side_node_info(
     conn: &mut SqliteConnection,
     node_id_val: i32,
@@ -129,6 +354,113 @@ pub fn get_node_list(
         .load::<crate::models::Node>(conn)
 }
 
+/// Which nodes [`get_filtered_node_list`] returns; unset fields don't filter.
+#[derive(Default)]
+pub struct NodeFilter<'a> {
+    pub name_contains: Option<&'a str>,
+    pub mesh_group_id: Option<i32>,
+    /// `(online, cutoff)`: keep nodes seen at or after `cutoff` if `online`, the rest otherwise.
+    pub online: Option<(bool, chrono::NaiveDateTime)>,
+}
+
+pub fn get_filtered_node_list(
+    conn: &mut SqliteConnection,
+    filter: &NodeFilter,
+) -> Result<Vec<crate::models::Node>, diesel::result::Error> {
+    use crate::schema::mesh_group_memberships;
+    use crate::schema::nodes::dsl::*;
+
+    let mut query = nodes.select(crate::models::Node::as_select()).into_boxed();
+
+    if let Some(part) = filter.name_contains {
+        let escaped = part.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        query = query.filter(name.like(format!("%{}%", escaped)).escape('\\'));
+    }
+    if let Some(mesh_id_val) = filter.mesh_group_id {
+        query = query.filter(
+            id.eq_any(
+                mesh_group_memberships::table
+                    .filter(mesh_group_memberships::mesh_group_id.eq(mesh_id_val))
+                    .select(mesh_group_memberships::node_id),
+            ),
+        );
+    }
+    match filter.online {
+        Some((true, cutoff)) => query = query.filter(last_seen.ge(cutoff)),
+        Some((false, cutoff)) => query = query.filter(last_seen.is_null().or(last_seen.lt(cutoff))),
+        None => {}
+    }
+
+    query.order(id.asc()).load(conn)
+}
+
+pub fn get_node_metadata(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+) -> Result<BTreeMap<String, String>, diesel::result::Error> {
+    use crate::schema::node_metadata::dsl::*;
+
+    let entries = node_metadata
+        .filter(node_id.eq(node_id_val))
+        .select((key, value))
+        .load::<(String, String)>(conn)?;
+
+    Ok(entries.into_iter().collect())
+}
+
+/// Metadata of every node that has any, keyed by node id.
+pub fn get_all_node_metadata(
+    conn: &mut SqliteConnection,
+) -> Result<HashMap<i32, BTreeMap<String, String>>, diesel::result::Error> {
+    use crate::schema::node_metadata::dsl::*;
+
+    let entries = node_metadata
+        .select((node_id, key, value))
+        .load::<(i32, String, String)>(conn)?;
+
+    let mut result: HashMap<i32, BTreeMap<String, String>> = HashMap::new();
+    for (nid, k, v) in entries {
+        result.entry(nid).or_default().insert(k, v);
+    }
+
+    Ok(result)
+}
+
+/// Set or, with `None`, remove one metadata entry. Callers validate key and value.
+pub fn set_node_metadata(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+    key_val: &str,
+    value_val: Option<&str>,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::node_metadata;
+    use crate::schema::node_metadata::dsl::*;
+
+    let Some(value_val) = value_val else {
+        diesel::delete(node_metadata.filter(node_id.eq(node_id_val)).filter(key.eq(key_val)))
+            .execute(conn)?;
+        return Ok(());
+    };
+
+    let new_entry = crate::models::NewNodeMetadata {
+        node_id: node_id_val,
+        key: key_val,
+        value: value_val,
+    };
+
+    diesel::insert_into(node_metadata::table)
+        .values(&new_entry)
+        .on_conflict((node_id, key))
+        .do_update()
+        .set((
+            value.eq(value_val),
+            updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .execute(conn)?;
+
+    Ok(())
+}
+
 pub fn update_wireguard_pubkey(
     conn: &mut SqliteConnection,
     node_id_val: i32,
@@ -142,12 +474,24 @@ pub fn update_wireguard_pubkey(
         public_key: pubkey,
     };
 
+    let previous = wireguard_static_key
+        .filter(node_id.eq(node_id_val))
+        .select(public_key)
+        .first::<String>(conn)
+        .optional()?;
+
     diesel::insert_into(wireguard_static_key::table)
         .values(&new_pk)
         .on_conflict(node_id)
         .do_update()
         .set(public_key.eq(pubkey))
         .execute(conn)?;
+
+    // peers see this key in their tunnel list, so a change (or the first upload, which fills
+    // in the empty key they were served) must move its `updated_at`
+    if previous.as_deref() != Some(pubkey) {
+        touch_node_tunnels(conn, node_id_val)?;
+    }
     Ok(())
 }
 
@@ -165,44 +509,110 @@ pub fn get_wireguard_pubkey(
     Ok(key_record)
 }
 
+diesel::define_sql_function! {
+    fn last_insert_rowid() -> Integer;
+}
+
+pub fn get_tunnel_between(
+    conn: &mut SqliteConnection,
+    peer1_id: i32,
+    peer2_id: i32,
+    endpoint_should_be_ipv6: bool,
+) -> Result<Option<crate::models::WireguardTunnel>, diesel::result::Error> {
+    use crate::schema::wireguard_tunnels::dsl::*;
+
+    wireguard_tunnels
+        .filter(
+            ((node_id_peer1.eq(peer1_id).and(node_id_peer2.eq(peer2_id)))
+                .or(node_id_peer1.eq(peer2_id).and(node_id_peer2.eq(peer1_id))))
+            .and(endpoint_ipv6.eq(endpoint_should_be_ipv6)),
+        )
+        .select(crate::models::WireguardTunnel::as_select())
+        .first::<crate::models::WireguardTunnel>(conn)
+        .optional()
+}
+
+/// Create a tunnel between two nodes and return its ID. The lower node ID is stored as
+/// peer 1, so (A, B) and (B, A) are the same tunnel. A tunnel from a node to itself is a
+/// `QueryBuilderError`; a second tunnel for the same pair and address family is a
+/// `UniqueViolation`, whether caught here or by the `wireguard_tunnels_pair_unique` index.
 pub fn create_wireguard_tunnel(
     conn: &mut SqliteConnection,
     peer1_id: i32,
     peer2_id: i32,
     mtu_val: i32,
     endpoint_should_be_ipv6: bool,
-) -> Result<(), diesel::result::Error> {
+) -> Result<i32, diesel::result::Error> {
     use crate::schema::wireguard_tunnels;
+    use diesel::result::{DatabaseErrorKind, Error};
+
+    if peer1_id == peer2_id {
+        return Err(Error::QueryBuilderError(
+            format!("cannot create a tunnel from node {} to itself", peer1_id).into(),
+        ));
+    }
 
-    // guard pair peer1-peer2 and ipv6 uniqueness
-    use crate::schema::wireguard_tunnels::dsl as wgt_dsl;
+    if !cat4igp_shared::limits::is_valid_tunnel_mtu(mtu_val) {
+        return Err(Error::QueryBuilderError(
+            format!("MTU {} is out of range", mtu_val).into(),
+        ));
+    }
 
-    let existing_tunnel = wgt_dsl::wireguard_tunnels
-        .filter(
-            ((wgt_dsl::node_id_peer1.eq(peer1_id).and(wgt_dsl::node_id_peer2.eq(peer2_id)))
-                .or(wgt_dsl::node_id_peer1.eq(peer2_id).and(wgt_dsl::node_id_peer2.eq(peer1_id))))
-            .and(wgt_dsl::endpoint_ipv6.eq(endpoint_should_be_ipv6)),
+    let duplicate = |id: i32| {
+        Error::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            Box::new(format!("tunnel {} already connects these nodes", id)),
         )
-        .first::<crate::models::WireguardTunnel>(conn)
-        .optional()?;
-
-    if existing_tunnel.is_some() {
-        return Err(diesel::result::Error::NotFound);
+    };
+    if let Some(existing) = get_tunnel_between(conn, peer1_id, peer2_id, endpoint_should_be_ipv6)? {
+        return Err(duplicate(existing.id));
     }
 
     let new_tunnel = crate::models::NewWireguardTunnel {
-        node_id_peer1: peer1_id,
-        node_id_peer2: peer2_id,
+        node_id_peer1: peer1_id.min(peer2_id),
+        node_id_peer2: peer1_id.max(peer2_id),
         endpoint_peer1: None,
         endpoint_peer2: None,
         mtu: mtu_val,
         endpoint_ipv6: endpoint_should_be_ipv6,
     };
 
-    diesel::insert_into(wireguard_tunnels::table)
+    let inserted = diesel::insert_into(wireguard_tunnels::table)
         .values(&new_tunnel)
+        .execute(conn);
+
+    // lost a race with a concurrent insert of the same pair
+    if let Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) = inserted
+        && let Some(existing) = get_tunnel_between(conn, peer1_id, peer2_id, endpoint_should_be_ipv6)?
+    {
+        return Err(duplicate(existing.id));
+    }
+    inserted?;
+
+    // last_insert_rowid() is scoped to this connection, so it refers to the row above
+    diesel::select(last_insert_rowid()).get_result::<i32>(conn)
+}
+
+pub fn set_tunnel_allowed_ips(
+    conn: &mut SqliteConnection,
+    tunnel_id_val: i32,
+    allowed_ips_peer1_val: Option<String>,
+    allowed_ips_peer2_val: Option<String>,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::wireguard_tunnels::dsl::*;
+
+    let updated = diesel::update(wireguard_tunnels.filter(id.eq(tunnel_id_val)))
+        .set((
+            allowed_ips_peer1.eq(allowed_ips_peer1_val),
+            allowed_ips_peer2.eq(allowed_ips_peer2_val),
+            updated_at.eq(chrono::Utc::now().naive_utc()),
+        ))
         .execute(conn)?;
 
+    if updated == 0 {
+        return Err(diesel::result::Error::NotFound);
+    }
+
     Ok(())
 }
 
@@ -220,62 +630,170 @@ pub fn get_wireguard_answers(
     Ok(results)
 }
 
+/// Latest `updated_at` among the node's tunnels, plus how many there are (so a removed
+/// tunnel changes the result too). Cheap enough to build an ETag from on every poll.
+pub fn get_tunnels_max_updated_at(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+) -> Result<(Option<chrono::NaiveDateTime>, i64), diesel::result::Error> {
+    use crate::schema::wireguard_tunnels::dsl::*;
+    use diesel::dsl::{count_star, max};
+
+    wireguard_tunnels
+        .filter((node_id_peer1.eq(node_id_val)).or(node_id_peer2.eq(node_id_val)))
+        .select((max(updated_at), count_star()))
+        .first(conn)
+}
+
+pub fn get_all_tunnels(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<crate::models::WireguardTunnel>, diesel::result::Error> {
+    use crate::schema::wireguard_tunnels::dsl::*;
+
+    wireguard_tunnels
+        .order(id.asc())
+        .select(crate::models::WireguardTunnel::as_select())
+        .load::<crate::models::WireguardTunnel>(conn)
+}
+
+pub fn get_tunnel(
+    conn: &mut SqliteConnection,
+    tunnel_id_val: i32,
+) -> Result<crate::models::WireguardTunnel, diesel::result::Error> {
+    use crate::schema::wireguard_tunnels::dsl::*;
+
+    wireguard_tunnels
+        .filter(id.eq(tunnel_id_val))
+        .select(crate::models::WireguardTunnel::as_select())
+        .first::<crate::models::WireguardTunnel>(conn)
+}
+
+/// Record a node's answer and endpoint on its own side of a tunnel. `NotFound` if the
+/// tunnel doesn't exist or the node isn't one of its peers.
 pub fn answer_wireguard_tunnel(
     conn: &mut SqliteConnection,
     tunnel_id_val: i32,
     node_id_val: i32,
     endpoint: Option<String>,
-    decline_type: Option<i16>,
+    decline_type: Option<ext::WireguardAnswered>,
 ) -> Result<(), diesel::result::Error> {
     use crate::schema::wireguard_tunnels::dsl::*;
 
     let target = wireguard_tunnels.filter(id.eq(tunnel_id_val));
+    let answer = i16::from(decline_type.unwrap_or(ext::WireguardAnswered::Answered));
 
-    if target
-        .filter(node_id_peer1.eq(node_id_val))
-        .first::<crate::models::WireguardTunnel>(conn)
-        .is_ok()
-    {
-        if let Some(decline) = decline_type {
-            diesel::update(target)
-                .set((
-                    peer1_answered.eq(decline),
-                    endpoint_peer1.eq(endpoint),
-                    updated_at.eq(chrono::Utc::now().naive_utc()),
-                ))
-                .execute(conn)?;
-        } else {
-            diesel::update(target)
-                .set((
-                    peer1_answered.eq(ext::WireguardAnswered::Answered as i16),
-                    endpoint_peer1.eq(endpoint),
-                    updated_at.eq(chrono::Utc::now().naive_utc()),
-                ))
-                .execute(conn)?;
-        }
+    // a node only ever answers for its own side, so it can't plant an endpoint for a
+    // tunnel it isn't part of
+    let (peer1, peer2) = target.select((node_id_peer1, node_id_peer2)).first::<(i32, i32)>(conn)?;
+    if node_id_val != peer1 && node_id_val != peer2 {
+        return Err(diesel::result::Error::NotFound);
+    }
+
+    if node_id_val == peer1 {
+        diesel::update(target)
+            .set((
+                peer1_answered.eq(answer),
+                endpoint_peer1.eq(endpoint),
+                updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
     } else {
-        if let Some(decline) = decline_type {
-            diesel::update(target)
-                .set((
-                    peer2_answered.eq(decline),
-                    endpoint_peer2.eq(endpoint),
-                    updated_at.eq(chrono::Utc::now().naive_utc()),
-                ))
-                .execute(conn)?;
-        } else {
-            diesel::update(target)
-                .set((
-                    peer2_answered.eq(ext::WireguardAnswered::Answered as i16),
-                    endpoint_peer2.eq(endpoint),
-                    updated_at.eq(chrono::Utc::now().naive_utc()),
-                ))
-                .execute(conn)?;
-        }
+        diesel::update(target)
+            .set((
+                peer2_answered.eq(answer),
+                endpoint_peer2.eq(endpoint),
+                updated_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .execute(conn)?;
     }
 
     Ok(())
 }
 
+/// Node metadata key that designates a node as a relay, valued `host:port`. Only operators
+/// may set it; see `router::metadata`.
+pub const RELAY_ENDPOINT_KEY: &str = "relay_endpoint";
+
+/// Assign a relay to a tunnel whose peers cannot connect directly. Prefers a node an operator
+/// designated with [`RELAY_ENDPOINT_KEY`] metadata (never one of the two peers), then the
+/// controller's relay from the `relay_endpoint` setting. `None` when neither exists.
+/// Offering twice returns the existing assignment.
+pub fn offer_relay(
+    conn: &mut SqliteConnection,
+    tunnel_id_val: i32,
+) -> Result<Option<crate::models::RelayAssignment>, diesel::result::Error> {
+    use crate::schema::relay_assignments::dsl::*;
+    use crate::schema::node_metadata::dsl as md;
+
+    conn.transaction(|conn| {
+        let existing = relay_assignments
+            .filter(tunnel_id.eq(tunnel_id_val))
+            .select(crate::models::RelayAssignment::as_select())
+            .first(conn)
+            .optional()?;
+        if existing.is_some() {
+            return Ok(existing);
+        }
+
+        let tunnel = get_tunnel(conn, tunnel_id_val)?;
+        let relay_node = md::node_metadata
+            .filter(md::key.eq(RELAY_ENDPOINT_KEY))
+            .filter(md::node_id.ne(tunnel.node_id_peer1))
+            .filter(md::node_id.ne(tunnel.node_id_peer2))
+            .order(md::node_id.asc())
+            .select((md::node_id, md::value))
+            .first::<(i32, String)>(conn)
+            .optional()?;
+
+        let (relay_node, endpoint) = match relay_node {
+            Some((relay_node, endpoint)) => (Some(relay_node), endpoint),
+            None => match get_setting(conn, RELAY_ENDPOINT_KEY)? {
+                Some(endpoint) => (None, endpoint),
+                None => return Ok(None),
+            },
+        };
+
+        let new_token = Uuid::new_v4().to_string();
+        let assignment = diesel::insert_into(relay_assignments)
+            .values(&crate::models::NewRelayAssignment {
+                tunnel_id: tunnel_id_val,
+                relay_node_id: relay_node,
+                relay_endpoint: &endpoint,
+                token: &new_token,
+            })
+            .returning(crate::models::RelayAssignment::as_returning())
+            .get_result(conn)?;
+
+        Ok(Some(assignment))
+    })
+}
+
+/// Relay assignments a node takes part in, as one of the peers or as the relay.
+pub fn get_relay_assignments(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+) -> Result<Vec<(crate::models::RelayAssignment, crate::models::WireguardTunnel)>, diesel::result::Error> {
+    use crate::schema::relay_assignments::dsl::*;
+    use crate::schema::wireguard_tunnels::dsl as wt;
+
+    let assignments = relay_assignments
+        .inner_join(wt::wireguard_tunnels.on(wt::id.eq(tunnel_id)))
+        .filter(
+            wt::node_id_peer1
+                .eq(node_id_val)
+                .or(wt::node_id_peer2.eq(node_id_val))
+                .or(relay_node_id.eq(node_id_val)),
+        )
+        .order(id.asc())
+        .select((
+            crate::models::RelayAssignment::as_select(),
+            crate::models::WireguardTunnel::as_select(),
+        ))
+        .load(conn)?;
+
+    Ok(assignments)
+}
+
 pub fn get_mesh_members(
     conn: &mut SqliteConnection,
     mesh_id_val: i32,
@@ -286,12 +804,48 @@ pub fn get_mesh_members(
     let results = mm_dsl::mesh_group_memberships
         .inner_join(nodes_dsl::nodes.on(mm_dsl::node_id.eq(nodes_dsl::id)))
         .filter(mm_dsl::mesh_group_id.eq(mesh_id_val))
+        .order(nodes_dsl::id.asc())
         .select(crate::models::Node::as_select())
         .load::<crate::models::Node>(conn)?;
 
     Ok(results)
 }
 
+pub fn get_meshes(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<crate::models::MeshGroup>, diesel::result::Error> {
+    use crate::schema::mesh_groups::dsl::*;
+
+    mesh_groups
+        .order(id.asc())
+        .select(crate::models::MeshGroup::as_select())
+        .load(conn)
+}
+
+pub fn get_mesh(
+    conn: &mut SqliteConnection,
+    mesh_id_val: i32,
+) -> Result<crate::models::MeshGroup, diesel::result::Error> {
+    use crate::schema::mesh_groups::dsl::*;
+
+    mesh_groups
+        .filter(id.eq(mesh_id_val))
+        .select(crate::models::MeshGroup::as_select())
+        .first(conn)
+}
+
+/// Member count of every mesh that has members; meshes without any are absent.
+pub fn get_mesh_member_counts(conn: &mut SqliteConnection) -> Result<HashMap<i32, usize>, diesel::result::Error> {
+    use crate::schema::mesh_group_memberships::dsl::*;
+
+    let counts = mesh_group_memberships
+        .group_by(mesh_group_id)
+        .select((mesh_group_id, diesel::dsl::count_star()))
+        .load::<(i32, i64)>(conn)?;
+
+    Ok(counts.into_iter().map(|(mesh, count)| (mesh, count as usize)).collect())
+}
+
 pub fn get_joined_meshes(
     conn: &mut SqliteConnection,
     node_id_val: i32,
@@ -302,6 +856,7 @@ pub fn get_joined_meshes(
     let results = mm_dsl::mesh_group_memberships
         .filter(mm_dsl::node_id.eq(node_id_val))
         .inner_join(mg_dsl::mesh_groups.on(mm_dsl::mesh_group_id.eq(mg_dsl::id)))
+        .order(mg_dsl::id.asc())
         .select(crate::models::MeshGroup::as_select())
         .load::<crate::models::MeshGroup>(conn)?;
 
@@ -312,7 +867,7 @@ pub fn join_mesh(
     conn: &mut SqliteConnection,
     node_id_val: i32,
     mesh_id_val: i32,
-) -> Result<(), diesel::result::Error> {
+) -> Result<Vec<i32>, diesel::result::Error> {
     use crate::schema::mesh_group_memberships;
     use crate::schema::mesh_group_memberships::dsl as mgm_dsl;
     use crate::schema::mesh_groups::dsl as mg_dsl;
@@ -326,19 +881,27 @@ pub fn join_mesh(
         return Err(diesel::result::Error::NotFound);
     }
 
-    let new_membership = crate::models::NewMeshGroupMembership {
-        mesh_group_id: mesh_id_val,
-        node_id: node_id_val,
-    };
+    // together, so a concurrent join can't take the address between the two, and before the
+    // membership so a full subnet refuses the join instead of leaving a member without an address
+    conn.transaction(|conn| {
+        allocate_overlay_address(conn, node_id_val, mesh_id_val)?;
 
-    diesel::insert_into(mesh_group_memberships::table)
-        .values(&new_membership)
-        .on_conflict((mgm_dsl::mesh_group_id, mgm_dsl::node_id))
-        .do_nothing()
-        .execute(conn)?;
+        let new_membership = crate::models::NewMeshGroupMembership {
+            mesh_group_id: mesh_id_val,
+            node_id: node_id_val,
+            created_at: chrono::Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(mesh_group_memberships::table)
+            .values(&new_membership)
+            .on_conflict((mgm_dsl::mesh_group_id, mgm_dsl::node_id))
+            .do_nothing()
+            .execute(conn)
+    })?;
 
     // should be safe to unwrap here
     let mesh = mesh_exists.unwrap();
+    let mut created_tunnels = Vec::new();
 
     if mesh.auto_wireguard {
         let peer_nodes = get_mesh_members(conn, mesh_id_val)?;
@@ -347,66 +910,273 @@ pub fn join_mesh(
             if peer.id != node_id_val {
                 // create wireguard tunnel for both ipv4 and ipv6 channel
                 // we do not care about errors here, as the tunnel may already exist
-                let _ = create_wireguard_tunnel(
-                    conn,
-                    node_id_val,
-                    peer.id,
-                    mesh.auto_wireguard_mtu,
-                    false,
-                );
-
-                let _ = create_wireguard_tunnel(
-                    conn,
-                    node_id_val,
-                    peer.id,
-                    mesh.auto_wireguard_mtu,
-                    true,
-                );
+                for ipv6 in [false, true] {
+                    if let Ok(tunnel_id) = create_wireguard_tunnel(
+                        conn,
+                        node_id_val,
+                        peer.id,
+                        mesh.auto_wireguard_mtu,
+                        ipv6,
+                    ) {
+                        created_tunnels.push(tunnel_id);
+                    }
+                }
             }
         }
     }
 
-    Ok(())
+    Ok(created_tunnels)
 }
 
-pub fn leave_mesh(
+/// Remove a node from a mesh, releasing its overlay address there. For an auto-WireGuard
+/// mesh, the tunnels between the node and the remaining members are deleted too, except
+/// towards members it still shares another auto-WireGuard mesh with. Returns the deleted
+/// tunnel IDs; `NotFound` when the node is not a member.
+pub fn remove_node_from_mesh(
+    conn: &mut SqliteConnection,
+    mesh_id_val: i32,
+    node_id_val: i32,
+) -> Result<Vec<i32>, diesel::result::Error> {
+    use crate::schema::mesh_group_memberships::dsl::*;
+    use crate::schema::mesh_groups::dsl as mg_dsl;
+    use crate::schema::relay_assignments::dsl as ra_dsl;
+    use crate::schema::wireguard_tunnels::dsl as wt_dsl;
+
+    conn.transaction(|conn| {
+        let removed = diesel::delete(
+            mesh_group_memberships
+                .filter(node_id.eq(node_id_val))
+                .filter(mesh_group_id.eq(mesh_id_val)),
+        )
+        .execute(conn)?;
+        if removed == 0 {
+            return Err(diesel::result::Error::NotFound);
+        }
+        release_overlay_address(conn, node_id_val, mesh_id_val)?;
+
+        let auto_wireguard = mg_dsl::mesh_groups
+            .filter(mg_dsl::id.eq(mesh_id_val))
+            .select(mg_dsl::auto_wireguard)
+            .first::<bool>(conn)?;
+        if !auto_wireguard {
+            return Ok(Vec::new());
+        }
+
+        // peers still reached through another auto-WireGuard mesh keep their tunnels
+        let other_meshes = get_joined_meshes(conn, node_id_val)?
+            .into_iter()
+            .filter(|mesh| mesh.auto_wireguard)
+            .map(|mesh| mesh.id)
+            .collect::<Vec<_>>();
+        let still_shared = mesh_group_memberships
+            .filter(mesh_group_id.eq_any(&other_meshes))
+            .select(node_id)
+            .load::<i32>(conn)?;
+        let peers = get_mesh_members(conn, mesh_id_val)?
+            .into_iter()
+            .map(|node| node.id)
+            .filter(|peer| !still_shared.contains(peer))
+            .collect::<Vec<_>>();
+
+        let tunnel_ids = wt_dsl::wireguard_tunnels
+            .filter(
+                (wt_dsl::node_id_peer1.eq(node_id_val).and(wt_dsl::node_id_peer2.eq_any(&peers)))
+                    .or(wt_dsl::node_id_peer2.eq(node_id_val).and(wt_dsl::node_id_peer1.eq_any(&peers))),
+            )
+            .order(wt_dsl::id.asc())
+            .select(wt_dsl::id)
+            .load::<i32>(conn)?;
+        // explicit rather than through ON DELETE CASCADE, which is off without foreign_keys
+        diesel::delete(ra_dsl::relay_assignments.filter(ra_dsl::tunnel_id.eq_any(&tunnel_ids))).execute(conn)?;
+        diesel::delete(wt_dsl::wireguard_tunnels.filter(wt_dsl::id.eq_any(&tunnel_ids))).execute(conn)?;
+
+        Ok(tunnel_ids)
+    })
+}
+
+/// The node's overlay address in a mesh, allocating the lowest free host address of the
+/// mesh subnet the first time. `None` when the mesh has no subnet, `NotFound` for an unknown
+/// mesh, and a `QueryBuilderError` once the subnet is exhausted. Allocating bumps the node's
+/// tunnels so their ETag changes and clients pick up the address.
+pub fn allocate_overlay_address(
+    conn: &mut SqliteConnection,
+    node_id_val: i32,
+    mesh_id_val: i32,
+) -> Result<Option<std::net::IpAddr>, diesel::result::Error> {
+    use crate::schema::mesh_groups::dsl as mg_dsl;
+    use crate::schema::node_overlay_addrs;
+    use crate::schema::node_overlay_addrs::dsl::*;
+    use diesel::result::Error;
+
+    let (subnet, ula) = mg_dsl::mesh_groups
+        .filter(mg_dsl::id.eq(mesh_id_val))
+        .select((mg_dsl::mesh_subnet, mg_dsl::ula_overlay))
+        .first::<(Option<String>, bool)>(conn)?;
+    let Some(subnet) = subnet else {
+        return Ok(None);
+    };
+    let subnet: ipnet::IpNet = subnet.parse().map_err(|e| {
+        Error::QueryBuilderError(format!("mesh {} has an invalid subnet {}: {}", mesh_id_val, subnet, e).into())
+    })?;
+
+    let assigned = node_overlay_addrs
+        .filter(mesh_group_id.eq(mesh_id_val))
+        .select((node_id, address))
+        .load::<(i32, String)>(conn)?;
+    if let Some((_, existing)) = assigned.iter().find(|(node, _)| *node == node_id_val)
+        && let Ok(existing) = existing.parse()
+    {
+        return Ok(Some(existing));
+    }
+
+    let free = match subnet {
+        // derived from the node ID, which no other member has
+        ipnet::IpNet::V6(prefix) if ula => {
+            std::net::IpAddr::V6(cat4igp_shared::addressing::ula_node_address(prefix.network(), node_id_val))
+        }
+        _ => {
+            let taken: std::collections::HashSet<std::net::IpAddr> =
+                assigned.iter().filter_map(|(_, addr)| addr.parse().ok()).collect();
+            // IPv4 hosts() already skips the network and broadcast addresses; skip IPv6's
+            // subnet-router anycast address too
+            subnet
+                .hosts()
+                .filter(|addr| *addr != subnet.network() || subnet.prefix_len() == subnet.max_prefix_len())
+                .find(|addr| !taken.contains(addr))
+                .ok_or_else(|| {
+                    Error::QueryBuilderError(format!("mesh subnet {} has no free overlay address", subnet).into())
+                })?
+        }
+    };
+
+    diesel::insert_into(node_overlay_addrs::table)
+        .values(&crate::models::NewNodeOverlayAddr {
+            mesh_group_id: mesh_id_val,
+            node_id: node_id_val,
+            address: &free.to_string(),
+        })
+        .execute(conn)?;
+    touch_node_tunnels(conn, node_id_val)?;
+
+    Ok(Some(free))
+}
+
+/// Free the node's overlay address in a mesh, if it has one.
+pub fn release_overlay_address(
     conn: &mut SqliteConnection,
     node_id_val: i32,
     mesh_id_val: i32,
 ) -> Result<(), diesel::result::Error> {
-    use crate::schema::mesh_group_memberships::dsl::*;
+    use crate::schema::node_overlay_addrs::dsl::*;
 
-    diesel::delete(
-        mesh_group_memberships
+    let deleted = diesel::delete(
+        node_overlay_addrs
             .filter(node_id.eq(node_id_val))
             .filter(mesh_group_id.eq(mesh_id_val)),
     )
     .execute(conn)?;
+    if deleted > 0 {
+        touch_node_tunnels(conn, node_id_val)?;
+    }
 
     Ok(())
 }
 
+/// Overlay addresses of two nodes in the lowest-numbered mesh where both have one, as
+/// `(node_a's, node_b's)`.
+pub fn get_shared_overlay_addresses(
+    conn: &mut SqliteConnection,
+    node_a: i32,
+    node_b: i32,
+) -> Result<Option<(std::net::IpAddr, std::net::IpAddr)>, diesel::result::Error> {
+    use crate::schema::node_overlay_addrs::dsl::*;
+
+    let rows = node_overlay_addrs
+        .filter(node_id.eq(node_a).or(node_id.eq(node_b)))
+        .order(mesh_group_id.asc())
+        .select((mesh_group_id, node_id, address))
+        .load::<(i32, i32, String)>(conn)?;
+
+    let mut by_mesh: BTreeMap<i32, (Option<std::net::IpAddr>, Option<std::net::IpAddr>)> = BTreeMap::new();
+    for (mesh, node, addr) in rows {
+        let entry = by_mesh.entry(mesh).or_default();
+        let addr = addr.parse().ok();
+        if node == node_a {
+            entry.0 = addr;
+        } else {
+            entry.1 = addr;
+        }
+    }
+
+    Ok(by_mesh.into_values().find_map(|(a, b)| Some((a?, b?))))
+}
+
+/// Bump `updated_at` on every tunnel of a node, so polls see a new ETag.
+fn touch_node_tunnels(conn: &mut SqliteConnection, node_id_val: i32) -> Result<(), diesel::result::Error> {
+    use crate::schema::wireguard_tunnels::dsl::*;
+
+    diesel::update(wireguard_tunnels.filter(node_id_peer1.eq(node_id_val).or(node_id_peer2.eq(node_id_val))))
+        .set(updated_at.eq(chrono::Utc::now().naive_utc()))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Create a mesh group. `mesh_subnet` is a CIDR members get overlay addresses from; host
+/// bits are cleared, and anything that doesn't parse is a `QueryBuilderError`. With
+/// `ula_overlay` instead, the subnet is a ULA /64 derived from the mesh's ID and name
+/// (see [`cat4igp_shared::addressing`]); asking for both is a `QueryBuilderError`.
 pub fn create_mesh_group(
     conn: &mut SqliteConnection,
     name_val: &str,
     auto_wg: bool,
     auto_wg_mtu: i32,
+    mesh_subnet_val: Option<&str>,
+    ula_overlay_val: bool,
 ) -> Result<i32, diesel::result::Error> {
     use crate::schema::mesh_groups;
 
+    if ula_overlay_val && mesh_subnet_val.is_some() {
+        return Err(diesel::result::Error::QueryBuilderError(
+            "a mesh takes either a mesh subnet or a ULA overlay, not both".into(),
+        ));
+    }
+
+    let mesh_subnet_val = mesh_subnet_val
+        .map(|subnet| {
+            subnet.trim().parse::<ipnet::IpNet>().map(|net| net.trunc().to_string()).map_err(|e| {
+                diesel::result::Error::QueryBuilderError(format!("invalid mesh subnet {}: {}", subnet, e).into())
+            })
+        })
+        .transpose()?;
+
     let new_mesh = crate::models::NewMeshGroup {
         name: name_val,
         auto_wireguard: auto_wg,
         auto_wireguard_mtu: auto_wg_mtu,
+        created_at: chrono::Utc::now().naive_utc(),
+        mesh_subnet: mesh_subnet_val.as_deref(),
+        ula_overlay: ula_overlay_val,
     };
 
-    let result = diesel::insert_into(mesh_groups::table)
-        .values(&new_mesh)
-        .get_result::<crate::models::MeshGroup>(conn)?;
+    conn.transaction(|conn| {
+        let result = diesel::insert_into(mesh_groups::table)
+            .values(&new_mesh)
+            .get_result::<crate::models::MeshGroup>(conn)?;
 
-    let mesh_id = result.id;
+        let mesh_id = result.id;
 
-    Ok(mesh_id)
+        // the seed needs the ID, which only exists once the row does
+        if ula_overlay_val {
+            let seed = format!("mesh:{}:{}", mesh_id, name_val);
+            let prefix = cat4igp_shared::addressing::generate_ula_prefix_from_seed(seed.as_bytes());
+            diesel::update(mesh_groups::table.filter(mesh_groups::id.eq(mesh_id)))
+                .set(mesh_groups::mesh_subnet.eq(format!("{}/64", prefix)))
+                .execute(conn)?;
+        }
+
+        Ok(mesh_id)
+    })
 }
 
 pub fn delete_mesh_group(
@@ -416,26 +1186,40 @@ pub fn delete_mesh_group(
     use crate::schema::mesh_group_memberships::dsl as mgm_dsl;
     use crate::schema::mesh_groups::dsl::*;
 
+    let members = mgm_dsl::mesh_group_memberships
+        .filter(mgm_dsl::mesh_group_id.eq(mesh_id_val))
+        .select(mgm_dsl::node_id)
+        .load::<i32>(conn)?;
+
     diesel::delete(mgm_dsl::mesh_group_memberships.filter(mgm_dsl::mesh_group_id.eq(mesh_id_val)))
         .execute(conn)?;
+    diesel::delete(
+        crate::schema::node_overlay_addrs::table
+            .filter(crate::schema::node_overlay_addrs::mesh_group_id.eq(mesh_id_val)),
+    )
+    .execute(conn)?;
 
     diesel::delete(mesh_groups.filter(id.eq(mesh_id_val))).execute(conn)?;
 
+    // their tunnels lose the overlay addresses from this mesh
+    for member in members {
+        touch_node_tunnels(conn, member)?;
+    }
+
     Ok(())
 }
 
 pub fn get_setting(
     conn: &mut SqliteConnection,
     key_val: &str,
-) -> Result<String, diesel::result::Error> {
+) -> Result<Option<String>, diesel::result::Error> {
     use crate::schema::settings::dsl::*;
 
-    let result = settings
+    settings
         .filter(key.eq(key_val))
         .select(value)
-        .first::<String>(conn)?;
-
-    Ok(result)
+        .first::<String>(conn)
+        .optional()
 }
 
 pub fn set_setting(
@@ -446,20 +1230,840 @@ pub fn set_setting(
     use crate::schema::settings;
     use crate::schema::settings::dsl::*;
 
+    let now = chrono::Utc::now().naive_utc();
     let new_setting = crate::models::NewSetting {
         key: key_val,
         value: value_val,
+        created_at: now,
+        updated_at: now,
     };
 
+    // relies on the unique index on `key`
     diesel::insert_into(settings::table)
         .values(&new_setting)
         .on_conflict(key)
         .do_update()
         .set((
             value.eq(value_val),
-            updated_at.eq(chrono::Utc::now().naive_utc()),
+            updated_at.eq(now),
         ))
         .execute(conn)?;
 
     Ok(())
 }
+
+/// MTU used for new tunnels when neither the request nor the settings table names one.
+pub const DEFAULT_WIREGUARD_MTU: i32 = 1420;
+
+/// Default MTU for new tunnels, from the `default_wireguard_mtu` setting.
+/// Missing or invalid values fall back to [`DEFAULT_WIREGUARD_MTU`].
+pub fn get_default_wireguard_mtu(conn: &mut SqliteConnection) -> Result<i32, diesel::result::Error> {
+    Ok(get_setting(conn, "default_wireguard_mtu")?
+        .and_then(|v| v.parse::<i32>().ok())
+        .filter(|mtu| cat4igp_shared::limits::is_valid_tunnel_mtu(*mtu))
+        .unwrap_or(DEFAULT_WIREGUARD_MTU))
+}
+
+/// Default endpoint family for new tunnels, from the `default_endpoint_ipv6` setting.
+pub fn get_default_endpoint_ipv6(conn: &mut SqliteConnection) -> Result<bool, diesel::result::Error> {
+    Ok(get_setting(conn, "default_endpoint_ipv6")?
+        .and_then(|v| v.parse::<bool>().ok())
+        .unwrap_or(false))
+}
+
+/// How long after its last request a node still counts as online.
+pub const DEFAULT_NODE_ONLINE_WINDOW_SECS: i64 = 120;
+
+/// Longest accepted online window, 30 days.
+pub const MAX_NODE_ONLINE_WINDOW_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// Online window from the `node_online_window_secs` setting, falling back to
+/// [`DEFAULT_NODE_ONLINE_WINDOW_SECS`] when missing or outside `1..=MAX_NODE_ONLINE_WINDOW_SECS`.
+pub fn get_node_online_window(conn: &mut SqliteConnection) -> Result<chrono::TimeDelta, diesel::result::Error> {
+    let window = get_setting(conn, "node_online_window_secs")?
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|secs| (1..=MAX_NODE_ONLINE_WINDOW_SECS).contains(secs))
+        .and_then(chrono::TimeDelta::try_seconds);
+    Ok(window.unwrap_or_else(|| chrono::TimeDelta::seconds(DEFAULT_NODE_ONLINE_WINDOW_SECS)))
+}
+
+pub fn list_settings(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<crate::models::Setting>, diesel::result::Error> {
+    use crate::schema::settings::dsl::*;
+
+    settings
+        .order(key.asc())
+        .select(crate::models::Setting::as_select())
+        .load::<crate::models::Setting>(conn)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+
+    const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+    /// In-memory database with every migration applied; shared with the router tests.
+    pub(crate) fn establish_test_connection() -> SqliteConnection {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        conn.run_pending_migrations(MIGRATIONS).unwrap();
+        conn
+    }
+
+    pub(crate) fn insert_node(conn: &mut SqliteConnection, node_name: &str) -> i32 {
+        let new_node = crate::models::NewNode {
+            name: node_name,
+            auth_key: &Uuid::new_v4().to_string(),
+        };
+
+        diesel::insert_into(crate::schema::nodes::table)
+            .values(&new_node)
+            .get_result::<Node>(conn)
+            .unwrap()
+            .id
+    }
+
+    #[test]
+    fn test_sqlite_options_from_env() {
+        let options = SqliteOptions::from_lookup(|_| None).unwrap();
+        assert_eq!(options, SqliteOptions::default());
+
+        let options = SqliteOptions::from_lookup(|name| match name {
+            "SQLITE_JOURNAL_MODE" => Some("delete".to_string()),
+            "SQLITE_BUSY_TIMEOUT_MS" => Some("250".to_string()),
+            "SQLITE_FOREIGN_KEYS" => Some("off".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(options.journal_mode, "DELETE");
+        assert_eq!(options.busy_timeout_ms, 250);
+        assert!(!options.foreign_keys);
+
+        let invalid = |name: &'static str, value: &'static str| {
+            SqliteOptions::from_lookup(move |n| (n == name).then(|| value.to_string())).is_err()
+        };
+        assert!(invalid("SQLITE_JOURNAL_MODE", "wal; DROP TABLE nodes"));
+        assert!(invalid("SQLITE_BUSY_TIMEOUT_MS", "-1"));
+        assert!(invalid("SQLITE_FOREIGN_KEYS", "maybe"));
+    }
+
+    #[test]
+    fn test_concurrent_writers_wait_for_each_other() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let url = dir.path().join("db.sqlite").to_str().unwrap().to_string();
+        let open = || {
+            let mut conn = SqliteConnection::establish(&url).unwrap();
+            SqliteOptions::default().apply(&mut conn).unwrap();
+            conn
+        };
+        let mut first = open();
+        first.run_pending_migrations(MIGRATIONS).unwrap();
+        let mut second = open();
+
+        // hold the write lock while the other connection tries to write
+        diesel::sql_query("BEGIN IMMEDIATE").execute(&mut first).unwrap();
+        insert_node(&mut first, "node-a");
+        let writer = std::thread::spawn(move || {
+            insert_node(&mut second, "node-b");
+        });
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        diesel::sql_query("COMMIT").execute(&mut first).unwrap();
+        writer.join().unwrap();
+
+        let names: Vec<String> = crate::schema::nodes::table
+            .select(crate::schema::nodes::name)
+            .order(crate::schema::nodes::id)
+            .load(&mut first)
+            .unwrap();
+        assert_eq!(names, vec!["node-a", "node-b"]);
+
+        #[derive(QueryableByName)]
+        struct JournalMode {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            journal_mode: String,
+        }
+        let mode = diesel::sql_query("PRAGMA journal_mode")
+            .get_result::<JournalMode>(&mut first)
+            .unwrap();
+        assert_eq!(mode.journal_mode, "wal");
+    }
+
+    #[test]
+    fn test_delete_node_cascades() {
+        let conn = &mut SqliteConnection::establish(":memory:").unwrap();
+        SqliteOptions::default().apply(conn).unwrap();
+        conn.run_pending_migrations(MIGRATIONS).unwrap();
+
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let node_c = insert_node(conn, "node-c");
+        update_wireguard_pubkey(conn, node_b, "key-b").unwrap();
+        set_node_metadata(conn, node_b, "site", Some("ams")).unwrap();
+        let now = chrono::Utc::now().naive_utc();
+        let mesh_id = diesel::insert_into(crate::schema::mesh_groups::table)
+            .values((
+                crate::schema::mesh_groups::name.eq("mesh"),
+                crate::schema::mesh_groups::auto_wireguard.eq(false),
+                crate::schema::mesh_groups::auto_wireguard_mtu.eq(1420),
+                crate::schema::mesh_groups::created_at.eq(now),
+            ))
+            .returning(crate::schema::mesh_groups::id)
+            .get_result::<i32>(conn)
+            .unwrap();
+        diesel::insert_into(crate::schema::mesh_group_memberships::table)
+            .values((
+                crate::schema::mesh_group_memberships::mesh_group_id.eq(mesh_id),
+                crate::schema::mesh_group_memberships::node_id.eq(node_b),
+                crate::schema::mesh_group_memberships::created_at.eq(now),
+            ))
+            .execute(conn)
+            .unwrap();
+        let doomed = create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+        let kept = create_wireguard_tunnel(conn, node_a, node_c, 1420, false).unwrap();
+        set_setting(conn, "relay_endpoint", "198.51.100.9:4000").unwrap();
+        offer_relay(conn, doomed).unwrap().unwrap();
+
+        delete_node(conn, node_b).unwrap();
+
+        let tunnel_ids: Vec<i32> = get_all_tunnels(conn).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(tunnel_ids, vec![kept]);
+        assert!(get_relay_assignments(conn, node_a).unwrap().is_empty());
+        assert!(get_wireguard_pubkey(conn, node_b).is_err());
+        assert!(get_node_metadata(conn, node_b).unwrap().is_empty());
+        assert!(get_mesh_members(conn, mesh_id).unwrap().is_empty());
+        assert!(matches!(delete_node(conn, node_b), Err(diesel::result::Error::NotFound)));
+    }
+
+    #[test]
+    fn test_touch_last_seen() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let last_seen = |conn: &mut SqliteConnection| {
+            crate::schema::nodes::table
+                .find(node_a)
+                .select(crate::schema::nodes::last_seen)
+                .first::<Option<chrono::NaiveDateTime>>(conn)
+                .unwrap()
+        };
+        assert_eq!(last_seen(conn), None);
+
+        touch_last_seen(conn, node_a, None).unwrap();
+        let first = last_seen(conn).unwrap();
+        // within the resolution the stored time stays put
+        touch_last_seen(conn, node_a, None).unwrap();
+        assert_eq!(last_seen(conn), Some(first));
+
+        // but a new source address is recorded at once, v4-mapped ones as IPv4
+        let source_ip = |conn: &mut SqliteConnection| {
+            crate::schema::nodes::table
+                .find(node_a)
+                .select(crate::schema::nodes::last_source_ip)
+                .first::<Option<String>>(conn)
+                .unwrap()
+        };
+        assert_eq!(source_ip(conn), None);
+        touch_last_seen(conn, node_a, Some("::ffff:198.51.100.7".parse().unwrap())).unwrap();
+        assert_eq!(source_ip(conn).as_deref(), Some("198.51.100.7"));
+        touch_last_seen(conn, node_a, Some("2001:db8::7".parse().unwrap())).unwrap();
+        assert_eq!(source_ip(conn).as_deref(), Some("2001:db8::7"));
+        touch_last_seen(conn, node_a, None).unwrap();
+        assert_eq!(source_ip(conn).as_deref(), Some("2001:db8::7"));
+
+        assert_eq!(get_node_online_window(conn).unwrap().num_seconds(), DEFAULT_NODE_ONLINE_WINDOW_SECS);
+        set_setting(conn, "node_online_window_secs", "300").unwrap();
+        assert_eq!(get_node_online_window(conn).unwrap().num_seconds(), 300);
+        set_setting(conn, "node_online_window_secs", "0").unwrap();
+        assert_eq!(get_node_online_window(conn).unwrap().num_seconds(), DEFAULT_NODE_ONLINE_WINDOW_SECS);
+        // would overflow TimeDelta, which used to panic
+        set_setting(conn, "node_online_window_secs", &i64::MAX.to_string()).unwrap();
+        assert_eq!(get_node_online_window(conn).unwrap().num_seconds(), DEFAULT_NODE_ONLINE_WINDOW_SECS);
+    }
+
+    #[test]
+    fn test_create_wireguard_tunnel_returns_id() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+
+        let ipv4_id = create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+        let ipv6_id = create_wireguard_tunnel(conn, node_a, node_b, 1420, true).unwrap();
+        assert_ne!(ipv4_id, ipv6_id);
+
+        let tunnel = get_tunnel(conn, ipv4_id).unwrap();
+        assert_eq!(tunnel.id, ipv4_id);
+        assert_eq!(tunnel.node_id_peer1, node_a);
+        assert_eq!(tunnel.node_id_peer2, node_b);
+        assert!(!tunnel.endpoint_ipv6);
+
+        let found = get_tunnel_between(conn, node_b, node_a, true).unwrap().unwrap();
+        assert_eq!(found.id, ipv6_id);
+    }
+
+    #[test]
+    fn test_revoked_invite_cannot_register() {
+        let conn = &mut establish_test_connection();
+        let invite_code = create_invite_key(conn, None, None, None, None).unwrap();
+        let invite_id = get_invites(conn).unwrap()[0].id;
+
+        revoke_invite(conn, invite_id).unwrap();
+        assert!(get_invites(conn).unwrap()[0].revoked);
+        assert!(register_node(conn, "node-a", &invite_code).is_err());
+        assert!(revoke_invite(conn, invite_id + 1).is_err());
+    }
+
+    #[test]
+    fn test_remove_node_from_mesh_keeps_tunnels_of_other_meshes() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let shared_peer = insert_node(conn, "shared-peer");
+        let only_first = insert_node(conn, "only-first");
+        let first = create_mesh_group(conn, "first", true, 1420, None, false).unwrap();
+        let second = create_mesh_group(conn, "second", true, 1420, None, false).unwrap();
+        join_mesh(conn, shared_peer, first).unwrap();
+        join_mesh(conn, only_first, first).unwrap();
+        join_mesh(conn, shared_peer, second).unwrap();
+        join_mesh(conn, node_a, first).unwrap();
+        join_mesh(conn, node_a, second).unwrap();
+
+        let to_only_first = get_tunnel_between(conn, node_a, only_first, false).unwrap().unwrap();
+        set_setting(conn, "relay_endpoint", "198.51.100.9:4000").unwrap();
+        assert!(offer_relay(conn, to_only_first.id).unwrap().is_some());
+
+        let removed = remove_node_from_mesh(conn, first, node_a).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(removed.contains(&to_only_first.id));
+        for ipv6 in [false, true] {
+            assert!(get_tunnel_between(conn, node_a, only_first, ipv6).unwrap().is_none());
+            assert!(get_tunnel_between(conn, node_a, shared_peer, ipv6).unwrap().is_some());
+        }
+        // other members of the mesh keep their tunnels
+        assert!(get_tunnel_between(conn, shared_peer, only_first, false).unwrap().is_some());
+        assert_eq!(get_mesh_members(conn, first).unwrap().len(), 2);
+
+        assert_eq!(remove_node_from_mesh(conn, first, node_a).err(), Some(diesel::result::Error::NotFound));
+
+        // leaving the last mesh the two share takes the remaining tunnels
+        assert_eq!(remove_node_from_mesh(conn, second, node_a).unwrap().len(), 2);
+        assert!(get_tunnel_between(conn, node_a, shared_peer, false).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_meshes_and_members() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let empty = create_mesh_group(conn, "empty", false, 0, None, false).unwrap();
+        let full = create_mesh_group(conn, "full", true, 1400, Some("10.9.0.0/24"), false).unwrap();
+        join_mesh(conn, node_b, full).unwrap();
+        join_mesh(conn, node_a, full).unwrap();
+
+        let meshes = get_meshes(conn).unwrap();
+        assert_eq!(meshes.iter().map(|m| m.id).collect::<Vec<_>>(), vec![empty, full]);
+
+        let counts = get_mesh_member_counts(conn).unwrap();
+        assert_eq!(counts.get(&full), Some(&2));
+        assert_eq!(counts.get(&empty), None);
+
+        let mesh = get_mesh(conn, full).unwrap();
+        assert_eq!(mesh.name, "full");
+        assert!(mesh.auto_wireguard);
+        assert_eq!(mesh.auto_wireguard_mtu, 1400);
+        let members: Vec<i32> = get_mesh_members(conn, full).unwrap().into_iter().map(|n| n.id).collect();
+        assert_eq!(members, vec![node_a, node_b]);
+
+        assert_eq!(get_mesh(conn, full + 100).err(), Some(diesel::result::Error::NotFound));
+    }
+
+    #[test]
+    fn test_filtered_node_list() {
+        let conn = &mut establish_test_connection();
+        let alpha = insert_node(conn, "edge-alpha");
+        let beta = insert_node(conn, "edge-beta");
+        let core = insert_node(conn, "core_1");
+        let mesh = create_mesh_group(conn, "fleet", false, 1420, None, false).unwrap();
+        join_mesh(conn, alpha, mesh).unwrap();
+        join_mesh(conn, core, mesh).unwrap();
+        touch_last_seen(conn, beta, None).unwrap();
+        touch_last_seen(conn, core, None).unwrap();
+
+        let ids = |conn: &mut SqliteConnection, filter: NodeFilter| -> Vec<i32> {
+            get_filtered_node_list(conn, &filter).unwrap().into_iter().map(|n| n.id).collect()
+        };
+
+        assert_eq!(ids(conn, NodeFilter::default()), vec![alpha, beta, core]);
+
+        assert_eq!(ids(conn, NodeFilter { name_contains: Some("EDGE"), ..Default::default() }), vec![alpha, beta]);
+        // LIKE wildcards in the search are literal
+        assert_eq!(ids(conn, NodeFilter { name_contains: Some("_"), ..Default::default() }), vec![core]);
+        assert!(ids(conn, NodeFilter { name_contains: Some("%"), ..Default::default() }).is_empty());
+
+        assert_eq!(ids(conn, NodeFilter { mesh_group_id: Some(mesh), ..Default::default() }), vec![alpha, core]);
+        assert!(ids(conn, NodeFilter { mesh_group_id: Some(mesh + 100), ..Default::default() }).is_empty());
+
+        let cutoff = chrono::Utc::now().naive_utc() - chrono::TimeDelta::seconds(60);
+        assert_eq!(ids(conn, NodeFilter { online: Some((true, cutoff)), ..Default::default() }), vec![beta, core]);
+        assert_eq!(ids(conn, NodeFilter { online: Some((false, cutoff)), ..Default::default() }), vec![alpha]);
+
+        let offline_in_mesh = NodeFilter { mesh_group_id: Some(mesh), online: Some((false, cutoff)), ..Default::default() };
+        assert_eq!(ids(conn, offline_in_mesh), vec![alpha]);
+    }
+
+    #[test]
+    fn test_invite_remaining_uses() {
+        let conn = &mut establish_test_connection();
+        let limited = create_invite_key(conn, None, Some(2), None, None).unwrap();
+        let unlimited = create_invite_key(conn, None, None, None, None).unwrap();
+
+        assert_eq!(register_node(conn, "node-a", &limited).unwrap().3, Some(1));
+        assert_eq!(register_node(conn, "node-b", &limited).unwrap().3, Some(0));
+        assert!(register_node(conn, "node-c", &limited).is_err());
+        assert_eq!(register_node(conn, "node-d", &unlimited).unwrap().3, None);
+
+        let invites: Vec<cat4igp_shared::rest::operator::Invite> =
+            get_invites(conn).unwrap().into_iter().map(Into::into).collect();
+        let limited = invites.iter().find(|i| i.code == limited).unwrap();
+        assert_eq!((limited.used_count, limited.remaining_uses), (2, Some(0)));
+        let unlimited = invites.iter().find(|i| i.code == unlimited).unwrap();
+        assert_eq!((unlimited.used_count, unlimited.remaining_uses), (1, None));
+
+        // an invite whose limit was lowered below its uses reports 0, not a negative count
+        let over = Invite { max_uses: Some(1), used_count: 3, ..get_invites(conn).unwrap().remove(0) };
+        assert_eq!(over.remaining_uses(), Some(0));
+    }
+
+    #[test]
+    fn test_rotate_node_auth_key() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let old_key = crate::schema::nodes::table
+            .find(node_a)
+            .select(Node::as_select())
+            .first(conn)
+            .unwrap()
+            .auth_key;
+
+        let new_key = rotate_node_auth_key(conn, node_a).unwrap();
+        assert_ne!(new_key, old_key);
+        assert!(authenticate(conn, &old_key).is_err());
+        assert_eq!(authenticate(conn, &new_key).unwrap().id, node_a);
+
+        assert_eq!(rotate_node_auth_key(conn, node_a + 1), Err(diesel::result::Error::NotFound));
+
+        // a node-proposed key replaces the current one, but never another node's key
+        let node_b = insert_node(conn, "node-b");
+        let proposed = Uuid::new_v4().to_string();
+        set_node_auth_key(conn, node_b, &proposed).unwrap();
+        assert_eq!(authenticate(conn, &proposed).unwrap().id, node_b);
+        assert_eq!(set_node_auth_key(conn, node_b, &new_key), Err(diesel::result::Error::RollbackTransaction));
+        assert_eq!(authenticate(conn, &new_key).unwrap().id, node_a);
+        assert_eq!(set_node_auth_key(conn, node_b, &proposed), Err(diesel::result::Error::RollbackTransaction));
+    }
+
+    #[test]
+    fn test_create_wireguard_tunnel_rejects_duplicate() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+
+        let is_duplicate = |result: Result<i32, diesel::result::Error>| {
+            matches!(
+                result,
+                Err(diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::UniqueViolation,
+                    _
+                ))
+            )
+        };
+
+        create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+        assert!(is_duplicate(create_wireguard_tunnel(conn, node_a, node_b, 1420, false)));
+        assert!(is_duplicate(create_wireguard_tunnel(conn, node_b, node_a, 1420, false)));
+
+        // the index holds even when the lookup is bypassed, in either order
+        for (peer1, peer2) in [(node_a, node_b), (node_b, node_a)] {
+            let inserted = diesel::insert_into(crate::schema::wireguard_tunnels::table)
+                .values(&crate::models::NewWireguardTunnel {
+                    node_id_peer1: peer1,
+                    node_id_peer2: peer2,
+                    endpoint_peer1: None,
+                    endpoint_peer2: None,
+                    mtu: 1420,
+                    endpoint_ipv6: false,
+                })
+                .execute(conn)
+                .map(|_| 0);
+            assert!(is_duplicate(inserted));
+        }
+        assert_eq!(get_all_tunnels(conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_create_wireguard_tunnel_rejects_self_pair() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+
+        assert!(matches!(
+            create_wireguard_tunnel(conn, node_a, node_a, 1420, false),
+            Err(diesel::result::Error::QueryBuilderError(_))
+        ));
+        assert!(get_all_tunnels(conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_create_wireguard_tunnel_orders_peers() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+
+        let tunnel_id = create_wireguard_tunnel(conn, node_b, node_a, 1420, false).unwrap();
+        let tunnel = get_tunnel(conn, tunnel_id).unwrap();
+        assert_eq!((tunnel.node_id_peer1, tunnel.node_id_peer2), (node_a, node_b));
+
+        // the other family is a separate tunnel
+        create_wireguard_tunnel(conn, node_b, node_a, 1420, true).unwrap();
+    }
+
+    #[test]
+    fn test_set_tunnel_allowed_ips() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let tunnel_id = create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+
+        let tunnel = get_tunnel(conn, tunnel_id).unwrap();
+        assert_eq!(tunnel.allowed_ips_peer1, None);
+        assert_eq!(tunnel.persistent_keepalive, Some(25));
+
+        set_tunnel_allowed_ips(conn, tunnel_id, Some("10.0.0.0/24".to_string()), None).unwrap();
+        let tunnel = get_tunnel(conn, tunnel_id).unwrap();
+        assert_eq!(tunnel.allowed_ips_peer1.as_deref(), Some("10.0.0.0/24"));
+        assert_eq!(tunnel.allowed_ips_peer2, None);
+
+        assert!(set_tunnel_allowed_ips(conn, tunnel_id + 1, None, None).is_err());
+    }
+
+    #[test]
+    fn test_create_wireguard_tunnel_rejects_invalid_mtu() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+
+        assert!(create_wireguard_tunnel(conn, node_a, node_b, -1, false).is_err());
+        assert!(create_wireguard_tunnel(conn, node_a, node_b, 65535, false).is_err());
+        assert!(get_all_tunnels(conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invite_converts_to_rest_type() {
+        let conn = &mut establish_test_connection();
+        let mesh_id = 42;
+        let expires_at = chrono::Utc::now().naive_utc() + chrono::Duration::days(1);
+        let invite_code = create_invite_key(conn, Some(expires_at), Some(3), Some(mesh_id), Some("rack 4")).unwrap();
+        let invite_id = get_invites(conn).unwrap()[0].id;
+        revoke_invite(conn, invite_id).unwrap();
+
+        let invite = get_invites(conn).unwrap().remove(0);
+        let id = invite.id;
+        let created_at = invite.created_at;
+        let rest: cat4igp_shared::rest::operator::Invite = invite.into();
+
+        assert_eq!(rest.id, id);
+        assert_eq!(rest.code, invite_code);
+        assert_eq!(rest.created_at, created_at);
+        assert_eq!(rest.expires_at, Some(expires_at));
+        assert_eq!(rest.used_count, 0);
+        assert_eq!(rest.override_join_mesh, Some(mesh_id));
+        assert_eq!(rest.max_uses, Some(3));
+        assert_eq!(rest.remaining_uses, Some(3));
+        assert!(rest.revoked);
+        assert_eq!(rest.label.as_deref(), Some("rack 4"));
+    }
+
+    #[test]
+    fn test_answer_reject_reason_round_trips() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let tunnel_id = create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+
+        answer_wireguard_tunnel(conn, tunnel_id, node_b, None, Some(ext::WireguardAnswered::RejectedNoIpStack)).unwrap();
+        answer_wireguard_tunnel(conn, tunnel_id, node_a, Some("192.0.2.1:51820".to_string()), None).unwrap();
+
+        let tunnel = get_tunnel(conn, tunnel_id).unwrap();
+        assert_eq!(tunnel.peer1_answer(), ext::WireguardAnswered::Answered);
+        assert_eq!(tunnel.peer2_answer(), ext::WireguardAnswered::RejectedNoIpStack);
+        assert_eq!(tunnel.endpoint_peer1.as_deref(), Some("192.0.2.1:51820"));
+    }
+
+    #[test]
+    fn test_answer_cannot_set_other_nodes_endpoints() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let outsider = insert_node(conn, "outsider");
+        let tunnel_id = create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+        answer_wireguard_tunnel(conn, tunnel_id, node_b, Some("198.51.100.2:51820".to_string()), None).unwrap();
+
+        let hijack = answer_wireguard_tunnel(conn, tunnel_id, outsider, Some("203.0.113.66:51820".to_string()), None);
+        assert_eq!(hijack.err(), Some(diesel::result::Error::NotFound));
+        assert_eq!(
+            answer_wireguard_tunnel(conn, tunnel_id + 1, node_a, None, None).err(),
+            Some(diesel::result::Error::NotFound)
+        );
+
+        // a peer's answer only ever touches its own side
+        answer_wireguard_tunnel(conn, tunnel_id, node_a, Some("192.0.2.1:51821".to_string()), None).unwrap();
+        let tunnel = get_tunnel(conn, tunnel_id).unwrap();
+        assert_eq!(tunnel.endpoint_peer1.as_deref(), Some("192.0.2.1:51821"));
+        assert_eq!(tunnel.endpoint_peer2.as_deref(), Some("198.51.100.2:51820"));
+        assert_eq!(tunnel.peer2_answer(), ext::WireguardAnswered::Answered);
+    }
+
+    #[test]
+    fn test_offer_relay() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let tunnel_id = create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+
+        // nothing to relay through yet; a peer advertising itself does not count
+        set_node_metadata(conn, node_a, RELAY_ENDPOINT_KEY, Some("192.0.2.1:3478")).unwrap();
+        assert!(offer_relay(conn, tunnel_id).unwrap().is_none());
+
+        set_setting(conn, RELAY_ENDPOINT_KEY, "relay.example.com:3478").unwrap();
+        let relay_node = insert_node(conn, "relay");
+        set_node_metadata(conn, relay_node, RELAY_ENDPOINT_KEY, Some("198.51.100.9:3478")).unwrap();
+
+        let assignment = offer_relay(conn, tunnel_id).unwrap().unwrap();
+        assert_eq!(assignment.relay_node_id, Some(relay_node));
+        assert_eq!(assignment.relay_endpoint, "198.51.100.9:3478");
+        assert_eq!(offer_relay(conn, tunnel_id).unwrap().unwrap().token, assignment.token);
+
+        for node in [node_a, node_b, relay_node] {
+            let assignments = get_relay_assignments(conn, node).unwrap();
+            assert_eq!(assignments.len(), 1);
+            assert_eq!(assignments[0].1.id, tunnel_id);
+        }
+        let outsider = insert_node(conn, "node-c");
+        assert!(get_relay_assignments(conn, outsider).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invite_codes_are_unique() {
+        let conn = &mut establish_test_connection();
+
+        let mut codes = std::collections::HashSet::new();
+        for _ in 0..1000 {
+            let code = create_invite_key(conn, None, None, None, None).unwrap();
+            assert_eq!(code.len(), 32);
+            assert!(codes.insert(code));
+        }
+    }
+
+    #[test]
+    fn test_invite_code_unique_constraint() {
+        let conn = &mut establish_test_connection();
+        let code = create_invite_key(conn, None, None, None, None).unwrap();
+
+        let duplicate = crate::models::NewInvite {
+            code: &code,
+            expires_at: None,
+            max_uses: None,
+            override_join_mesh: None,
+            label: None,
+        };
+        let result = diesel::insert_into(crate::schema::invites::table)
+            .values(&duplicate)
+            .execute(conn);
+        assert!(matches!(
+            result,
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_node_metadata_set_and_remove() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+
+        set_node_metadata(conn, node_a, "region", Some("eu-west")).unwrap();
+        set_node_metadata(conn, node_a, "role", Some("edge")).unwrap();
+        set_node_metadata(conn, node_a, "region", Some("us-east")).unwrap();
+        set_node_metadata(conn, node_b, "region", Some("ap-south")).unwrap();
+
+        let metadata = get_node_metadata(conn, node_a).unwrap();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata["region"], "us-east");
+        assert_eq!(metadata["role"], "edge");
+
+        set_node_metadata(conn, node_a, "role", None).unwrap();
+        let all = get_all_node_metadata(conn).unwrap();
+        assert_eq!(all[&node_a].keys().collect::<Vec<_>>(), vec!["region"]);
+        assert_eq!(all[&node_b]["region"], "ap-south");
+    }
+
+    #[test]
+    fn test_is_well_formed_auth_key() {
+        let key = Uuid::new_v4().to_string();
+        assert!(is_well_formed_auth_key(&key));
+        assert!(!is_well_formed_auth_key(&key.to_uppercase()));
+        assert!(!is_well_formed_auth_key(&key.replace('-', "")));
+        assert!(!is_well_formed_auth_key(&format!("{{{}}}", key)));
+        assert!(!is_well_formed_auth_key(""));
+        assert!(!is_well_formed_auth_key(&"a".repeat(4096)));
+    }
+
+    #[test]
+    fn test_ping() {
+        let conn = &mut establish_test_connection();
+        assert!(ping(conn).is_ok());
+    }
+
+    #[test]
+    fn test_set_setting_upserts() {
+        let conn = &mut establish_test_connection();
+
+        assert_eq!(get_setting(conn, "default_wireguard_mtu").unwrap(), None);
+
+        set_setting(conn, "default_wireguard_mtu", "1420").unwrap();
+        let created = list_settings(conn).unwrap().remove(0);
+
+        set_setting(conn, "default_wireguard_mtu", "1380").unwrap();
+        let settings = list_settings(conn).unwrap();
+        assert_eq!(settings.len(), 1);
+        assert_eq!(settings[0].value, "1380");
+        assert_eq!(settings[0].created_at, created.created_at);
+        assert!(settings[0].updated_at >= created.updated_at);
+        assert_eq!(get_setting(conn, "default_wireguard_mtu").unwrap().as_deref(), Some("1380"));
+    }
+
+    #[test]
+    fn test_default_wireguard_mtu_applies_to_new_tunnels_only() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+
+        assert_eq!(get_default_wireguard_mtu(conn).unwrap(), DEFAULT_WIREGUARD_MTU);
+        assert!(!get_default_endpoint_ipv6(conn).unwrap());
+
+        let mtu = get_default_wireguard_mtu(conn).unwrap();
+        let old_id = create_wireguard_tunnel(conn, node_a, node_b, mtu, false).unwrap();
+
+        set_setting(conn, "default_wireguard_mtu", "1280").unwrap();
+        set_setting(conn, "default_endpoint_ipv6", "true").unwrap();
+        let mtu = get_default_wireguard_mtu(conn).unwrap();
+        let ipv6 = get_default_endpoint_ipv6(conn).unwrap();
+        let new_id = create_wireguard_tunnel(conn, node_a, node_b, mtu, ipv6).unwrap();
+
+        let old_tunnel = get_tunnel(conn, old_id).unwrap();
+        let new_tunnel = get_tunnel(conn, new_id).unwrap();
+        assert_eq!(old_tunnel.mtu, DEFAULT_WIREGUARD_MTU);
+        assert!(!old_tunnel.endpoint_ipv6);
+        assert_eq!(new_tunnel.mtu, 1280);
+        assert!(new_tunnel.endpoint_ipv6);
+
+        // out-of-range values are ignored rather than producing unusable tunnels
+        set_setting(conn, "default_wireguard_mtu", "100").unwrap();
+        assert_eq!(get_default_wireguard_mtu(conn).unwrap(), DEFAULT_WIREGUARD_MTU);
+    }
+
+    #[test]
+    fn test_join_mesh_twice_keeps_one_membership() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let mesh = create_mesh_group(conn, "mesh", false, 1420, None, false).unwrap();
+
+        join_mesh(conn, node_a, mesh).unwrap();
+        join_mesh(conn, node_a, mesh).unwrap();
+        assert_eq!(get_mesh_members(conn, mesh).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_overlay_addresses_allocated_and_released() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let node_c = insert_node(conn, "node-c");
+
+        assert!(create_mesh_group(conn, "bad", false, 1420, Some("10.0.0.0/33"), false).is_err());
+        let plain = create_mesh_group(conn, "plain", false, 1420, None, false).unwrap();
+        assert_eq!(allocate_overlay_address(conn, node_a, plain).unwrap(), None);
+
+        // /30 has two usable hosts
+        let mesh = create_mesh_group(conn, "overlay", false, 1420, Some("10.20.30.1/30"), false).unwrap();
+        join_mesh(conn, node_a, mesh).unwrap();
+        join_mesh(conn, node_b, mesh).unwrap();
+        assert_eq!(allocate_overlay_address(conn, node_a, mesh).unwrap(), Some("10.20.30.1".parse().unwrap()));
+        assert_eq!(allocate_overlay_address(conn, node_b, mesh).unwrap(), Some("10.20.30.2".parse().unwrap()));
+        assert_eq!(
+            get_shared_overlay_addresses(conn, node_b, node_a).unwrap(),
+            Some(("10.20.30.2".parse().unwrap(), "10.20.30.1".parse().unwrap()))
+        );
+        assert_eq!(get_shared_overlay_addresses(conn, node_a, node_c).unwrap(), None);
+
+        // exhausted: the join is refused and leaves no membership behind
+        assert!(join_mesh(conn, node_c, mesh).is_err());
+        assert_eq!(get_mesh_members(conn, mesh).unwrap().len(), 2);
+
+        remove_node_from_mesh(conn, mesh, node_a).unwrap();
+        assert_eq!(get_shared_overlay_addresses(conn, node_b, node_a).unwrap(), None);
+        join_mesh(conn, node_c, mesh).unwrap();
+        assert_eq!(allocate_overlay_address(conn, node_c, mesh).unwrap(), Some("10.20.30.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_delete_mesh_group_bumps_member_tunnels() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let mesh = create_mesh_group(conn, "overlay", false, 1420, Some("10.20.30.0/24"), false).unwrap();
+        join_mesh(conn, node_a, mesh).unwrap();
+        join_mesh(conn, node_b, mesh).unwrap();
+        let tunnel_id = create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+        let before = get_tunnel(conn, tunnel_id).unwrap().updated_at;
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        delete_mesh_group(conn, mesh).unwrap();
+        assert_eq!(get_shared_overlay_addresses(conn, node_a, node_b).unwrap(), None);
+        assert!(get_tunnel(conn, tunnel_id).unwrap().updated_at > before);
+    }
+
+    #[test]
+    fn test_overlay_addresses_ipv6_skip_subnet_router() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let mesh = create_mesh_group(conn, "overlay6", false, 1420, Some("fd00:cafe::/64"), false).unwrap();
+        join_mesh(conn, node_a, mesh).unwrap();
+        assert_eq!(allocate_overlay_address(conn, node_a, mesh).unwrap(), Some("fd00:cafe::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_ula_overlay_addresses_follow_node_id() {
+        let conn = &mut establish_test_connection();
+        assert!(create_mesh_group(conn, "both", false, 1420, Some("fd00:cafe::/64"), true).is_err());
+
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let mesh = create_mesh_group(conn, "ula", true, 1420, None, true).unwrap();
+        let subnet: ipnet::Ipv6Net = get_mesh(conn, mesh).unwrap().mesh_subnet.unwrap().parse().unwrap();
+        assert_eq!(subnet.prefix_len(), 64);
+        assert_eq!(subnet.network().octets()[0], 0xfd);
+        // the same mesh ID and name always give the same prefix
+        let seed = format!("mesh:{}:ula", mesh);
+        assert_eq!(subnet.network(), cat4igp_shared::addressing::generate_ula_prefix_from_seed(seed.as_bytes()));
+
+        // allocation order doesn't matter, only the node ID
+        join_mesh(conn, node_b, mesh).unwrap();
+        join_mesh(conn, node_a, mesh).unwrap();
+        for node in [node_b, node_a] {
+            let expected = cat4igp_shared::addressing::ula_node_address(subnet.network(), node);
+            assert_eq!(allocate_overlay_address(conn, node, mesh).unwrap(), Some(expected.into()));
+        }
+    }
+}
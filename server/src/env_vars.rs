@@ -0,0 +1,142 @@
+/// An environment variable the server reads.
+pub struct EnvVar {
+    pub name: &'static str,
+    pub required: bool,
+    /// Value written into the generated template
+    pub example: &'static str,
+    pub description: &'static str,
+}
+
+pub const ENV_VARS: &[EnvVar] = &[
+    EnvVar {
+        name: "DATABASE_URL",
+        required: true,
+        example: "cat4igp.sqlite",
+        description: "Path of the SQLite database; created on first run, apply migrations with `diesel migration run`",
+    },
+    EnvVar {
+        name: "BIND_HOST_PORT",
+        required: true,
+        example: "0.0.0.0:3000",
+        description: "Address and port to listen on, e.g. 127.0.0.1:3000 behind a reverse proxy",
+    },
+    EnvVar {
+        name: "SQLITE_JOURNAL_MODE",
+        required: false,
+        example: "WAL",
+        description: "SQLite journal mode; WAL lets reads run alongside a write",
+    },
+    EnvVar {
+        name: "SQLITE_BUSY_TIMEOUT_MS",
+        required: false,
+        example: "5000",
+        description: "How long a write waits for another writer before failing",
+    },
+    EnvVar {
+        name: "SQLITE_FOREIGN_KEYS",
+        required: false,
+        example: "on",
+        description: "Enforce foreign keys (on/off)",
+    },
+    EnvVar {
+        name: "OPERATOR_AUTH_KEY",
+        required: false,
+        example: "",
+        description: "Bearer token for /operator; the operator API refuses every request while unset",
+    },
+    EnvVar {
+        name: "CORS_ALLOWED_ORIGINS",
+        required: false,
+        example: "",
+        description: "Comma-separated origins allowed to call the API from a browser; CORS stays off while empty",
+    },
+    EnvVar {
+        name: "REGISTER_RATE_PER_MINUTE",
+        required: false,
+        example: "10",
+        description: "Registrations allowed per minute from one address",
+    },
+    EnvVar {
+        name: "REGISTER_RATE_BURST",
+        required: false,
+        example: "5",
+        description: "Registrations one address may make back to back",
+    },
+    EnvVar {
+        name: "RUST_LOG",
+        required: false,
+        example: "info",
+        description: "Log filter, e.g. `info` or `cat4igp_server=debug`",
+    },
+];
+
+/// `.env` template listing every variable with its description; `operator_key` fills in
+/// `OPERATOR_AUTH_KEY` so a fresh install has a usable operator API.
+pub fn template(operator_key: &str) -> String {
+    let mut out = String::from("# cat4igp-server configuration, read from the environment or this file\n");
+    for var in ENV_VARS {
+        let value = if var.name == "OPERATOR_AUTH_KEY" { operator_key } else { var.example };
+        out.push('\n');
+        out.push_str(&format!("# {}\n", var.description));
+        // leave empty optional variables commented out rather than set to ""
+        if value.is_empty() && !var.required {
+            out.push_str(&format!("#{}=\n", var.name));
+        } else {
+            out.push_str(&format!("{}={}\n", var.name, value));
+        }
+    }
+    out
+}
+
+/// Required variables that `lookup` reports as unset or empty.
+pub fn missing_required(lookup: impl Fn(&str) -> Option<String>) -> Vec<&'static EnvVar> {
+    ENV_VARS
+        .iter()
+        .filter(|var| var.required)
+        .filter(|var| lookup(var.name).is_none_or(|value| value.trim().is_empty()))
+        .collect()
+}
+
+/// Startup message naming every missing variable at once, instead of panicking on the first.
+pub fn missing_message(missing: &[&EnvVar]) -> String {
+    let mut message = String::from("Missing required environment variables:\n");
+    for var in missing {
+        message.push_str(&format!("  {} - {}\n", var.name, var.description));
+    }
+    message.push_str("Set them in the environment or in a .env file; `cat4igp-server gen-env` writes a template.");
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_lists_every_variable() {
+        let template = template("op-key");
+        for var in ENV_VARS {
+            assert!(template.contains(var.name), "{} missing from template", var.name);
+        }
+        assert!(template.contains("\nDATABASE_URL=cat4igp.sqlite\n"));
+        assert!(template.contains("\nOPERATOR_AUTH_KEY=op-key\n"));
+        assert!(template.contains("\n#CORS_ALLOWED_ORIGINS=\n"));
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let missing = missing_required(|_| None);
+        let names: Vec<_> = missing.iter().map(|var| var.name).collect();
+        assert_eq!(names, vec!["DATABASE_URL", "BIND_HOST_PORT"]);
+        let message = missing_message(&missing);
+        assert!(message.contains("DATABASE_URL") && message.contains("BIND_HOST_PORT"));
+
+        let missing = missing_required(|name| match name {
+            "DATABASE_URL" => Some("db.sqlite".to_string()),
+            _ => Some(" ".to_string()),
+        });
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].name, "BIND_HOST_PORT");
+
+        assert!(missing_required(|_| Some("x".to_string())).is_empty());
+    }
+}
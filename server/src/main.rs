@@ -1,6 +1,7 @@
 pub mod models;
 pub mod schema;
 pub mod db;
+pub mod env_vars;
 pub mod ext;
 pub mod router;
 
@@ -10,15 +11,102 @@ use serde::{Deserialize, Serialize};
 
 #[tokio::main]
 async fn main() {
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("gen-env") {
+        gen_env(args.next().unwrap_or_else(|| ".env".to_string()));
+        return;
+    }
+
     dotenv().ok();
 
-    // initialize tracing
-    tracing_subscriber::fmt::init();
+    if let Err(e) = run().await {
+        eprintln!("Fatal: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let missing = env_vars::missing_required(|name| env::var(name).ok());
+    if !missing.is_empty() {
+        return Err(env_vars::missing_message(&missing).into());
+    }
+
+    // initialize tracing; RUST_LOG picks the level (default: info)
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    // a bad DATABASE_URL should stop startup, not fail the first request
+    let mut conn = db::establish_connection()?;
+    db::ping(&mut conn).map_err(|e| format!("database is not usable: {}", e))?;
+    drop(conn);
 
     // build our application with a route
-    let app = router::make_router().await.unwrap();
+    let app = router::make_router()
+        .await
+        .map_err(|e| format!("failed to build router: {}", e))?;
+
+    let bind_host_port = env::var("BIND_HOST_PORT")?;
+    let listener = tokio::net::TcpListener::bind(&bind_host_port)
+        .await
+        .map_err(|e| format!("cannot listen on BIND_HOST_PORT={}: {}", bind_host_port, e))?;
+    // connect info gives the registration rate limiter the peer address
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    tracing::info!("shutdown complete");
+    Ok(())
+}
+
+/// `cat4igp-server gen-env [PATH]`: write a documented `.env` template with a fresh
+/// operator key. Never overwrites an existing file.
+fn gen_env(path: String) {
+    let template = env_vars::template(&uuid::Uuid::new_v4().to_string());
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // the file holds the operator key
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let written = options
+        .open(&path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, template.as_bytes()));
+
+    match written {
+        Ok(()) => println!("Wrote {}; review it before starting the server", path),
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Resolves on SIGINT (Ctrl+C) or SIGTERM, letting in-flight requests finish before exit.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind(env::var("BIND_HOST_PORT").expect("BIND_HOST_PORT must be set")).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    tracing::info!("shutting down, draining {} connections", router::in_flight_requests());
 }
@@ -11,6 +11,8 @@ pub struct Node {
     pub auth_key: String,
     pub created_at: chrono::NaiveDateTime,
     pub last_seen: Option<chrono::NaiveDateTime>,
+    /// Source address of the latest authenticated request, as the server saw it
+    pub last_source_ip: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -20,6 +22,27 @@ pub struct NewNode<'a> {
     pub auth_key: &'a str,
 }
 
+#[derive(Queryable, Selectable)]
+#[derive(Clone)]
+#[diesel(table_name = crate::schema::node_metadata)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NodeMetadata {
+    pub id: i32,
+    pub node_id: i32,
+    pub key: String,
+    pub value: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::node_metadata)]
+pub struct NewNodeMetadata<'a> {
+    pub node_id: i32,
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
 #[derive(Queryable, Selectable)]
 #[derive(Clone)]
 #[diesel(table_name = crate::schema::wireguard_static_key)]
@@ -55,6 +78,19 @@ pub struct WireguardTunnel {
     pub faketcp: bool,
     pub created_at: chrono::NaiveDateTime,
     pub updated_at: chrono::NaiveDateTime,
+    pub persistent_keepalive: Option<i32>,
+    pub allowed_ips_peer1: Option<String>,
+    pub allowed_ips_peer2: Option<String>,
+}
+
+impl WireguardTunnel {
+    pub fn peer1_answer(&self) -> crate::ext::WireguardAnswered {
+        self.peer1_answered.into()
+    }
+
+    pub fn peer2_answer(&self) -> crate::ext::WireguardAnswered {
+        self.peer2_answered.into()
+    }
 }
 
 #[derive(Insertable)]
@@ -68,6 +104,30 @@ pub struct NewWireguardTunnel {
     pub endpoint_ipv6: bool
 }
 
+/// Relay offered for a tunnel whose peers cannot reach each other directly.
+#[derive(Queryable, Selectable)]
+#[derive(Clone)]
+#[diesel(table_name = crate::schema::relay_assignments)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct RelayAssignment {
+    pub id: i32,
+    pub tunnel_id: i32,
+    /// `None` when the controller's own relay (the `relay_endpoint` setting) is used
+    pub relay_node_id: Option<i32>,
+    pub relay_endpoint: String,
+    pub token: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::relay_assignments)]
+pub struct NewRelayAssignment<'a> {
+    pub tunnel_id: i32,
+    pub relay_node_id: Option<i32>,
+    pub relay_endpoint: &'a str,
+    pub token: &'a str,
+}
+
 #[derive(Queryable, Selectable)]
 #[derive(Clone)]
 #[derive(Serialize, Deserialize)]
@@ -81,6 +141,32 @@ pub struct Invite {
     pub used_count: i32,
     pub override_join_mesh: Option<i32>,
     pub max_uses: Option<i32>,
+    pub revoked: bool,
+    pub label: Option<String>,
+}
+
+impl Invite {
+    /// Registrations left before `max_uses` is reached; `None` for unlimited invites.
+    pub fn remaining_uses(&self) -> Option<i32> {
+        self.max_uses.map(|max| (max - self.used_count).max(0))
+    }
+}
+
+impl From<Invite> for cat4igp_shared::rest::operator::Invite {
+    fn from(invite: Invite) -> Self {
+        Self {
+            remaining_uses: invite.remaining_uses(),
+            id: invite.id,
+            code: invite.code,
+            created_at: invite.created_at,
+            expires_at: invite.expires_at,
+            used_count: invite.used_count,
+            override_join_mesh: invite.override_join_mesh,
+            max_uses: invite.max_uses,
+            revoked: invite.revoked,
+            label: invite.label,
+        }
+    }
 }
 
 #[derive(Insertable)]
@@ -90,6 +176,7 @@ pub struct NewInvite<'a> {
     pub expires_at: Option<chrono::NaiveDateTime>,
     pub max_uses: Option<i32>,
     pub override_join_mesh: Option<i32>,
+    pub label: Option<&'a str>,
 }
 
 #[derive(Queryable, Selectable)]
@@ -101,7 +188,11 @@ pub struct MeshGroup {
     pub name: String,
     pub auto_wireguard: bool,
     pub auto_wireguard_mtu: i32,
-    pub created_at: chrono::NaiveDateTime
+    pub created_at: chrono::NaiveDateTime,
+    /// CIDR overlay addresses of members are allocated from; `None` allocates none
+    pub mesh_subnet: Option<String>,
+    /// `mesh_subnet` is a ULA /64 derived from the mesh, and members get `{prefix}::{node_id}`
+    pub ula_overlay: bool,
 }
 
 #[derive(Insertable)]
@@ -110,6 +201,9 @@ pub struct NewMeshGroup<'a> {
     pub name: &'a str,
     pub auto_wireguard: bool,
     pub auto_wireguard_mtu: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub mesh_subnet: Option<&'a str>,
+    pub ula_overlay: bool,
 }
 
 #[derive(Queryable, Selectable)]
@@ -128,6 +222,27 @@ pub struct MeshGroupMembership {
 pub struct NewMeshGroupMembership {
     pub mesh_group_id: i32,
     pub node_id: i32,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable)]
+#[derive(Clone)]
+#[diesel(table_name = crate::schema::node_overlay_addrs)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct NodeOverlayAddr {
+    pub id: i32,
+    pub mesh_group_id: i32,
+    pub node_id: i32,
+    pub address: String,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::node_overlay_addrs)]
+pub struct NewNodeOverlayAddr<'a> {
+    pub mesh_group_id: i32,
+    pub node_id: i32,
+    pub address: &'a str,
 }
 
 #[derive(Queryable, Selectable)]
@@ -147,4 +262,6 @@ pub struct Setting {
 pub struct NewSetting<'a> {
     pub key: &'a str,
     pub value: &'a str,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
 }
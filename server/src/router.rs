@@ -1,47 +1,161 @@
+mod access_log;
+
+pub use access_log::in_flight_requests;
 mod client;
+mod metadata;
 mod operator;
+mod rate_limit;
+#[cfg(test)]
+mod round_trip;
+
+use std::net::SocketAddr;
 
 use axum::{
     Router,
-    extract::Request,
+    extract::{ConnectInfo, Request},
     http::{HeaderMap, StatusCode},
     middleware::Next,
-    response::Response,
-    routing::{get, post},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
 };
 
 use crate::db;
 
 pub async fn make_router() -> Result<Router, Box<dyn std::error::Error>> {
-    Ok(Router::new()
+    let mut router = Router::new()
         .route(
             "/",
             axum::routing::get(|| async {
                 "CAT4IGP Controller Server - https://github.com/BadAimWeeb/cat4igp"
             }),
         )
+        .route("/health", get(health))
+        .route("/version", get(version))
         .nest("/client", make_router_client().await?)
-        .nest("/operator", make_router_operator().await?))
+        .nest("/operator", make_router_operator().await?);
+
+    if let Some(cors) = cors_layer() {
+        router = router.layer(cors);
+    }
+
+    Ok(router.layer(axum::middleware::from_fn(access_log::access_log_middleware)))
+}
+
+/// Unauthenticated liveness probe for load balancers; 503 when the database is unreachable.
+async fn health() -> (StatusCode, axum::Json<cat4igp_shared::rest::HealthResponse>) {
+    let healthy = db::establish_connection()
+        .is_ok_and(|mut conn| db::ping(&mut conn).is_ok());
+
+    let (status_code, status) = if healthy {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "db_error")
+    };
+
+    (status_code, axum::Json(cat4igp_shared::rest::HealthResponse { status: status.to_string() }))
+}
+
+/// Unauthenticated, so clients can check compatibility before registering.
+async fn version() -> axum::Json<cat4igp_shared::rest::VersionResponse> {
+    use cat4igp_shared::rest::capability;
+
+    axum::Json(cat4igp_shared::rest::VersionResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        api_version: cat4igp_shared::rest::API_VERSION,
+        capabilities: [
+            capability::WG_TUN_ETAG,
+            capability::ROTATE_KEY,
+            capability::SELF_METADATA,
+            capability::RELAY,
+        ]
+        .map(str::to_string)
+        .to_vec(),
+    })
+}
+
+/// CORS for browser dashboards, enabled only when `CORS_ALLOWED_ORIGINS` lists origins (comma-separated).
+fn cors_layer() -> Option<tower_http::cors::CorsLayer> {
+    let origins = parse_allowed_origins(&std::env::var("CORS_ALLOWED_ORIGINS").unwrap_or_default());
+    if origins.is_empty() {
+        return None;
+    }
+
+    Some(cors_for(origins))
+}
+
+/// Every method a route uses must be listed, or the browser's preflight for it fails.
+fn cors_for(origins: Vec<axum::http::HeaderValue>) -> tower_http::cors::CorsLayer {
+    tower_http::cors::CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([axum::http::Method::GET, axum::http::Method::POST, axum::http::Method::PUT])
+        .allow_headers([axum::http::header::AUTHORIZATION, axum::http::header::CONTENT_TYPE])
+}
+
+fn parse_allowed_origins(value: &str) -> Vec<axum::http::HeaderValue> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .filter_map(|origin| axum::http::HeaderValue::from_str(origin).ok())
+        .collect()
+}
+
+/// 503 for requests that can't reach the database; the cause is logged, not sent to the client.
+fn db_unavailable(
+    e: Box<dyn std::error::Error + Send + Sync>,
+) -> (StatusCode, axum::Json<cat4igp_shared::rest::StandardResponse>) {
+    tracing::error!("database unavailable: {}", e);
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        axum::Json(cat4igp_shared::rest::StandardResponse {
+            success: false,
+            message: Some("Database unavailable".to_string()),
+        }),
+    )
+}
+
+/// Token from the `Authorization` header, either bare or as `Bearer <token>` (scheme case-insensitive).
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(axum::http::header::AUTHORIZATION)?.to_str().ok()?;
+
+    let token = match value.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+        Some(_) => return None,
+        None => value,
+    };
+
+    if token.is_empty() || token.contains(char::is_whitespace) {
+        None
+    } else {
+        Some(token)
+    }
 }
 
 async fn auth_middleware(mut request: Request, next: Next) -> Response {
-    let token_option: Option<&str> =
-        if let Some(auth_header) = request.headers().get("Authorization") {
-            if let Ok(token_str) = auth_header.to_str() {
-                Some(token_str)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+    let token_option = bearer_token(request.headers());
 
-    if let Some(token) = token_option {
-        let conn = &mut db::establish_connection();
+    // reject malformed tokens before touching the database
+    if let Some(token) = token_option.filter(|t| db::is_well_formed_auth_key(t)) {
+        let conn = &mut match db::establish_connection() {
+            Ok(conn) => conn,
+            Err(e) => return db_unavailable(e).into_response(),
+        };
         let node_result = db::authenticate(conn, token);
         if let Ok(node) = node_result {
+            let node_id = node.id;
+            let source_ip = request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip());
+            if let Err(e) = db::touch_last_seen(conn, node_id, source_ip) {
+                tracing::warn!(node_id, "failed to update last_seen: {}", e);
+            }
             request.extensions_mut().insert(node);
-            next.run(request).await
+            let mut response = next.run(request).await;
+            response
+                .extensions_mut()
+                .insert(access_log::AuthenticatedNodeId(node_id));
+            response
         } else {
             Response::builder()
                 .status(StatusCode::UNAUTHORIZED)
@@ -56,17 +170,16 @@ async fn auth_middleware(mut request: Request, next: Next) -> Response {
     }
 }
 
+/// Compare secrets without exiting early on the first differing byte. Only the length,
+/// which a token's format gives away anyway, may leak through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    use subtle::ConstantTimeEq;
+
+    a.ct_eq(b).into()
+}
+
 async fn auth_middleware_operator(mut request: Request, next: Next) -> Response {
-    let token_option: Option<&str> =
-        if let Some(auth_header) = request.headers().get("Authorization") {
-            if let Ok(token_str) = auth_header.to_str() {
-                Some(token_str)
-            } else {
-                None
-            }
-        } else {
-            None
-        };
+    let token_option = bearer_token(request.headers());
 
     if let Some(token) = token_option {
         let operator_key = std::env::var("OPERATOR_AUTH_KEY").unwrap_or_default();
@@ -75,7 +188,7 @@ async fn auth_middleware_operator(mut request: Request, next: Next) -> Response
                 .status(StatusCode::UNAUTHORIZED)
                 .body("Unauthorized: Please set OPERATOR_AUTH_KEY environment variable".into())
                 .unwrap()
-        } else if token == operator_key {
+        } else if constant_time_eq(token.as_bytes(), operator_key.as_bytes()) {
             next.run(request).await
         } else {
             Response::builder()
@@ -95,18 +208,115 @@ pub async fn make_router_client() -> Result<Router, Box<dyn std::error::Error>>
     Ok(Router::new()
         .route("/self", post(client::update_name))
         .route("/self", get(client::get_self_info))
+        .route("/self/metadata", post(client::set_self_metadata))
+        .route("/rotate_key", post(client::rotate_key))
         .route("/all_nodes", get(client::get_all_nodes))
         .route("/wg_tun", get(client::get_wireguard_tunnels))
         .route("/wg_tun", post(client::answer_wireguard_tunnel))
+        .route("/relays", get(client::get_relay_assignments))
+        .route("/my_meshes", get(client::get_my_meshes))
         .route("/wg_pubkey", get(client::get_wireguard_pubkey))
         .route("/wg_pubkey", post(client::update_wireguard_pubkey))
         // future: please add routes BEFORE this "layer" line.
         .layer(axum::middleware::from_fn(auth_middleware))
-        .route("/register", post(client::register)))
+        .route(
+            "/register",
+            post(client::register).layer(axum::middleware::from_fn_with_state(
+                std::sync::Arc::new(rate_limit::RegisterRateLimiter::from_env()),
+                rate_limit::register_rate_limit_middleware,
+            )),
+        ))
 }
 
 pub async fn make_router_operator() -> Result<Router, Box<dyn std::error::Error>> {
     Ok(Router::new()
         .route("/create_invite", post(operator::create_invite))
+        .route("/invites", get(operator::get_invites))
+        .route("/revoke_invite", post(operator::revoke_invite))
+        .route("/rotate_node_key", post(operator::rotate_node_key))
+        .route("/delete_node", post(operator::delete_node))
+        .route("/create_mesh", post(operator::create_mesh))
+        .route("/remove_from_mesh", post(operator::remove_from_mesh))
+        .route("/meshes", get(operator::get_meshes))
+        .route("/meshes/{id}", get(operator::get_mesh))
+        .route("/create_tunnel", post(operator::create_tunnel))
+        .route("/tunnels", get(operator::get_tunnels))
+        .route("/tunnels/{id}", get(operator::get_tunnel))
+        .route("/nodes/{id}/metadata", get(operator::get_node_metadata))
+        .route("/nodes/{id}/metadata", put(operator::set_node_metadata))
+        .route("/settings", get(operator::get_settings))
+        .route("/settings", put(operator::set_setting))
         .layer(axum::middleware::from_fn(auth_middleware_operator)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_token() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(axum::http::header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(bearer_token(&headers("abc-123")), Some("abc-123"));
+        assert_eq!(bearer_token(&headers("Bearer abc-123")), Some("abc-123"));
+        assert_eq!(bearer_token(&headers("bEaReR  abc-123")), Some("abc-123"));
+        assert_eq!(bearer_token(&headers("Basic abc-123")), None);
+        assert_eq!(bearer_token(&headers("Bearer ")), None);
+        assert_eq!(bearer_token(&headers("Bearer a b")), None);
+        assert_eq!(bearer_token(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[tokio::test]
+    async fn test_version_lists_capabilities() {
+        let axum::Json(version) = version().await;
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(version.api_version, cat4igp_shared::rest::API_VERSION);
+        assert!(version.supports(cat4igp_shared::rest::capability::ROTATE_KEY));
+        assert!(!version.supports("heartbeat"));
+    }
+
+    #[test]
+    fn test_parse_allowed_origins() {
+        assert!(parse_allowed_origins("").is_empty());
+        assert!(parse_allowed_origins(" , ").is_empty());
+
+        let origins = parse_allowed_origins("https://dash.example.com, http://localhost:5173");
+        assert_eq!(origins, vec!["https://dash.example.com", "http://localhost:5173"]);
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight_allows_put() {
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route("/operator/settings", put(|| async {}))
+            .layer(cors_for(parse_allowed_origins("https://dash.example.com")));
+        let request = axum::http::Request::builder()
+            .method(axum::http::Method::OPTIONS)
+            .uri("/operator/settings")
+            .header(axum::http::header::ORIGIN, "https://dash.example.com")
+            .header(axum::http::header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .header(axum::http::header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+            .body(axum::body::Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://dash.example.com");
+        let methods = headers[axum::http::header::ACCESS_CONTROL_ALLOW_METHODS].to_str().unwrap();
+        assert!(methods.split(',').any(|m| m.trim() == "PUT"), "{}", methods);
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use axum::{extract::Request, middleware::Next, response::Response};
+
+/// Set on the response by the client auth middleware so the access log can name the node.
+#[derive(Clone, Copy)]
+pub struct AuthenticatedNodeId(pub i32);
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of requests currently being handled; reported when shutting down.
+pub fn in_flight_requests() -> usize {
+    IN_FLIGHT.load(Ordering::Relaxed)
+}
+
+/// Decrements [`IN_FLIGHT`] even if the handler future is dropped.
+struct InFlightGuard;
+
+impl InFlightGuard {
+    fn new() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Log method, path, node id, status and latency of every request.
+///
+/// The summary line is emitted at `info`; at `debug` the request headers are logged too,
+/// with `Authorization` redacted. Pick the level with `RUST_LOG` (e.g. `RUST_LOG=debug`).
+pub async fn access_log_middleware(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        let headers: Vec<String> = request
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                if name == axum::http::header::AUTHORIZATION {
+                    format!("{}: <redacted>", name)
+                } else {
+                    format!("{}: {}", name, value.to_str().unwrap_or("<binary>"))
+                }
+            })
+            .collect();
+        tracing::debug!(%method, %path, headers = ?headers, "request");
+    }
+
+    let start = Instant::now();
+    let response = {
+        let _in_flight = InFlightGuard::new();
+        next.run(request).await
+    };
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let node_id = response.extensions().get::<AuthenticatedNodeId>().map(|n| n.0);
+    tracing::info!(
+        %method,
+        %path,
+        node_id = ?node_id,
+        status = response.status().as_u16(),
+        latency_ms = format!("{:.2}", latency_ms),
+        "request served"
+    );
+
+    response
+}
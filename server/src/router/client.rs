@@ -1,4 +1,11 @@
-use axum::{Json, extract::Extension};
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    Json,
+    extract::{ConnectInfo, Extension, Query},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
 
 use cat4igp_shared::rest::StandardResponse;
 use cat4igp_shared::rest::client as REST;
@@ -6,9 +13,9 @@ use cat4igp_shared::rest::client as REST;
 pub async fn register(
     Json(payload): Json<REST::RegisterPayload>,
 ) -> Result<Json<REST::RegisterResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
-    let (nid, auth_key, override_join_mesh) =
+    let (nid, auth_key, override_join_mesh, remaining_uses) =
         crate::db::register_node(&mut conn, &payload.node_name, &payload.invitation_key).map_err(
             |e| {
                 (
@@ -31,7 +38,7 @@ pub async fn register(
     } else {
         // join default mesh
         let default_mesh_group = crate::db::get_setting(&mut conn, "default_mesh_group");
-        if let Ok(group_id_string) = default_mesh_group {
+        if let Ok(Some(group_id_string)) = default_mesh_group {
             // convert from string to integer
             let group_id_try = group_id_string.parse::<i32>();
             if let Ok(group_id) = group_id_try {
@@ -44,6 +51,7 @@ pub async fn register(
     Ok(Json(REST::RegisterResponse {
         success: true,
         auth_key,
+        remaining_uses,
     }))
 }
 
@@ -51,7 +59,7 @@ pub async fn update_name(
     Extension(node): Extension<crate::models::Node>,
     Json(payload): Json<REST::UpdateNamePayload>,
 ) -> Result<Json<StandardResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
     crate::db::update_node_name(&mut conn, node.id, &payload.new_name).map_err(|e| {
         (
@@ -69,6 +77,54 @@ pub async fn update_name(
     }))
 }
 
+/// Switch the calling node to a new auth key, proposed by the node or generated here.
+/// The key used for this request stops working as soon as the call succeeds.
+pub async fn rotate_key(
+    Extension(node): Extension<crate::models::Node>,
+    Json(payload): Json<REST::RotateKeyPayload>,
+) -> Result<Json<REST::RotateKeyResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let auth_key = match payload.auth_key {
+        Some(key) => {
+            let acceptable = uuid::Uuid::parse_str(&key)
+                .is_ok_and(|parsed| !parsed.is_nil() && crate::db::is_well_formed_auth_key(&key));
+            if !acceptable {
+                return Err((
+                    axum::http::StatusCode::BAD_REQUEST,
+                    Json(StandardResponse {
+                        success: false,
+                        message: Some("Auth key must be a non-nil lowercase hyphenated UUID".to_string()),
+                    }),
+                ));
+            }
+            crate::db::set_node_auth_key(&mut conn, node.id, &key).map(|_| key)
+        }
+        None => crate::db::rotate_node_auth_key(&mut conn, node.id),
+    }
+    .map_err(|e| match e {
+        diesel::result::Error::RollbackTransaction => (
+            axum::http::StatusCode::CONFLICT,
+            Json(StandardResponse {
+                success: false,
+                message: Some("Auth key is already in use".to_string()),
+            }),
+        ),
+        e => (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(StandardResponse {
+                success: false,
+                message: Some(format!("Failed to rotate key: {}", e)),
+            }),
+        ),
+    })?;
+
+    Ok(Json(REST::RotateKeyResponse {
+        success: true,
+        auth_key,
+    }))
+}
+
 pub async fn get_self_info(
     Extension(node): Extension<crate::models::Node>,
 ) -> Json<REST::NodeInfoResponse> {
@@ -80,11 +136,28 @@ pub async fn get_self_info(
     })
 }
 
-pub async fn get_all_nodes()
--> Result<Json<REST::AllNodesResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+pub async fn get_all_nodes(
+    Query(query): Query<REST::AllNodesQuery>,
+) -> Result<Json<REST::AllNodesResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
-    let nodes = crate::db::get_node_list(&mut conn).map_err(|e| {
+    let online_window = crate::db::get_node_online_window(&mut conn).map_err(|e| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(StandardResponse {
+                success: false,
+                message: Some(format!("Failed to get online window: {}", e)),
+            }),
+        )
+    })?;
+    let now = chrono::Utc::now().naive_utc();
+
+    let filter = crate::db::NodeFilter {
+        name_contains: query.name.as_deref(),
+        mesh_group_id: query.mesh,
+        online: query.online.map(|online| (online, now - online_window)),
+    };
+    let nodes = crate::db::get_filtered_node_list(&mut conn, &filter).map_err(|e| {
         (
             axum::http::StatusCode::BAD_REQUEST,
             Json(StandardResponse {
@@ -94,12 +167,25 @@ pub async fn get_all_nodes()
         )
     })?;
 
+    let mut metadata = if query.metadata {
+        Some(crate::db::get_all_node_metadata(&mut conn).map_err(|e| {
+            (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(StandardResponse {
+                    success: false,
+                    message: Some(format!("Failed to get node metadata: {}", e)),
+                }),
+            )
+        })?)
+    } else {
+        None
+    };
+
     let node_responses: Vec<REST::SingleNode> = nodes
         .into_iter()
-        .map(|n| REST::SingleNode {
-            id: n.id,
-            name: n.name,
-            created_at: n.created_at.and_utc().timestamp_millis(),
+        .map(|n| {
+            let node_metadata = metadata.as_mut().map(|m| m.remove(&n.id).unwrap_or_default());
+            single_node(n, node_metadata, now, online_window)
         })
         .collect();
 
@@ -109,12 +195,52 @@ pub async fn get_all_nodes()
     }))
 }
 
+fn single_node(
+    node: crate::models::Node,
+    metadata: Option<std::collections::BTreeMap<String, String>>,
+    now: chrono::NaiveDateTime,
+    online_window: chrono::TimeDelta,
+) -> REST::SingleNode {
+    REST::SingleNode {
+        id: node.id,
+        name: node.name,
+        created_at: node.created_at.and_utc().timestamp_millis(),
+        metadata,
+        last_seen_ms: node.last_seen.map(|t| t.and_utc().timestamp_millis()),
+        online: node.last_seen.is_some_and(|t| now - t <= online_window),
+    }
+}
+
+pub async fn set_self_metadata(
+    Extension(node): Extension<crate::models::Node>,
+    Json(payload): Json<REST::SetNodeMetadataPayload>,
+) -> Result<Json<StandardResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    super::metadata::apply_node_metadata(&mut conn, node.id, &payload, super::metadata::MetadataWriter::Node)?;
+
+    Ok(Json(StandardResponse {
+        success: true,
+        message: None,
+    }))
+}
+
+/// Returns the node's tunnels with an `ETag`; answers `304 Not Modified` when the
+/// client's `If-None-Match` still matches, so idle polls carry no body.
 pub async fn get_wireguard_tunnels(
     Extension(node): Extension<crate::models::Node>,
-) -> Result<Json<REST::WireguardTunnelsResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    headers: HeaderMap,
+) -> Result<Response, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+    wireguard_tunnels_response(&mut conn, node.id, &headers)
+}
 
-    let tunnels = crate::db::get_wireguard_answers(&mut conn, node.id).map_err(|e| {
+fn wireguard_tunnels_response(
+    conn: &mut diesel::SqliteConnection,
+    node_id: i32,
+    headers: &HeaderMap,
+) -> Result<Response, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let db_error = |e: diesel::result::Error| {
         (
             axum::http::StatusCode::BAD_REQUEST,
             Json(StandardResponse {
@@ -122,12 +248,23 @@ pub async fn get_wireguard_tunnels(
                 message: Some(format!("Failed to get wireguard tunnels: {}", e)),
             }),
         )
-    })?;
+    };
+
+    // decide on 304 before loading and assembling the full list
+    let (max_updated_at, count) =
+        crate::db::get_tunnels_max_updated_at(conn, node_id).map_err(db_error)?;
+    let etag = tunnels_etag(max_updated_at, count);
+    let etag_header = HeaderValue::from_str(&etag).expect("etag is a quoted ASCII string");
+    if if_none_match(headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag_header)]).into_response());
+    }
+
+    let tunnels = crate::db::get_wireguard_answers(conn, node_id).map_err(db_error)?;
 
     let mut tunnel_infos: Vec<REST::WireguardTunnelInfo> = Vec::new();
 
     for tunnel in tunnels {
-        let self_p1 = tunnel.node_id_peer1 == node.id;
+        let self_p1 = tunnel.node_id_peer1 == node_id;
 
         let peer_node_id = if self_p1 {
             tunnel.node_id_peer2
@@ -136,15 +273,15 @@ pub async fn get_wireguard_tunnels(
         };
 
         let local_answered = if self_p1 {
-            tunnel.peer1_answered
+            tunnel.peer1_answer()
         } else {
-            tunnel.peer2_answered
+            tunnel.peer2_answer()
         };
 
         let remote_response = if self_p1 {
-            tunnel.peer2_answered
+            tunnel.peer2_answer()
         } else {
-            tunnel.peer1_answered
+            tunnel.peer1_answer()
         };
 
         let local_endpoint = if self_p1 {
@@ -153,14 +290,24 @@ pub async fn get_wireguard_tunnels(
             tunnel.endpoint_peer2.clone()
         };
 
+        // the other peer's endpoint, and only once it accepted; a declined or
+        // unanswered peer may have left a stale endpoint behind
         let remote_endpoint = if self_p1 {
             tunnel.endpoint_peer2.clone()
         } else {
             tunnel.endpoint_peer1.clone()
+        }
+        .filter(|e| matches!(remote_response, crate::ext::WireguardAnswered::Answered) && !e.is_empty());
+
+        let allowed_ips = if self_p1 {
+            tunnel.allowed_ips_peer1.clone()
+        } else {
+            tunnel.allowed_ips_peer2.clone()
         };
 
         let public_key =
-            crate::db::get_wireguard_pubkey(&mut conn, peer_node_id).unwrap_or_default();
+            crate::db::get_wireguard_pubkey(conn, peer_node_id).unwrap_or_default();
+        let overlay = crate::db::get_shared_overlay_addresses(conn, node_id, peer_node_id).map_err(db_error)?;
 
         tunnel_infos.push(REST::WireguardTunnelInfo {
             tunnel_id: tunnel.id,
@@ -172,39 +319,110 @@ pub async fn get_wireguard_tunnels(
                         .map_or(0, |p| p.parse::<u16>().unwrap_or_default())
                 }),
             remote_endpoint,
-            local_answered: local_answered.into(),
-            remote_response: remote_response.into(),
+            local_answered,
+            remote_response,
             mtu: tunnel.mtu,
             endpoint_ipv6: tunnel.endpoint_ipv6,
             fec: tunnel.fec,
             faketcp: tunnel.faketcp,
+            keepalive: tunnel.persistent_keepalive.and_then(|k| u16::try_from(k).ok()),
+            allowed_ips: allowed_ips.map_or_else(Vec::new, |ips| {
+                ips.split(',').map(|ip| ip.trim().to_string()).filter(|ip| !ip.is_empty()).collect()
+            }),
+            local_overlay_addr: overlay.map(|(local, _)| local.to_string()),
+            peer_overlay_addr: overlay.map(|(_, peer)| peer.to_string()),
             created_at: tunnel.created_at.and_utc().timestamp_millis(),
             updated_at: tunnel.updated_at.and_utc().timestamp_millis(),
         });
     }
 
-    Ok(Json(REST::WireguardTunnelsResponse {
+    let response = REST::WireguardTunnelsResponse {
         success: true,
         tunnels: tunnel_infos,
-    }))
+    };
+    Ok(([(header::ETAG, etag_header)], Json(response)).into_response())
+}
+
+/// Every change to the list moves the latest `updated_at` (peer key changes included,
+/// see `db::update_wireguard_pubkey`) or the count.
+fn tunnels_etag(max_updated_at: Option<chrono::NaiveDateTime>, count: i64) -> String {
+    let micros = max_updated_at.map_or(0, |t| t.and_utc().timestamp_micros());
+    format!("\"{}-{}\"", count, micros)
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+/// Whether a self-reported `endpoint` names another address than the request came from.
+/// Hostnames, and addresses of the other family than the request's, can't be compared.
+fn endpoint_mismatch(endpoint: &str, source_ip: IpAddr) -> bool {
+    let Ok(endpoint) = endpoint.parse::<SocketAddr>() else {
+        return false;
+    };
+    let (reported, source) = (endpoint.ip().to_canonical(), source_ip.to_canonical());
+    reported.is_ipv4() == source.is_ipv4() && reported != source
 }
 
 pub async fn answer_wireguard_tunnel(
     Extension(node): Extension<crate::models::Node>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
     Json(payload): Json<REST::WireguardTunnelAnswerPayload>,
 ) -> Result<Json<StandardResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
+    let decline_type = payload.decline_type.map(crate::ext::WireguardAnswered::from);
+    if let Some(decline) = decline_type
+        && !decline.is_rejection()
+    {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(StandardResponse {
+                success: false,
+                message: Some(format!("Invalid decline type: {:?}", payload.decline_type)),
+            }),
+        ));
+    }
+
+    // not refused: behind a reverse proxy or split-horizon routing the two legitimately differ
+    let source_ip = connect_info.map(|Extension(ConnectInfo(addr))| addr.ip());
+    let mismatch = match (payload.endpoint.as_deref(), source_ip) {
+        (Some(endpoint), Some(source_ip)) if endpoint_mismatch(endpoint, source_ip) => {
+            tracing::warn!(
+                node_id = node.id,
+                tunnel_id = payload.tunnel_id,
+                endpoint,
+                %source_ip,
+                "reported endpoint does not match the request's source address"
+            );
+            Some(format!("Reported endpoint {} does not match source address {}", endpoint, source_ip))
+        }
+        _ => None,
+    };
+
+    // the node comes from the auth key, so it can only ever answer for its own side
     crate::db::answer_wireguard_tunnel(
         &mut conn,
         payload.tunnel_id,
         node.id,
         payload.endpoint,
-        payload.decline_type,
+        decline_type,
     )
     .map_err(|e| {
+        let status = if e == diesel::result::Error::NotFound {
+            axum::http::StatusCode::NOT_FOUND
+        } else {
+            axum::http::StatusCode::BAD_REQUEST
+        };
+
         (
-            axum::http::StatusCode::BAD_REQUEST,
+            status,
             Json(StandardResponse {
                 success: false,
                 message: Some(format!("Failed to answer wireguard tunnel: {}", e)),
@@ -212,16 +430,96 @@ pub async fn answer_wireguard_tunnel(
         )
     })?;
 
+    // the direct path is hopeless, so offer a relay; the tunnel stays declined either way
+    if decline_type == Some(crate::ext::WireguardAnswered::RejectedNatImpossible) {
+        match crate::db::offer_relay(&mut conn, payload.tunnel_id) {
+            Ok(Some(relay)) => tracing::info!(
+                tunnel_id = payload.tunnel_id,
+                relay = %relay.relay_endpoint,
+                "offered relay for un-punchable tunnel"
+            ),
+            Ok(None) => tracing::warn!(
+                tunnel_id = payload.tunnel_id,
+                "tunnel needs a relay, but none is configured"
+            ),
+            Err(e) => tracing::warn!(tunnel_id = payload.tunnel_id, "failed to offer relay: {}", e),
+        }
+    }
+
     Ok(Json(StandardResponse {
         success: true,
-        message: None,
+        message: mismatch,
+    }))
+}
+
+pub async fn get_my_meshes(
+    Extension(node): Extension<crate::models::Node>,
+) -> Result<Json<REST::MyMeshesResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let meshes = crate::db::get_joined_meshes(&mut conn, node.id).map_err(|e| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(StandardResponse {
+                success: false,
+                message: Some(format!("Failed to get meshes: {}", e)),
+            }),
+        )
+    })?;
+
+    Ok(Json(REST::MyMeshesResponse {
+        success: true,
+        meshes: meshes
+            .into_iter()
+            .map(|mesh| REST::MeshMembershipInfo {
+                mesh_id: mesh.id,
+                name: mesh.name,
+                auto_wireguard: mesh.auto_wireguard,
+                auto_wireguard_mtu: mesh.auto_wireguard_mtu,
+                mesh_subnet: mesh.mesh_subnet,
+                ula_overlay: mesh.ula_overlay,
+            })
+            .collect(),
+    }))
+}
+
+pub async fn get_relay_assignments(
+    Extension(node): Extension<crate::models::Node>,
+) -> Result<Json<REST::RelayAssignmentsResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let assignments = crate::db::get_relay_assignments(&mut conn, node.id).map_err(|e| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(StandardResponse {
+                success: false,
+                message: Some(format!("Failed to get relay assignments: {}", e)),
+            }),
+        )
+    })?;
+
+    let relays = assignments
+        .into_iter()
+        .map(|(relay, tunnel)| REST::RelayAssignmentInfo {
+            tunnel_id: relay.tunnel_id,
+            peer_node_ids: [tunnel.node_id_peer1, tunnel.node_id_peer2],
+            relay_node_id: relay.relay_node_id,
+            relay_endpoint: relay.relay_endpoint,
+            token: relay.token,
+            created_at: relay.created_at.and_utc().timestamp_millis(),
+        })
+        .collect();
+
+    Ok(Json(REST::RelayAssignmentsResponse {
+        success: true,
+        relays,
     }))
 }
 
 pub async fn get_wireguard_pubkey(
     Json(payload): Json<REST::WireguardPubKeyAskPayload>,
 ) -> Result<Json<REST::WireguardPubKeyResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
     let public_key =
         crate::db::get_wireguard_pubkey(&mut conn, payload.node_id_peer).map_err(|e| {
@@ -244,7 +542,7 @@ pub async fn update_wireguard_pubkey(
     Extension(node): Extension<crate::models::Node>,
     Json(payload): Json<REST::WireguardPubKeyUpdatePayload>,
 ) -> Result<Json<StandardResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
     crate::db::update_wireguard_pubkey(&mut conn, node.id, &payload.public_key).map_err(|e| {
         (
@@ -261,3 +559,178 @@ pub async fn update_wireguard_pubkey(
         message: None,
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{establish_test_connection, insert_node};
+    use diesel::SqliteConnection;
+
+    fn get_tunnels(conn: &mut SqliteConnection, node_id: i32, headers: &HeaderMap) -> Response {
+        wireguard_tunnels_response(conn, node_id, headers)
+            .unwrap_or_else(|(status, _)| panic!("request failed with {}", status))
+    }
+
+    #[test]
+    fn test_node_cannot_make_itself_a_relay() {
+        use super::super::metadata::{MetadataWriter, apply_node_metadata};
+
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let rogue = insert_node(conn, "rogue");
+        let tunnel_id = crate::db::create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+
+        let payload = |value: Option<&str>| REST::SetNodeMetadataPayload {
+            key: crate::db::RELAY_ENDPOINT_KEY.to_string(),
+            value: value.map(str::to_string),
+        };
+        let (status, _) = apply_node_metadata(conn, rogue, &payload(Some("203.0.113.66:3478")), MetadataWriter::Node)
+            .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(crate::db::get_node_metadata(conn, rogue).unwrap().is_empty());
+        assert!(crate::db::offer_relay(conn, tunnel_id).unwrap().is_none());
+
+        // nor can it step down once an operator designated it
+        assert!(apply_node_metadata(conn, rogue, &payload(Some("203.0.113.66:3478")), MetadataWriter::Operator).is_ok());
+        let (status, _) = apply_node_metadata(conn, rogue, &payload(None), MetadataWriter::Node).unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(crate::db::offer_relay(conn, tunnel_id).unwrap().unwrap().relay_node_id, Some(rogue));
+
+        // other keys stay the node's own
+        let own = REST::SetNodeMetadataPayload { key: "site".to_string(), value: Some("rack 4".to_string()) };
+        assert!(apply_node_metadata(conn, rogue, &own, MetadataWriter::Node).is_ok());
+    }
+
+    #[test]
+    fn test_single_node_online() {
+        let now = chrono::Utc::now().naive_utc();
+        let window = chrono::TimeDelta::seconds(120);
+        let node = |last_seen: Option<chrono::NaiveDateTime>| crate::models::Node {
+            id: 1,
+            name: "node-a".to_string(),
+            auth_key: uuid::Uuid::new_v4().to_string(),
+            created_at: now,
+            last_seen,
+            last_source_ip: None,
+        };
+
+        let recent = single_node(node(Some(now - chrono::TimeDelta::seconds(30))), None, now, window);
+        assert!(recent.online);
+        assert_eq!(
+            recent.last_seen_ms,
+            Some((now - chrono::TimeDelta::seconds(30)).and_utc().timestamp_millis())
+        );
+
+        let stale = single_node(node(Some(now - chrono::TimeDelta::minutes(10))), None, now, window);
+        assert!(!stale.online);
+        assert!(stale.last_seen_ms.is_some());
+
+        let never = single_node(node(None), None, now, window);
+        assert!(!never.online);
+        assert_eq!(never.last_seen_ms, None);
+    }
+
+    #[test]
+    fn test_wireguard_tunnels_etag() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        crate::db::update_wireguard_pubkey(conn, node_b, "key-1").unwrap();
+        crate::db::create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+
+        let first = get_tunnels(conn, node_a, &HeaderMap::new());
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag.clone());
+        let second = get_tunnels(conn, node_a, &headers);
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(second.headers()[header::ETAG], etag);
+
+        // the peer rotating its key must invalidate the cached list
+        crate::db::update_wireguard_pubkey(conn, node_b, "key-2").unwrap();
+        let third = get_tunnels(conn, node_a, &headers);
+        assert_eq!(third.status(), StatusCode::OK);
+        assert_ne!(third.headers()[header::ETAG], etag);
+    }
+
+    #[test]
+    fn test_wireguard_tunnels_etag_changes_on_first_key_upload() {
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        crate::db::create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+
+        // served with an empty key until the peer uploads one
+        let first = get_tunnels(conn, node_a, &HeaderMap::new());
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, first.headers()[header::ETAG].clone());
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        crate::db::update_wireguard_pubkey(conn, node_b, "key-1").unwrap();
+        assert_eq!(get_tunnels(conn, node_a, &headers).status(), StatusCode::OK);
+    }
+
+    async fn tunnels_of(conn: &mut SqliteConnection, node_id: i32) -> Vec<REST::WireguardTunnelInfo> {
+        let response = get_tunnels(conn, node_id, &HeaderMap::new());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice::<REST::WireguardTunnelsResponse>(&body).unwrap().tunnels
+    }
+
+    #[tokio::test]
+    async fn test_remote_endpoint_is_the_other_peers() {
+        use crate::ext::WireguardAnswered;
+
+        let conn = &mut establish_test_connection();
+        let node_a = insert_node(conn, "node-a");
+        let node_b = insert_node(conn, "node-b");
+        let tunnel_id = crate::db::create_wireguard_tunnel(conn, node_a, node_b, 1420, false).unwrap();
+
+        assert_eq!(tunnels_of(conn, node_a).await[0].remote_endpoint, None);
+        assert_eq!(tunnels_of(conn, node_b).await[0].remote_endpoint, None);
+
+        // peer2 answers: peer1 sees its endpoint, peer2 still waits for peer1
+        crate::db::answer_wireguard_tunnel(conn, tunnel_id, node_b, Some("198.51.100.2:51820".to_string()), None).unwrap();
+        assert_eq!(tunnels_of(conn, node_a).await[0].remote_endpoint.as_deref(), Some("198.51.100.2:51820"));
+        assert_eq!(tunnels_of(conn, node_b).await[0].remote_endpoint, None);
+
+        crate::db::answer_wireguard_tunnel(conn, tunnel_id, node_a, Some("192.0.2.1:51821".to_string()), None).unwrap();
+        assert_eq!(tunnels_of(conn, node_b).await[0].remote_endpoint.as_deref(), Some("192.0.2.1:51821"));
+        assert_eq!(tunnels_of(conn, node_a).await[0].preferred_port, 51821);
+        assert_eq!(tunnels_of(conn, node_b).await[0].preferred_port, 51820);
+
+        // a declining peer's endpoint is withdrawn
+        crate::db::answer_wireguard_tunnel(
+            conn,
+            tunnel_id,
+            node_b,
+            Some("198.51.100.2:51820".to_string()),
+            Some(WireguardAnswered::RejectedGeneric),
+        )
+        .unwrap();
+        assert_eq!(tunnels_of(conn, node_a).await[0].remote_endpoint, None);
+    }
+
+    #[test]
+    fn test_endpoint_mismatch() {
+        let source: IpAddr = "198.51.100.2".parse().unwrap();
+        assert!(!endpoint_mismatch("198.51.100.2:51820", source));
+        assert!(!endpoint_mismatch("198.51.100.2:51820", "::ffff:198.51.100.2".parse().unwrap()));
+        assert!(endpoint_mismatch("203.0.113.66:51820", source));
+        assert!(endpoint_mismatch("[2001:db8::2]:51820", "2001:db8::3".parse().unwrap()));
+        // not comparable
+        assert!(!endpoint_mismatch("[2001:db8::2]:51820", source));
+        assert!(!endpoint_mismatch("node.example.com:51820", source));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let mut headers = HeaderMap::new();
+        assert!(!if_none_match(&headers, "\"1-2\""));
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"x\", \"1-2\""));
+        assert!(if_none_match(&headers, "\"1-2\""));
+        assert!(!if_none_match(&headers, "\"1-3\""));
+    }
+}
@@ -0,0 +1,77 @@
+use axum::Json;
+use axum::http::StatusCode;
+use diesel::{Connection, SqliteConnection};
+
+use cat4igp_shared::limits::{MAX_METADATA_ENTRIES, MAX_METADATA_VALUE_LEN, is_valid_metadata_key};
+use cat4igp_shared::rest::StandardResponse;
+use cat4igp_shared::rest::client::SetNodeMetadataPayload;
+
+/// Keys that grant a node a role other nodes rely on, so only operators may set or clear them.
+const OPERATOR_ONLY_KEYS: &[&str] = &[crate::db::RELAY_ENDPOINT_KEY];
+
+/// Who is changing a node's metadata
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MetadataWriter {
+    /// The node itself, through `/client/self/metadata`
+    Node,
+    Operator,
+}
+
+/// Validate and apply a metadata change for `node_id`; shared by the client and operator routes.
+pub fn apply_node_metadata(
+    conn: &mut SqliteConnection,
+    node_id: i32,
+    payload: &SetNodeMetadataPayload,
+    writer: MetadataWriter,
+) -> Result<(), (StatusCode, Json<StandardResponse>)> {
+    let bad_request = |message: String| {
+        (StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(message),
+        }))
+    };
+
+    if !is_valid_metadata_key(&payload.key) {
+        return Err(bad_request(format!(
+            "Invalid metadata key '{}': use lowercase letters, digits, '_', '-' or '.'",
+            payload.key
+        )));
+    }
+
+    if writer == MetadataWriter::Node && OPERATOR_ONLY_KEYS.contains(&payload.key.as_str()) {
+        return Err((StatusCode::FORBIDDEN, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Metadata key '{}' can only be set by an operator", payload.key)),
+        })));
+    }
+
+    if let Some(value) = &payload.value
+        && value.chars().count() > MAX_METADATA_VALUE_LEN
+    {
+        return Err(bad_request(format!(
+            "Metadata values must be at most {} characters",
+            MAX_METADATA_VALUE_LEN
+        )));
+    }
+
+    // count and insert together, so concurrent requests can't each take the last free entry
+    let applied = conn
+        .transaction(|conn| {
+            if payload.value.is_some() {
+                let existing = crate::db::get_node_metadata(conn, node_id)?;
+                if !existing.contains_key(&payload.key) && existing.len() >= MAX_METADATA_ENTRIES {
+                    return Ok(false);
+                }
+            }
+            crate::db::set_node_metadata(conn, node_id, &payload.key, payload.value.as_deref())?;
+            Ok(true)
+        })
+        .map_err(|e: diesel::result::Error| bad_request(format!("Failed to set metadata: {}", e)))?;
+    if !applied {
+        return Err(bad_request(format!(
+            "Nodes may carry at most {} metadata entries",
+            MAX_METADATA_ENTRIES
+        )));
+    }
+    Ok(())
+}
@@ -1,9 +1,72 @@
+use std::collections::HashMap;
+
 use axum::Json;
+use axum::extract::Path;
+use diesel::{Connection, OptionalExtension};
 use cat4igp_shared::rest::StandardResponse;
 use cat4igp_shared::rest::operator as REST;
+use cat4igp_shared::limits::{MAX_INVITE_LABEL_LEN, MAX_TUNNEL_MTU, MIN_TUNNEL_MTU, is_valid_tunnel_mtu};
+
+type SettingValidator = fn(&str) -> bool;
+
+/// Settings operators may change, with a validator for each value.
+/// Secrets such as the operator token stay in the environment so `GET /settings` can't leak them.
+const SETTING_KEYS: &[(&str, SettingValidator)] = &[
+    ("default_mesh_group", |v| v.parse::<i32>().is_ok()),
+    ("default_wireguard_mtu", |v| v.parse::<i32>().is_ok_and(is_valid_tunnel_mtu)),
+    ("default_endpoint_ipv6", |v| v.parse::<bool>().is_ok()),
+    ("node_online_window_secs", |v| {
+        v.parse::<i64>().is_ok_and(|secs| (1..=crate::db::MAX_NODE_ONLINE_WINDOW_SECS).contains(&secs))
+    }),
+    // fallback relay (`host:port`) for tunnels no node-advertised relay can serve
+    (crate::db::RELAY_ENDPOINT_KEY, |v| {
+        v.rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p != 0))
+    }),
+];
+
+/// Validate and join allowed IPs for storage; `None` keeps the catch-all default. An empty
+/// list is rejected rather than stored, since clients would read it as the catch-all too.
+fn normalize_allowed_ips(ips: Option<Vec<String>>) -> Result<Option<String>, String> {
+    let Some(ips) = ips else {
+        return Ok(None);
+    };
+    if ips.is_empty() {
+        return Err("Allowed IPs must list at least one CIDR; omit them to route everything".to_string());
+    }
+
+    let mut nets = Vec::with_capacity(ips.len());
+    for ip in ips {
+        let net = ip
+            .trim()
+            .parse::<ipnet::IpNet>()
+            .map_err(|_| format!("Invalid allowed IP '{}', expected CIDR notation", ip))?;
+        nets.push(net.trunc().to_string());
+    }
+
+    Ok(Some(nets.join(",")))
+}
+
+/// Trim an invite label; blank labels are dropped and long ones rejected.
+fn normalize_invite_label(label: Option<String>) -> Result<Option<String>, String> {
+    let Some(label) = label else {
+        return Ok(None);
+    };
+
+    let label = label.trim();
+    if label.is_empty() {
+        return Ok(None);
+    }
+
+    if label.chars().count() > MAX_INVITE_LABEL_LEN {
+        return Err(format!("Label must be at most {} characters", MAX_INVITE_LABEL_LEN));
+    }
+
+    Ok(Some(label.to_string()))
+}
 
 pub async fn create_invite(Json(payload): Json<REST::CreateInvitePayload>) -> Result<Json<REST::CreateInviteResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
     let expires_at = if let Some(ts) = payload.expires_at {
         let o = chrono::DateTime::<chrono::Utc>::from_timestamp(ts / 1000, (ts % 1000) as u32 * 1_000_000);
@@ -23,7 +86,14 @@ pub async fn create_invite(Json(payload): Json<REST::CreateInvitePayload>) -> Re
         None
     };
 
-    let invite_code = crate::db::create_invite_key(&mut conn, expires_at, payload.max_uses, payload.join_mesh).map_err(|e| {
+    let label = normalize_invite_label(payload.label).map_err(|message| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(message),
+        }))
+    })?;
+
+    let invite_code = crate::db::create_invite_key(&mut conn, expires_at, payload.max_uses, payload.join_mesh, label.as_deref()).map_err(|e| {
         (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
             success: false,
             message: Some(format!("Failed to create invite: {}", e))
@@ -37,7 +107,7 @@ pub async fn create_invite(Json(payload): Json<REST::CreateInvitePayload>) -> Re
 }
 
 pub async fn get_invites() -> Result<Json<REST::GetInvitesResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
     let invites = crate::db::get_invites(&mut conn).map_err(|e| {
         (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
@@ -48,27 +118,128 @@ pub async fn get_invites() -> Result<Json<REST::GetInvitesResponse>, (axum::http
 
     Ok(Json(REST::GetInvitesResponse {
         success: true,
-        invites: invites.into_iter().map(|inv| REST::Invite {
-            id: inv.id,
-            code: inv.code,
-            created_at: inv.created_at,
-            expires_at: inv.expires_at,
-            used_count: inv.used_count,
-            override_join_mesh: inv.override_join_mesh,
-            max_uses: inv.max_uses,
-        }).collect(),
+        invites: invites.into_iter().map(REST::Invite::from).collect(),
+    }))
+}
+
+pub async fn revoke_invite(
+    Json(payload): Json<REST::RevokeInvitePayload>,
+) -> Result<Json<StandardResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    crate::db::revoke_invite(&mut conn, payload.invite_id).map_err(|e| {
+        let status = if e == diesel::result::Error::NotFound {
+            axum::http::StatusCode::NOT_FOUND
+        } else {
+            axum::http::StatusCode::BAD_REQUEST
+        };
+
+        (status, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to revoke invite: {}", e))
+        }))
+    })?;
+
+    Ok(Json(StandardResponse {
+        success: true,
+        message: None,
+    }))
+}
+
+pub async fn rotate_node_key(
+    Json(payload): Json<REST::RotateNodeKeyPayload>,
+) -> Result<Json<REST::RotateNodeKeyResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let auth_key = crate::db::rotate_node_auth_key(&mut conn, payload.node_id).map_err(|e| {
+        let status = if e == diesel::result::Error::NotFound {
+            axum::http::StatusCode::NOT_FOUND
+        } else {
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        (status, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to rotate node key: {}", e))
+        }))
+    })?;
+
+    tracing::info!("rotated auth key of node {}", payload.node_id);
+
+    Ok(Json(REST::RotateNodeKeyResponse {
+        success: true,
+        auth_key,
+    }))
+}
+
+/// Remove a node for good; its tunnels, mesh memberships and metadata go with it.
+pub async fn delete_node(
+    Json(payload): Json<REST::DeleteNodePayload>,
+) -> Result<Json<StandardResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    crate::db::delete_node(&mut conn, payload.node_id).map_err(|e| {
+        let status = if e == diesel::result::Error::NotFound {
+            axum::http::StatusCode::NOT_FOUND
+        } else {
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        (status, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to delete node: {}", e))
+        }))
+    })?;
+
+    tracing::info!("deleted node {}", payload.node_id);
+
+    Ok(Json(StandardResponse {
+        success: true,
+        message: None,
     }))
 }
 
 pub async fn create_mesh(
     Json(payload): Json<REST::CreateMeshPayload>,
 ) -> Result<Json<REST::CreateMeshResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
-    let mut conn = crate::db::establish_connection();
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
 
     let auto_wireguard = payload.auto_wireguard.unwrap_or(false);
-    let auto_wireguard_mtu = if auto_wireguard { payload.auto_wireguard_mtu.unwrap_or(1420) } else { 0 };
+    let auto_wireguard_mtu = match payload.auto_wireguard_mtu {
+        _ if !auto_wireguard => 0,
+        Some(mtu) => mtu,
+        None => crate::db::get_default_wireguard_mtu(&mut conn).map_err(|e| {
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                Json(StandardResponse {
+                    success: false,
+                    message: Some(format!("Failed to read default MTU: {}", e)),
+                }),
+            )
+        })?,
+    };
+
+    if auto_wireguard && !is_valid_tunnel_mtu(auto_wireguard_mtu) {
+        return Err((
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(StandardResponse {
+                success: false,
+                message: Some(format!(
+                    "MTU must be between {} and {}",
+                    MIN_TUNNEL_MTU, MAX_TUNNEL_MTU
+                )),
+            }),
+        ));
+    }
 
-    let mesh_group = crate::db::create_mesh_group(&mut conn, &payload.name, auto_wireguard, auto_wireguard_mtu).map_err(|e| {
+    let mesh_group = crate::db::create_mesh_group(
+        &mut conn,
+        &payload.name,
+        auto_wireguard,
+        auto_wireguard_mtu,
+        payload.mesh_subnet.as_deref(),
+        payload.ula_overlay,
+    ).map_err(|e| {
         (
             axum::http::StatusCode::BAD_REQUEST,
             Json(StandardResponse {
@@ -83,3 +254,401 @@ pub async fn create_mesh(
         mesh_group_id: mesh_group,
     }))
 }
+
+fn tunnel_to_rest(
+    tunnel: crate::models::WireguardTunnel,
+    node_names: &HashMap<i32, String>,
+) -> REST::WireguardTunnel {
+    REST::WireguardTunnel {
+        id: tunnel.id,
+        node_id_peer1: tunnel.node_id_peer1,
+        node_name_peer1: node_names.get(&tunnel.node_id_peer1).cloned(),
+        node_id_peer2: tunnel.node_id_peer2,
+        node_name_peer2: node_names.get(&tunnel.node_id_peer2).cloned(),
+        peer1_answered: tunnel.peer1_answer(),
+        peer2_answered: tunnel.peer2_answer(),
+        endpoint_peer1: tunnel.endpoint_peer1,
+        endpoint_peer2: tunnel.endpoint_peer2,
+        mtu: tunnel.mtu,
+        endpoint_ipv6: tunnel.endpoint_ipv6,
+        fec: tunnel.fec,
+        faketcp: tunnel.faketcp,
+        created_at: tunnel.created_at.and_utc().timestamp_millis(),
+        updated_at: tunnel.updated_at.and_utc().timestamp_millis(),
+    }
+}
+
+fn get_node_names(
+    conn: &mut diesel::SqliteConnection,
+) -> Result<HashMap<i32, String>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let nodes = crate::db::get_node_list(conn).map_err(|e| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get node list: {}", e))
+        }))
+    })?;
+
+    Ok(nodes.into_iter().map(|n| (n.id, n.name)).collect())
+}
+
+pub async fn get_tunnels() -> Result<Json<REST::GetTunnelsResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let tunnels = crate::db::get_all_tunnels(&mut conn).map_err(|e| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get tunnels: {}", e))
+        }))
+    })?;
+
+    let node_names = get_node_names(&mut conn)?;
+
+    Ok(Json(REST::GetTunnelsResponse {
+        success: true,
+        tunnels: tunnels.into_iter().map(|t| tunnel_to_rest(t, &node_names)).collect(),
+    }))
+}
+
+pub async fn get_tunnel(
+    Path(tunnel_id): Path<i32>,
+) -> Result<Json<REST::GetTunnelResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let tunnel = crate::db::get_tunnel(&mut conn, tunnel_id).map_err(|e| {
+        let status = if e == diesel::result::Error::NotFound {
+            axum::http::StatusCode::NOT_FOUND
+        } else {
+            axum::http::StatusCode::BAD_REQUEST
+        };
+
+        (status, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get tunnel: {}", e))
+        }))
+    })?;
+
+    let node_names = get_node_names(&mut conn)?;
+
+    Ok(Json(REST::GetTunnelResponse {
+        success: true,
+        tunnel: tunnel_to_rest(tunnel, &node_names),
+    }))
+}
+
+pub async fn remove_from_mesh(
+    Json(payload): Json<REST::RemoveFromMeshPayload>,
+) -> Result<Json<REST::RemoveFromMeshResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let removed_tunnels = crate::db::remove_node_from_mesh(&mut conn, payload.mesh_id, payload.node_id).map_err(|e| {
+        let status = if e == diesel::result::Error::NotFound {
+            axum::http::StatusCode::NOT_FOUND
+        } else {
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        };
+
+        (status, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to remove node from mesh: {}", e))
+        }))
+    })?;
+
+    tracing::info!(
+        "removed node {} from mesh {}, tearing down tunnels {:?}",
+        payload.node_id, payload.mesh_id, removed_tunnels
+    );
+
+    Ok(Json(REST::RemoveFromMeshResponse {
+        success: true,
+        removed_tunnels,
+    }))
+}
+
+fn mesh_to_rest(mesh: crate::models::MeshGroup, member_count: usize) -> REST::Mesh {
+    REST::Mesh {
+        id: mesh.id,
+        name: mesh.name,
+        auto_wireguard: mesh.auto_wireguard,
+        auto_wireguard_mtu: mesh.auto_wireguard_mtu,
+        mesh_subnet: mesh.mesh_subnet,
+        ula_overlay: mesh.ula_overlay,
+        created_at: mesh.created_at.and_utc().timestamp_millis(),
+        member_count,
+    }
+}
+
+pub async fn get_meshes() -> Result<Json<REST::GetMeshesResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let failed = |e: diesel::result::Error| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get meshes: {}", e))
+        }))
+    };
+    let meshes = crate::db::get_meshes(&mut conn).map_err(failed)?;
+    let counts = crate::db::get_mesh_member_counts(&mut conn).map_err(failed)?;
+
+    Ok(Json(REST::GetMeshesResponse {
+        success: true,
+        meshes: meshes
+            .into_iter()
+            .map(|m| {
+                let count = counts.get(&m.id).copied().unwrap_or(0);
+                mesh_to_rest(m, count)
+            })
+            .collect(),
+    }))
+}
+
+pub async fn get_mesh(
+    Path(mesh_id): Path<i32>,
+) -> Result<Json<REST::GetMeshResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let mesh = crate::db::get_mesh(&mut conn, mesh_id).map_err(|e| {
+        let status = if e == diesel::result::Error::NotFound {
+            axum::http::StatusCode::NOT_FOUND
+        } else {
+            axum::http::StatusCode::BAD_REQUEST
+        };
+
+        (status, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get mesh: {}", e))
+        }))
+    })?;
+
+    let members = crate::db::get_mesh_members(&mut conn, mesh_id).map_err(|e| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get mesh members: {}", e))
+        }))
+    })?;
+
+    Ok(Json(REST::GetMeshResponse {
+        success: true,
+        mesh: mesh_to_rest(mesh, members.len()),
+        members: members
+            .into_iter()
+            .map(|n| REST::MeshMember { node_id: n.id, node_name: n.name })
+            .collect(),
+    }))
+}
+
+pub async fn create_tunnel(
+    Json(payload): Json<REST::CreateTunnelPayload>,
+) -> Result<Json<REST::CreateTunnelResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let bad_request = |message: String| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(message),
+        }))
+    };
+
+    if payload.peer1_id == payload.peer2_id {
+        return Err(bad_request("Cannot create a tunnel from a node to itself".to_string()));
+    }
+
+    let mtu = match payload.mtu {
+        Some(mtu) => mtu,
+        None => crate::db::get_default_wireguard_mtu(&mut conn)
+            .map_err(|e| bad_request(format!("Failed to read default MTU: {}", e)))?,
+    };
+    let endpoint_ipv6 = match payload.endpoint_ipv6 {
+        Some(ipv6) => ipv6,
+        None => crate::db::get_default_endpoint_ipv6(&mut conn)
+            .map_err(|e| bad_request(format!("Failed to read default endpoint family: {}", e)))?,
+    };
+
+    if !is_valid_tunnel_mtu(mtu) {
+        return Err(bad_request(format!(
+            "MTU must be between {} and {}",
+            MIN_TUNNEL_MTU, MAX_TUNNEL_MTU
+        )));
+    }
+
+    let allowed_ips_peer1 = normalize_allowed_ips(payload.allowed_ips_peer1).map_err(bad_request)?;
+    let allowed_ips_peer2 = normalize_allowed_ips(payload.allowed_ips_peer2).map_err(bad_request)?;
+
+    for peer_id in [payload.peer1_id, payload.peer2_id] {
+        let node = crate::db::get_server_side_node_info(&mut conn, peer_id)
+            .optional()
+            .map_err(|e| bad_request(format!("Failed to look up node {}: {}", peer_id, e)))?;
+
+        if node.is_none() {
+            return Err(bad_request(format!("Node {} does not exist", peer_id)));
+        }
+    }
+
+    let existing = crate::db::get_tunnel_between(&mut conn, payload.peer1_id, payload.peer2_id, endpoint_ipv6)
+        .map_err(|e| bad_request(format!("Failed to look up existing tunnels: {}", e)))?;
+
+    if let Some(tunnel) = existing {
+        return Err((axum::http::StatusCode::CONFLICT, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Tunnel {} already exists between these nodes", tunnel.id)),
+        })));
+    }
+
+    // the tunnel stores the lower node ID as peer 1
+    let (allowed_ips_peer1, allowed_ips_peer2) = if payload.peer1_id < payload.peer2_id {
+        (allowed_ips_peer1, allowed_ips_peer2)
+    } else {
+        (allowed_ips_peer2, allowed_ips_peer1)
+    };
+
+    // one transaction, so clients never see the tunnel routing everything before its allowed IPs land
+    let tunnel_id = conn.transaction(|conn| {
+        let tunnel_id = crate::db::create_wireguard_tunnel(
+            conn,
+            payload.peer1_id,
+            payload.peer2_id,
+            mtu,
+            endpoint_ipv6,
+        )?;
+        if allowed_ips_peer1.is_some() || allowed_ips_peer2.is_some() {
+            crate::db::set_tunnel_allowed_ips(conn, tunnel_id, allowed_ips_peer1, allowed_ips_peer2)?;
+        }
+        Ok(tunnel_id)
+    })
+    .map_err(|e| match e {
+        diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, info) => {
+            (axum::http::StatusCode::CONFLICT, Json(StandardResponse {
+                success: false,
+                message: Some(format!("Failed to create tunnel: {}", info.message())),
+            }))
+        }
+        e => bad_request(format!("Failed to create tunnel: {}", e)),
+    })?;
+
+    Ok(Json(REST::CreateTunnelResponse {
+        success: true,
+        tunnel_id,
+    }))
+}
+
+pub async fn get_settings() -> Result<Json<REST::GetSettingsResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let settings = crate::db::list_settings(&mut conn).map_err(|e| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get settings: {}", e))
+        }))
+    })?;
+
+    Ok(Json(REST::GetSettingsResponse {
+        success: true,
+        settings: settings.into_iter().map(|setting| REST::Setting {
+            key: setting.key,
+            value: setting.value,
+            created_at: setting.created_at.and_utc().timestamp_millis(),
+            updated_at: setting.updated_at.and_utc().timestamp_millis(),
+        }).collect(),
+    }))
+}
+
+pub async fn set_setting(
+    Json(payload): Json<REST::SetSettingPayload>,
+) -> Result<Json<StandardResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    let bad_request = |message: String| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(message),
+        }))
+    };
+
+    let (_, is_valid) = SETTING_KEYS
+        .iter()
+        .find(|(key, _)| *key == payload.key)
+        .ok_or_else(|| bad_request(format!("Unknown setting '{}'", payload.key)))?;
+
+    if !is_valid(&payload.value) {
+        return Err(bad_request(format!("Invalid value for setting '{}'", payload.key)));
+    }
+
+    crate::db::set_setting(&mut conn, &payload.key, &payload.value)
+        .map_err(|e| bad_request(format!("Failed to set setting: {}", e)))?;
+
+    Ok(Json(StandardResponse {
+        success: true,
+        message: None,
+    }))
+}
+
+pub async fn get_node_metadata(
+    Path(node_id): Path<i32>,
+) -> Result<Json<REST::NodeMetadataResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    ensure_node_exists(&mut conn, node_id)?;
+
+    let metadata = crate::db::get_node_metadata(&mut conn, node_id).map_err(|e| {
+        (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Failed to get metadata: {}", e))
+        }))
+    })?;
+
+    Ok(Json(REST::NodeMetadataResponse {
+        success: true,
+        node_id,
+        metadata,
+    }))
+}
+
+pub async fn set_node_metadata(
+    Path(node_id): Path<i32>,
+    Json(payload): Json<cat4igp_shared::rest::client::SetNodeMetadataPayload>,
+) -> Result<Json<REST::NodeMetadataResponse>, (axum::http::StatusCode, Json<StandardResponse>)> {
+    let mut conn = crate::db::establish_connection().map_err(super::db_unavailable)?;
+
+    ensure_node_exists(&mut conn, node_id)?;
+    super::metadata::apply_node_metadata(&mut conn, node_id, &payload, super::metadata::MetadataWriter::Operator)?;
+
+    get_node_metadata(Path(node_id)).await
+}
+
+fn ensure_node_exists(
+    conn: &mut diesel::SqliteConnection,
+    node_id: i32,
+) -> Result<(), (axum::http::StatusCode, Json<StandardResponse>)> {
+    let node = crate::db::get_server_side_node_info(conn, node_id)
+        .optional()
+        .map_err(|e| {
+            (axum::http::StatusCode::BAD_REQUEST, Json(StandardResponse {
+                success: false,
+                message: Some(format!("Failed to look up node {}: {}", node_id, e))
+            }))
+        })?;
+
+    if node.is_none() {
+        return Err((axum::http::StatusCode::NOT_FOUND, Json(StandardResponse {
+            success: false,
+            message: Some(format!("Node {} does not exist", node_id))
+        })));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_allowed_ips() {
+        assert_eq!(normalize_allowed_ips(None), Ok(None));
+        assert_eq!(
+            normalize_allowed_ips(Some(vec!["10.0.0.1/24".to_string(), " fd00::/64".to_string()])),
+            Ok(Some("10.0.0.0/24,fd00::/64".to_string()))
+        );
+        assert!(normalize_allowed_ips(Some(Vec::new())).is_err());
+        assert!(normalize_allowed_ips(Some(vec!["10.0.0.0/24".to_string(), "bogus".to_string()])).is_err());
+    }
+}
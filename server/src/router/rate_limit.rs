@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Failed registrations allowed from one address before backoff kicks in.
+const FREE_FAILURES: u32 = 3;
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60 * 60);
+/// Prune idle entries once the map grows past this many addresses.
+const PRUNE_THRESHOLD: usize = 10_000;
+/// Prune at most this often, since each pass walks the whole map.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+/// Hard cap on tracked addresses; a new one past it evicts the least recently seen.
+const MAX_CLIENTS: usize = 50_000;
+/// Entries no longer blocked are forgotten, failures included, after this long without a request.
+const IDLE_EXPIRY: Duration = BACKOFF_MAX;
+
+struct ClientState {
+    tokens: f64,
+    last_refill: Instant,
+    failures: u32,
+    blocked_until: Option<Instant>,
+}
+
+struct Clients {
+    entries: HashMap<IpAddr, ClientState>,
+    last_prune: Option<Instant>,
+}
+
+/// Per-IP token bucket for `/client/register`, plus exponential backoff after
+/// repeated failed registrations (i.e. guessed invite codes). IPv6 clients share
+/// one entry per /64, since a single host usually controls the whole prefix.
+pub struct RegisterRateLimiter {
+    clients: Mutex<Clients>,
+    per_second: f64,
+    burst: f64,
+}
+
+impl RegisterRateLimiter {
+    pub fn new(per_minute: u32, burst: u32) -> Self {
+        Self {
+            clients: Mutex::new(Clients { entries: HashMap::new(), last_prune: None }),
+            per_second: f64::from(per_minute.max(1)) / 60.0,
+            burst: f64::from(burst.max(1)),
+        }
+    }
+
+    /// Reads `REGISTER_RATE_PER_MINUTE` (default 10) and `REGISTER_RATE_BURST` (default 5).
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: u32| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+
+        Self::new(read("REGISTER_RATE_PER_MINUTE", 10), read("REGISTER_RATE_BURST", 5))
+    }
+
+    /// Take a token for `ip`, or return how long it has to wait.
+    fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut clients = self.clients.lock().unwrap();
+        let key = client_key(ip);
+
+        if clients.entries.len() > PRUNE_THRESHOLD
+            && clients.last_prune.is_none_or(|last| now.duration_since(last) >= PRUNE_INTERVAL)
+        {
+            clients.last_prune = Some(now);
+            clients.entries.retain(|_, c| {
+                c.blocked_until.is_some_and(|until| until > now)
+                    || now.duration_since(c.last_refill) < IDLE_EXPIRY
+            });
+        }
+
+        if clients.entries.len() >= MAX_CLIENTS && !clients.entries.contains_key(&key) {
+            let oldest = clients.entries.iter().min_by_key(|(_, c)| c.last_refill).map(|(ip, _)| *ip);
+            if let Some(oldest) = oldest {
+                clients.entries.remove(&oldest);
+            }
+        }
+
+        let client = clients.entries.entry(key).or_insert(ClientState {
+            tokens: self.burst,
+            last_refill: now,
+            failures: 0,
+            blocked_until: None,
+        });
+
+        if let Some(until) = client.blocked_until
+            && until > now
+        {
+            return Err(until - now);
+        }
+
+        let elapsed = now.duration_since(client.last_refill).as_secs_f64();
+        client.tokens = (client.tokens + elapsed * self.per_second).min(self.burst);
+        client.last_refill = now;
+
+        if client.tokens >= 1.0 {
+            client.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - client.tokens) / self.per_second))
+        }
+    }
+
+    /// Successful registrations reset the failure count; failures past
+    /// [`FREE_FAILURES`] block the address for an exponentially growing time.
+    fn record(&self, ip: IpAddr, success: bool, now: Instant) {
+        let mut clients = self.clients.lock().unwrap();
+        let Some(client) = clients.entries.get_mut(&client_key(ip)) else {
+            return;
+        };
+
+        if success {
+            client.failures = 0;
+            client.blocked_until = None;
+            return;
+        }
+
+        client.failures = client.failures.saturating_add(1);
+        if client.failures > FREE_FAILURES {
+            let exponent = (client.failures - FREE_FAILURES - 1).min(16);
+            let backoff = BACKOFF_BASE.saturating_mul(1 << exponent).min(BACKOFF_MAX);
+            client.blocked_until = Some(now + backoff);
+        }
+    }
+}
+
+/// The entry `ip` is counted under: IPv4 addresses as they are, IPv6 ones by their /64.
+fn client_key(ip: IpAddr) -> IpAddr {
+    match ip.to_canonical() {
+        IpAddr::V6(v6) => IpAddr::V6(std::net::Ipv6Addr::from(u128::from(v6) & !u128::from(u64::MAX))),
+        v4 => v4,
+    }
+}
+
+/// Rejects over-limit requests with 429 and `Retry-After`. Requests without a
+/// known peer address (e.g. served without connect info) pass through.
+pub async fn register_rate_limit_middleware(
+    State(limiter): State<Arc<RegisterRateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ip) = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+    else {
+        return next.run(request).await;
+    };
+
+    if let Err(retry_after) = limiter.check(ip, Instant::now()) {
+        let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        tracing::warn!(%ip, retry_after = secs, "registration rate limited");
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, secs.to_string())],
+            "Too Many Requests",
+        )
+            .into_response();
+    }
+
+    let response = next.run(request).await;
+    limiter.record(ip, response.status().is_success(), Instant::now());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+
+    #[test]
+    fn test_token_bucket_refills() {
+        let limiter = RegisterRateLimiter::new(60, 2);
+        let now = Instant::now();
+
+        assert!(limiter.check(IP, now).is_ok());
+        assert!(limiter.check(IP, now).is_ok());
+        let wait = limiter.check(IP, now).unwrap_err();
+        assert!(wait <= Duration::from_secs(1));
+
+        // other addresses have their own bucket
+        assert!(limiter.check(IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2)), now).is_ok());
+
+        assert!(limiter.check(IP, now + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn test_failures_back_off_exponentially() {
+        let limiter = RegisterRateLimiter::new(6000, 100);
+        let now = Instant::now();
+
+        for _ in 0..FREE_FAILURES {
+            limiter.check(IP, now).unwrap();
+            limiter.record(IP, false, now);
+        }
+        assert!(limiter.check(IP, now).is_ok());
+
+        limiter.record(IP, false, now);
+        assert_eq!(limiter.check(IP, now).unwrap_err(), BACKOFF_BASE);
+
+        let later = now + BACKOFF_BASE;
+        limiter.check(IP, later).unwrap();
+        limiter.record(IP, false, later);
+        assert_eq!(limiter.check(IP, later).unwrap_err(), BACKOFF_BASE * 2);
+
+        let later = later + BACKOFF_BASE * 2;
+        limiter.check(IP, later).unwrap();
+        limiter.record(IP, true, later);
+        assert!(limiter.check(IP, later).is_ok());
+    }
+
+    #[test]
+    fn test_ipv6_shares_a_bucket_per_64() {
+        let limiter = RegisterRateLimiter::new(60, 1);
+        let now = Instant::now();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert!(limiter.check(ip("2001:db8:0:1::1"), now).is_ok());
+        assert!(limiter.check(ip("2001:db8:0:1:ffff::2"), now).is_err());
+        assert!(limiter.check(ip("2001:db8:0:2::1"), now).is_ok());
+        // v4-mapped addresses count as the IPv4 address
+        assert!(limiter.check(ip("::ffff:192.0.2.1"), now).is_ok());
+        assert!(limiter.check(IP, now).is_err());
+    }
+
+    #[test]
+    fn test_failed_entries_are_pruned() {
+        let limiter = RegisterRateLimiter::new(6000, 100);
+        let now = Instant::now();
+        for _ in 0..=FREE_FAILURES {
+            limiter.check(IP, now).unwrap();
+            limiter.record(IP, false, now);
+        }
+        assert!(limiter.check(IP, now).is_err());
+
+        // addresses rotated in later push the map past the threshold
+        let later = now + IDLE_EXPIRY + BACKOFF_BASE;
+        for i in 0..=PRUNE_THRESHOLD as u32 {
+            let rotated = IpAddr::V4(std::net::Ipv4Addr::from(0x0a00_0000 + i));
+            limiter.check(rotated, later).unwrap();
+            limiter.record(rotated, false, later);
+        }
+        limiter.check(IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, 1)), later).unwrap();
+
+        let clients = limiter.clients.lock().unwrap();
+        assert!(!clients.entries.contains_key(&IP));
+        assert_eq!(clients.entries.len(), PRUNE_THRESHOLD + 2);
+    }
+
+    #[test]
+    fn test_prune_runs_at_most_once_per_interval() {
+        let limiter = RegisterRateLimiter::new(6000, 100);
+        let start = Instant::now();
+        let other = IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, 1));
+        limiter.check(IP, start).unwrap();
+
+        // the map passes the threshold shortly before IP goes idle, so that prune keeps it
+        let before_idle = start + IDLE_EXPIRY - Duration::from_secs(30);
+        for i in 0..=PRUNE_THRESHOLD as u32 {
+            limiter.check(IpAddr::V4(std::net::Ipv4Addr::from(0x0a00_0000 + i)), before_idle).unwrap();
+        }
+
+        // idle now, but the last prune was too recent
+        limiter.check(other, start + IDLE_EXPIRY + Duration::from_secs(1)).unwrap();
+        assert!(limiter.clients.lock().unwrap().entries.contains_key(&IP));
+
+        limiter.check(other, before_idle + PRUNE_INTERVAL).unwrap();
+        assert!(!limiter.clients.lock().unwrap().entries.contains_key(&IP));
+    }
+
+    #[test]
+    fn test_full_map_evicts_least_recently_seen() {
+        let limiter = RegisterRateLimiter::new(6000, 100);
+        let start = Instant::now();
+        limiter.check(IP, start).unwrap();
+
+        // none of these are idle, so pruning can't make room
+        let later = start + Duration::from_secs(1);
+        for i in 1..MAX_CLIENTS as u32 {
+            limiter.check(IpAddr::V4(std::net::Ipv4Addr::from(0x0a00_0000 + i)), later).unwrap();
+        }
+        assert_eq!(limiter.clients.lock().unwrap().entries.len(), MAX_CLIENTS);
+
+        limiter.check(IpAddr::V4(std::net::Ipv4Addr::new(198, 51, 100, 1)), later).unwrap();
+        let clients = limiter.clients.lock().unwrap();
+        assert_eq!(clients.entries.len(), MAX_CLIENTS);
+        assert!(!clients.entries.contains_key(&IP));
+    }
+}
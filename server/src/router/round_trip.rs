@@ -0,0 +1,254 @@
+//! Drives the real router against a temp database and decodes every answer with the
+//! shared REST types, so a field the server renames or retypes breaks here instead of
+//! on deployed clients.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use cat4igp_shared::custom_type::WireguardAnswered;
+use cat4igp_shared::rest::StandardResponse;
+use cat4igp_shared::rest::client as CLIENT;
+use cat4igp_shared::rest::operator as OPERATOR;
+use diesel::{Connection, SqliteConnection};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use serde::de::DeserializeOwned;
+use tower::ServiceExt;
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+const OPERATOR_KEY: &str = "round-trip-operator-key";
+
+async fn call<T: DeserializeOwned>(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    token: &str,
+    body: Option<serde_json::Value>,
+) -> T {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json");
+    let request = match body {
+        Some(body) => request.body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(status, StatusCode::OK, "{} answered {}", uri, String::from_utf8_lossy(&bytes));
+
+    serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+        panic!("{} answered {} which does not decode: {}", uri, String::from_utf8_lossy(&bytes), e)
+    })
+}
+
+async fn register(app: &Router, invite_code: &str, node_name: &str) -> (i32, String) {
+    let registered: CLIENT::RegisterResponse = call(
+        app,
+        Method::POST,
+        "/client/register",
+        "",
+        Some(serde_json::to_value(CLIENT::RegisterPayload {
+            node_name: node_name.to_string(),
+            invitation_key: invite_code.to_string(),
+        }).unwrap()),
+    )
+    .await;
+    assert!(registered.success);
+
+    let info: CLIENT::NodeInfoResponse =
+        call(app, Method::GET, "/client/self", &registered.auth_key, None).await;
+    assert_eq!(info.name, node_name);
+    (info.id, registered.auth_key)
+}
+
+async fn upload_pubkey(app: &Router, auth_key: &str, public_key: &str) {
+    let response: StandardResponse = call(
+        app,
+        Method::POST,
+        "/client/wg_pubkey",
+        auth_key,
+        Some(serde_json::to_value(CLIENT::WireguardPubKeyUpdatePayload {
+            public_key: public_key.to_string(),
+        }).unwrap()),
+    )
+    .await;
+    assert!(response.success);
+}
+
+async fn tunnels(app: &Router, auth_key: &str) -> Vec<CLIENT::WireguardTunnelInfo> {
+    let response: CLIENT::WireguardTunnelsResponse =
+        call(app, Method::GET, "/client/wg_tun", auth_key, None).await;
+    assert!(response.success);
+    response.tunnels
+}
+
+#[tokio::test]
+async fn test_register_and_fetch_tunnel() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let database_url = dir.path().join("cat4igp.sqlite").to_str().unwrap().to_string();
+    SqliteConnection::establish(&database_url)
+        .unwrap()
+        .run_pending_migrations(MIGRATIONS)
+        .unwrap();
+    // SAFETY: no other test in this binary reads or writes these variables
+    unsafe {
+        std::env::set_var("DATABASE_URL", &database_url);
+        std::env::set_var("OPERATOR_AUTH_KEY", OPERATOR_KEY);
+    }
+    let app = super::make_router().await.unwrap();
+
+    let invite: OPERATOR::CreateInviteResponse = call(
+        &app,
+        Method::POST,
+        "/operator/create_invite",
+        OPERATOR_KEY,
+        Some(serde_json::json!({ "max_uses": 2, "label": "round trip" })),
+    )
+    .await;
+    let invites: OPERATOR::GetInvitesResponse =
+        call(&app, Method::GET, "/operator/invites", OPERATOR_KEY, None).await;
+    let listed = invites.invites.iter().find(|i| i.code == invite.invite_code).unwrap();
+    assert_eq!(listed.override_join_mesh, None);
+    assert_eq!(listed.max_uses, Some(2));
+    assert_eq!(listed.label.as_deref(), Some("round trip"));
+
+    let (node_a, key_a) = register(&app, &invite.invite_code, "node-a").await;
+    let (node_b, key_b) = register(&app, &invite.invite_code, "node-b").await;
+    upload_pubkey(&app, &key_a, "pubkey-a").await;
+    upload_pubkey(&app, &key_b, "pubkey-b").await;
+
+    // both nodes just made authenticated requests
+    let all_nodes: CLIENT::AllNodesResponse =
+        call(&app, Method::GET, "/client/all_nodes", &key_a, None).await;
+    assert_eq!(all_nodes.nodes.len(), 2);
+    assert!(all_nodes.nodes.iter().all(|n| n.online && n.last_seen_ms.is_some()));
+    let offline: CLIENT::AllNodesResponse =
+        call(&app, Method::GET, "/client/all_nodes?online=false", &key_a, None).await;
+    assert!(offline.nodes.is_empty());
+    let named: CLIENT::AllNodesResponse =
+        call(&app, Method::GET, "/client/all_nodes?name=de-b&online=true", &key_a, None).await;
+    assert_eq!(named.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![node_b]);
+
+    let created: OPERATOR::CreateTunnelResponse = call(
+        &app,
+        Method::POST,
+        "/operator/create_tunnel",
+        OPERATOR_KEY,
+        Some(serde_json::to_value(OPERATOR::CreateTunnelPayload {
+            peer1_id: node_a,
+            peer2_id: node_b,
+            mtu: Some(1400),
+            endpoint_ipv6: Some(false),
+            allowed_ips_peer1: Some(vec!["10.0.0.0/24".to_string()]),
+            allowed_ips_peer2: None,
+        }).unwrap()),
+    )
+    .await;
+
+    let fetched = tunnels(&app, &key_a).await;
+    assert_eq!(fetched.len(), 1);
+    let tunnel = &fetched[0];
+    assert_eq!(tunnel.tunnel_id, created.tunnel_id);
+    assert_eq!(tunnel.peer_node_id, node_b);
+    assert_eq!(tunnel.public_key, "pubkey-b");
+    assert_eq!(tunnel.mtu, 1400);
+    assert!(!tunnel.endpoint_ipv6);
+    assert_eq!(tunnel.allowed_ips, vec!["10.0.0.0/24"]);
+    assert_eq!(tunnel.local_answered, WireguardAnswered::Unanswered);
+    assert_eq!(tunnel.remote_response, WireguardAnswered::Unanswered);
+    assert_eq!(tunnel.remote_endpoint, None);
+
+    // node B accepts; node A now sees the answer and B's endpoint
+    let answered: StandardResponse = call(
+        &app,
+        Method::POST,
+        "/client/wg_tun",
+        &key_b,
+        Some(serde_json::to_value(CLIENT::WireguardTunnelAnswerPayload {
+            tunnel_id: created.tunnel_id,
+            decline_type: None,
+            endpoint: Some("198.51.100.2:51821".to_string()),
+        }).unwrap()),
+    )
+    .await;
+    assert!(answered.success);
+
+    let tunnel = tunnels(&app, &key_a).await.remove(0);
+    assert_eq!(tunnel.remote_response, WireguardAnswered::Answered);
+    assert_eq!(tunnel.remote_endpoint.as_deref(), Some("198.51.100.2:51821"));
+    assert_eq!(tunnel.local_answered, WireguardAnswered::Unanswered);
+
+    let tunnel = tunnels(&app, &key_b).await.remove(0);
+    assert_eq!(tunnel.peer_node_id, node_a);
+    assert_eq!(tunnel.public_key, "pubkey-a");
+    assert_eq!(tunnel.local_answered, WireguardAnswered::Answered);
+    assert_eq!(tunnel.preferred_port, 51821);
+
+    let operator_view: OPERATOR::GetTunnelResponse = call(
+        &app,
+        Method::GET,
+        &format!("/operator/tunnels/{}", created.tunnel_id),
+        OPERATOR_KEY,
+        None,
+    )
+    .await;
+    assert_eq!(operator_view.tunnel.peer1_answered, WireguardAnswered::Unanswered);
+    assert_eq!(operator_view.tunnel.peer2_answered, WireguardAnswered::Answered);
+    assert_eq!(operator_view.tunnel.endpoint_peer2.as_deref(), Some("198.51.100.2:51821"));
+
+    let mesh: OPERATOR::CreateMeshResponse = call(
+        &app,
+        Method::POST,
+        "/operator/create_mesh",
+        OPERATOR_KEY,
+        Some(serde_json::json!({ "name": "round trip", "auto_wireguard": true, "auto_wireguard_mtu": 1400 })),
+    )
+    .await;
+    let meshes: OPERATOR::GetMeshesResponse =
+        call(&app, Method::GET, "/operator/meshes", OPERATOR_KEY, None).await;
+    let listed = meshes.meshes.iter().find(|m| m.id == mesh.mesh_group_id).unwrap();
+    assert_eq!(listed.name, "round trip");
+    assert_eq!(listed.member_count, 0);
+    let detail: OPERATOR::GetMeshResponse = call(
+        &app,
+        Method::GET,
+        &format!("/operator/meshes/{}", mesh.mesh_group_id),
+        OPERATOR_KEY,
+        None,
+    )
+    .await;
+    assert_eq!(detail.mesh.auto_wireguard_mtu, 1400);
+    assert!(detail.members.is_empty());
+
+    let none: CLIENT::MyMeshesResponse = call(&app, Method::GET, "/client/my_meshes", &key_a, None).await;
+    assert!(none.meshes.is_empty());
+    let joining: OPERATOR::CreateInviteResponse = call(
+        &app,
+        Method::POST,
+        "/operator/create_invite",
+        OPERATOR_KEY,
+        Some(serde_json::json!({ "join_mesh": mesh.mesh_group_id })),
+    )
+    .await;
+    let (node_c, key_c) = register(&app, &joining.invite_code, "node-c").await;
+    let mine: CLIENT::MyMeshesResponse = call(&app, Method::GET, "/client/my_meshes", &key_c, None).await;
+    assert_eq!(mine.meshes.len(), 1);
+    assert_eq!(mine.meshes[0].mesh_id, mesh.mesh_group_id);
+    assert!(mine.meshes[0].auto_wireguard);
+    assert_eq!(mine.meshes[0].auto_wireguard_mtu, 1400);
+    let detail: OPERATOR::GetMeshResponse = call(
+        &app,
+        Method::GET,
+        &format!("/operator/meshes/{}", mesh.mesh_group_id),
+        OPERATOR_KEY,
+        None,
+    )
+    .await;
+    assert_eq!(detail.members.iter().map(|m| m.node_id).collect::<Vec<_>>(), vec![node_c]);
+}
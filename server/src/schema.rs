@@ -9,6 +9,8 @@ diesel::table! {
         used_count -> Integer,
         max_uses -> Nullable<Integer>,
         override_join_mesh -> Nullable<Integer>,
+        revoked -> Bool,
+        label -> Nullable<Text>,
     }
 }
 
@@ -28,6 +30,18 @@ diesel::table! {
         auto_wireguard -> Bool,
         auto_wireguard_mtu -> Integer,
         created_at -> Timestamp,
+        mesh_subnet -> Nullable<Text>,
+        ula_overlay -> Bool,
+    }
+}
+
+diesel::table! {
+    node_overlay_addrs (id) {
+        id -> Integer,
+        mesh_group_id -> Integer,
+        node_id -> Integer,
+        address -> Text,
+        created_at -> Timestamp,
     }
 }
 
@@ -38,6 +52,18 @@ diesel::table! {
         auth_key -> Text,
         created_at -> Timestamp,
         last_seen -> Nullable<Timestamp>,
+        last_source_ip -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    relay_assignments (id) {
+        id -> Integer,
+        tunnel_id -> Integer,
+        relay_node_id -> Nullable<Integer>,
+        relay_endpoint -> Text,
+        token -> Text,
+        created_at -> Timestamp,
     }
 }
 
@@ -59,6 +85,17 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    node_metadata (id) {
+        id -> Integer,
+        node_id -> Integer,
+        key -> Text,
+        value -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     wireguard_tunnels (id) {
         id -> Integer,
@@ -74,14 +111,29 @@ diesel::table! {
         faketcp -> Bool,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        persistent_keepalive -> Nullable<Integer>,
+        allowed_ips_peer1 -> Nullable<Text>,
+        allowed_ips_peer2 -> Nullable<Text>,
     }
 }
 
+diesel::joinable!(mesh_group_memberships -> mesh_groups (mesh_group_id));
+diesel::joinable!(mesh_group_memberships -> nodes (node_id));
+diesel::joinable!(node_metadata -> nodes (node_id));
+diesel::joinable!(node_overlay_addrs -> mesh_groups (mesh_group_id));
+diesel::joinable!(node_overlay_addrs -> nodes (node_id));
+diesel::joinable!(relay_assignments -> nodes (relay_node_id));
+diesel::joinable!(relay_assignments -> wireguard_tunnels (tunnel_id));
+diesel::joinable!(wireguard_static_key -> nodes (node_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
     invites,
     mesh_group_memberships,
     mesh_groups,
+    node_metadata,
+    node_overlay_addrs,
     nodes,
+    relay_assignments,
     settings,
     wireguard_static_key,
     wireguard_tunnels,
@@ -0,0 +1,50 @@
+//! Overlay addresses both ends derive the same way.
+
+use blake2::{Blake2s256, Digest};
+use std::net::Ipv6Addr;
+
+/// Derive a stable RFC 4193 unique local `/64` prefix from `seed`: `fd`, then a 40-bit
+/// global ID from the seed's Blake2s hash, then subnet ID 0.
+pub fn generate_ula_prefix_from_seed(seed: &[u8]) -> Ipv6Addr {
+    let hash = Blake2s256::digest(seed);
+    let mut addr = [0u8; 16];
+    addr[0] = 0xfd;
+    addr[1..6].copy_from_slice(&hash[..5]);
+    Ipv6Addr::from(addr)
+}
+
+/// The address of `node_id` in a ULA `/64`: the node ID as the interface identifier,
+/// e.g. node 12 in `fd12:3456:789a::/64` is `fd12:3456:789a::c`.
+pub fn ula_node_address(prefix: Ipv6Addr, node_id: i32) -> Ipv6Addr {
+    let network = u128::from(prefix) & !u128::from(u64::MAX);
+    Ipv6Addr::from(network | u128::from(node_id as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ula_prefix_is_deterministic_and_unique_local() {
+        let prefix = generate_ula_prefix_from_seed(b"mesh:1:backbone");
+        assert_eq!(prefix, generate_ula_prefix_from_seed(b"mesh:1:backbone"));
+        assert_ne!(prefix, generate_ula_prefix_from_seed(b"mesh:2:backbone"));
+
+        for seed in [&b""[..], b"a", b"mesh:1:backbone", &[0xff; 64]] {
+            let octets = generate_ula_prefix_from_seed(seed).octets();
+            // fd00::/8, with the subnet ID and interface identifier left zero
+            assert_eq!(octets[0], 0xfd);
+            assert!(octets[6..].iter().all(|b| *b == 0), "{:?}", octets);
+        }
+    }
+
+    #[test]
+    fn test_ula_node_address() {
+        let prefix: Ipv6Addr = "fd12:3456:789a::".parse().unwrap();
+        assert_eq!(ula_node_address(prefix, 12), "fd12:3456:789a::c".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(ula_node_address(prefix, 0x1_0000), "fd12:3456:789a::1:0".parse::<Ipv6Addr>().unwrap());
+        // stray host bits in the prefix are ignored
+        let dirty: Ipv6Addr = "fd12:3456:789a::ffff".parse().unwrap();
+        assert_eq!(ula_node_address(dirty, 1), "fd12:3456:789a::1".parse::<Ipv6Addr>().unwrap());
+    }
+}
@@ -1,15 +1,30 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum WireguardAnswered {
     Unanswered = 0,
     Answered = 1,
     RejectedGeneric = 2,
     RejectedNoIpStack = 3,
     RejectedFakeTCPNotSupported = 4,
+    /// Both sides are behind NATs that cannot be punched; the controller may offer a relay
+    RejectedNatImpossible = 5,
     Unknown = -1
 }
 
+impl WireguardAnswered {
+    /// Whether this is one of the reasons a peer can decline a tunnel with.
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            WireguardAnswered::RejectedGeneric
+                | WireguardAnswered::RejectedNoIpStack
+                | WireguardAnswered::RejectedFakeTCPNotSupported
+                | WireguardAnswered::RejectedNatImpossible
+        )
+    }
+}
+
 impl From<i16> for WireguardAnswered {
     fn from(value: i16) -> Self {
         match value {
@@ -18,6 +33,7 @@ impl From<i16> for WireguardAnswered {
             2 => WireguardAnswered::RejectedGeneric,
             3 => WireguardAnswered::RejectedNoIpStack,
             4 => WireguardAnswered::RejectedFakeTCPNotSupported,
+            5 => WireguardAnswered::RejectedNatImpossible,
             _ => WireguardAnswered::Unknown,
         }
     }
@@ -31,6 +47,7 @@ impl From<WireguardAnswered> for i16 {
             WireguardAnswered::RejectedGeneric => 2,
             WireguardAnswered::RejectedNoIpStack => 3,
             WireguardAnswered::RejectedFakeTCPNotSupported => 4,
+            WireguardAnswered::RejectedNatImpossible => 5,
             WireguardAnswered::Unknown => -1,
         }
     }
@@ -1,2 +1,6 @@
 pub mod rest;
-pub mod custom_type;
\ No newline at end of file
+pub mod addressing;
+pub mod custom_type;
+pub mod limits;
+#[cfg(feature = "operator-client")]
+pub mod operator_client;
\ No newline at end of file
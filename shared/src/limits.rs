@@ -0,0 +1,54 @@
+/// Smallest tunnel MTU accepted anywhere (the IPv4 minimum reassembly size).
+pub const MIN_TUNNEL_MTU: i32 = 576;
+/// Largest tunnel MTU accepted anywhere (jumbo frames).
+pub const MAX_TUNNEL_MTU: i32 = 9000;
+
+/// Longest invite label, in characters, after trimming.
+pub const MAX_INVITE_LABEL_LEN: usize = 128;
+
+/// Longest node metadata key.
+pub const MAX_METADATA_KEY_LEN: usize = 64;
+/// Longest node metadata value, in characters.
+pub const MAX_METADATA_VALUE_LEN: usize = 256;
+/// Most metadata entries a single node may carry.
+pub const MAX_METADATA_ENTRIES: usize = 32;
+
+/// Metadata keys are short lowercase identifiers such as `region` or `owner.team`.
+pub fn is_valid_metadata_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_METADATA_KEY_LEN
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'_' | b'-' | b'.'))
+}
+
+/// Whether `mtu` is within [`MIN_TUNNEL_MTU`, `MAX_TUNNEL_MTU`]. Server and client both check this.
+pub fn is_valid_tunnel_mtu(mtu: i32) -> bool {
+    (MIN_TUNNEL_MTU..=MAX_TUNNEL_MTU).contains(&mtu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_tunnel_mtu() {
+        assert!(is_valid_tunnel_mtu(1420));
+        assert!(is_valid_tunnel_mtu(MIN_TUNNEL_MTU));
+        assert!(is_valid_tunnel_mtu(MAX_TUNNEL_MTU));
+        assert!(!is_valid_tunnel_mtu(575));
+        assert!(!is_valid_tunnel_mtu(9001));
+        assert!(!is_valid_tunnel_mtu(-1));
+        assert!(!is_valid_tunnel_mtu(0));
+    }
+
+    #[test]
+    fn test_is_valid_metadata_key() {
+        assert!(is_valid_metadata_key("region"));
+        assert!(is_valid_metadata_key("owner.team-2_a"));
+        assert!(!is_valid_metadata_key(""));
+        assert!(!is_valid_metadata_key("Region"));
+        assert!(!is_valid_metadata_key("has space"));
+        assert!(!is_valid_metadata_key(&"k".repeat(MAX_METADATA_KEY_LEN + 1)));
+    }
+}
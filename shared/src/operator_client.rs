@@ -0,0 +1,149 @@
+use std::fmt;
+
+use reqwest::Method;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::rest::StandardResponse;
+use crate::rest::operator as REST;
+
+/// Error returned by [`OperatorClient`].
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request could not be sent or the response could not be decoded.
+    Http(reqwest::Error),
+    /// The server answered with a non-success status.
+    Status { status: u16, message: String },
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Http(e) => write!(f, "request failed: {}", e),
+            ApiError::Status { status, message } => write!(f, "server returned {}: {}", status, message),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        ApiError::Http(e)
+    }
+}
+
+/// Typed client for the `/operator` API, authenticated with `OPERATOR_AUTH_KEY`.
+#[derive(Clone)]
+pub struct OperatorClient {
+    base_url: String,
+    auth_key: String,
+    client: reqwest::Client,
+}
+
+impl OperatorClient {
+    pub fn new(base_url: &str, auth_key: &str) -> Self {
+        Self::with_client(base_url, auth_key, reqwest::Client::new())
+    }
+
+    /// Use a preconfigured `reqwest::Client` (timeouts, TLS settings, ...).
+    pub fn with_client(base_url: &str, auth_key: &str, client: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            auth_key: auth_key.to_string(),
+            client,
+        }
+    }
+
+    async fn send_json<T, P>(&self, method: Method, path: &str, payload: Option<&P>) -> Result<T, ApiError>
+    where
+        T: DeserializeOwned,
+        P: Serialize,
+    {
+        let mut request = self
+            .client
+            .request(method, format!("{}/operator/{}", self.base_url, path))
+            .header("Authorization", format!("Bearer {}", self.auth_key));
+        if let Some(payload) = payload {
+            request = request.json(payload);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(status_error(status.as_u16(), &body));
+        }
+
+        Ok(response.json::<T>().await?)
+    }
+
+    pub async fn create_invite(&self, payload: &REST::CreateInvitePayload) -> Result<REST::CreateInviteResponse, ApiError> {
+        self.send_json(Method::POST, "create_invite", Some(payload)).await
+    }
+
+    pub async fn get_invites(&self) -> Result<REST::GetInvitesResponse, ApiError> {
+        self.send_json::<_, ()>(Method::GET, "invites", None).await
+    }
+
+    pub async fn revoke_invite(&self, invite_id: i32) -> Result<StandardResponse, ApiError> {
+        let payload = REST::RevokeInvitePayload { invite_id };
+        self.send_json(Method::POST, "revoke_invite", Some(&payload)).await
+    }
+
+    pub async fn delete_node(&self, node_id: i32) -> Result<StandardResponse, ApiError> {
+        let payload = REST::DeleteNodePayload { node_id };
+        self.send_json(Method::POST, "delete_node", Some(&payload)).await
+    }
+
+    pub async fn create_mesh(&self, payload: &REST::CreateMeshPayload) -> Result<REST::CreateMeshResponse, ApiError> {
+        self.send_json(Method::POST, "create_mesh", Some(payload)).await
+    }
+
+    pub async fn get_tunnels(&self) -> Result<REST::GetTunnelsResponse, ApiError> {
+        self.send_json::<_, ()>(Method::GET, "tunnels", None).await
+    }
+
+    pub async fn get_tunnel(&self, tunnel_id: i32) -> Result<REST::GetTunnelResponse, ApiError> {
+        self.send_json::<_, ()>(Method::GET, &format!("tunnels/{}", tunnel_id), None).await
+    }
+
+    pub async fn create_tunnel(&self, payload: &REST::CreateTunnelPayload) -> Result<REST::CreateTunnelResponse, ApiError> {
+        self.send_json(Method::POST, "create_tunnel", Some(payload)).await
+    }
+
+    pub async fn get_node_metadata(&self, node_id: i32) -> Result<REST::NodeMetadataResponse, ApiError> {
+        self.send_json::<_, ()>(Method::GET, &format!("nodes/{}/metadata", node_id), None).await
+    }
+
+    pub async fn set_node_metadata(
+        &self,
+        node_id: i32,
+        payload: &crate::rest::client::SetNodeMetadataPayload,
+    ) -> Result<REST::NodeMetadataResponse, ApiError> {
+        self.send_json(Method::PUT, &format!("nodes/{}/metadata", node_id), Some(payload)).await
+    }
+}
+
+/// Error bodies are usually a `StandardResponse`, but auth failures are plain text.
+fn status_error(status: u16, body: &str) -> ApiError {
+    let message = match serde_json::from_str::<StandardResponse>(body) {
+        Ok(StandardResponse { message: Some(message), .. }) => message,
+        _ => body.to_string(),
+    };
+    ApiError::Status { status, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_error_message() {
+        let err = status_error(404, r#"{"success":false,"message":"Tunnel not found"}"#);
+        assert_eq!(err.to_string(), "server returned 404: Tunnel not found");
+
+        let err = status_error(401, "Unauthorized");
+        assert_eq!(err.to_string(), "server returned 401: Unauthorized");
+    }
+}
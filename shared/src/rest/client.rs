@@ -1,4 +1,5 @@
 use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct RegisterPayload {
@@ -10,6 +11,10 @@ pub struct RegisterPayload {
 pub struct RegisterResponse {
     pub success: bool,
     pub auth_key: String,
+    /// Registrations the invite allows after this one; `None` for unlimited invites (and
+    /// from servers that predate the field)
+    #[serde(default)]
+    pub remaining_uses: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -17,6 +22,20 @@ pub struct UpdateNamePayload {
     pub new_name: String,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RotateKeyPayload {
+    /// Key proposed by the node (a lowercase hyphenated UUID), so it can be saved locally
+    /// before the server switches over; the server generates one when absent.
+    #[serde(default)]
+    pub auth_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RotateKeyResponse {
+    pub success: bool,
+    pub auth_key: String,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct NodeInfoResponse {
     pub success: bool,
@@ -30,6 +49,37 @@ pub struct SingleNode {
     pub id: i32,
     pub name: String,
     pub created_at: i64,
+    /// Only filled in when requested with `?metadata=true`.
+    #[serde(default)]
+    pub metadata: Option<BTreeMap<String, String>>,
+    /// Last authenticated request from the node, in milliseconds; `None` if never seen.
+    #[serde(default)]
+    pub last_seen_ms: Option<i64>,
+    /// Seen within the server's `node_online_window_secs` (120 seconds by default).
+    #[serde(default)]
+    pub online: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct AllNodesQuery {
+    #[serde(default)]
+    pub metadata: bool,
+    /// Only nodes whose name contains this substring (ASCII case-insensitive)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Only members of this mesh group; an unknown id matches no nodes
+    #[serde(default)]
+    pub mesh: Option<i32>,
+    /// Only nodes that are (`true`) or are not (`false`) online
+    #[serde(default)]
+    pub online: Option<bool>,
+}
+
+/// Set one metadata entry on a node; a `None` value removes the key.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SetNodeMetadataPayload {
+    pub key: String,
+    pub value: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -51,16 +101,75 @@ pub struct WireguardTunnelInfo {
     pub endpoint_ipv6: bool,
     pub fec: bool,
     pub faketcp: bool,
+    /// Persistent keepalive interval in seconds; `None` or `Some(0)` disables it.
+    #[serde(default = "default_keepalive")]
+    pub keepalive: Option<u16>,
+    /// CIDRs to route into the tunnel; empty means `0.0.0.0/0` and `::/0`.
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    /// This node's overlay address in a mesh shared with the peer, to assign to the interface
+    #[serde(default)]
+    pub local_overlay_addr: Option<String>,
+    /// The peer's overlay address in that mesh, routed into the tunnel
+    #[serde(default)]
+    pub peer_overlay_addr: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
 }
 
+fn default_keepalive() -> Option<u16> {
+    Some(25)
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct WireguardTunnelsResponse {
     pub success: bool,
     pub tunnels: Vec<WireguardTunnelInfo>,
 }
 
+/// Relay standing in for a direct tunnel that was declined as `RejectedNatImpossible`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RelayAssignmentInfo {
+    pub tunnel_id: i32,
+    /// The two nodes whose traffic goes through the relay
+    pub peer_node_ids: [i32; 2],
+    /// Node acting as the relay; `None` for the controller's own relay
+    pub relay_node_id: Option<i32>,
+    /// `host:port` both peers connect to
+    pub relay_endpoint: String,
+    /// Shared by both peers and the relay to pair the two connections
+    pub token: String,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RelayAssignmentsResponse {
+    pub success: bool,
+    pub relays: Vec<RelayAssignmentInfo>,
+}
+
+/// A mesh the node belongs to
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MeshMembershipInfo {
+    pub mesh_id: i32,
+    pub name: String,
+    /// Whether the server connects every pair of members with WireGuard tunnels
+    pub auto_wireguard: bool,
+    /// MTU of those tunnels; 0 when `auto_wireguard` is off
+    pub auto_wireguard_mtu: i32,
+    /// CIDR overlay addresses are allocated from; `None` when the mesh has none
+    pub mesh_subnet: Option<String>,
+    /// Whether `mesh_subnet` is a derived ULA /64 addressing members by node ID
+    #[serde(default)]
+    pub ula_overlay: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MyMeshesResponse {
+    pub success: bool,
+    pub meshes: Vec<MeshMembershipInfo>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct WireguardTunnelAnswerPayload {
     pub tunnel_id: i32,
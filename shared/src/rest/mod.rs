@@ -6,4 +6,40 @@ use serde::{Serialize, Deserialize};
 pub struct StandardResponse {
     pub success: bool,
     pub message: Option<String>,
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HealthResponse {
+    /// `ok`, or `db_error` when the database can't be queried.
+    pub status: String,
+}
+
+/// REST API revision, bumped on breaking changes. Additions are announced as capabilities.
+pub const API_VERSION: u32 = 1;
+
+/// Optional server features listed in [`VersionResponse::capabilities`].
+pub mod capability {
+    /// `GET /client/wg_tun` sends an `ETag` and honors `If-None-Match`
+    pub const WG_TUN_ETAG: &str = "wg_tun_etag";
+    /// `POST /client/rotate_key` exists
+    pub const ROTATE_KEY: &str = "rotate_key";
+    /// `POST /client/self/metadata` exists
+    pub const SELF_METADATA: &str = "self_metadata";
+    /// Declining with `RejectedNatImpossible` offers a relay, listed by `GET /client/relays`
+    pub const RELAY: &str = "relay";
+}
+
+/// Answer of the unauthenticated `GET /version`. Servers older than this endpoint return 404.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VersionResponse {
+    /// Server package version, e.g. `0.1.0`
+    pub version: String,
+    pub api_version: u32,
+    pub capabilities: Vec<String>,
+}
+
+impl VersionResponse {
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
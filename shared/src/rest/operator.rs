@@ -1,11 +1,15 @@
 use serde::{Serialize, Deserialize};
 use chrono;
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CreateInvitePayload {
     pub expires_at: Option<i64>,
     pub max_uses: Option<i32>,
-    pub join_mesh: Option<i32>
+    pub join_mesh: Option<i32>,
+    /// Free-form note to tell invites apart; trimmed, at most 128 characters.
+    #[serde(default)]
+    pub label: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -23,6 +27,12 @@ pub struct Invite {
     pub used_count: i32,
     pub override_join_mesh: Option<i32>,
     pub max_uses: Option<i32>,
+    pub revoked: bool,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// `max_uses - used_count`, never below 0; `None` for unlimited invites
+    #[serde(default)]
+    pub remaining_uses: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -31,11 +41,40 @@ pub struct GetInvitesResponse {
     pub invites: Vec<Invite>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RevokeInvitePayload {
+    pub invite_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DeleteNodePayload {
+    pub node_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RotateNodeKeyPayload {
+    pub node_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RotateNodeKeyResponse {
+    pub success: bool,
+    /// New auth key; the old one stops working immediately
+    pub auth_key: String,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CreateMeshPayload {
     pub name: String,
     pub auto_wireguard: Option<bool>,
     pub auto_wireguard_mtu: Option<i32>,
+    /// CIDR each member gets an overlay address from, e.g. `10.77.0.0/24`
+    #[serde(default)]
+    pub mesh_subnet: Option<String>,
+    /// Derive a ULA /64 for the mesh instead, and give each member `{prefix}::{node_id}`;
+    /// excludes `mesh_subnet`
+    #[serde(default)]
+    pub ula_overlay: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -43,3 +82,130 @@ pub struct CreateMeshResponse {
     pub success: bool,
     pub mesh_group_id: i32,
 }
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RemoveFromMeshPayload {
+    pub mesh_id: i32,
+    pub node_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RemoveFromMeshResponse {
+    pub success: bool,
+    /// Tunnels torn down with the membership
+    pub removed_tunnels: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Mesh {
+    pub id: i32,
+    pub name: String,
+    pub auto_wireguard: bool,
+    pub auto_wireguard_mtu: i32,
+    pub mesh_subnet: Option<String>,
+    #[serde(default)]
+    pub ula_overlay: bool,
+    pub created_at: i64,
+    pub member_count: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MeshMember {
+    pub node_id: i32,
+    pub node_name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GetMeshesResponse {
+    pub success: bool,
+    pub meshes: Vec<Mesh>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GetMeshResponse {
+    pub success: bool,
+    pub mesh: Mesh,
+    pub members: Vec<MeshMember>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WireguardTunnel {
+    pub id: i32,
+    pub node_id_peer1: i32,
+    pub node_name_peer1: Option<String>,
+    pub node_id_peer2: i32,
+    pub node_name_peer2: Option<String>,
+    pub endpoint_peer1: Option<String>,
+    pub endpoint_peer2: Option<String>,
+    pub peer1_answered: crate::custom_type::WireguardAnswered,
+    pub peer2_answered: crate::custom_type::WireguardAnswered,
+    pub mtu: i32,
+    pub endpoint_ipv6: bool,
+    pub fec: bool,
+    pub faketcp: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GetTunnelsResponse {
+    pub success: bool,
+    pub tunnels: Vec<WireguardTunnel>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GetTunnelResponse {
+    pub success: bool,
+    pub tunnel: WireguardTunnel,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CreateTunnelPayload {
+    pub peer1_id: i32,
+    pub peer2_id: i32,
+    /// Falls back to the server's `default_wireguard_mtu` setting when omitted.
+    #[serde(default)]
+    pub mtu: Option<i32>,
+    /// Falls back to the server's `default_endpoint_ipv6` setting when omitted.
+    #[serde(default)]
+    pub endpoint_ipv6: Option<bool>,
+    /// CIDRs peer 1 routes into the tunnel; `None` means `0.0.0.0/0` and `::/0`.
+    #[serde(default)]
+    pub allowed_ips_peer1: Option<Vec<String>>,
+    /// CIDRs peer 2 routes into the tunnel; `None` means `0.0.0.0/0` and `::/0`.
+    #[serde(default)]
+    pub allowed_ips_peer2: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Setting {
+    pub key: String,
+    pub value: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GetSettingsResponse {
+    pub success: bool,
+    pub settings: Vec<Setting>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SetSettingPayload {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CreateTunnelResponse {
+    pub success: bool,
+    pub tunnel_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NodeMetadataResponse {
+    pub success: bool,
+    pub node_id: i32,
+    pub metadata: BTreeMap<String, String>,
+}